@@ -2,17 +2,29 @@
 
 use exonum::{
     api::{self, ServiceApiBuilder, ServiceApiState},
-    blockchain::Transaction,
-    crypto::Hash,
+    blockchain::{Schema as CoreSchema, Transaction},
+    crypto::{Hash, PublicKey},
     encoding::serialize::FromHex,
+    helpers::Height,
     node::TransactionSend,
+    storage::{ProofListIndex, Snapshot},
 };
 
+use std::sync::Arc;
+
+use config::ServiceConfig;
+use cypher;
+use limits;
+use neo4j::GraphBackend;
 use schema::Schema;
-use structures::Neo4jTransaction;
-use transactions::Neo4JTransactions;
+use structures::{
+    definition_hash, FailureCode, Neo4jTransaction, NodeChange, Proposal, ProposalStatus,
+    ScheduledBatch, SchemaChangeRecord, SchemaObject, StatementClassification, StatusTransition,
+    TransactionFailure, TransactionStatus,
+};
+use transactions::{proposal_transaction_hash, Neo4JTransactions};
 
-use std::io;
+use serde_json;
 
 /// Describes the query parameters for the `insert_transaction` endpoint.
 encoding_struct! {
@@ -28,9 +40,368 @@ encoding_struct! {
 pub struct CommitResponse {
     /// Hash of the transaction.
     pub tx_hash: Hash,
-    ///error msg, if none, is empty
-    pub error_msg: std::string::String,
 }
+
+/// Errors returned by the service API.
+#[derive(Debug, Fail)]
+pub enum ApiError {
+    ///Hash in the request could not be parsed
+    #[fail(display = "Invalid hash: {}", _0)]
+    InvalidHash(String),
+    ///Other request parameter has an invalid value
+    #[fail(display = "Invalid parameter: {}", _0)]
+    InvalidParameter(String),
+    ///No neo4j transaction with the requested hash
+    #[fail(display = "Transaction not found: {}", _0)]
+    TransactionNotFound(String),
+    ///Transaction could not be handed over to the node
+    #[fail(display = "Transaction could not be sent: {}", _0)]
+    MempoolUnavailable(String),
+    ///Stored value could not be decoded
+    #[fail(display = "Corrupt entry: {}", _0)]
+    CorruptEntry(String),
+    ///No proposal with the requested hash
+    #[fail(display = "Proposal not found: {}", _0)]
+    ProposalNotFound(String),
+    ///Transaction is larger than the limits allow
+    #[fail(display = "Limit exceeded: {}", _0)]
+    LimitExceeded(String),
+    ///Author submitted too many transactions recently
+    #[fail(display = "Rate limited: {}", _0)]
+    RateLimited(String),
+    ///Queries call a function or procedure of the deny-list
+    #[fail(display = "Invalid query: {}", _0)]
+    InvalidQuery(String),
+    ///Graph database of this node does not accept transactions
+    #[fail(display = "Neo4j unavailable: {}", _0)]
+    Neo4jUnavailable(String),
+}
+
+/// Machine readable body of an error response.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ErrorBody {
+    /// Error code clients can branch on, e.g. `TRANSACTION_NOT_FOUND`.
+    pub code: String,
+    /// Intended HTTP status of the response.
+    pub status: u16,
+    /// Human readable description.
+    pub message: String,
+}
+
+impl ApiError {
+    /// Error code clients can branch on.
+    pub fn code(&self) -> &'static str {
+        match self {
+            ApiError::InvalidHash(_) => "INVALID_HASH",
+            ApiError::InvalidParameter(_) => "INVALID_PARAMETER",
+            ApiError::TransactionNotFound(_) => "TRANSACTION_NOT_FOUND",
+            ApiError::MempoolUnavailable(_) => "MEMPOOL_UNAVAILABLE",
+            ApiError::CorruptEntry(_) => "CORRUPT_ENTRY",
+            ApiError::ProposalNotFound(_) => "PROPOSAL_NOT_FOUND",
+            ApiError::LimitExceeded(_) => "LIMIT_EXCEEDED",
+            ApiError::RateLimited(_) => "RATE_LIMITED",
+            ApiError::InvalidQuery(_) => "INVALID_QUERY",
+            ApiError::Neo4jUnavailable(_) => "NEO4J_UNAVAILABLE",
+        }
+    }
+
+    /// HTTP status matching the error.
+    pub fn status(&self) -> u16 {
+        match self {
+            ApiError::InvalidHash(_)
+            | ApiError::InvalidParameter(_)
+            | ApiError::InvalidQuery(_) => 400,
+            ApiError::TransactionNotFound(_) | ApiError::ProposalNotFound(_) => 404,
+            ApiError::MempoolUnavailable(_) | ApiError::Neo4jUnavailable(_) => 503,
+            ApiError::CorruptEntry(_) => 500,
+            ApiError::LimitExceeded(_) => 413,
+            ApiError::RateLimited(_) => 429,
+        }
+    }
+
+    /// Machine readable body of the error.
+    pub fn body(&self) -> ErrorBody {
+        ErrorBody {
+            code: self.code().to_string(),
+            status: self.status(),
+            message: self.to_string(),
+        }
+    }
+}
+
+/// Exonum's API errors only know 400, 404 and 500, so other refusals of the request are sent as
+/// 400 and unavailability as 500. The intended status is always present in the JSON body.
+impl From<ApiError> for api::Error {
+    fn from(e: ApiError) -> api::Error {
+        let body = serde_json::to_string(&e.body()).unwrap_or_else(|_| e.to_string());
+        match e.status() {
+            404 => api::Error::NotFound(body),
+            400..=499 => api::Error::BadRequest(body),
+            _ => api::Error::InternalError(format_err!("{}", body)),
+        }
+    }
+}
+
+/// Amount of transactions returned by paginated endpoints when no limit is given.
+pub const DEFAULT_PAGE_LIMIT: u64 = 50;
+/// Upper bound for the limit of paginated endpoints.
+pub const MAX_PAGE_LIMIT: u64 = 1000;
+/// Largest amount of transactions looked at by a single `transactions` request, so that
+/// selective filters cannot make one request scan the whole chain.
+pub const MAX_SCANNED_TRANSACTIONS: u64 = 10 * MAX_PAGE_LIMIT;
+
+/// Describes the query parameters for the `author_transactions` endpoint.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct AuthorTransactionsQuery {
+    /// Public key of the author.
+    pub pub_key: PublicKey,
+    /// Position of the first transaction to return, 0 when not given.
+    pub offset: Option<u64>,
+    /// Maximum amount of transactions to return.
+    pub limit: Option<u64>,
+}
+
+/// Describes the query parameters for the `transactions` endpoint.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct TransactionsQuery {
+    /// Position in the ordered transaction list to start from, 0 when not given. With
+    /// `pub_key`, the position in the transactions of that author.
+    pub cursor: Option<u64>,
+    /// Maximum amount of transactions to return.
+    pub limit: Option<u64>,
+    /// Only return transactions with this result, the name of a `TransactionStatus`.
+    pub status: Option<String>,
+    /// Only return transactions submitted by this author.
+    pub pub_key: Option<PublicKey>,
+    /// Only return transactions committed at this block height or later.
+    pub from_height: Option<u64>,
+    /// Only return transactions committed at this block height or earlier.
+    pub to_height: Option<u64>,
+}
+
+/// Neo4j transaction together with its hash and location.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct TransactionEntry {
+    /// Hash of the transaction.
+    pub tx_hash: Hash,
+    /// Height of the block the transaction was committed in.
+    pub height: Option<u64>,
+    /// Stored transaction, including its result.
+    pub transaction: Neo4jTransaction,
+    /// Why Neo4j failed to execute the transaction, if it did.
+    pub failure: Option<FailureDetails>,
+}
+
+/// Failure details of a transaction, as returned by the API.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct FailureDetails {
+    /// Reason of the failure, none if it is unknown to this version of the service.
+    pub code: Option<FailureCode>,
+    /// Index of the failed statement among the transaction's queries.
+    pub statement_index: Option<u32>,
+    /// The failed statement.
+    pub query: Option<String>,
+    /// Error Neo4j gave for the failed statement.
+    pub query_error: Option<String>,
+    /// Neo4j status code of the failed statement.
+    pub neo4j_code: Option<String>,
+}
+
+impl From<TransactionFailure> for FailureDetails {
+    fn from(failure: TransactionFailure) -> Self {
+        let non_empty = |value: &str| {
+            if value.is_empty() {
+                None
+            } else {
+                Some(value.to_string())
+            }
+        };
+        let has_query = !failure.query().is_empty();
+        FailureDetails {
+            code: failure.failure_code(),
+            statement_index: if has_query {
+                Some(failure.statement_index())
+            } else {
+                None
+            },
+            query: non_empty(failure.query()),
+            query_error: non_empty(failure.query_error()),
+            neo4j_code: non_empty(failure.neo4j_code()),
+        }
+    }
+}
+
+/// A single page of transactions.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct TransactionsPage {
+    /// Total amount of transactions available, before filtering.
+    pub total: u64,
+    /// Transactions on this page.
+    pub transactions: Vec<TransactionEntry>,
+    /// Cursor for the next page, none when there are no more transactions.
+    pub next_cursor: Option<u64>,
+    /// Hashes of the transactions left out of the page because they cannot be decoded.
+    #[serde(default)]
+    pub corrupt: Vec<Hash>,
+}
+
+/// Describes the query parameters for the `label_nodes` endpoint.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct LabelNodesQuery {
+    /// Label name.
+    pub label: String,
+}
+
+/// Describes the query parameters for the `property_nodes` endpoint.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct PropertyNodesQuery {
+    /// Property key.
+    pub key: String,
+}
+
+/// Status transition of a transaction, as returned by the `transaction_status_history` endpoint.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct StatusTransitionEntry {
+    /// New status, none if it is unknown to this version of the service.
+    pub status: Option<TransactionStatus>,
+    /// Height of the block in which the transition happened.
+    pub height: u64,
+}
+
+/// Describes the query parameters for the `proposals` endpoint.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ProposalsQuery {
+    /// Only return proposals in this state, the name of a `ProposalStatus`.
+    pub status: Option<ProposalStatus>,
+}
+
+/// Approval of a proposal.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ApprovalEntry {
+    /// Key of the approving author.
+    pub pub_key: PublicKey,
+    /// Height of the block the approval was committed in.
+    pub height: u64,
+}
+
+/// Proposal together with its approvals.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ProposalEntry {
+    /// Hash of the `ProposeQueries` transaction.
+    pub proposal_hash: Hash,
+    /// State of the proposal at the current height.
+    pub status: ProposalStatus,
+    /// Stored proposal.
+    pub proposal: Proposal,
+    /// Approvals so far.
+    pub approvals: Vec<ApprovalEntry>,
+    /// Hash of the neo4j transaction the queries were sent as, once approved.
+    pub tx_hash: Option<Hash>,
+}
+
+/// Scheduled batch waiting for activation.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ScheduledEntry {
+    /// Hash of the `ScheduleQueries` transaction.
+    pub batch_hash: Hash,
+    /// Queries of the batch.
+    pub queries: String,
+    /// Stored activation.
+    pub batch: ScheduledBatch,
+}
+
+/// Describes the query parameters for the `schema_object_history` endpoint.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SchemaObjectHistoryQuery {
+    /// Definition of the index or constraint, like `INDEX ON :Person(name)`.
+    pub definition: String,
+}
+
+/// Successful schema change of an index or constraint.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SchemaChangeEntry {
+    /// Hash of the `SchemaChange` transaction.
+    pub tx_hash: Hash,
+    /// Stored statement.
+    pub change: Option<SchemaChangeRecord>,
+}
+
+/// Describes the query parameters for the `audit_divergences` endpoint.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct AuditDivergencesQuery {
+    /// Only return the divergences of this block.
+    pub block_hash: Option<Hash>,
+}
+
+/// Changes of an audited block a validator reported differently from the recorded ones.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct AuditDivergenceEntry {
+    /// Hash of the audited block.
+    pub block_hash: Hash,
+    /// Service key of the diverging validator.
+    pub pub_key: PublicKey,
+    /// Digest of the changes the validator reported.
+    pub digest: Hash,
+}
+
+/// Largest amount of blocks scanned by a single `events` request.
+pub const MAX_EVENT_BLOCKS: u64 = 100;
+
+/// Describes the query parameters for the `events` endpoint.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct EventsQuery {
+    /// Height of the first block to return events for. When not given only
+    /// the height to poll from next is returned.
+    pub from_height: Option<u64>,
+    /// Only return events that touch this node.
+    pub node_uuid: Option<String>,
+    /// Only return events that touch nodes which ever carried this label.
+    pub label: Option<String>,
+    /// Only return events of transactions submitted by this author.
+    pub pub_key: Option<PublicKey>,
+}
+
+/// Something that happened to a neo4j transaction or the graph in a block.
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(tag = "type")]
+pub enum Event {
+    /// Transaction was committed, or its result was recorded by an audit.
+    #[serde(rename = "status")]
+    Status {
+        /// Height of the block in which this happened.
+        height: u64,
+        /// Hash of the transaction.
+        tx_hash: Hash,
+        /// Author of the transaction.
+        pub_key: PublicKey,
+        /// Result of the transaction after this event.
+        status: TransactionStatus,
+        /// Error from the database, if any.
+        error_msg: String,
+        /// Why Neo4j failed to execute the transaction, set with the `ERROR` status.
+        failure: Option<FailureDetails>,
+    },
+    /// An audited change of the graph.
+    #[serde(rename = "node_change")]
+    Change {
+        /// Height of the block in which the change was audited.
+        height: u64,
+        /// Hash of the transaction that made the change.
+        tx_hash: Hash,
+        /// The change itself.
+        change: NodeChange,
+    },
+}
+
+/// Events of a range of blocks.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct EventsPage {
+    /// Events in the order they happened.
+    pub events: Vec<Event>,
+    /// Height to poll from next.
+    pub next_height: u64,
+}
+
 ///Node history query
 encoding_struct! {
     ///Node history query
@@ -49,29 +420,165 @@ encoding_struct! {
     }
 }
 
-///Node history line, includes transaction hash in hex format and description
-encoding_struct! {
-    ///Node history line, includes transaction hash in hex format and description
-    struct NodeHistoryLine {
-        ///transaction_id
-        transaction_id: &str,
-        ///description
-        description: &str,
-    }
-}
-
 /// Public service API description.
 #[derive(Debug, Clone)]
 pub struct Neo4JApi;
 
 impl Neo4JApi {
-    /// Endpoint for dumping all queries from the storage.
-    pub fn get_queries(state: &ServiceApiState, _query: ()) -> api::Result<Vec<Neo4jTransaction>> {
+    /// Looks up a stored transaction, reporting it if it cannot be decoded.
+    fn stored_transaction<T: AsRef<dyn Snapshot>>(
+        schema: &Schema<T>,
+        tx_hash: &Hash,
+    ) -> Result<Option<Neo4jTransaction>, ApiError> {
+        match schema.neo4j_transactions_raw().get(tx_hash) {
+            Some(bytes) => Neo4jTransaction::decode(&bytes).map(Some).map_err(|e| {
+                ApiError::CorruptEntry(format!("transaction {}: {}", tx_hash.to_hex(), e))
+            }),
+            None => Ok(None),
+        }
+    }
+
+    /// Looks up a stored transaction, which must exist.
+    fn existing_transaction<T: AsRef<dyn Snapshot>>(
+        schema: &Schema<T>,
+        tx_hash: &Hash,
+    ) -> Result<Neo4jTransaction, ApiError> {
+        Self::stored_transaction(schema, tx_hash)?
+            .ok_or_else(|| ApiError::TransactionNotFound(tx_hash.to_hex()))
+    }
+
+    /// Looks up the failure details of a transaction, reporting them if they cannot be decoded.
+    fn stored_failure<T: AsRef<dyn Snapshot>>(
+        schema: &Schema<T>,
+        tx_hash: &Hash,
+    ) -> Result<Option<FailureDetails>, ApiError> {
+        match schema.transaction_failures_raw().get(tx_hash) {
+            Some(bytes) => TransactionFailure::decode(&bytes)
+                .map(|failure| Some(FailureDetails::from(failure)))
+                .map_err(|e| {
+                    ApiError::CorruptEntry(format!("failure of {}: {}", tx_hash.to_hex(), e))
+                }),
+            None => Ok(None),
+        }
+    }
+
+    /// Decodes the status transitions of a transaction, reporting the first corrupt one.
+    fn stored_status_history<T: AsRef<dyn Snapshot>>(
+        schema: &Schema<T>,
+        tx_hash: &Hash,
+    ) -> Result<Vec<StatusTransition>, ApiError> {
+        schema
+            .status_history_raw(tx_hash)
+            .iter()
+            .enumerate()
+            .map(|(i, bytes)| {
+                StatusTransition::decode(&bytes).map_err(|e| {
+                    ApiError::CorruptEntry(format!(
+                        "status transition {} of {}: {}",
+                        i,
+                        tx_hash.to_hex(),
+                        e
+                    ))
+                })
+            })
+            .collect()
+    }
+
+    /// Looks up a transaction together with the height of the block it was committed in.
+    fn transaction_entry(
+        core_schema: &CoreSchema<&dyn Snapshot>,
+        schema: &Schema<&dyn Snapshot>,
+        tx_hash: Hash,
+    ) -> Result<Option<TransactionEntry>, ApiError> {
+        let transaction = match Self::stored_transaction(schema, &tx_hash)? {
+            Some(transaction) => transaction,
+            None => return Ok(None),
+        };
+        Ok(Some(TransactionEntry {
+            tx_hash,
+            height: core_schema
+                .transactions_locations()
+                .get(&tx_hash)
+                .map(|location| location.block_height().0),
+            failure: Self::stored_failure(schema, &tx_hash)?,
+            transaction,
+        }))
+    }
+
+    /// Endpoint for listing queries from the storage in commit order, a page at a time.
+    pub fn get_queries(
+        state: &ServiceApiState,
+        query: TransactionsQuery,
+    ) -> api::Result<TransactionsPage> {
         let snapshot = state.snapshot();
-        let schema = Schema::new(snapshot);
-        let idx = schema.neo4j_transactions();
-        let values = idx.values().collect();
-        Ok(values)
+        let core_schema = CoreSchema::new(snapshot.as_ref());
+        let schema = Schema::new(snapshot.as_ref());
+        let status = match query.status {
+            Some(ref status) => Some(
+                status
+                    .to_uppercase()
+                    .parse::<TransactionStatus>()
+                    .map_err(ApiError::InvalidParameter)?,
+            ),
+            None => None,
+        };
+        let limit = query
+            .limit
+            .unwrap_or(DEFAULT_PAGE_LIMIT)
+            .min(MAX_PAGE_LIMIT) as usize;
+
+        // The transactions of an author are indexed, so they are not searched among all others.
+        let idx = match query.pub_key {
+            Some(ref pub_key) => schema.author_transactions(pub_key),
+            None => schema.neo4j_transactions_ordered(),
+        };
+        let start = query.cursor.unwrap_or(0);
+        let mut transactions = Vec::new();
+        let mut corrupt = Vec::new();
+        let mut next_cursor = None;
+        let mut position = start;
+        for tx_hash in idx.iter_from(start) {
+            if transactions.len() == limit || position - start == MAX_SCANNED_TRANSACTIONS {
+                next_cursor = Some(position);
+                break;
+            }
+            position += 1;
+
+            let entry = match Self::transaction_entry(&core_schema, &schema, tx_hash) {
+                Ok(Some(entry)) => entry,
+                Ok(None) => continue,
+                Err(e) => {
+                    warn!(
+                        "Leaving transaction {} out of a page: {}",
+                        tx_hash.to_hex(),
+                        e
+                    );
+                    corrupt.push(tx_hash);
+                    continue;
+                }
+            };
+            if status.is_some() && entry.transaction.status() != status {
+                continue;
+            }
+            if let Some(from_height) = query.from_height {
+                if entry.height.map_or(true, |height| height < from_height) {
+                    continue;
+                }
+            }
+            if let Some(to_height) = query.to_height {
+                if entry.height.map_or(true, |height| height > to_height) {
+                    continue;
+                }
+            }
+            transactions.push(entry);
+        }
+
+        Ok(TransactionsPage {
+            total: idx.len(),
+            transactions,
+            next_cursor,
+            corrupt,
+        })
     }
 
     /// Returns transaction based on provided hash.
@@ -82,72 +589,534 @@ impl Neo4JApi {
         let snapshot = state.snapshot();
         let schema = Schema::new(snapshot);
         match Hash::from_hex(query.hash_string()) {
-            Ok(query_hash) => {
-                let query = schema.neo4j_transaction(&query_hash);
-                match query {
-                    Some(x) => Ok(x),
-                    None => Err(api::Error::from(io::Error::new(
-                        io::ErrorKind::Other,
-                        "No query found",
-                    ))),
-                }
+            Ok(query_hash) => Ok(Self::existing_transaction(&schema, &query_hash)?),
+            Err(e) => Err(ApiError::InvalidHash(format!("{:?}", e)).into()),
+        }
+    }
+
+    /// Returns failure details of a transaction based on provided hash.
+    pub fn get_transaction_failure(
+        state: &ServiceApiState,
+        query: GetQueryQuery,
+    ) -> api::Result<Option<FailureDetails>> {
+        let snapshot = state.snapshot();
+        let schema = Schema::new(snapshot);
+        let hash = Hash::from_hex(query.hash_string())
+            .map_err(|e| ApiError::InvalidHash(format!("{:?}", e)))?;
+        Self::existing_transaction(&schema, &hash)?;
+        Ok(Self::stored_failure(&schema, &hash)?)
+    }
+
+    /// Returns the classes of the statements of a transaction based on provided hash, none for
+    /// transactions that do not commit queries.
+    pub fn get_statement_classes(
+        state: &ServiceApiState,
+        query: GetQueryQuery,
+    ) -> api::Result<Option<StatementClassification>> {
+        let snapshot = state.snapshot();
+        let schema = Schema::new(snapshot);
+        let hash = Hash::from_hex(query.hash_string())
+            .map_err(|e| ApiError::InvalidHash(format!("{:?}", e)))?;
+        Self::existing_transaction(&schema, &hash)?;
+        Ok(schema.statement_classes().get(&hash))
+    }
+
+    /// Returns status transitions of a transaction based on provided hash.
+    pub fn get_transaction_status_history(
+        state: &ServiceApiState,
+        query: GetQueryQuery,
+    ) -> api::Result<Vec<StatusTransitionEntry>> {
+        let snapshot = state.snapshot();
+        let schema = Schema::new(snapshot);
+        let hash = Hash::from_hex(query.hash_string())
+            .map_err(|e| ApiError::InvalidHash(format!("{:?}", e)))?;
+        Self::existing_transaction(&schema, &hash)?;
+        let values = Self::stored_status_history(&schema, &hash)?
+            .into_iter()
+            .map(|transition| StatusTransitionEntry {
+                status: transition.transaction_status(),
+                height: transition.height(),
+            })
+            .collect();
+        Ok(values)
+    }
+
+    /// Builds the API view of a stored proposal.
+    fn proposal_entry(
+        schema: &Schema<&dyn Snapshot>,
+        height: Height,
+        proposal_hash: Hash,
+        proposal: Proposal,
+    ) -> ProposalEntry {
+        let approvals = schema
+            .proposal_approvals(&proposal_hash)
+            .iter()
+            .map(|(pub_key, height)| ApprovalEntry { pub_key, height })
+            .collect();
+        ProposalEntry {
+            proposal_hash,
+            status: proposal.status(height),
+            tx_hash: if proposal.approved() {
+                Some(proposal_transaction_hash(&proposal_hash))
+            } else {
+                None
+            },
+            proposal,
+            approvals,
+        }
+    }
+
+    /// Returns a proposal and its approvals based on provided hash.
+    pub fn get_proposal(
+        state: &ServiceApiState,
+        query: GetQueryQuery,
+    ) -> api::Result<ProposalEntry> {
+        let snapshot = state.snapshot();
+        let core_schema = CoreSchema::new(snapshot.as_ref());
+        let schema = Schema::new(snapshot.as_ref());
+        let hash = Hash::from_hex(query.hash_string())
+            .map_err(|e| ApiError::InvalidHash(format!("{:?}", e)))?;
+        let proposal = schema
+            .proposals()
+            .get(&hash)
+            .ok_or_else(|| ApiError::ProposalNotFound(hash.to_hex()))?;
+        let height = Height(core_schema.block_hashes_by_height().len());
+        Ok(Self::proposal_entry(&schema, height, hash, proposal))
+    }
+
+    /// Returns all proposals, optionally only those in the given state.
+    pub fn get_proposals(
+        state: &ServiceApiState,
+        query: ProposalsQuery,
+    ) -> api::Result<Vec<ProposalEntry>> {
+        let snapshot = state.snapshot();
+        let core_schema = CoreSchema::new(snapshot.as_ref());
+        let schema = Schema::new(snapshot.as_ref());
+        let height = Height(core_schema.block_hashes_by_height().len());
+        let entries = schema
+            .proposals()
+            .iter()
+            .filter(|&(_, ref proposal)| {
+                query.status.map_or(true, |s| proposal.status(height) == s)
+            })
+            .map(|(hash, proposal)| Self::proposal_entry(&schema, height, hash, proposal))
+            .collect();
+        Ok(entries)
+    }
+
+    /// Returns the scheduled batches that were neither sent to Neo4j nor cancelled yet.
+    pub fn get_scheduled(state: &ServiceApiState, _query: ()) -> api::Result<Vec<ScheduledEntry>> {
+        let snapshot = state.snapshot();
+        let schema = Schema::new(snapshot.as_ref());
+        let mut entries = Vec::new();
+        for (batch_hash, batch) in schema.scheduled().iter() {
+            entries.push(ScheduledEntry {
+                batch_hash,
+                queries: Self::stored_transaction(&schema, &batch_hash)?
+                    .map(|t| t.queries().to_string())
+                    .unwrap_or_default(),
+                batch,
+            });
+        }
+        Ok(entries)
+    }
+
+    /// Returns the indexes and constraints active in Neo4j.
+    pub fn get_schema_objects(
+        state: &ServiceApiState,
+        _query: (),
+    ) -> api::Result<Vec<SchemaObject>> {
+        let snapshot = state.snapshot();
+        let schema = Schema::new(snapshot.as_ref());
+        let objects = schema.schema_objects().values().collect();
+        Ok(objects)
+    }
+
+    /// Returns the successful schema changes of an index or constraint, including those of
+    /// dropped ones.
+    pub fn get_schema_object_history(
+        state: &ServiceApiState,
+        query: SchemaObjectHistoryQuery,
+    ) -> api::Result<Vec<SchemaChangeEntry>> {
+        let snapshot = state.snapshot();
+        let schema = Schema::new(snapshot.as_ref());
+        let entries = schema
+            .schema_object_history(&definition_hash(&query.definition))
+            .iter()
+            .map(|tx_hash| SchemaChangeEntry {
+                tx_hash,
+                change: schema.schema_changes().get(&tx_hash),
+            })
+            .collect();
+        Ok(entries)
+    }
+
+    /// Returns the validators whose audits diverged from the recorded changes, for every block
+    /// or only the given one.
+    pub fn get_audit_divergences(
+        state: &ServiceApiState,
+        query: AuditDivergencesQuery,
+    ) -> api::Result<Vec<AuditDivergenceEntry>> {
+        let snapshot = state.snapshot();
+        let schema = Schema::new(snapshot.as_ref());
+        let blocks: Vec<Hash> = match query.block_hash {
+            Some(block_hash) => vec![block_hash],
+            None => schema.audit_divergence_roots().keys().collect(),
+        };
+        let mut entries = Vec::new();
+        for block_hash in blocks {
+            for (pub_key, digest) in schema.audit_divergences(&block_hash).iter() {
+                entries.push(AuditDivergenceEntry {
+                    block_hash,
+                    pub_key,
+                    digest,
+                });
             }
-            Err(e) => Err(api::Error::from(io::Error::new(
-                io::ErrorKind::Other,
-                format!("Error unpacking hash: {:?}", e),
-            ))),
         }
+        Ok(entries)
+    }
+
+    /// Decodes stored changes, reporting the position of the first corrupt one.
+    fn decode_changes(
+        idx: &ProofListIndex<&dyn Snapshot, Vec<u8>>,
+    ) -> Result<Vec<NodeChange>, ApiError> {
+        idx.iter()
+            .enumerate()
+            .map(|(i, bytes)| {
+                NodeChange::decode(&bytes)
+                    .map_err(|e| ApiError::CorruptEntry(format!("change {}: {}", i, e)))
+            })
+            .collect()
     }
 
     /// Endpoint for getting a single node's history by providing it's uuid.
+    /// Nodes without changes have an empty history.
     pub fn get_node_history(
         state: &ServiceApiState,
         query: NodeHistoryQuery,
-    ) -> api::Result<Vec<NodeHistoryLine>> {
-        println!("Getting node history");
+    ) -> api::Result<Vec<NodeChange>> {
+        let snapshot = state.snapshot();
+        let schema = Schema::new(snapshot);
+        let idx = schema.node_history_raw(query.node_uuid());
+        let values = Self::decode_changes(&idx)?;
+        Ok(values)
+    }
+
+    /// Endpoint for getting the changes a single transaction made to the graph, by providing its hash.
+    pub fn get_transaction_changes(
+        state: &ServiceApiState,
+        query: GetQueryQuery,
+    ) -> api::Result<Vec<NodeChange>> {
         let snapshot = state.snapshot();
         let schema = Schema::new(snapshot);
-        let idx = schema.node_history(query.node_uuid());
-        let mut values = Vec::new();
-        for val in idx.iter() {
-            let new_line: NodeHistoryLine =
-                NodeHistoryLine::new(val.get_transaction_id(), format!("{}", val).as_str());
-            values.push(new_line);
+        match Hash::from_hex(query.hash_string()) {
+            Ok(transaction_hash) => {
+                Self::existing_transaction(&schema, &transaction_hash)?;
+                let idx = schema.transaction_changes_raw(&transaction_hash);
+                let values = Self::decode_changes(&idx)?;
+                Ok(values)
+            }
+            Err(e) => Err(ApiError::InvalidHash(format!("{:?}", e)).into()),
         }
+    }
+
+    /// Endpoint for listing transactions submitted by a single author, with their results.
+    pub fn get_author_transactions(
+        state: &ServiceApiState,
+        query: AuthorTransactionsQuery,
+    ) -> api::Result<TransactionsPage> {
+        let snapshot = state.snapshot();
+        let core_schema = CoreSchema::new(snapshot.as_ref());
+        let schema = Schema::new(snapshot.as_ref());
+        let idx = schema.author_transactions(&query.pub_key);
+        let offset = query.offset.unwrap_or(0);
+        let limit = query
+            .limit
+            .unwrap_or(DEFAULT_PAGE_LIMIT)
+            .min(MAX_PAGE_LIMIT);
+
+        let mut transactions = Vec::new();
+        for tx_hash in idx.iter_from(offset).take(limit as usize) {
+            transactions.extend(Self::transaction_entry(&core_schema, &schema, tx_hash)?);
+        }
+        let next_cursor = if offset + limit < idx.len() {
+            Some(offset + limit)
+        } else {
+            None
+        };
+        Ok(TransactionsPage {
+            total: idx.len(),
+            transactions,
+            next_cursor,
+            corrupt: Vec::new(),
+        })
+    }
+
+    /// Endpoint for getting uuids of all nodes that ever carried a label.
+    pub fn get_label_nodes(
+        state: &ServiceApiState,
+        query: LabelNodesQuery,
+    ) -> api::Result<Vec<String>> {
+        let snapshot = state.snapshot();
+        let schema = Schema::new(snapshot);
+        let values = schema.label_nodes(&query.label).iter().collect();
+        Ok(values)
+    }
+
+    /// Endpoint for getting uuids of all nodes where a property key was ever changed.
+    pub fn get_property_nodes(
+        state: &ServiceApiState,
+        query: PropertyNodesQuery,
+    ) -> api::Result<Vec<String>> {
+        let snapshot = state.snapshot();
+        let schema = Schema::new(snapshot);
+        let values = schema.property_nodes(&query.key).iter().collect();
         Ok(values)
     }
 
-    /// Common processing for transaction-accepting endpoints.
+    /// Checks whether an audited change passes the node and label filters of an events query.
+    fn change_matches(
+        schema: &Schema<&dyn Snapshot>,
+        query: &EventsQuery,
+        change: &NodeChange,
+    ) -> bool {
+        let uuids = change.get_uuis();
+        if let Some(ref node_uuid) = query.node_uuid {
+            if !uuids.contains(&node_uuid.as_str()) {
+                return false;
+            }
+        }
+        if let Some(ref label) = query.label {
+            let nodes = schema.label_nodes(label);
+            if !uuids.iter().any(|uuid| nodes.contains(&uuid.to_string())) {
+                return false;
+            }
+        }
+        true
+    }
+
+    /// Collects the events of a neo4j transaction that match the query.
+    /// When `audited` is false only the submission of the transaction is reported.
+    fn push_transaction_events(
+        schema: &Schema<&dyn Snapshot>,
+        query: &EventsQuery,
+        height: u64,
+        tx_hash: Hash,
+        transaction: &Neo4jTransaction,
+        audited: bool,
+        events: &mut Vec<Event>,
+    ) -> Result<(), ApiError> {
+        if let Some(ref pub_key) = query.pub_key {
+            if transaction.pub_key() != pub_key {
+                return Ok(());
+            }
+        }
+        let filtered = query.node_uuid.is_some() || query.label.is_some();
+
+        let mut changes = Vec::new();
+        if audited {
+            let idx = schema.transaction_changes_raw(&tx_hash);
+            for change in Self::decode_changes(&idx)? {
+                if Self::change_matches(schema, query, &change) {
+                    changes.push(Event::Change {
+                        height,
+                        tx_hash,
+                        change,
+                    });
+                }
+            }
+        }
+        if filtered && changes.is_empty() {
+            return Ok(());
+        }
+
+        let mut statuses: Vec<TransactionStatus> = Self::stored_status_history(schema, &tx_hash)?
+            .into_iter()
+            .filter(|transition| transition.height() == height)
+            .filter_map(|transition| transition.transaction_status())
+            .collect();
+        if statuses.is_empty() {
+            // Transactions from before status transitions were recorded.
+            let status = if audited {
+                transaction.status()
+            } else {
+                Some(TransactionStatus::Pending)
+            };
+            statuses.extend(status);
+        }
+        let failure = Self::stored_failure(schema, &tx_hash)?;
+        for status in statuses {
+            let error_msg = if transaction.status() == Some(status) {
+                transaction.error_msg()
+            } else {
+                ""
+            };
+            events.push(Event::Status {
+                height,
+                tx_hash,
+                pub_key: *transaction.pub_key(),
+                status,
+                error_msg: error_msg.to_string(),
+                failure: if status == TransactionStatus::Error {
+                    failure.clone()
+                } else {
+                    None
+                },
+            });
+        }
+        events.extend(changes);
+        Ok(())
+    }
+
+    /// Endpoint for following transaction status changes and audited graph changes block by block.
+    pub fn get_events(state: &ServiceApiState, query: EventsQuery) -> api::Result<EventsPage> {
+        let snapshot = state.snapshot();
+        let core_schema = CoreSchema::new(snapshot.as_ref());
+        let schema = Schema::new(snapshot.as_ref());
+        let block_count = core_schema.block_hashes_by_height().len();
+        let from_height = query.from_height.unwrap_or(block_count);
+        let to_height = block_count.min(from_height.saturating_add(MAX_EVENT_BLOCKS));
+
+        let mut events = Vec::new();
+        for height in from_height..to_height {
+            for tx_hash in schema.block_neo4j_transactions(Height(height)) {
+                if let Some(transaction) = Self::stored_transaction(&schema, &tx_hash)? {
+                    Self::push_transaction_events(
+                        &schema,
+                        &query,
+                        height,
+                        tx_hash,
+                        &transaction,
+                        false,
+                        &mut events,
+                    )?;
+                }
+                for block_hash in schema.audited_blocks(&tx_hash).iter() {
+                    let block = match core_schema.blocks().get(&block_hash) {
+                        Some(block) => block,
+                        None => continue,
+                    };
+                    for audited_hash in schema.block_neo4j_transactions(block.height()) {
+                        if let Some(transaction) = Self::stored_transaction(&schema, &audited_hash)?
+                        {
+                            Self::push_transaction_events(
+                                &schema,
+                                &query,
+                                height,
+                                audited_hash,
+                                &transaction,
+                                true,
+                                &mut events,
+                            )?;
+                        }
+                    }
+                }
+            }
+        }
+
+        Ok(EventsPage {
+            events,
+            next_height: to_height.max(from_height),
+        })
+    }
+
+    /// Common processing for transaction-accepting endpoints. Transactions running queries are
+    /// refused while the graph of this node is unavailable.
     pub fn post_transaction(
         state: &ServiceApiState,
         query: Neo4JTransactions,
+        neo4j: &dyn GraphBackend,
     ) -> api::Result<CommitResponse> {
-        println!("Processing transaction {:?}", &query);
+        // Refuses early what the nodes would refuse, instead of letting it into the mempool.
+        if let Some((queries, pub_key)) = Self::submitted_queries(&query) {
+            if !limits::within_hard_caps(queries) {
+                Err(ApiError::LimitExceeded(String::from(
+                    "Queries exceed the hard caps of the service",
+                )))?;
+            }
+            let snapshot = state.snapshot();
+            let config = ServiceConfig::actual(snapshot.as_ref());
+            let height = Height(
+                CoreSchema::new(snapshot.as_ref())
+                    .block_hashes_by_height()
+                    .len(),
+            );
+            let schema = Schema::new(snapshot.as_ref());
+            limits::check_limits(&schema, &config.limits, queries, pub_key, height).map_err(
+                |(code, description)| match code {
+                    FailureCode::RateLimited => ApiError::RateLimited(description),
+                    _ => ApiError::LimitExceeded(description),
+                },
+            )?;
+            cypher::check_denied(&config.deny_list, queries)
+                .map_err(|e| ApiError::InvalidQuery(e.to_string()))?;
+            let is_schema_change = match query {
+                Neo4JTransactions::SchemaChange(_) => true,
+                _ => false,
+            };
+            if !is_schema_change {
+                let classification = cypher::classify_queries(queries)
+                    .map_err(|e| ApiError::InvalidQuery(e.to_string()))?;
+                cypher::check_classes(&classification)
+                    .map_err(|(_, description)| ApiError::InvalidQuery(description))?;
+            }
+            neo4j.health().map_err(ApiError::Neo4jUnavailable)?;
+        }
         let transaction: Box<dyn Transaction> = query.into();
         let tx_hash = transaction.hash();
 
         match state.sender().send(transaction) {
-            Ok(()) => Ok(CommitResponse {
-                tx_hash,
-                error_msg: "".to_string(),
-            }),
-            Err(err) => Ok(CommitResponse {
-                tx_hash,
-                error_msg: format!("got error: {:?}", err),
-            }),
+            Ok(()) => Ok(CommitResponse { tx_hash }),
+            Err(err) => Err(ApiError::MempoolUnavailable(format!("{:?}", err)).into()),
+        }
+    }
+
+    /// Queries and author of the transactions that run queries on Neo4j.
+    fn submitted_queries(transaction: &Neo4JTransactions) -> Option<(&str, &PublicKey)> {
+        match transaction {
+            Neo4JTransactions::CommitQueries(tx) => Some((tx.queries(), tx.pub_key())),
+            Neo4JTransactions::CommitQueriesWithPreconditions(tx) => {
+                Some((tx.queries(), tx.pub_key()))
+            }
+            Neo4JTransactions::ProposeQueries(tx) => Some((tx.queries(), tx.pub_key())),
+            Neo4JTransactions::ScheduleQueries(tx) => Some((tx.queries(), tx.pub_key())),
+            Neo4JTransactions::SchemaChange(tx) => Some((tx.statement(), tx.pub_key())),
+            _ => None,
         }
     }
 
     /// 'ServiceApiBuilder' facilitates conversion between transactions/read requests and REST
     /// endpoints; for example, it parses `POST`ed JSON into the binary transaction
     /// representation used in Exonum internally.
-    pub fn wire(builder: &mut ServiceApiBuilder) {
+    pub fn wire(builder: &mut ServiceApiBuilder, neo4j: Arc<dyn GraphBackend>) {
         // Binds handlers to specific routes.
         builder
             .public_scope()
             .endpoint("v1/transactions", Self::get_queries)
             .endpoint("v1/node_history", Self::get_node_history)
             .endpoint("v1/transaction", Self::get_transaction)
-            .endpoint_mut("v1/insert_transaction", Self::post_transaction);
+            .endpoint("v1/transaction_changes", Self::get_transaction_changes)
+            .endpoint("v1/transaction_failure", Self::get_transaction_failure)
+            .endpoint("v1/statement_classes", Self::get_statement_classes)
+            .endpoint(
+                "v1/transaction_status_history",
+                Self::get_transaction_status_history,
+            )
+            .endpoint("v1/author_transactions", Self::get_author_transactions)
+            .endpoint("v1/label_nodes", Self::get_label_nodes)
+            .endpoint("v1/property_nodes", Self::get_property_nodes)
+            .endpoint("v1/events", Self::get_events)
+            .endpoint("v1/proposal", Self::get_proposal)
+            .endpoint("v1/proposals", Self::get_proposals)
+            .endpoint("v1/scheduled", Self::get_scheduled)
+            .endpoint("v1/schema_objects", Self::get_schema_objects)
+            .endpoint("v1/schema_object_history", Self::get_schema_object_history)
+            .endpoint("v1/audit_divergences", Self::get_audit_divergences)
+            .endpoint_mut(
+                "v1/insert_transaction",
+                move |state: &ServiceApiState, query: Neo4JTransactions| {
+                    Self::post_transaction(state, query, &*neo4j)
+                },
+            );
     }
 }
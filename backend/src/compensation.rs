@@ -0,0 +1,333 @@
+//! Compensating queries, undoing changes Neo4j has already committed.
+//!
+//! Some changes can only be refused after Neo4j committed them, like those breaking a write
+//! policy. The service then sends queries reverting them, built from the recorded changes and
+//! the node histories. Neo4j gives new uuids to the nodes and relationships these queries
+//! recreate, so the original uuid is kept in the `restored_uuid` property.
+
+use exonum::{crypto::Hash, storage::Snapshot};
+
+use serde_json;
+
+use schema::Schema;
+use structures::NodeChange::{self, AL, AN, ANP, AR, ARP, RL, RN, RNP, RR, RRP};
+
+/// Property holding the original uuid of a recreated node or relationship.
+pub const RESTORED_UUID: &str = "restored_uuid";
+
+/// Queries reverting `changes`, separated by `;`. When the plugin recorded previous property
+/// values they are taken from the changes, with their types, otherwise from the node histories,
+/// in which case the queries must be built before the changes are added to them and the values
+/// are restored as strings.
+///
+/// Deleted entities are recreated first, so that their labels and properties can be restored,
+/// and created entities are deleted last.
+pub fn compensating_queries<T: AsRef<dyn Snapshot>>(
+    schema: &Schema<T>,
+    changes: &[NodeChange],
+    previous_values_recorded: bool,
+) -> String {
+    let history = |value: Option<String>| value.map(|value| literal(&value));
+    let mut recreate_nodes = Vec::new();
+    let mut recreate_relations = Vec::new();
+    let mut restore = Vec::new();
+    let mut delete_relations = Vec::new();
+    let mut delete_nodes = Vec::new();
+
+    for change in changes {
+        match change {
+            RN(x) => recreate_nodes.push(format!(
+                "CREATE (n {{{}: {}}})",
+                RESTORED_UUID,
+                literal(x.node_uuid())
+            )),
+            RR(x) => {
+                // Relations stored before their type was recorded cannot be recreated.
+                let field_type = schema
+                    .relation(x.rel_uuid())
+                    .map(|relation| relation.field_type().to_string())
+                    .unwrap_or_default();
+                if field_type.is_empty() {
+                    continue;
+                }
+                recreate_relations.push(format!(
+                    "MATCH (a), (b) WHERE {} AND {} CREATE (a)-[r:{} {{{}: {}}}]->(b)",
+                    matches_uuid("a", x.from_uuid()),
+                    matches_uuid("b", x.to_uuid()),
+                    identifier(&field_type),
+                    RESTORED_UUID,
+                    literal(x.rel_uuid())
+                ));
+            }
+            AL(x) => restore.push(format!(
+                "{} REMOVE n:{}",
+                match_node(x.node_uuid()),
+                identifier(x.label_name())
+            )),
+            RL(x) => restore.push(format!(
+                "{} SET n:{}",
+                match_node(x.node_uuid()),
+                identifier(x.label_name())
+            )),
+            ANP(x) => restore.push(restore_property(
+                match_node(x.node_uuid()),
+                "n",
+                x.key(),
+                if previous_values_recorded {
+                    x.previous_value()
+                        .map(|value| typed_literal(value, x.previous_value_type()))
+                } else {
+                    history(previous_node_property(schema, x.node_uuid(), x.key()))
+                },
+            )),
+            RNP(x) => restore.push(restore_property(
+                match_node(x.node_uuid()),
+                "n",
+                x.key(),
+                if previous_values_recorded {
+                    x.previous_value()
+                        .map(|value| typed_literal(value, x.previous_value_type()))
+                } else {
+                    history(previous_node_property(schema, x.node_uuid(), x.key()))
+                },
+            )),
+            ARP(x) => restore.push(restore_property(
+                match_relation(x.relation_uuid()),
+                "r",
+                x.key(),
+                if previous_values_recorded {
+                    x.previous_value()
+                        .map(|value| typed_literal(value, x.previous_value_type()))
+                } else {
+                    history(previous_relation_property(
+                        schema,
+                        x.from_uuid(),
+                        x.relation_uuid(),
+                        x.key(),
+                    ))
+                },
+            )),
+            RRP(x) => restore.push(restore_property(
+                match_relation(x.relation_uuid()),
+                "r",
+                x.key(),
+                if previous_values_recorded {
+                    x.previous_value()
+                        .map(|value| typed_literal(value, x.previous_value_type()))
+                } else {
+                    history(previous_relation_property(
+                        schema,
+                        x.from_uuid(),
+                        x.relation_uuid(),
+                        x.key(),
+                    ))
+                },
+            )),
+            AR(x) => delete_relations.push(format!("{} DELETE r", match_relation(x.rel_uuid()))),
+            AN(x) => delete_nodes.push(format!("{} DETACH DELETE n", match_node(x.node_uuid()))),
+        }
+    }
+
+    recreate_nodes
+        .into_iter()
+        .chain(recreate_relations)
+        .chain(restore)
+        .chain(delete_relations)
+        .chain(delete_nodes)
+        .collect::<Vec<String>>()
+        .join(";")
+}
+
+/// Checks that no other transaction changed the nodes `changes` touch after `transaction_id`
+/// did, returning the first node that was. Relation changes are recorded in the histories of
+/// their end nodes, so later changes of an end node count as well.
+pub fn check_unchanged_since<T: AsRef<dyn Snapshot>>(
+    schema: &Schema<T>,
+    transaction_id: &str,
+    changes: &[NodeChange],
+) -> Result<(), String> {
+    let mut uuids: Vec<&str> = changes.iter().flat_map(NodeChange::get_uuis).collect();
+    uuids.sort();
+    uuids.dedup();
+    for uuid in uuids {
+        let history: Vec<NodeChange> = schema.node_history(uuid).iter().collect();
+        let own = history
+            .iter()
+            .rposition(|change| change.get_transaction_id() == transaction_id);
+        let later = match own {
+            Some(position) => &history[position + 1..],
+            None => &history[..],
+        };
+        if later
+            .iter()
+            .any(|change| change.get_transaction_id() != transaction_id)
+        {
+            return Err(uuid.to_string());
+        }
+    }
+    Ok(())
+}
+
+/// Checks that every transaction committed after `transaction` got its changes recorded,
+/// returning the first one still waiting for them. Until then its changes are unknown, so they
+/// may touch the nodes a revert restores.
+pub fn check_audited_since<T: AsRef<dyn Snapshot>>(
+    schema: &Schema<T>,
+    transaction: &Hash,
+) -> Result<(), Hash> {
+    let ordered = schema.neo4j_transactions_ordered();
+    for index in (0..ordered.len()).rev() {
+        let later = match ordered.get(index) {
+            Some(ref later) if later == transaction => break,
+            Some(later) => later,
+            None => break,
+        };
+        let awaits_changes = schema
+            .neo4j_transaction(&later)
+            .and_then(|t| t.status())
+            .map_or(false, |status| status.awaits_changes());
+        if awaits_changes {
+            return Err(later);
+        }
+    }
+    Ok(())
+}
+
+/// Cypher string literal. Statements are split on `;`, so it is written as an escape.
+pub fn literal(value: &str) -> String {
+    let escaped = value
+        .replace('\\', "\\\\")
+        .replace('\'', "\\'")
+        .replace(';', "\\u003B");
+    format!("'{}'", escaped)
+}
+
+/// Cypher expression for a previous property value the plugin reported with `value_type`, such
+/// as `INTEGER`, `DATE` or `LIST<FLOAT>`. Lists are reported as JSON arrays of their elements.
+/// Values without a type, of an unknown type, or that do not read as their type are written as
+/// strings.
+pub fn typed_literal(value: &str, value_type: Option<&str>) -> String {
+    let value_type = match value_type {
+        Some(value_type) => value_type,
+        None => return literal(value),
+    };
+    if value_type.starts_with("LIST<") && value_type.ends_with('>') {
+        let element_type = &value_type["LIST<".len()..value_type.len() - 1];
+        return match serde_json::from_str::<Vec<String>>(value) {
+            Ok(elements) => {
+                let elements: Vec<String> = elements
+                    .iter()
+                    .map(|element| typed_literal(element, Some(element_type)))
+                    .collect();
+                format!("[{}]", elements.join(", "))
+            }
+            Err(_) => literal(value),
+        };
+    }
+    let function = match value_type {
+        "BOOLEAN" if value == "true" || value == "false" => return value.to_string(),
+        "INTEGER" if value.parse::<i64>().is_ok() => return value.to_string(),
+        "FLOAT" => return float_literal(value).unwrap_or_else(|| literal(value)),
+        "DATE" => "date",
+        "TIME" => "time",
+        "LOCAL_TIME" => "localtime",
+        "DATE_TIME" => "datetime",
+        "LOCAL_DATE_TIME" => "localdatetime",
+        "DURATION" => "duration",
+        _ => return literal(value),
+    };
+    format!("{}({})", function, literal(value))
+}
+
+/// Cypher expression for a float written by Java, which has no literals for NaN and infinities.
+fn float_literal(value: &str) -> Option<String> {
+    match value {
+        "NaN" => Some(String::from("0.0 / 0.0")),
+        "Infinity" => Some(String::from("1.0 / 0.0")),
+        "-Infinity" => Some(String::from("-1.0 / 0.0")),
+        _ => match value.parse::<f64>() {
+            Ok(number) if number.is_finite() => Some(value.to_string()),
+            _ => None,
+        },
+    }
+}
+
+/// Cypher identifier, for labels, relationship types and property keys.
+pub fn identifier(name: &str) -> String {
+    format!("`{}`", name.replace('`', "``"))
+}
+
+fn matches_uuid(variable: &str, uuid: &str) -> String {
+    let value = literal(uuid);
+    format!(
+        "({0}.uuid = {1} OR {0}.{2} = {1})",
+        variable, value, RESTORED_UUID
+    )
+}
+
+fn match_node(uuid: &str) -> String {
+    format!("MATCH (n) WHERE {}", matches_uuid("n", uuid))
+}
+
+fn match_relation(uuid: &str) -> String {
+    format!("MATCH ()-[r]->() WHERE {}", matches_uuid("r", uuid))
+}
+
+/// Sets the property back to `previous`, a Cypher expression, or removes it.
+fn restore_property(
+    matched: String,
+    variable: &str,
+    key: &str,
+    previous: Option<String>,
+) -> String {
+    match previous {
+        Some(value) => format!(
+            "{} SET {}.{} = {}",
+            matched,
+            variable,
+            identifier(key),
+            value
+        ),
+        None => format!("{} REMOVE {}.{}", matched, variable, identifier(key)),
+    }
+}
+
+/// Value of a node property before the current changes, if it was set.
+fn previous_node_property<T: AsRef<dyn Snapshot>>(
+    schema: &Schema<T>,
+    node_uuid: &str,
+    key: &str,
+) -> Option<String> {
+    let mut value = None;
+    for change in schema.node_history(node_uuid).iter() {
+        match change {
+            ANP(ref x) if x.node_uuid() == node_uuid && x.key() == key => {
+                value = Some(x.value().to_string())
+            }
+            RNP(ref x) if x.node_uuid() == node_uuid && x.key() == key => value = None,
+            _ => {}
+        }
+    }
+    value
+}
+
+/// Value of a relation property before the current changes, if it was set. Relation changes
+/// are recorded in the histories of their end nodes.
+fn previous_relation_property<T: AsRef<dyn Snapshot>>(
+    schema: &Schema<T>,
+    from_uuid: &str,
+    relation_uuid: &str,
+    key: &str,
+) -> Option<String> {
+    let mut value = None;
+    for change in schema.node_history(from_uuid).iter() {
+        match change {
+            ARP(ref x) if x.relation_uuid() == relation_uuid && x.key() == key => {
+                value = Some(x.value().to_string())
+            }
+            RRP(ref x) if x.relation_uuid() == relation_uuid && x.key() == key => value = None,
+            _ => {}
+        }
+    }
+    value
+}
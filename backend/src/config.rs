@@ -0,0 +1,211 @@
+//! Global configuration of the service.
+//!
+//! The configuration is part of the blockchain configuration, so it is changed through
+//! proposals and votes of the `exonum-configuration` service like any other setting.
+
+use exonum::{blockchain::Schema as CoreSchema, crypto::PublicKey, storage::Snapshot};
+use serde_json::{self, Value};
+
+use SERVICE_NAME;
+
+/// Roles an author in the allow-list can have.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Role {
+    /// May do everything the other roles may.
+    Admin,
+    /// May commit queries.
+    Writer,
+    /// May change the database schema.
+    SchemaManager,
+    /// May approve proposed queries.
+    Approver,
+}
+
+/// Author allowed to submit transactions.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Author {
+    /// Key the author signs transactions with.
+    pub pub_key: PublicKey,
+    /// Roles of the author.
+    pub roles: Vec<Role>,
+}
+
+/// Labels and relationship types an author may write.
+/// Empty lists mean the author may not write any node or relation.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct WritePolicy {
+    /// Key the policy applies to.
+    pub pub_key: PublicKey,
+    /// Labels of the nodes the author may create, change or delete.
+    #[serde(default)]
+    pub labels: Vec<String>,
+    /// Types of the relationships the author may create, change or delete.
+    #[serde(default)]
+    pub relationship_types: Vec<String>,
+}
+
+/// Approvals needed before proposed queries are sent to Neo4j.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct ApprovalConfig {
+    /// Amount of distinct approvers a proposal needs.
+    pub threshold: u32,
+    /// Amount of blocks after which a proposal no longer accepts approvals.
+    pub ttl: u64,
+}
+
+impl Default for ApprovalConfig {
+    fn default() -> Self {
+        ApprovalConfig {
+            threshold: 2,
+            ttl: 100,
+        }
+    }
+}
+
+/// Limits on the size and rate of the queries an author submits. A zero disables a limit.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct LimitsConfig {
+    /// Largest amount of `;` separated statements in a transaction.
+    pub max_statements: u32,
+    /// Largest size of the queries of a transaction, in bytes.
+    pub max_query_bytes: u64,
+    /// Largest amount of transactions of an author within `window` blocks.
+    pub max_transactions: u32,
+    /// Amount of blocks the transactions of an author are counted over.
+    pub window: u64,
+}
+
+impl Default for LimitsConfig {
+    fn default() -> Self {
+        LimitsConfig {
+            max_statements: 100,
+            max_query_bytes: 64 * 1024,
+            max_transactions: 50,
+            window: 10,
+        }
+    }
+}
+
+/// Functions, procedures and clauses queries may not use, since their results differ between
+/// validators. An entry ending in `()` only denies calls without arguments, one ending in `.*`
+/// a whole namespace. Names are compared case-insensitively.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct DenyList {
+    /// Denied functions.
+    pub functions: Vec<String>,
+    /// Denied procedures, called with `CALL`.
+    pub procedures: Vec<String>,
+    /// Denied clauses, given as their keywords separated by spaces, such as `LOAD CSV`.
+    /// Configurations without the field deny the default clauses.
+    #[serde(default = "DenyList::default_clauses")]
+    pub clauses: Vec<String>,
+}
+
+impl DenyList {
+    /// Clauses denied by default. `LOAD CSV` reads files or URLs, which differ between
+    /// validators.
+    pub fn default_clauses() -> Vec<String> {
+        vec![String::from("LOAD CSV")]
+    }
+}
+
+impl Default for DenyList {
+    fn default() -> Self {
+        let functions = [
+            "rand",
+            "randomUUID",
+            "timestamp",
+            "date()",
+            "datetime()",
+            "localdatetime()",
+            "time()",
+            "localtime()",
+            // Clock functions read the current time whatever their arguments.
+            "date.realtime",
+            "date.statement",
+            "date.transaction",
+            "datetime.realtime",
+            "datetime.statement",
+            "datetime.transaction",
+            "localdatetime.realtime",
+            "localdatetime.statement",
+            "localdatetime.transaction",
+            "time.realtime",
+            "time.statement",
+            "time.transaction",
+            "localtime.realtime",
+            "localtime.statement",
+            "localtime.transaction",
+            "apoc.*",
+        ];
+        DenyList {
+            functions: functions.iter().map(|f| f.to_string()).collect(),
+            procedures: vec![String::from("apoc.*")],
+            clauses: DenyList::default_clauses(),
+        }
+    }
+}
+
+/// Configuration of the neo4j service, stored under its name in the `services` section.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct ServiceConfig {
+    /// Authors allowed to submit transactions. Without an allow-list anyone may submit,
+    /// which is the case for chains started before the allow-list existed.
+    #[serde(default)]
+    pub authors: Option<Vec<Author>>,
+    /// Write policies of authors. Authors without a policy may write everything.
+    #[serde(default)]
+    pub write_policies: Vec<WritePolicy>,
+    /// Approvals needed by `ProposeQueries`.
+    #[serde(default)]
+    pub approvals: ApprovalConfig,
+    /// Limits on the queries of a single author.
+    #[serde(default)]
+    pub limits: LimitsConfig,
+    /// Functions and procedures queries may not call.
+    #[serde(default)]
+    pub deny_list: DenyList,
+}
+
+impl ServiceConfig {
+    /// Reads the configuration that is currently in effect.
+    /// A configuration that cannot be parsed allows no one, rather than everyone.
+    pub fn actual<T: AsRef<dyn Snapshot>>(view: T) -> Self {
+        let configuration = CoreSchema::new(view).actual_configuration();
+        match configuration.services.get(SERVICE_NAME) {
+            None | Some(&Value::Null) => ServiceConfig::default(),
+            Some(value) => serde_json::from_value(value.clone()).unwrap_or_else(|e| {
+                error!("Invalid neo4j service configuration: {}", e);
+                ServiceConfig {
+                    authors: Some(Vec::new()),
+                    write_policies: Vec::new(),
+                    approvals: ApprovalConfig::default(),
+                    limits: LimitsConfig::default(),
+                    deny_list: DenyList::default(),
+                }
+            }),
+        }
+    }
+
+    /// Whether the author has the role, directly or by being an admin.
+    pub fn is_authorized(&self, pub_key: &PublicKey, role: Role) -> bool {
+        match self.authors {
+            None => true,
+            Some(ref authors) => authors.iter().any(|author| {
+                author.pub_key == *pub_key
+                    && (author.roles.contains(&role) || author.roles.contains(&Role::Admin))
+            }),
+        }
+    }
+
+    /// Policy limiting what the author may write, if any. Admins are never limited.
+    pub fn write_policy(&self, pub_key: &PublicKey) -> Option<&WritePolicy> {
+        if self.authors.is_some() && self.is_authorized(pub_key, Role::Admin) {
+            return None;
+        }
+        self.write_policies
+            .iter()
+            .find(|policy| policy.pub_key == *pub_key)
+    }
+}
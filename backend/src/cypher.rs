@@ -0,0 +1,416 @@
+//! Static analysis of Cypher statements.
+//!
+//! Finding the functions and procedures a statement calls does not need a full Cypher grammar,
+//! only its tokens, with strings, quoted identifiers and comments told apart from code.
+//! Statements are separated by `;` outside of strings, quoted identifiers and comments, the way
+//! they are sent to Neo4j. The same tokens tell whether a statement reads the graph, writes data
+//! or changes the schema.
+
+use std::fmt;
+
+use config::DenyList;
+use structures::{FailureCode, StatementClass, StatementClassification};
+
+/// Kind of a token.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TokenKind {
+    /// Name or keyword, including backtick quoted identifiers.
+    Identifier,
+    /// Quoted string.
+    String,
+    /// Numeric literal.
+    Number,
+    /// Query parameter such as `$name`.
+    Parameter,
+    /// Any other single character, such as `(` or `.`.
+    Symbol,
+}
+
+/// Token of a statement.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Token<'a> {
+    /// Kind of the token.
+    pub kind: TokenKind,
+    /// Text of the token, without the quotes of strings and quoted identifiers.
+    pub text: &'a str,
+    /// Byte offset of the token in its statement.
+    pub offset: usize,
+}
+
+impl<'a> Token<'a> {
+    /// Whether the token is the given keyword, compared case-insensitively.
+    pub fn is_keyword(&self, keyword: &str) -> bool {
+        self.kind == TokenKind::Identifier && self.text.eq_ignore_ascii_case(keyword)
+    }
+
+    /// Whether the token is the given symbol.
+    pub fn is_symbol(&self, symbol: &str) -> bool {
+        self.kind == TokenKind::Symbol && self.text == symbol
+    }
+}
+
+/// Problem found in a statement, pointing at the offending token.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CypherError {
+    /// Index of the statement in its batch.
+    pub statement: usize,
+    /// Byte offset of the token in the statement.
+    pub offset: usize,
+    /// Offending token.
+    pub token: String,
+    /// Description of the problem.
+    pub message: String,
+}
+
+impl fmt::Display for CypherError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "Statement {} at offset {}, `{}`: {}",
+            self.statement, self.offset, self.token, self.message
+        )
+    }
+}
+
+fn is_identifier_byte(b: u8) -> bool {
+    // Bytes of non-ASCII characters are kept in identifiers, so tokens never split a character.
+    b.is_ascii_alphanumeric() || b == b'_' || b >= 0x80
+}
+
+/// Splits a single statement into tokens, skipping whitespace and comments.
+pub fn tokenize(statement: &str) -> Result<Vec<Token>, CypherError> {
+    let bytes = statement.as_bytes();
+    let error = |offset: usize, message: &str| CypherError {
+        statement: 0,
+        offset,
+        token: statement[offset..].chars().take(10).collect(),
+        message: message.to_string(),
+    };
+    let mut tokens = Vec::new();
+    let mut i = 0;
+    while i < bytes.len() {
+        let b = bytes[i];
+        if b.is_ascii_whitespace() {
+            i += 1;
+        } else if b == b'/' && bytes.get(i + 1) == Some(&b'/') {
+            while i < bytes.len() && bytes[i] != b'\n' {
+                i += 1;
+            }
+        } else if b == b'/' && bytes.get(i + 1) == Some(&b'*') {
+            match statement[i + 2..].find("*/") {
+                Some(end) => i += end + 4,
+                None => return Err(error(i, "Unterminated comment")),
+            }
+        } else if b == b'\'' || b == b'"' {
+            let mut j = i + 1;
+            loop {
+                match bytes.get(j) {
+                    None => return Err(error(i, "Unterminated string")),
+                    Some(&b'\\') => j += 2,
+                    Some(&quote) if quote == b => break,
+                    _ => j += 1,
+                }
+            }
+            tokens.push(Token {
+                kind: TokenKind::String,
+                text: &statement[i + 1..j],
+                offset: i,
+            });
+            i = j + 1;
+        } else if b == b'`' {
+            // A backtick inside a quoted identifier is written twice.
+            let mut j = i + 1;
+            loop {
+                match bytes.get(j) {
+                    None => return Err(error(i, "Unterminated identifier")),
+                    Some(&b'`') if bytes.get(j + 1) == Some(&b'`') => j += 2,
+                    Some(&b'`') => break,
+                    _ => j += 1,
+                }
+            }
+            tokens.push(Token {
+                kind: TokenKind::Identifier,
+                text: &statement[i + 1..j],
+                offset: i,
+            });
+            i = j + 1;
+        } else if b == b'$' || is_identifier_byte(b) {
+            let is_number = b.is_ascii_digit();
+            let mut j = i + 1;
+            while j < bytes.len()
+                && (is_identifier_byte(bytes[j])
+                    || (is_number
+                        && bytes[j] == b'.'
+                        && bytes.get(j + 1).map_or(false, u8::is_ascii_digit)))
+            {
+                j += 1;
+            }
+            let kind = if b == b'$' {
+                TokenKind::Parameter
+            } else if is_number {
+                TokenKind::Number
+            } else {
+                TokenKind::Identifier
+            };
+            tokens.push(Token {
+                kind,
+                text: &statement[i..j],
+                offset: i,
+            });
+            i = j;
+        } else {
+            tokens.push(Token {
+                kind: TokenKind::Symbol,
+                text: &statement[i..i + 1],
+                offset: i,
+            });
+            i += 1;
+        }
+    }
+    Ok(tokens)
+}
+
+/// Splits queries into their `;` separated statements. Separators inside strings, quoted
+/// identifiers and comments belong to the statement. Like `str::split`, empty statements are
+/// kept, so that indexes match the statements sent to Neo4j.
+pub fn split_statements(queries: &str) -> Result<Vec<&str>, CypherError> {
+    let separators = |tokens: &[Token]| -> Vec<usize> {
+        tokens
+            .iter()
+            .filter(|token| token.is_symbol(";"))
+            .map(|token| token.offset)
+            .collect()
+    };
+    let tokens = match tokenize(queries) {
+        Ok(tokens) => tokens,
+        Err(e) => {
+            // Everything before the offending token was read, so its separators are known.
+            let before = separators(&tokenize(&queries[..e.offset])?);
+            let start = before.last().map_or(0, |offset| offset + 1);
+            return Err(CypherError {
+                statement: before.len(),
+                offset: e.offset - start,
+                ..e
+            });
+        }
+    };
+    let mut statements = Vec::new();
+    let mut start = 0;
+    for offset in separators(&tokens) {
+        statements.push(&queries[start..offset]);
+        start = offset + 1;
+    }
+    statements.push(&queries[start..]);
+    Ok(statements)
+}
+
+/// Function or procedure called by a statement.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Call {
+    /// Name of the function or procedure, including its namespace.
+    pub name: String,
+    /// Byte offset of the name in its statement.
+    pub offset: usize,
+    /// Whether it is a procedure called with `CALL`.
+    pub procedure: bool,
+    /// Whether arguments are given, none for a procedure called without parentheses.
+    pub has_arguments: Option<bool>,
+}
+
+/// Finds the functions and procedures the tokens of a statement call.
+pub fn calls(tokens: &[Token]) -> Vec<Call> {
+    let mut calls = Vec::new();
+    let mut i = 0;
+    while i < tokens.len() {
+        let token = &tokens[i];
+        let previous = if i > 0 { tokens.get(i - 1) } else { None };
+        // Labels, relationship types and properties are not calls.
+        let starts_name = token.kind == TokenKind::Identifier
+            && !previous.map_or(false, |p| p.is_symbol(":") || p.is_symbol("."));
+        if !starts_name {
+            i += 1;
+            continue;
+        }
+
+        let mut parts = vec![token.text];
+        let mut j = i + 1;
+        while j + 1 < tokens.len()
+            && tokens[j].is_symbol(".")
+            && tokens[j + 1].kind == TokenKind::Identifier
+        {
+            parts.push(tokens[j + 1].text);
+            j += 2;
+        }
+        let opens = tokens.get(j).map_or(false, |t| t.is_symbol("("));
+        let has_arguments = if opens {
+            Some(!tokens.get(j + 1).map_or(false, |t| t.is_symbol(")")))
+        } else {
+            None
+        };
+        let procedure = previous.map_or(false, |p| p.is_keyword("CALL"));
+        if procedure || opens {
+            calls.push(Call {
+                name: parts.join("."),
+                offset: token.offset,
+                procedure,
+                has_arguments,
+            });
+        }
+        i = j;
+    }
+    calls
+}
+
+/// Whether a deny-list entry matches the call. An entry ending in `.*` matches a namespace,
+/// one ending in `()` only calls without arguments.
+fn matches(entry: &str, call: &Call) -> bool {
+    let name = call.name.to_lowercase();
+    let entry = entry.to_lowercase();
+    if entry.ends_with(".*") {
+        name.starts_with(&entry[..entry.len() - 1])
+    } else if entry.ends_with("()") {
+        name == entry[..entry.len() - 2] && call.has_arguments == Some(false)
+    } else {
+        name == entry
+    }
+}
+
+/// Whether the tokens from `i` on are the keywords of a deny-list clause entry. Keywords used as
+/// labels or property keys are names, not clauses.
+fn starts_clause(tokens: &[Token], i: usize, entry: &str) -> bool {
+    if i > 0 && (tokens[i - 1].is_symbol(":") || tokens[i - 1].is_symbol(".")) {
+        return false;
+    }
+    let keywords: Vec<&str> = entry.split_whitespace().collect();
+    !keywords.is_empty()
+        && keywords.iter().enumerate().all(|(j, keyword)| {
+            tokens
+                .get(i + j)
+                .map_or(false, |token| token.is_keyword(keyword))
+        })
+}
+
+/// Checks that no statement of the queries calls a denied function or procedure, or uses a
+/// denied clause.
+pub fn check_denied(deny_list: &DenyList, queries: &str) -> Result<(), CypherError> {
+    for (index, statement) in split_statements(queries)?.into_iter().enumerate() {
+        let tokens = tokenize(statement).map_err(|e| CypherError {
+            statement: index,
+            ..e
+        })?;
+        for (i, token) in tokens.iter().enumerate() {
+            if let Some(entry) = deny_list
+                .clauses
+                .iter()
+                .find(|entry| starts_clause(&tokens, i, entry))
+            {
+                return Err(CypherError {
+                    statement: index,
+                    offset: token.offset,
+                    token: entry.to_uppercase(),
+                    message: String::from("Clause is not deterministic and may not be used"),
+                });
+            }
+        }
+        for call in calls(&tokens) {
+            let (entries, kind) = if call.procedure {
+                (&deny_list.procedures, "Procedure")
+            } else {
+                (&deny_list.functions, "Function")
+            };
+            if entries.iter().any(|entry| matches(entry, &call)) {
+                return Err(CypherError {
+                    statement: index,
+                    offset: call.offset,
+                    token: call.name,
+                    message: format!("{} is not deterministic and may not be called", kind),
+                });
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Clauses a read-only statement may start with.
+const READ_CLAUSES: &[&str] = &["MATCH", "OPTIONAL", "WITH", "UNWIND", "RETURN"];
+
+/// Clauses that create, change or delete nodes and relations.
+const WRITE_CLAUSES: &[&str] = &["CREATE", "MERGE", "SET", "DELETE", "DETACH", "REMOVE"];
+
+/// Classifies the tokens of a single statement. A procedure call counts as a data write, since
+/// what a procedure does is not known from the statement, and so does a statement that does not
+/// start with a known clause, leaving it to Neo4j to refuse. A statement starting with `EXPLAIN`
+/// is only planned, never run, so it is read-only whatever it would do.
+pub fn classify(tokens: &[Token]) -> StatementClass {
+    if tokens
+        .first()
+        .map_or(false, |first| first.is_keyword("EXPLAIN"))
+    {
+        return StatementClass::ReadOnly;
+    }
+    let starts_read = tokens.first().map_or(false, |first| {
+        READ_CLAUSES.iter().any(|clause| first.is_keyword(clause))
+    });
+    let mut class = if starts_read {
+        StatementClass::ReadOnly
+    } else {
+        StatementClass::DataWrite
+    };
+    for (i, token) in tokens.iter().enumerate() {
+        // Keywords used as labels, property keys or map keys are names, not clauses.
+        let previous = if i > 0 { tokens.get(i - 1) } else { None };
+        let is_name = previous.map_or(false, |p| p.is_symbol(":") || p.is_symbol("."))
+            || tokens.get(i + 1).map_or(false, |n| n.is_symbol(":"));
+        if token.kind != TokenKind::Identifier || is_name {
+            continue;
+        }
+        let next = tokens.get(i + 1);
+        if (token.is_keyword("CREATE") || token.is_keyword("DROP"))
+            && next.map_or(false, |n| {
+                n.is_keyword("INDEX") || n.is_keyword("CONSTRAINT")
+            })
+        {
+            return StatementClass::SchemaWrite;
+        }
+        if WRITE_CLAUSES.iter().any(|clause| token.is_keyword(clause)) || token.is_keyword("CALL") {
+            class = StatementClass::DataWrite;
+        }
+    }
+    class
+}
+
+/// Classifies each non-empty statement of the queries.
+pub fn classify_queries(queries: &str) -> Result<StatementClassification, CypherError> {
+    let mut classes = Vec::new();
+    for (index, statement) in split_statements(queries)?.into_iter().enumerate() {
+        let tokens = tokenize(statement).map_err(|e| CypherError {
+            statement: index,
+            ..e
+        })?;
+        if !tokens.is_empty() {
+            classes.push(classify(&tokens));
+        }
+    }
+    Ok(StatementClassification::new(classes))
+}
+
+/// Checks that the classified statements may be sent to Neo4j as one transaction. Statements that
+/// only read waste a block, and Neo4j refuses transactions mixing schema and data writes, so
+/// index and constraint changes go through `SchemaChange` instead.
+pub fn check_classes(
+    classification: &StatementClassification,
+) -> Result<(), (FailureCode, String)> {
+    if classification.has_schema_writes() {
+        Err((
+            FailureCode::SchemaInQueries,
+            String::from("Queries change the schema, send them as a SchemaChange transaction"),
+        ))
+    } else if classification.is_read_only() {
+        Err((
+            FailureCode::ReadOnlyQuery,
+            String::from("Queries do not write to the database"),
+        ))
+    } else {
+        Ok(())
+    }
+}
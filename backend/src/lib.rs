@@ -23,10 +23,14 @@
 
 #[macro_use]
 extern crate exonum;
+extern crate exonum_time;
 extern crate serde;
+extern crate serde_json;
 #[macro_use]
 extern crate failure;
 #[macro_use]
+extern crate log;
+#[macro_use]
 extern crate serde_derive;
 
 extern crate grpc;
@@ -38,25 +42,38 @@ extern crate toml;
 pub use schema::Schema;
 
 pub mod api;
+pub mod compensation;
+pub mod config;
+pub mod cypher;
+pub mod limits;
+pub mod migrations;
 ///Module for communicating with Neo4j
 pub mod neo4j;
+pub mod policy;
 pub mod schema;
 pub mod structures;
 pub mod transactions;
 pub mod util;
 
-use transactions::AuditBlocks;
 use transactions::Neo4JTransactions;
+use transactions::{ActivateScheduled, AuditBlocks};
 
 use exonum::{
     api::ServiceApiBuilder,
     blockchain::{self, Schema as CoreSchema, ServiceContext, Transaction, TransactionSet},
-    crypto::Hash,
+    crypto::{Hash, PublicKey, SecretKey},
     encoding::Error as EncodingError,
-    helpers::fabric::{self, Context},
+    helpers::{
+        fabric::{self, Context},
+        Height,
+    },
     messages::RawTransaction,
-    storage::Snapshot,
+    storage::{Fork, Snapshot},
 };
+use protobuf::Message;
+use serde_json::Value;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
 
 use neo4j::ExecuteResponse::*;
 
@@ -67,7 +84,9 @@ const SERVICE_NAME: &str = "neo4j_blockchain";
 
 /// Exonum `Neo4jService` implementation.
 pub struct Neo4jService {
-    neo4j: neo4j::Neo4jRpc,
+    neo4j: Arc<dyn neo4j::GraphBackend>,
+    /// Set once the node found indexes written by a newer version of the service.
+    incompatible_storage: AtomicBool,
 }
 
 impl ::std::fmt::Debug for Neo4jService {
@@ -78,8 +97,111 @@ impl ::std::fmt::Debug for Neo4jService {
 
 impl Neo4jService {
     /// Creates  a Neo4j RPC service
-    pub fn new(neo4j: neo4j::Neo4jRpc) -> Self {
-        Self { neo4j }
+    pub fn new<B: neo4j::GraphBackend + 'static>(neo4j: B) -> Self {
+        Self::with_backend(Arc::new(neo4j))
+    }
+
+    /// Creates a service running its transactions on a shared graph backend.
+    pub fn with_backend(neo4j: Arc<dyn neo4j::GraphBackend>) -> Self {
+        Self {
+            neo4j,
+            incompatible_storage: AtomicBool::new(false),
+        }
+    }
+
+    /// Creates a service for a node whose storage is at hand, failing if its indexes were
+    /// written by a newer version of the service. Exonum does not give the service factory
+    /// access to the storage, so services it creates check the indexes after each commit.
+    pub fn checked<B: neo4j::GraphBackend + 'static>(
+        neo4j: B,
+        snapshot: &dyn Snapshot,
+    ) -> Result<Self, migrations::MigrationError> {
+        migrations::check_schema_version(&Schema::new(snapshot))?;
+        Ok(Self::new(neo4j))
+    }
+
+    /// Asks to send the scheduled batches that are due with the next block to Neo4j.
+    /// Only validators can sign the activation, and only one of them is designated for each
+    /// height, taking turns, so that a due batch is not activated by every validator at once.
+    fn activate_due_batches(&self, context: &ServiceContext) {
+        let snapshot = context.snapshot();
+        let core_schema = CoreSchema::new(snapshot);
+        let next_height = Height(core_schema.block_hashes_by_height().len());
+        let validators = core_schema.actual_configuration().validator_keys.len() as u64;
+        match context.validator_id() {
+            Some(id) if validators > 0 && u64::from(id.0) == next_height.0 % validators => {}
+            _ => return,
+        }
+        let schema = Schema::new(snapshot);
+        let time = schema.current_time();
+        let tx_sender = context.transaction_sender();
+        for (batch_hash, batch) in schema.scheduled().iter() {
+            if batch.is_due(next_height, time) {
+                let new_tx =
+                    ActivateScheduled::new(&batch_hash, context.public_key(), context.secret_key());
+                match tx_sender.send(Box::new(new_tx)) {
+                    _ => {}
+                };
+            }
+        }
+    }
+
+    /// Runs the last committed block on the graph of this node and deletes the changes audited
+    /// with it. Returns the audit of the blocks awaiting one, signed with the given service key,
+    /// whenever the graph is reachable and there is something to audit: the block it just
+    /// executed, or earlier blocks still waiting for their changes, so that an audit lost or
+    /// missed is retried with the following blocks.
+    pub fn handle_commit(
+        &self,
+        snapshot: &dyn Snapshot,
+        public_key: &PublicKey,
+        secret_key: &SecretKey,
+    ) -> Option<AuditBlocks> {
+        let core_schema = CoreSchema::new(snapshot);
+        let schema = Schema::new(snapshot);
+        let block_hash = core_schema.block_hashes_by_height().last()?;
+        let block = core_schema.blocks().get(&block_hash)?;
+        let result =
+            neo4j::execute_block(&*self.neo4j, &block, block_hash.to_hex().as_str(), &schema);
+        let executed = match result {
+            OkExe(_) => true,
+            neo4j::ExecuteResponse::Error(ref e) => {
+                warn!(
+                    "Block {} was not executed on Neo4j: {}",
+                    block_hash.to_hex(),
+                    e.msg()
+                );
+                false
+            }
+            _ => false,
+        };
+
+        // A block this graph failed to execute has no changes here, other validators audit it.
+        let blocks: Vec<Hash> = schema
+            .unaudited_blocks()
+            .into_iter()
+            .filter(|hash| executed || *hash != block_hash)
+            .collect();
+        let reachable = executed || self.neo4j.health().is_ok();
+        let audit = if reachable && (executed || !blocks.is_empty()) {
+            let changes = neo4j::collect_audited_changes(&*self.neo4j, &blocks);
+            // Only an executed block has its pending transactions marked by the audit.
+            let block_id = if executed {
+                block_hash.to_hex()
+            } else {
+                String::new()
+            };
+            Some(AuditBlocks::new(
+                &block_id,
+                &changes.write_to_bytes().unwrap(),
+                public_key,
+                secret_key,
+            ))
+        } else {
+            None
+        };
+        neo4j::remove_audited_changes(&*self.neo4j, block, core_schema, schema);
+        audit
     }
 }
 
@@ -101,43 +223,36 @@ impl blockchain::Service for Neo4jService {
         Neo4JTransactions::tx_from_raw(raw).map(Into::into)
     }
 
+    fn initialize(&self, fork: &mut Fork) -> Value {
+        Schema::new(fork).set_schema_version(migrations::CURRENT_SCHEMA_VERSION);
+        serde_json::to_value(config::ServiceConfig::default()).unwrap()
+    }
+
     fn after_commit(&self, context: &ServiceContext) {
         let snapshot = context.snapshot();
-
-        let core_schema = CoreSchema::new(snapshot);
         let schema = Schema::new(snapshot);
-        let last_block = core_schema.block_hashes_by_height().last();
-
-        match last_block {
-            Some(block_hash) => {
-                let block_option = core_schema.blocks().get(&block_hash);
-                if let Some(block) = block_option {
-                    let result = self.neo4j.execute_block(
-                        &block,
-                        block_hash.to_hex().as_str(),
-                        &core_schema,
-                        &schema,
-                    );
-                    if let OkExe(_) = result {
-                        let tx_sender = context.transaction_sender();
-                        let new_tx =
-                            AuditBlocks::new(block_hash.to_hex().as_str(), context.secret_key());
-                        match tx_sender.send(Box::new(new_tx)) {
-                            _ => {}
-                        };
-                    }
-                    self.neo4j
-                        .remove_audited_changes(block, core_schema, schema);
-                }
-            }
-            None => {
-                println!("ERROR: Should not be here 006");
+        // Indexes written by a newer version of the service cannot be read safely, so nothing
+        // is sent to Neo4j or to the other nodes until the service is upgraded.
+        if let Err(e) = migrations::check_schema_version(&schema) {
+            if !self.incompatible_storage.swap(true, Ordering::SeqCst) {
+                error!("Not running committed blocks on Neo4j: {}", e);
             }
+            return;
+        }
+        self.activate_due_batches(context);
+
+        let audit = self.handle_commit(snapshot, context.public_key(), context.secret_key());
+        // Only validators may audit, other nodes keep their graph in sync.
+        if let (Some(audit), Some(_)) = (audit, context.validator_id()) {
+            let tx_sender = context.transaction_sender();
+            match tx_sender.send(Box::new(audit)) {
+                _ => {}
+            };
         }
     }
 
     fn wire_api(&self, builder: &mut ServiceApiBuilder) {
-        api::Neo4JApi::wire(builder);
+        api::Neo4JApi::wire(builder, self.neo4j.clone());
     }
 }
 
@@ -152,13 +267,16 @@ impl fabric::ServiceFactory for Neo4jServiceFactory {
 
     fn make_service(&mut self, _: &Context) -> Box<dyn blockchain::Service> {
         match util::parse_port() {
-            Ok(x) => println!("All good, neo4j port is {}", x),
-            Err(e) => println!("error: {:?}", e),
+            Ok(x) => info!("Using the transaction manager on port {}", x),
+            Err(e) => error!("Invalid transaction manager port: {:?}", e),
         };
 
-        let neo4j_rpc = neo4j::get_neo4j_rpc_client();
+        let backend = neo4j::configured_backend();
+        if let Err(e) = backend.health() {
+            warn!("Graph backend is not available yet: {}", e);
+        }
 
-        let service = Neo4jService::new(neo4j_rpc);
+        let service = Neo4jService::with_backend(backend);
 
         Box::new(service)
     }
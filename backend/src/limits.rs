@@ -0,0 +1,129 @@
+//! Limits on the size and rate of the queries authors submit.
+//!
+//! Every validator runs the queries of a transaction on Neo4j, so a single key must not be able
+//! to flood the network with large or frequent transactions. Hard caps hold whatever the
+//! configuration and are checked in `verify`, the configured limits in `execute`.
+
+use exonum::{
+    crypto::{hash, PublicKey},
+    helpers::Height,
+    storage::Snapshot,
+};
+
+use config::LimitsConfig;
+use schema::Schema;
+use structures::FailureCode;
+
+/// Largest size of the queries of a transaction in bytes, whatever the configuration.
+pub const MAX_QUERY_BYTES: usize = 1024 * 1024;
+/// Largest amount of statements in a transaction, whatever the configuration.
+pub const MAX_STATEMENTS: usize = 1000;
+/// Amount of bytes stored of queries refused for their size.
+pub const STORED_QUERY_BYTES: usize = 1024;
+
+/// Amount of non-empty `;` separated statements in the queries.
+pub fn statement_count(queries: &str) -> usize {
+    queries
+        .split(';')
+        .filter(|statement| !statement.trim().is_empty())
+        .count()
+}
+
+/// Whether the queries fit the hard caps.
+pub fn within_hard_caps(queries: &str) -> bool {
+    queries.len() <= MAX_QUERY_BYTES && statement_count(queries) <= MAX_STATEMENTS
+}
+
+/// Checks the queries against the configured statement count and size.
+pub fn check_size(limits: &LimitsConfig, queries: &str) -> Result<(), (FailureCode, String)> {
+    let statements = statement_count(queries);
+    if limits.max_statements != 0 && statements > limits.max_statements as usize {
+        return Err((
+            FailureCode::TooManyStatements,
+            format!(
+                "Transaction has {} statements, at most {} are allowed",
+                statements, limits.max_statements
+            ),
+        ));
+    }
+    if limits.max_query_bytes != 0 && queries.len() as u64 > limits.max_query_bytes {
+        return Err((
+            FailureCode::QueryTooLarge,
+            format!(
+                "Queries are {} bytes, at most {} are allowed",
+                queries.len(),
+                limits.max_query_bytes
+            ),
+        ));
+    }
+    Ok(())
+}
+
+/// Text stored for queries refused with `code`. Queries refused for their size are cut to
+/// `STORED_QUERY_BYTES` and followed by their size and hash, so that refusing them does not
+/// store what the limits keep out.
+pub fn stored_queries(code: FailureCode, queries: &str) -> String {
+    let refused_for_size = match code {
+        FailureCode::TooManyStatements | FailureCode::QueryTooLarge => true,
+        _ => false,
+    };
+    if !refused_for_size || queries.len() <= STORED_QUERY_BYTES {
+        return queries.to_string();
+    }
+    let mut end = STORED_QUERY_BYTES;
+    while !queries.is_char_boundary(end) {
+        end -= 1;
+    }
+    format!(
+        "{}... ({} bytes, hash {})",
+        &queries[..end],
+        queries.len(),
+        hash(queries.as_bytes()).to_hex()
+    )
+}
+
+/// Checks that the author did not already submit the allowed amount of transactions in the
+/// `window` blocks up to `height`. Only accepted submissions are charged, including proposals
+/// and scheduled batches, so retrying a refused transaction does not extend the lockout.
+pub fn check_rate<T: AsRef<dyn Snapshot>>(
+    schema: &Schema<T>,
+    limits: &LimitsConfig,
+    pub_key: &PublicKey,
+    height: Height,
+) -> Result<(), (FailureCode, String)> {
+    if limits.max_transactions == 0 || limits.window == 0 {
+        return Ok(());
+    }
+    let submissions = schema.author_submissions(pub_key);
+    let since = height.0.saturating_sub(limits.window - 1);
+    let mut recent = 0;
+    // Submissions are listed in the order they were charged, so counting stops at the first old one.
+    for index in (0..submissions.len()).rev() {
+        match submissions.get(index) {
+            Some(charged_at) if charged_at >= since => recent += 1,
+            _ => break,
+        }
+        if recent >= limits.max_transactions {
+            return Err((
+                FailureCode::RateLimited,
+                format!(
+                    "Author submitted {} transactions in the last {} blocks",
+                    recent, limits.window
+                ),
+            ));
+        }
+    }
+    Ok(())
+}
+
+/// Checks the configured size and rate limits.
+pub fn check_limits<T: AsRef<dyn Snapshot>>(
+    schema: &Schema<T>,
+    limits: &LimitsConfig,
+    queries: &str,
+    pub_key: &PublicKey,
+    height: Height,
+) -> Result<(), (FailureCode, String)> {
+    check_size(limits, queries)?;
+    check_rate(schema, limits, pub_key, height)
+}
@@ -1,6 +1,7 @@
 extern crate exonum;
 extern crate exonum_configuration;
 extern crate exonum_neo4j;
+extern crate exonum_time;
 
 use exonum::helpers::{self, fabric::NodeBuilder};
 use exonum_configuration as configuration;
@@ -13,6 +14,7 @@ fn main() {
 
     let node = NodeBuilder::new()
         .with_service(Box::new(configuration::ServiceFactory))
+        .with_service(Box::new(exonum_time::TimeServiceFactory))
         .with_service(Box::new(neo4j_service::Neo4jServiceFactory));
     node.run();
 }
@@ -0,0 +1,232 @@
+//! Migrations of the layout of the `neo4j.*` indexes.
+//!
+//! Every chain records its schema version in `neo4j.values`. Chains started before the
+//! version was recorded are at `LEGACY_SCHEMA_VERSION`. A migration step rewrites the indexes
+//! from one version to the next, and steps only run through `MigrateSchema` transactions,
+//! once more than two thirds of the validators voted for the target version, so that all nodes
+//! rewrite their state in the same block.
+
+use exonum::{
+    crypto::Hash,
+    storage::{Fork, ProofListIndex, Snapshot, StorageValue},
+};
+use std::collections::BTreeSet;
+
+use schema::Schema;
+use structures::NodeChange;
+
+/// Schema version of chains started before the version was recorded.
+pub const LEGACY_SCHEMA_VERSION: u32 = 1;
+/// Schema version written by this version of the service.
+pub const CURRENT_SCHEMA_VERSION: u32 = 3;
+
+/// A step rewriting the indexes from `from_version` to the next version.
+pub struct Migration {
+    /// Version the step starts from.
+    pub from_version: u32,
+    /// What the step changes.
+    pub description: &'static str,
+    /// Rewrites the indexes.
+    pub run: fn(&mut Fork),
+}
+
+impl ::std::fmt::Debug for Migration {
+    fn fmt(&self, f: &mut ::std::fmt::Formatter) -> ::std::fmt::Result {
+        f.debug_struct("Migration")
+            .field("from_version", &self.from_version)
+            .field("description", &self.description)
+            .finish()
+    }
+}
+
+/// Errors of running migrations.
+#[derive(Debug, Fail, PartialEq)]
+pub enum MigrationError {
+    /// Stored or requested version is newer than this version of the service.
+    #[fail(display = "Unknown schema version {}, newest known is {}", _0, _1)]
+    UnknownVersion(u32, u32),
+    /// Requested version is older than the stored one.
+    #[fail(display = "Cannot migrate from schema version {} down to {}", _0, _1)]
+    Downgrade(u32, u32),
+    /// No step starts from this version.
+    #[fail(display = "No migration registered from schema version {}", _0)]
+    MissingStep(u32),
+}
+
+/// All registered migration steps.
+pub fn migrations() -> Vec<Migration> {
+    vec![
+        Migration {
+            from_version: 1,
+            description:
+                "Re-encode stored values as protobuf messages and node changes with a header",
+            run: reencode_values,
+        },
+        Migration {
+            from_version: 2,
+            description: "Store the status of neo4j transactions as its number",
+            run: number_statuses,
+        },
+    ]
+}
+
+/// Schema version of the stored indexes.
+pub fn schema_version<T: AsRef<dyn Snapshot>>(schema: &Schema<T>) -> u32 {
+    schema.schema_version().unwrap_or(LEGACY_SCHEMA_VERSION)
+}
+
+/// Fails if the stored indexes were written by a newer version of the service.
+pub fn check_schema_version<T: AsRef<dyn Snapshot>>(
+    schema: &Schema<T>,
+) -> Result<(), MigrationError> {
+    let version = schema_version(schema);
+    if version > CURRENT_SCHEMA_VERSION {
+        return Err(MigrationError::UnknownVersion(
+            version,
+            CURRENT_SCHEMA_VERSION,
+        ));
+    }
+    Ok(())
+}
+
+/// Fails if `target_version` is newer than this version of the service.
+pub fn check_target_version(target_version: u32) -> Result<(), MigrationError> {
+    if target_version > CURRENT_SCHEMA_VERSION {
+        return Err(MigrationError::UnknownVersion(
+            target_version,
+            CURRENT_SCHEMA_VERSION,
+        ));
+    }
+    Ok(())
+}
+
+/// Runs the steps from the stored version up to `target_version`, returning how many ran.
+/// The version is checked before anything is rewritten.
+pub fn migrate(fork: &mut Fork, target_version: u32) -> Result<usize, MigrationError> {
+    check_target_version(target_version)?;
+    let mut version = schema_version(&Schema::new(&*fork));
+    if target_version < version {
+        return Err(MigrationError::Downgrade(version, target_version));
+    }
+
+    let steps = migrations();
+    let mut plan = Vec::new();
+    while version + (plan.len() as u32) < target_version {
+        let from_version = version + plan.len() as u32;
+        let step = steps
+            .iter()
+            .find(|step| step.from_version == from_version)
+            .ok_or(MigrationError::MissingStep(from_version))?;
+        plan.push(step.run);
+    }
+
+    for run in &plan {
+        run(fork);
+        version += 1;
+        Schema::new(&mut *fork).set_schema_version(version);
+    }
+    Ok(plan.len())
+}
+
+/// Reading a value decodes either encoding, writing it back stores the current one.
+fn reencode_values(fork: &mut Fork) {
+    let (transactions, relations, nodes) = {
+        let schema = Schema::new(&*fork);
+        let transactions: Vec<Hash> = schema.neo4j_transactions().keys().collect();
+        let relations: Vec<Hash> = schema.relations().keys().collect();
+        let nodes = legacy_nodes(&schema, &transactions);
+        (transactions, relations, nodes)
+    };
+
+    let mut schema = Schema::new(fork);
+    for hash in &transactions {
+        if let Some(transaction) = schema.neo4j_transaction(hash) {
+            schema.neo4j_transactions_mut().put(hash, transaction);
+        }
+        let root = {
+            let mut changes = schema.transaction_changes_raw_mut(hash);
+            if changes.is_empty() {
+                continue;
+            }
+            reencode_changes(&mut changes);
+            changes.merkle_root()
+        };
+        schema.transaction_changes_roots_mut().put(hash, root);
+    }
+    for hash in &relations {
+        if let Some(relation) = schema.relations().get(hash) {
+            schema.relations_mut().put(hash, relation);
+        }
+    }
+    for uuid in nodes {
+        reencode_changes(&mut schema.node_history_raw_mut(&uuid));
+        schema.known_nodes_mut().insert(uuid);
+    }
+}
+
+/// Reading a transaction takes the status from its name, writing it back stores the number.
+fn number_statuses(fork: &mut Fork) {
+    let transactions: Vec<Hash> = Schema::new(&*fork).neo4j_transactions().keys().collect();
+    let mut schema = Schema::new(fork);
+    for hash in &transactions {
+        if let Some(transaction) = schema.neo4j_transaction(hash) {
+            schema.neo4j_transactions_mut().put(hash, transaction);
+        }
+    }
+}
+
+/// Uuids of the nodes that have a history. Legacy chains neither list their nodes nor index
+/// changes by transaction, so the nodes are found from the stored data itself: the nodes each
+/// transaction created, whose uuids are the transaction hash followed by an index, the end
+/// points of relations, and every node the changes of these nodes refer to.
+fn legacy_nodes<T: AsRef<dyn Snapshot>>(schema: &Schema<T>, transactions: &[Hash]) -> Vec<String> {
+    let mut candidates: Vec<String> = schema.known_nodes().iter().collect();
+    for hash in transactions {
+        for bytes in schema.transaction_changes_raw(hash).iter() {
+            if let Ok(change) = NodeChange::decode(&bytes) {
+                candidates.extend(change.get_uuis().into_iter().map(String::from));
+            }
+        }
+        let prefix = hash.to_hex();
+        candidates.extend(
+            (0..)
+                .map(|index| format!("{}_{}", prefix, index))
+                .take_while(|uuid| !schema.node_history_raw(uuid).is_empty()),
+        );
+    }
+    for relation in schema.relations().values() {
+        candidates.push(relation.start_node_uuid().to_string());
+        candidates.push(relation.end_node_uuid().to_string());
+    }
+
+    let mut nodes = BTreeSet::new();
+    while let Some(uuid) = candidates.pop() {
+        if nodes.contains(&uuid) {
+            continue;
+        }
+        let history = schema.node_history_raw(&uuid);
+        if history.is_empty() {
+            continue;
+        }
+        for bytes in history.iter() {
+            if let Ok(change) = NodeChange::decode(&bytes) {
+                candidates.extend(change.get_uuis().into_iter().map(String::from));
+            }
+        }
+        nodes.insert(uuid);
+    }
+    nodes.into_iter().collect()
+}
+
+/// Entries that cannot be decoded are kept as they are, to be reported when they are read.
+fn reencode_changes(changes: &mut ProofListIndex<&mut Fork, Vec<u8>>) {
+    let entries: Vec<Vec<u8>> = changes
+        .iter()
+        .map(|bytes| match NodeChange::decode(&bytes) {
+            Ok(change) => change.into_bytes(),
+            Err(_) => bytes,
+        })
+        .collect();
+    changes.clear();
+    changes.extend(entries);
+}
@@ -0,0 +1,1716 @@
+//! Property graph kept in memory, running a subset of Cypher.
+//!
+//! Lets the service run without a JVM. The statements of a transaction run on a copy of the
+//! graph, which replaces the graph once all of them succeeded, and the changes are found by
+//! comparing both. Like the transaction manager plugin, created nodes and then created
+//! relationships get the uuid `<transaction id>_<n>`, in the order they were created.
+//!
+//! Supported are `MATCH` with `WHERE`, `CREATE`, `MERGE` with `ON CREATE SET` and `ON MATCH SET`,
+//! `SET`, `REMOVE`, `DELETE`, `DETACH DELETE`, `WITH` of variables and a final `RETURN`, whose
+//! projection is ignored. Parameters, lists, functions, `OPTIONAL MATCH` and variable length
+//! relationships are not. Indexes and constraints are recorded, but not enforced.
+
+use std::cmp::Ordering;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::fmt;
+use std::sync::Mutex;
+
+use cypher::{tokenize, Token, TokenKind};
+use neo4j::proto::transaction_manager::{
+    BlockChangesResponse, BlockExecuteRequest, DatabaseModifications,
+    DatabaseModifications_AssignedLabel, DatabaseModifications_AssignedNodeProperty,
+    DatabaseModifications_AssignedRelationshipProperty, DatabaseModifications_CreatedNode,
+    DatabaseModifications_CreatedRelationShip, DatabaseModifications_DeletedNode,
+    DatabaseModifications_DeletedRelationship, DatabaseModifications_RemovedLabel,
+    DatabaseModifications_RemovedNodeProperty, DatabaseModifications_RemovedRelationshipProperty,
+    Error as ProtoError, ErrorCode, FailedQuery, SchemaChangeRequest, Status, TransactionRequest,
+    TransactionResponse,
+};
+use neo4j::GraphBackend;
+use structures::normalize_definition;
+
+///Property holding the uuid of nodes and relationships.
+const UUID: &str = "uuid";
+
+const SYNTAX_ERROR: &str = "Neo.ClientError.Statement.SyntaxError";
+const SEMANTIC_ERROR: &str = "Neo.ClientError.Statement.SemanticError";
+const TYPE_ERROR: &str = "Neo.ClientError.Statement.TypeError";
+
+///Value of a property.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Value {
+    ///Absent value, setting it removes the property
+    Null,
+    ///Boolean
+    Boolean(bool),
+    ///Integer
+    Integer(i64),
+    ///Floating point number
+    Float(f64),
+    ///String
+    String(String),
+}
+
+impl Value {
+    ///Order of comparable values, none for values of different types.
+    fn compare(&self, other: &Value) -> Option<Ordering> {
+        match (self, other) {
+            (Value::Boolean(a), Value::Boolean(b)) => Some(a.cmp(b)),
+            (Value::Integer(a), Value::Integer(b)) => Some(a.cmp(b)),
+            (Value::Integer(a), Value::Float(b)) => (*a as f64).partial_cmp(b),
+            (Value::Float(a), Value::Integer(b)) => a.partial_cmp(&(*b as f64)),
+            (Value::Float(a), Value::Float(b)) => a.partial_cmp(b),
+            (Value::String(a), Value::String(b)) => Some(a.cmp(b)),
+            _ => None,
+        }
+    }
+
+    fn is_equal(&self, other: &Value) -> bool {
+        self.compare(other) == Some(Ordering::Equal)
+    }
+
+    ///Cypher type of the value, as the plugin reports the type of previous values.
+    fn type_name(&self) -> &'static str {
+        match self {
+            Value::Null => "NULL",
+            Value::Boolean(_) => "BOOLEAN",
+            Value::Integer(_) => "INTEGER",
+            Value::Float(_) => "FLOAT",
+            Value::String(_) => "STRING",
+        }
+    }
+}
+
+///Written the way Java's `toString` writes the value, as the plugin reports property values.
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Value::Null => write!(f, "null"),
+            Value::Boolean(b) => write!(f, "{}", b),
+            Value::Integer(i) => write!(f, "{}", i),
+            Value::Float(x) if x.fract() == 0.0 && x.abs() < 1e7 => write!(f, "{:.1}", x),
+            Value::Float(x) => write!(f, "{}", x),
+            Value::String(s) => write!(f, "{}", s),
+        }
+    }
+}
+
+type Properties = BTreeMap<String, Value>;
+
+#[derive(Clone, Debug, Default)]
+struct Node {
+    labels: BTreeSet<String>,
+    properties: Properties,
+}
+
+#[derive(Clone, Debug)]
+struct Relationship {
+    rel_type: String,
+    start: u64,
+    end: u64,
+    properties: Properties,
+}
+
+///Nodes and relationships by id, with the indexes and constraints created on them.
+#[derive(Clone, Debug, Default)]
+struct Graph {
+    nodes: BTreeMap<u64, Node>,
+    relationships: BTreeMap<u64, Relationship>,
+    next_node_id: u64,
+    next_relationship_id: u64,
+    schema: BTreeSet<String>,
+}
+
+///Statement that could not be run, with the Neo4j status code it would have failed with.
+#[derive(Clone, Debug, PartialEq)]
+struct StatementError {
+    message: String,
+    status_code: &'static str,
+}
+
+impl StatementError {
+    fn new(message: String, status_code: &'static str) -> Self {
+        StatementError {
+            message,
+            status_code,
+        }
+    }
+
+    fn unsupported(what: &str) -> Self {
+        Self::new(
+            format!("{} is not supported by the in-memory graph", what),
+            SYNTAX_ERROR,
+        )
+    }
+}
+
+type StatementResult<T> = Result<T, StatementError>;
+
+#[derive(Clone, Debug)]
+enum Expr {
+    Literal(Value),
+    Variable(String),
+    Property(String, String),
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Direction {
+    Outgoing,
+    Incoming,
+    Both,
+}
+
+#[derive(Clone, Debug)]
+struct NodePattern {
+    variable: Option<String>,
+    labels: Vec<String>,
+    properties: Vec<(String, Expr)>,
+}
+
+#[derive(Clone, Debug)]
+struct RelationshipPattern {
+    variable: Option<String>,
+    types: Vec<String>,
+    properties: Vec<(String, Expr)>,
+    direction: Direction,
+}
+
+#[derive(Clone, Debug)]
+struct Pattern {
+    start: NodePattern,
+    steps: Vec<(RelationshipPattern, NodePattern)>,
+}
+
+#[derive(Clone, Copy, Debug)]
+enum Operator {
+    Equal,
+    NotEqual,
+    Less,
+    Greater,
+    LessOrEqual,
+    GreaterOrEqual,
+    StartsWith,
+    EndsWith,
+    Contains,
+}
+
+#[derive(Clone, Debug)]
+enum Condition {
+    And(Box<Condition>, Box<Condition>),
+    Or(Box<Condition>, Box<Condition>),
+    Not(Box<Condition>),
+    Compare(Expr, Operator, Expr),
+    IsNull(Expr, bool),
+    HasLabels(String, Vec<String>),
+}
+
+#[derive(Clone, Debug)]
+enum SetItem {
+    Property(String, String, Expr),
+    Labels(String, Vec<String>),
+    Merge(String, Vec<(String, Expr)>),
+    Replace(String, Vec<(String, Expr)>),
+}
+
+#[derive(Clone, Debug)]
+enum RemoveItem {
+    Property(String, String),
+    Labels(String, Vec<String>),
+}
+
+#[derive(Clone, Debug)]
+enum Clause {
+    Match(Vec<Pattern>, Option<Condition>),
+    Create(Vec<Pattern>),
+    Merge(Pattern, Vec<SetItem>, Vec<SetItem>),
+    Set(Vec<SetItem>),
+    Remove(Vec<RemoveItem>),
+    Delete(Vec<String>, bool),
+    With(Option<Vec<(String, String)>>, Option<Condition>),
+    Return,
+}
+
+///Turns the tokens of a statement into clauses.
+struct Parser<'a> {
+    tokens: Vec<Token<'a>>,
+    pos: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn peek(&self) -> Option<&Token<'a>> {
+        self.tokens.get(self.pos)
+    }
+
+    fn peek_kind(&self) -> Option<TokenKind> {
+        self.tokens.get(self.pos).map(|t| t.kind)
+    }
+
+    fn peek_keyword(&self, keyword: &str) -> bool {
+        self.peek().map_or(false, |t| t.is_keyword(keyword))
+    }
+
+    fn peek_symbol(&self, symbol: &str) -> bool {
+        self.peek().map_or(false, |t| t.is_symbol(symbol))
+    }
+
+    fn eat_keyword(&mut self, keyword: &str) -> bool {
+        let found = self.peek_keyword(keyword);
+        if found {
+            self.pos += 1;
+        }
+        found
+    }
+
+    fn eat_symbol(&mut self, symbol: &str) -> bool {
+        let found = self.peek_symbol(symbol);
+        if found {
+            self.pos += 1;
+        }
+        found
+    }
+
+    fn error(&self, expected: &str) -> StatementError {
+        let message = match self.peek() {
+            Some(token) => format!(
+                "Invalid input '{}': expected {} (offset: {})",
+                token.text, expected, token.offset
+            ),
+            None => format!("Unexpected end of input: expected {}", expected),
+        };
+        StatementError::new(message, SYNTAX_ERROR)
+    }
+
+    fn expect_symbol(&mut self, symbol: &str) -> StatementResult<()> {
+        if self.eat_symbol(symbol) {
+            Ok(())
+        } else {
+            Err(self.error(&format!("'{}'", symbol)))
+        }
+    }
+
+    fn expect_keyword(&mut self, keyword: &str) -> StatementResult<()> {
+        if self.eat_keyword(keyword) {
+            Ok(())
+        } else {
+            Err(self.error(keyword))
+        }
+    }
+
+    fn name(&mut self) -> StatementResult<String> {
+        if self.peek_kind() != Some(TokenKind::Identifier) {
+            return Err(self.error("a name"));
+        }
+        let name = self.tokens[self.pos].text.replace("``", "`");
+        self.pos += 1;
+        Ok(name)
+    }
+
+    fn statement(&mut self) -> StatementResult<Vec<Clause>> {
+        let mut clauses = Vec::new();
+        while self.peek().is_some() {
+            if self.eat_keyword("MATCH") {
+                let patterns = self.patterns()?;
+                let condition = self.where_condition()?;
+                clauses.push(Clause::Match(patterns, condition));
+            } else if self.eat_keyword("CREATE") {
+                if self.peek_keyword("INDEX") || self.peek_keyword("CONSTRAINT") {
+                    return Err(StatementError::new(
+                        String::from("Schema statements cannot be mixed with data writes"),
+                        "Neo.ClientError.Transaction.ForbiddenDueToTransactionType",
+                    ));
+                }
+                clauses.push(Clause::Create(self.patterns()?));
+            } else if self.eat_keyword("MERGE") {
+                let pattern = self.pattern()?;
+                let mut on_create = Vec::new();
+                let mut on_match = Vec::new();
+                while self.eat_keyword("ON") {
+                    if self.eat_keyword("CREATE") {
+                        self.expect_keyword("SET")?;
+                        on_create.extend(self.set_items()?);
+                    } else {
+                        self.expect_keyword("MATCH")?;
+                        self.expect_keyword("SET")?;
+                        on_match.extend(self.set_items()?);
+                    }
+                }
+                clauses.push(Clause::Merge(pattern, on_create, on_match));
+            } else if self.eat_keyword("SET") {
+                clauses.push(Clause::Set(self.set_items()?));
+            } else if self.eat_keyword("REMOVE") {
+                clauses.push(Clause::Remove(self.remove_items()?));
+            } else if self.eat_keyword("DETACH") {
+                self.expect_keyword("DELETE")?;
+                clauses.push(Clause::Delete(self.names()?, true));
+            } else if self.eat_keyword("DELETE") {
+                clauses.push(Clause::Delete(self.names()?, false));
+            } else if self.eat_keyword("WITH") {
+                let projection = if self.eat_symbol("*") {
+                    None
+                } else {
+                    let mut projection = Vec::new();
+                    loop {
+                        let variable = self.name()?;
+                        let alias = if self.eat_keyword("AS") {
+                            self.name()?
+                        } else {
+                            variable.clone()
+                        };
+                        projection.push((variable, alias));
+                        if !self.eat_symbol(",") {
+                            break;
+                        }
+                    }
+                    Some(projection)
+                };
+                let condition = self.where_condition()?;
+                clauses.push(Clause::With(projection, condition));
+            } else if self.eat_keyword("RETURN") {
+                // Nothing is returned to the service, so the projection is not needed.
+                clauses.push(Clause::Return);
+                break;
+            } else {
+                let clause = self.peek().map_or("", |t| t.text).to_uppercase();
+                return Err(match clause.as_str() {
+                    "OPTIONAL" | "UNWIND" | "CALL" | "FOREACH" | "LOAD" | "UNION" | "USING" => {
+                        StatementError::unsupported(&clause)
+                    }
+                    _ => self.error("a clause"),
+                });
+            }
+        }
+        match clauses.last() {
+            Some(Clause::Match(..)) | Some(Clause::With(..)) => Err(StatementError::new(
+                String::from(
+                    "Query cannot conclude with MATCH or WITH (must be RETURN or an update clause)",
+                ),
+                SYNTAX_ERROR,
+            )),
+            _ => Ok(clauses),
+        }
+    }
+
+    fn names(&mut self) -> StatementResult<Vec<String>> {
+        let mut names = vec![self.name()?];
+        while self.eat_symbol(",") {
+            names.push(self.name()?);
+        }
+        Ok(names)
+    }
+
+    fn labels(&mut self) -> StatementResult<Vec<String>> {
+        let mut labels = Vec::new();
+        while self.eat_symbol(":") {
+            labels.push(self.name()?);
+        }
+        Ok(labels)
+    }
+
+    fn patterns(&mut self) -> StatementResult<Vec<Pattern>> {
+        let mut patterns = vec![self.pattern()?];
+        while self.eat_symbol(",") {
+            patterns.push(self.pattern()?);
+        }
+        Ok(patterns)
+    }
+
+    fn pattern(&mut self) -> StatementResult<Pattern> {
+        let start = self.node_pattern()?;
+        let mut steps = Vec::new();
+        while self.peek_symbol("-") || self.peek_symbol("<") {
+            let relationship = self.relationship_pattern()?;
+            steps.push((relationship, self.node_pattern()?));
+        }
+        Ok(Pattern { start, steps })
+    }
+
+    fn node_pattern(&mut self) -> StatementResult<NodePattern> {
+        self.expect_symbol("(")?;
+        let variable = if self.peek_kind() == Some(TokenKind::Identifier) {
+            Some(self.name()?)
+        } else {
+            None
+        };
+        let labels = self.labels()?;
+        let properties = if self.peek_symbol("{") {
+            self.map()?
+        } else {
+            Vec::new()
+        };
+        self.expect_symbol(")")?;
+        Ok(NodePattern {
+            variable,
+            labels,
+            properties,
+        })
+    }
+
+    fn relationship_pattern(&mut self) -> StatementResult<RelationshipPattern> {
+        let incoming = self.eat_symbol("<");
+        self.expect_symbol("-")?;
+        let mut variable = None;
+        let mut types = Vec::new();
+        let mut properties = Vec::new();
+        if self.eat_symbol("[") {
+            if self.peek_kind() == Some(TokenKind::Identifier) {
+                variable = Some(self.name()?);
+            }
+            if self.eat_symbol(":") {
+                types.push(self.name()?);
+                while self.eat_symbol("|") {
+                    self.eat_symbol(":");
+                    types.push(self.name()?);
+                }
+            }
+            if self.peek_symbol("*") {
+                return Err(StatementError::unsupported(
+                    "Variable length relationships are",
+                ));
+            }
+            if self.peek_symbol("{") {
+                properties = self.map()?;
+            }
+            self.expect_symbol("]")?;
+        }
+        self.expect_symbol("-")?;
+        let outgoing = self.eat_symbol(">");
+        let direction = match (incoming, outgoing) {
+            (false, true) => Direction::Outgoing,
+            (true, false) => Direction::Incoming,
+            (false, false) => Direction::Both,
+            (true, true) => return Err(self.error("a relationship with one direction")),
+        };
+        Ok(RelationshipPattern {
+            variable,
+            types,
+            properties,
+            direction,
+        })
+    }
+
+    fn map(&mut self) -> StatementResult<Vec<(String, Expr)>> {
+        self.expect_symbol("{")?;
+        let mut entries = Vec::new();
+        if self.eat_symbol("}") {
+            return Ok(entries);
+        }
+        loop {
+            let key = if self.peek_kind() == Some(TokenKind::String) {
+                self.pos += 1;
+                unescape(self.tokens[self.pos - 1].text)
+            } else {
+                self.name()?
+            };
+            self.expect_symbol(":")?;
+            entries.push((key, self.expr()?));
+            if !self.eat_symbol(",") {
+                break;
+            }
+        }
+        self.expect_symbol("}")?;
+        Ok(entries)
+    }
+
+    fn expr(&mut self) -> StatementResult<Expr> {
+        let negative = self.eat_symbol("-");
+        let token = match self.peek() {
+            Some(token) => token.clone(),
+            None => return Err(self.error("an expression")),
+        };
+        let expr = match token.kind {
+            TokenKind::Number => {
+                let text = if negative {
+                    format!("-{}", token.text)
+                } else {
+                    token.text.to_string()
+                };
+                let value = if text.contains('.') {
+                    text.parse().map(Value::Float).ok()
+                } else {
+                    text.parse().map(Value::Integer).ok()
+                };
+                match value {
+                    Some(value) => Expr::Literal(value),
+                    None => return Err(self.error("a number")),
+                }
+            }
+            _ if negative => return Err(self.error("a number")),
+            TokenKind::String => Expr::Literal(Value::String(unescape(token.text))),
+            TokenKind::Parameter => return Err(StatementError::unsupported("Parameters are")),
+            TokenKind::Identifier if token.is_keyword("true") => {
+                Expr::Literal(Value::Boolean(true))
+            }
+            TokenKind::Identifier if token.is_keyword("false") => {
+                Expr::Literal(Value::Boolean(false))
+            }
+            TokenKind::Identifier if token.is_keyword("null") => Expr::Literal(Value::Null),
+            TokenKind::Identifier => {
+                let variable = token.text.replace("``", "`");
+                self.pos += 1;
+                if self.eat_symbol(".") {
+                    return Ok(Expr::Property(variable, self.name()?));
+                }
+                if self.peek_symbol("(") {
+                    return Err(StatementError::unsupported("Calling functions is"));
+                }
+                return Ok(Expr::Variable(variable));
+            }
+            TokenKind::Symbol => return Err(self.error("an expression")),
+        };
+        self.pos += 1;
+        Ok(expr)
+    }
+
+    fn where_condition(&mut self) -> StatementResult<Option<Condition>> {
+        if self.eat_keyword("WHERE") {
+            self.or_condition().map(Some)
+        } else {
+            Ok(None)
+        }
+    }
+
+    fn or_condition(&mut self) -> StatementResult<Condition> {
+        let mut condition = self.and_condition()?;
+        while self.eat_keyword("OR") {
+            let right = self.and_condition()?;
+            condition = Condition::Or(Box::new(condition), Box::new(right));
+        }
+        Ok(condition)
+    }
+
+    fn and_condition(&mut self) -> StatementResult<Condition> {
+        let mut condition = self.not_condition()?;
+        while self.eat_keyword("AND") {
+            let right = self.not_condition()?;
+            condition = Condition::And(Box::new(condition), Box::new(right));
+        }
+        Ok(condition)
+    }
+
+    fn not_condition(&mut self) -> StatementResult<Condition> {
+        if self.eat_keyword("NOT") {
+            return Ok(Condition::Not(Box::new(self.not_condition()?)));
+        }
+        if self.eat_symbol("(") {
+            let condition = self.or_condition()?;
+            self.expect_symbol(")")?;
+            return Ok(condition);
+        }
+        let left = self.expr()?;
+        if let Expr::Variable(ref variable) = left {
+            if self.peek_symbol(":") {
+                return Ok(Condition::HasLabels(variable.clone(), self.labels()?));
+            }
+        }
+        if self.eat_keyword("IS") {
+            let negated = self.eat_keyword("NOT");
+            self.expect_keyword("NULL")?;
+            return Ok(Condition::IsNull(left, negated));
+        }
+        let operator = if self.eat_symbol("=") {
+            Operator::Equal
+        } else if self.eat_symbol("<") {
+            if self.eat_symbol(">") {
+                Operator::NotEqual
+            } else if self.eat_symbol("=") {
+                Operator::LessOrEqual
+            } else {
+                Operator::Less
+            }
+        } else if self.eat_symbol(">") {
+            if self.eat_symbol("=") {
+                Operator::GreaterOrEqual
+            } else {
+                Operator::Greater
+            }
+        } else if self.eat_keyword("STARTS") {
+            self.expect_keyword("WITH")?;
+            Operator::StartsWith
+        } else if self.eat_keyword("ENDS") {
+            self.expect_keyword("WITH")?;
+            Operator::EndsWith
+        } else if self.eat_keyword("CONTAINS") {
+            Operator::Contains
+        } else {
+            return Err(self.error("a comparison"));
+        };
+        Ok(Condition::Compare(left, operator, self.expr()?))
+    }
+
+    fn set_items(&mut self) -> StatementResult<Vec<SetItem>> {
+        let mut items = Vec::new();
+        loop {
+            let variable = self.name()?;
+            let item = if self.eat_symbol(".") {
+                let key = self.name()?;
+                self.expect_symbol("=")?;
+                SetItem::Property(variable, key, self.expr()?)
+            } else if self.peek_symbol(":") {
+                SetItem::Labels(variable, self.labels()?)
+            } else if self.eat_symbol("+") {
+                self.expect_symbol("=")?;
+                SetItem::Merge(variable, self.map()?)
+            } else {
+                self.expect_symbol("=")?;
+                SetItem::Replace(variable, self.map()?)
+            };
+            items.push(item);
+            if !self.eat_symbol(",") {
+                return Ok(items);
+            }
+        }
+    }
+
+    fn remove_items(&mut self) -> StatementResult<Vec<RemoveItem>> {
+        let mut items = Vec::new();
+        loop {
+            let variable = self.name()?;
+            let item = if self.eat_symbol(".") {
+                RemoveItem::Property(variable, self.name()?)
+            } else if self.peek_symbol(":") {
+                RemoveItem::Labels(variable, self.labels()?)
+            } else {
+                return Err(self.error("'.' or ':'"));
+            };
+            items.push(item);
+            if !self.eat_symbol(",") {
+                return Ok(items);
+            }
+        }
+    }
+}
+
+///Resolves the escape sequences of a string literal.
+fn unescape(text: &str) -> String {
+    let mut result = String::with_capacity(text.len());
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            result.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('n') => result.push('\n'),
+            Some('t') => result.push('\t'),
+            Some('r') => result.push('\r'),
+            Some(other) => result.push(other),
+            None => result.push('\\'),
+        }
+    }
+    result
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Entity {
+    Node(u64),
+    Relationship(u64),
+}
+
+type Row = BTreeMap<String, Entity>;
+
+fn bound<'r>(row: &'r Row, variable: &str) -> StatementResult<&'r Entity> {
+    row.get(variable).ok_or_else(|| {
+        StatementError::new(format!("Variable `{}` not defined", variable), SYNTAX_ERROR)
+    })
+}
+
+fn bound_node(row: &Row, variable: &str) -> StatementResult<Option<u64>> {
+    match row.get(variable) {
+        None => Ok(None),
+        Some(Entity::Node(id)) => Ok(Some(*id)),
+        Some(Entity::Relationship(_)) => Err(StatementError::new(
+            format!(
+                "Type mismatch: `{}` is a relationship, not a node",
+                variable
+            ),
+            TYPE_ERROR,
+        )),
+    }
+}
+
+impl Graph {
+    fn properties(&self, entity: Entity) -> Option<&Properties> {
+        match entity {
+            Entity::Node(id) => self.nodes.get(&id).map(|n| &n.properties),
+            Entity::Relationship(id) => self.relationships.get(&id).map(|r| &r.properties),
+        }
+    }
+
+    fn properties_mut(&mut self, entity: Entity) -> Option<&mut Properties> {
+        match entity {
+            Entity::Node(id) => self.nodes.get_mut(&id).map(|n| &mut n.properties),
+            Entity::Relationship(id) => self.relationships.get_mut(&id).map(|r| &mut r.properties),
+        }
+    }
+
+    fn eval(&self, expr: &Expr, row: &Row) -> StatementResult<Value> {
+        match expr {
+            Expr::Literal(value) => Ok(value.clone()),
+            Expr::Property(variable, key) => {
+                let entity = bound(row, variable)?;
+                Ok(self
+                    .properties(*entity)
+                    .and_then(|p| p.get(key))
+                    .cloned()
+                    .unwrap_or(Value::Null))
+            }
+            Expr::Variable(variable) => {
+                bound(row, variable)?;
+                Err(StatementError::unsupported(
+                    "Using nodes and relationships as values is",
+                ))
+            }
+        }
+    }
+
+    fn eval_map(&self, entries: &[(String, Expr)], row: &Row) -> StatementResult<Properties> {
+        let mut properties = Properties::new();
+        for (key, expr) in entries {
+            let value = self.eval(expr, row)?;
+            if value != Value::Null {
+                properties.insert(key.clone(), value);
+            }
+        }
+        Ok(properties)
+    }
+
+    fn has_properties(
+        &self,
+        entity: Entity,
+        entries: &[(String, Expr)],
+        row: &Row,
+    ) -> StatementResult<bool> {
+        let properties = match self.properties(entity) {
+            Some(properties) => properties,
+            None => return Ok(false),
+        };
+        for (key, expr) in entries {
+            let expected = self.eval(expr, row)?;
+            if !properties.get(key).map_or(false, |v| v.is_equal(&expected)) {
+                return Ok(false);
+            }
+        }
+        Ok(true)
+    }
+
+    fn node_matches(&self, id: u64, pattern: &NodePattern, row: &Row) -> StatementResult<bool> {
+        let node = match self.nodes.get(&id) {
+            Some(node) => node,
+            None => return Ok(false),
+        };
+        if !pattern.labels.iter().all(|l| node.labels.contains(l)) {
+            return Ok(false);
+        }
+        self.has_properties(Entity::Node(id), &pattern.properties, row)
+    }
+
+    ///Rows extending the given row with each match of the pattern.
+    fn match_pattern(&self, pattern: &Pattern, row: &Row) -> StatementResult<Vec<Row>> {
+        let candidates: Vec<u64> = match pattern.start.variable {
+            Some(ref variable) => match bound_node(row, variable)? {
+                Some(id) => vec![id],
+                None => self.nodes.keys().cloned().collect(),
+            },
+            None => self.nodes.keys().cloned().collect(),
+        };
+        let mut rows = Vec::new();
+        for id in candidates {
+            if !self.node_matches(id, &pattern.start, row)? {
+                continue;
+            }
+            let mut row = row.clone();
+            if let Some(ref variable) = pattern.start.variable {
+                row.insert(variable.clone(), Entity::Node(id));
+            }
+            self.extend_path(pattern, 0, id, row, &mut Vec::new(), &mut rows)?;
+        }
+        Ok(rows)
+    }
+
+    fn extend_path(
+        &self,
+        pattern: &Pattern,
+        step: usize,
+        current: u64,
+        row: Row,
+        used: &mut Vec<u64>,
+        rows: &mut Vec<Row>,
+    ) -> StatementResult<()> {
+        let (rel_pattern, node_pattern) = match pattern.steps.get(step) {
+            Some(step) => step,
+            None => {
+                rows.push(row);
+                return Ok(());
+            }
+        };
+        let bound_relationship = match rel_pattern.variable {
+            Some(ref variable) => match row.get(variable) {
+                Some(Entity::Relationship(id)) => Some(*id),
+                Some(Entity::Node(_)) => {
+                    return Err(StatementError::new(
+                        format!(
+                            "Type mismatch: `{}` is a node, not a relationship",
+                            variable
+                        ),
+                        TYPE_ERROR,
+                    ))
+                }
+                None => None,
+            },
+            None => None,
+        };
+        let bound_end = match node_pattern.variable {
+            Some(ref variable) => bound_node(&row, variable)?,
+            None => None,
+        };
+        for (id, relationship) in &self.relationships {
+            if used.contains(id) || bound_relationship.map_or(false, |b| b != *id) {
+                continue;
+            }
+            let mut ends = Vec::new();
+            if rel_pattern.direction != Direction::Incoming && relationship.start == current {
+                ends.push(relationship.end);
+            }
+            if rel_pattern.direction != Direction::Outgoing
+                && relationship.end == current
+                && (relationship.start != current || ends.is_empty())
+            {
+                ends.push(relationship.start);
+            }
+            if ends.is_empty()
+                || (!rel_pattern.types.is_empty()
+                    && !rel_pattern.types.contains(&relationship.rel_type))
+                || !self.has_properties(Entity::Relationship(*id), &rel_pattern.properties, &row)?
+            {
+                continue;
+            }
+            for end in ends {
+                if bound_end.map_or(false, |b| b != end)
+                    || !self.node_matches(end, node_pattern, &row)?
+                {
+                    continue;
+                }
+                let mut next = row.clone();
+                if let Some(ref variable) = rel_pattern.variable {
+                    next.insert(variable.clone(), Entity::Relationship(*id));
+                }
+                if let Some(ref variable) = node_pattern.variable {
+                    next.insert(variable.clone(), Entity::Node(end));
+                }
+                used.push(*id);
+                self.extend_path(pattern, step + 1, end, next, used, rows)?;
+                used.pop();
+            }
+        }
+        Ok(())
+    }
+
+    fn check(&self, condition: &Condition, row: &Row) -> StatementResult<bool> {
+        Ok(match condition {
+            Condition::And(left, right) => self.check(left, row)? && self.check(right, row)?,
+            Condition::Or(left, right) => self.check(left, row)? || self.check(right, row)?,
+            Condition::Not(inner) => !self.check(inner, row)?,
+            Condition::IsNull(expr, negated) => (self.eval(expr, row)? == Value::Null) != *negated,
+            Condition::HasLabels(variable, labels) => match bound(row, variable)? {
+                Entity::Node(id) => self
+                    .nodes
+                    .get(id)
+                    .map_or(false, |n| labels.iter().all(|l| n.labels.contains(l))),
+                Entity::Relationship(_) => false,
+            },
+            Condition::Compare(left, operator, right) => {
+                let (left, right) = (self.eval(left, row)?, self.eval(right, row)?);
+                let ordering = left.compare(&right);
+                match (operator, &left, &right) {
+                    (Operator::Equal, _, _) => ordering == Some(Ordering::Equal),
+                    (Operator::NotEqual, _, _) => ordering.map_or(false, |o| o != Ordering::Equal),
+                    (Operator::Less, _, _) => ordering == Some(Ordering::Less),
+                    (Operator::Greater, _, _) => ordering == Some(Ordering::Greater),
+                    (Operator::LessOrEqual, _, _) => {
+                        ordering.map_or(false, |o| o != Ordering::Greater)
+                    }
+                    (Operator::GreaterOrEqual, _, _) => {
+                        ordering.map_or(false, |o| o != Ordering::Less)
+                    }
+                    (Operator::StartsWith, Value::String(a), Value::String(b)) => a.starts_with(b),
+                    (Operator::EndsWith, Value::String(a), Value::String(b)) => a.ends_with(b),
+                    (Operator::Contains, Value::String(a), Value::String(b)) => a.contains(b),
+                    _ => false,
+                }
+            }
+        })
+    }
+
+    fn create_node(&mut self, pattern: &NodePattern, row: &mut Row) -> StatementResult<u64> {
+        if let Some(ref variable) = pattern.variable {
+            if let Some(id) = bound_node(row, variable)? {
+                if !pattern.labels.is_empty() || !pattern.properties.is_empty() {
+                    return Err(StatementError::new(
+                        format!(
+                            "Can't create node `{}` with labels or properties here. \
+                             The variable is already declared in this context",
+                            variable
+                        ),
+                        SYNTAX_ERROR,
+                    ));
+                }
+                return Ok(id);
+            }
+        }
+        let node = Node {
+            labels: pattern.labels.iter().cloned().collect(),
+            properties: self.eval_map(&pattern.properties, row)?,
+        };
+        let id = self.next_node_id;
+        self.next_node_id += 1;
+        self.nodes.insert(id, node);
+        if let Some(ref variable) = pattern.variable {
+            row.insert(variable.clone(), Entity::Node(id));
+        }
+        Ok(id)
+    }
+
+    fn create_pattern(&mut self, pattern: &Pattern, row: &mut Row) -> StatementResult<()> {
+        let mut current = self.create_node(&pattern.start, row)?;
+        for (rel_pattern, node_pattern) in &pattern.steps {
+            if rel_pattern.types.len() != 1 {
+                return Err(StatementError::new(
+                    String::from(
+                        "Exactly one relationship type must be specified for CREATE or MERGE",
+                    ),
+                    SYNTAX_ERROR,
+                ));
+            }
+            if rel_pattern.direction == Direction::Both {
+                return Err(StatementError::new(
+                    String::from("Only directed relationships are supported in CREATE"),
+                    SYNTAX_ERROR,
+                ));
+            }
+            if let Some(ref variable) = rel_pattern.variable {
+                if row.contains_key(variable) {
+                    return Err(StatementError::new(
+                        format!("Variable `{}` already declared", variable),
+                        SYNTAX_ERROR,
+                    ));
+                }
+            }
+            let end = self.create_node(node_pattern, row)?;
+            let (start, end_node) = match rel_pattern.direction {
+                Direction::Incoming => (end, current),
+                _ => (current, end),
+            };
+            let relationship = Relationship {
+                rel_type: rel_pattern.types[0].clone(),
+                start,
+                end: end_node,
+                properties: self.eval_map(&rel_pattern.properties, row)?,
+            };
+            let id = self.next_relationship_id;
+            self.next_relationship_id += 1;
+            self.relationships.insert(id, relationship);
+            if let Some(ref variable) = rel_pattern.variable {
+                row.insert(variable.clone(), Entity::Relationship(id));
+            }
+            current = end;
+        }
+        Ok(())
+    }
+
+    fn set(&mut self, item: &SetItem, row: &Row) -> StatementResult<()> {
+        let (variable, update) = match item {
+            SetItem::Property(variable, key, expr) => {
+                let value = self.eval(expr, row)?;
+                let mut properties = Properties::new();
+                properties.insert(key.clone(), value);
+                (variable, Some((properties, false)))
+            }
+            SetItem::Merge(variable, entries) => {
+                let mut properties = Properties::new();
+                for (key, expr) in entries {
+                    properties.insert(key.clone(), self.eval(expr, row)?);
+                }
+                (variable, Some((properties, false)))
+            }
+            SetItem::Replace(variable, entries) => {
+                (variable, Some((self.eval_map(entries, row)?, true)))
+            }
+            SetItem::Labels(variable, _) => (variable, None),
+        };
+        let entity = *bound(row, variable)?;
+        match (update, item, entity) {
+            (Some((properties, replace)), _, _) => {
+                if let Some(current) = self.properties_mut(entity) {
+                    if replace {
+                        current.clear();
+                    }
+                    for (key, value) in properties {
+                        if value == Value::Null {
+                            current.remove(&key);
+                        } else {
+                            current.insert(key, value);
+                        }
+                    }
+                }
+            }
+            (None, SetItem::Labels(_, labels), Entity::Node(id)) => {
+                if let Some(node) = self.nodes.get_mut(&id) {
+                    node.labels.extend(labels.iter().cloned());
+                }
+            }
+            _ => {
+                return Err(StatementError::new(
+                    format!(
+                        "Type mismatch: `{}` is a relationship, not a node",
+                        variable
+                    ),
+                    TYPE_ERROR,
+                ))
+            }
+        }
+        Ok(())
+    }
+
+    fn remove(&mut self, item: &RemoveItem, row: &Row) -> StatementResult<()> {
+        match item {
+            RemoveItem::Property(variable, key) => {
+                let entity = *bound(row, variable)?;
+                if let Some(properties) = self.properties_mut(entity) {
+                    properties.remove(key);
+                }
+            }
+            RemoveItem::Labels(variable, labels) => match *bound(row, variable)? {
+                Entity::Node(id) => {
+                    if let Some(node) = self.nodes.get_mut(&id) {
+                        for label in labels {
+                            node.labels.remove(label);
+                        }
+                    }
+                }
+                Entity::Relationship(_) => {
+                    return Err(StatementError::new(
+                        format!(
+                            "Type mismatch: `{}` is a relationship, not a node",
+                            variable
+                        ),
+                        TYPE_ERROR,
+                    ))
+                }
+            },
+        }
+        Ok(())
+    }
+
+    fn delete(&mut self, entity: Entity, detach: bool) {
+        match entity {
+            Entity::Node(id) => {
+                if detach {
+                    self.relationships
+                        .retain(|_, r| r.start != id && r.end != id);
+                }
+                // Relationships left behind are refused when the transaction commits.
+                self.nodes.remove(&id);
+            }
+            Entity::Relationship(id) => {
+                self.relationships.remove(&id);
+            }
+        }
+    }
+
+    fn run_clause(&mut self, clause: &Clause, rows: Vec<Row>) -> StatementResult<Vec<Row>> {
+        let mut result = Vec::new();
+        match clause {
+            Clause::Match(patterns, condition) => {
+                let mut current = rows;
+                for pattern in patterns {
+                    let mut next = Vec::new();
+                    for row in &current {
+                        next.extend(self.match_pattern(pattern, row)?);
+                    }
+                    current = next;
+                }
+                for row in current {
+                    let keep = match condition {
+                        Some(condition) => self.check(condition, &row)?,
+                        None => true,
+                    };
+                    if keep {
+                        result.push(row);
+                    }
+                }
+            }
+            Clause::Create(patterns) => {
+                for mut row in rows {
+                    for pattern in patterns {
+                        self.create_pattern(pattern, &mut row)?;
+                    }
+                    result.push(row);
+                }
+            }
+            Clause::Merge(pattern, on_create, on_match) => {
+                for mut row in rows {
+                    let matches = self.match_pattern(pattern, &row)?;
+                    if matches.is_empty() {
+                        self.create_pattern(pattern, &mut row)?;
+                        for item in on_create {
+                            self.set(item, &row)?;
+                        }
+                        result.push(row);
+                    } else {
+                        for found in matches {
+                            for item in on_match {
+                                self.set(item, &found)?;
+                            }
+                            result.push(found);
+                        }
+                    }
+                }
+            }
+            Clause::Set(items) => {
+                for row in rows {
+                    for item in items {
+                        self.set(item, &row)?;
+                    }
+                    result.push(row);
+                }
+            }
+            Clause::Remove(items) => {
+                for row in rows {
+                    for item in items {
+                        self.remove(item, &row)?;
+                    }
+                    result.push(row);
+                }
+            }
+            Clause::Delete(variables, detach) => {
+                for row in rows {
+                    for variable in variables {
+                        let entity = *bound(&row, variable)?;
+                        self.delete(entity, *detach);
+                    }
+                    result.push(row);
+                }
+            }
+            Clause::With(projection, condition) => {
+                for row in rows {
+                    let row = match projection {
+                        Some(projection) => {
+                            let mut projected = Row::new();
+                            for (variable, alias) in projection {
+                                projected.insert(alias.clone(), *bound(&row, variable)?);
+                            }
+                            projected
+                        }
+                        None => row,
+                    };
+                    let keep = match condition {
+                        Some(condition) => self.check(condition, &row)?,
+                        None => true,
+                    };
+                    if keep {
+                        result.push(row);
+                    }
+                }
+            }
+            Clause::Return => result = rows,
+        }
+        Ok(result)
+    }
+
+    ///Runs a statement, leaving the graph partially changed if it fails.
+    fn run_statement(&mut self, statement: &str) -> StatementResult<()> {
+        let tokens =
+            tokenize(statement).map_err(|e| StatementError::new(e.message, SYNTAX_ERROR))?;
+        let clauses = Parser { tokens, pos: 0 }.statement()?;
+        let mut rows = vec![Row::new()];
+        for clause in &clauses {
+            rows = self.run_clause(clause, rows)?;
+        }
+        Ok(())
+    }
+
+    fn uuid(&self, entity: Entity) -> Option<String> {
+        self.properties(entity)
+            .and_then(|p| p.get(UUID))
+            .map(|v| v.to_string())
+    }
+}
+
+fn value_changed(before: Option<&Value>, after: Option<&Value>) -> bool {
+    match (before, after) {
+        (Some(a), Some(b)) => a != b,
+        (None, None) => false,
+        _ => true,
+    }
+}
+
+///Property changes between two states of a node or relationship, as
+/// `(key, value, previous value)`; a removed property has no value.
+fn property_changes(
+    before: Option<&Properties>,
+    after: Option<&Properties>,
+) -> Vec<(String, Option<String>, Option<Value>)> {
+    let empty = Properties::new();
+    let (before, after) = (before.unwrap_or(&empty), after.unwrap_or(&empty));
+    let keys: BTreeSet<&String> = before.keys().chain(after.keys()).collect();
+    keys.into_iter()
+        .filter(|key| value_changed(before.get(*key), after.get(*key)))
+        .map(|key| {
+            (
+                key.clone(),
+                after.get(key).map(|v| v.to_string()),
+                before.get(key).cloned(),
+            )
+        })
+        .collect()
+}
+
+///Failure of a whole transaction, reported without a failed query.
+type CommitError = (ErrorCode, String);
+
+///Assigns uuids to the created nodes and relationships, and lists the changes between both
+/// graphs the way the plugin does, each list sorted by uuid.
+fn commit(
+    before: &Graph,
+    after: &mut Graph,
+    transaction_id: &str,
+) -> Result<DatabaseModifications, CommitError> {
+    let created_nodes: Vec<u64> = after
+        .nodes
+        .keys()
+        .filter(|id| !before.nodes.contains_key(id))
+        .cloned()
+        .collect();
+    let created_relationships: Vec<u64> = after
+        .relationships
+        .keys()
+        .filter(|id| !before.relationships.contains_key(id))
+        .cloned()
+        .collect();
+
+    let modified_uuid = after.nodes.iter().any(|(id, node)| {
+        let previous = before.nodes.get(id).and_then(|n| n.properties.get(UUID));
+        value_changed(previous, node.properties.get(UUID))
+    }) || after.relationships.iter().any(|(id, relationship)| {
+        let previous = before
+            .relationships
+            .get(id)
+            .and_then(|r| r.properties.get(UUID));
+        value_changed(previous, relationship.properties.get(UUID))
+    });
+    if modified_uuid {
+        return Err((
+            ErrorCode::MODIFIED_UUID,
+            String::from("Transaction tried to modify UUID properties."),
+        ));
+    }
+    if let Some((_, relationship)) = after
+        .relationships
+        .iter()
+        .find(|(_, r)| !after.nodes.contains_key(&r.start) || !after.nodes.contains_key(&r.end))
+    {
+        let node = if after.nodes.contains_key(&relationship.start) {
+            relationship.end
+        } else {
+            relationship.start
+        };
+        return Err((
+            ErrorCode::CONSTRAINT_VIOLATION,
+            format!(
+                "Cannot delete node<{}>, because it still has relationships. \
+                 To delete this node, you must first delete its relationships.",
+                node
+            ),
+        ));
+    }
+
+    let mut counter = 0;
+    for id in &created_nodes {
+        let uuid = Value::String(format!("{}_{}", transaction_id, counter));
+        counter += 1;
+        if let Some(node) = after.nodes.get_mut(id) {
+            node.properties.insert(UUID.to_string(), uuid);
+        }
+    }
+    for id in &created_relationships {
+        let uuid = Value::String(format!("{}_{}", transaction_id, counter));
+        counter += 1;
+        if let Some(relationship) = after.relationships.get_mut(id) {
+            relationship.properties.insert(UUID.to_string(), uuid);
+        }
+    }
+
+    let after = &*after;
+    let uuid = |entity: Entity| {
+        after
+            .uuid(entity)
+            .or_else(|| before.uuid(entity))
+            .unwrap_or_default()
+    };
+    let mut modifications = DatabaseModifications::new();
+
+    let mut nodes: Vec<u64> = before
+        .nodes
+        .keys()
+        .chain(after.nodes.keys())
+        .cloned()
+        .collect();
+    nodes.sort();
+    nodes.dedup();
+    let mut node_uuids: Vec<(String, u64)> = nodes
+        .into_iter()
+        .map(|id| (uuid(Entity::Node(id)), id))
+        .collect();
+    node_uuids.sort();
+    for (node_uuid, id) in &node_uuids {
+        let (old, new) = (before.nodes.get(id), after.nodes.get(id));
+        match (old, new) {
+            (None, Some(_)) => {
+                let mut created = DatabaseModifications_CreatedNode::new();
+                created.set_node_UUID(node_uuid.clone());
+                modifications.mut_created_nodes().push(created);
+            }
+            (Some(_), None) => {
+                let mut deleted = DatabaseModifications_DeletedNode::new();
+                deleted.set_node_UUID(node_uuid.clone());
+                modifications.mut_deleted_nodes().push(deleted);
+            }
+            _ => {}
+        }
+        let empty = BTreeSet::new();
+        let old_labels = old.map_or(&empty, |n| &n.labels);
+        let new_labels = new.map_or(&empty, |n| &n.labels);
+        for label in new_labels.difference(old_labels) {
+            let mut assigned = DatabaseModifications_AssignedLabel::new();
+            assigned.set_node_UUID(node_uuid.clone());
+            assigned.set_name(label.clone());
+            modifications.mut_assigned_labels().push(assigned);
+        }
+        for label in old_labels.difference(new_labels) {
+            let mut removed = DatabaseModifications_RemovedLabel::new();
+            removed.set_node_UUID(node_uuid.clone());
+            removed.set_name(label.clone());
+            modifications.mut_removed_labels().push(removed);
+        }
+        let changes = property_changes(old.map(|n| &n.properties), new.map(|n| &n.properties));
+        for (key, value, previous) in changes {
+            match value {
+                Some(value) => {
+                    let mut assigned = DatabaseModifications_AssignedNodeProperty::new();
+                    assigned.set_node_UUID(node_uuid.clone());
+                    assigned.set_key(key);
+                    assigned.set_value(value);
+                    if let Some(previous) = previous {
+                        assigned.set_has_previous_value(true);
+                        assigned.set_previous_value(previous.to_string());
+                        assigned.set_previous_value_type(previous.type_name().to_string());
+                    }
+                    modifications.mut_assigned_node_properties().push(assigned);
+                }
+                // The uuid of a deleted node is reported by the deletion itself.
+                None if key == UUID => {}
+                None => {
+                    let mut removed = DatabaseModifications_RemovedNodeProperty::new();
+                    removed.set_node_UUID(node_uuid.clone());
+                    removed.set_key(key);
+                    if let Some(previous) = previous {
+                        removed.set_has_previous_value(true);
+                        removed.set_previous_value(previous.to_string());
+                        removed.set_previous_value_type(previous.type_name().to_string());
+                    }
+                    modifications.mut_removed_node_properties().push(removed);
+                }
+            }
+        }
+    }
+
+    let mut relationships: Vec<u64> = before
+        .relationships
+        .keys()
+        .chain(after.relationships.keys())
+        .cloned()
+        .collect();
+    relationships.sort();
+    relationships.dedup();
+    let mut relationship_uuids: Vec<(String, u64)> = relationships
+        .into_iter()
+        .map(|id| (uuid(Entity::Relationship(id)), id))
+        .collect();
+    relationship_uuids.sort();
+    for (relationship_uuid, id) in &relationship_uuids {
+        let (old, new) = (before.relationships.get(id), after.relationships.get(id));
+        match (old, new) {
+            (None, Some(relationship)) => {
+                let mut created = DatabaseModifications_CreatedRelationShip::new();
+                created.set_relationship_UUID(relationship_uuid.clone());
+                created.set_field_type(relationship.rel_type.clone());
+                created.set_start_node_UUID(uuid(Entity::Node(relationship.start)));
+                created.set_end_node_UUID(uuid(Entity::Node(relationship.end)));
+                modifications.mut_created_relationships().push(created);
+            }
+            (Some(_), None) => {
+                let mut deleted = DatabaseModifications_DeletedRelationship::new();
+                deleted.set_relationship_UUID(relationship_uuid.clone());
+                modifications.mut_deleted_relationships().push(deleted);
+            }
+            _ => {}
+        }
+        let changes = property_changes(old.map(|r| &r.properties), new.map(|r| &r.properties));
+        for (key, value, previous) in changes {
+            match value {
+                Some(value) => {
+                    let mut assigned = DatabaseModifications_AssignedRelationshipProperty::new();
+                    assigned.set_relationship_UUID(relationship_uuid.clone());
+                    assigned.set_key(key);
+                    assigned.set_value(value);
+                    if let Some(previous) = previous {
+                        assigned.set_has_previous_value(true);
+                        assigned.set_previous_value(previous.to_string());
+                        assigned.set_previous_value_type(previous.type_name().to_string());
+                    }
+                    modifications
+                        .mut_assigned_relationship_properties()
+                        .push(assigned);
+                }
+                None if key == UUID => {}
+                None => {
+                    let mut removed = DatabaseModifications_RemovedRelationshipProperty::new();
+                    removed.set_relationship_UUID(relationship_uuid.clone());
+                    removed.set_key(key);
+                    if let Some(previous) = previous {
+                        removed.set_has_previous_value(true);
+                        removed.set_previous_value(previous.to_string());
+                        removed.set_previous_value_type(previous.type_name().to_string());
+                    }
+                    modifications
+                        .mut_removed_relation_properties()
+                        .push(removed);
+                }
+            }
+        }
+    }
+
+    modifications.set_records_previous_values(true);
+    Ok(modifications)
+}
+
+fn failure(transaction_id: &str, code: ErrorCode, message: &str) -> TransactionResponse {
+    let mut error = ProtoError::new();
+    error.set_code(code);
+    error.set_message(message.to_string());
+    let mut response = TransactionResponse::new();
+    response.set_transaction_id(transaction_id.to_string());
+    response.set_result(Status::FAILURE);
+    response.set_error(error);
+    response
+}
+
+fn success(transaction_id: &str, modifications: DatabaseModifications) -> TransactionResponse {
+    let mut response = TransactionResponse::new();
+    response.set_transaction_id(transaction_id.to_string());
+    response.set_result(Status::SUCCESS);
+    response.set_modifications(modifications);
+    response
+}
+
+///Runs the queries of a transaction, changing the graph only if all of them succeed.
+fn run_transaction(graph: &mut Graph, request: &TransactionRequest) -> TransactionResponse {
+    let transaction_id = request.get_transaction_id();
+    let mut working = graph.clone();
+    for (index, query) in request.get_queries().iter().enumerate() {
+        if query.trim().is_empty() {
+            continue;
+        }
+        if let Err(e) = working.run_statement(query) {
+            let mut failed_query = FailedQuery::new();
+            failed_query.set_query(query.clone());
+            failed_query.set_error(e.message);
+            failed_query.set_error_code(e.status_code.to_string());
+            failed_query.set_statement_index(index as u32);
+            let mut response = failure(
+                transaction_id,
+                ErrorCode::FAILED_QUERY,
+                "Invalid query provided.",
+            );
+            response.mut_error().set_failed_query(failed_query);
+            return response;
+        }
+    }
+    match commit(graph, &mut working, transaction_id) {
+        Ok(modifications) => {
+            *graph = working;
+            success(transaction_id, modifications)
+        }
+        Err((code, message)) => failure(transaction_id, code, &message),
+    }
+}
+
+///Creates or drops an index or constraint, given as `CREATE INDEX ON :Label(key)` or alike.
+fn run_schema_change(graph: &mut Graph, statement: &str) -> StatementResult<()> {
+    let tokens = tokenize(statement).map_err(|e| StatementError::new(e.message, SYNTAX_ERROR))?;
+    let create = match tokens.get(0) {
+        Some(t) if t.is_keyword("CREATE") => true,
+        Some(t) if t.is_keyword("DROP") => false,
+        _ => {
+            return Err(StatementError::new(
+                String::from("Expected CREATE or DROP"),
+                SYNTAX_ERROR,
+            ))
+        }
+    };
+    let definition = match tokens.get(1) {
+        Some(t) if t.is_keyword("INDEX") || t.is_keyword("CONSTRAINT") => {
+            normalize_definition(&statement[t.offset..])
+        }
+        _ => {
+            return Err(StatementError::new(
+                String::from("Expected INDEX or CONSTRAINT"),
+                SYNTAX_ERROR,
+            ))
+        }
+    };
+    if create && !graph.schema.insert(definition.clone()) {
+        return Err(StatementError::new(
+            format!("An equivalent {} already exists", definition),
+            "Neo.ClientError.Schema.EquivalentSchemaRuleAlreadyExists",
+        ));
+    }
+    if !create && !graph.schema.remove(&definition) {
+        return Err(StatementError::new(
+            format!("Unable to drop {}: No such schema rule", definition),
+            SEMANTIC_ERROR,
+        ));
+    }
+    Ok(())
+}
+
+#[derive(Debug, Default)]
+struct State {
+    graph: Graph,
+    block_changes: HashMap<String, BlockChangesResponse>,
+}
+
+///Graph backend keeping the graph and the block changes in memory.
+#[derive(Debug, Default)]
+pub struct MemoryBackend {
+    state: Mutex<State>,
+}
+
+impl MemoryBackend {
+    ///Creates an empty graph.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    ///Uuids of the nodes with the given label, sorted.
+    pub fn label_nodes(&self, label: &str) -> Vec<String> {
+        let state = self.state.lock().unwrap();
+        let mut uuids: Vec<String> = state
+            .graph
+            .nodes
+            .iter()
+            .filter(|(_, n)| n.labels.contains(label))
+            .filter_map(|(id, _)| state.graph.uuid(Entity::Node(*id)))
+            .collect();
+        uuids.sort();
+        uuids
+    }
+
+    ///Value of a property of the node with the given uuid.
+    pub fn node_property(&self, node_uuid: &str, key: &str) -> Option<Value> {
+        let state = self.state.lock().unwrap();
+        let expected = Value::String(node_uuid.to_string());
+        state
+            .graph
+            .nodes
+            .values()
+            .find(|n| n.properties.get(UUID) == Some(&expected))
+            .and_then(|n| n.properties.get(key).cloned())
+    }
+}
+
+impl GraphBackend for MemoryBackend {
+    fn execute_block(&self, request: BlockExecuteRequest) -> Result<(), String> {
+        if request.get_block_id().is_empty() {
+            return Err(String::from("Block ID missing"));
+        }
+        if request
+            .get_transactions()
+            .iter()
+            .any(|t| t.get_transaction_id().is_empty())
+        {
+            return Err(String::from("Transaction ID missing"));
+        }
+        let mut state = self.state.lock().unwrap();
+        let mut block_changes = BlockChangesResponse::new();
+        block_changes.set_block_id(request.get_block_id().to_string());
+        for transaction in request.get_transactions() {
+            let response = run_transaction(&mut state.graph, transaction);
+            block_changes.mut_transactions().push(response);
+        }
+        state
+            .block_changes
+            .insert(request.get_block_id().to_string(), block_changes);
+        Ok(())
+    }
+
+    fn execute_schema_change(&self, request: SchemaChangeRequest) -> Result<(), String> {
+        if request.get_block_id().is_empty() {
+            return Err(String::from("Block ID missing"));
+        }
+        if request.get_transaction_id().is_empty() {
+            return Err(String::from("Transaction ID missing"));
+        }
+        let mut state = self.state.lock().unwrap();
+        let transaction_id = request.get_transaction_id();
+        let response = match run_schema_change(&mut state.graph, request.get_statement()) {
+            Ok(()) => {
+                let mut modifications = DatabaseModifications::new();
+                modifications.set_records_previous_values(true);
+                success(transaction_id, modifications)
+            }
+            Err(e) => {
+                let mut failed_query = FailedQuery::new();
+                failed_query.set_query(request.get_statement().to_string());
+                failed_query.set_error(e.message);
+                failed_query.set_error_code(e.status_code.to_string());
+                let mut response = failure(
+                    transaction_id,
+                    ErrorCode::FAILED_QUERY,
+                    "Invalid query provided.",
+                );
+                response.mut_error().set_failed_query(failed_query);
+                response
+            }
+        };
+        let block_id = request.get_block_id().to_string();
+        let block_changes = state
+            .block_changes
+            .entry(block_id.clone())
+            .or_insert_with(|| {
+                let mut block_changes = BlockChangesResponse::new();
+                block_changes.set_block_id(block_id);
+                block_changes
+            });
+        block_changes.mut_transactions().push(response);
+        Ok(())
+    }
+
+    fn retrieve_block_changes(&self, block_id: &str) -> Result<BlockChangesResponse, String> {
+        let state = self.state.lock().unwrap();
+        state
+            .block_changes
+            .get(block_id)
+            .cloned()
+            .ok_or_else(|| String::from("Could not find the changes for this block."))
+    }
+
+    fn delete_block_changes(&self, block_id: &str) -> Result<bool, String> {
+        let mut state = self.state.lock().unwrap();
+        Ok(state.block_changes.remove(block_id).is_some())
+    }
+
+    fn health(&self) -> Result<(), String> {
+        Ok(())
+    }
+}
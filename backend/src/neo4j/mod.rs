@@ -1,9 +1,11 @@
+pub mod memory;
 ///Module for communicating with neo4j.
 pub mod proto;
 
 pub use neo4j::proto::transaction_manager::*;
 pub use neo4j::proto::transaction_manager_grpc::*;
 
+use cypher;
 use exonum::{
     blockchain::{Block, Schema as CoreSchema},
     crypto::Hash,
@@ -14,7 +16,9 @@ use std::fmt;
 use std::string::String;
 use std::sync::Arc;
 use std::vec::Vec;
-use util::parse_port;
+use util::{parse_backend, parse_port};
+
+use self::memory::MemoryBackend;
 
 use self::ExecuteResponse::*;
 use structures::NodeChange::{AL, AN, ANP, AR, ARP, RL, RN, RNP, RR, RRP};
@@ -54,101 +58,189 @@ impl Neo4jRpc {
             transaction_manager,
         }
     }
+}
 
-    ///Function that asks neo4j to execute the whole block. It retrieves block transactions from Schema. If there are no transactions returns NoCommit(())
-    pub fn execute_block(
-        &self,
-        block: &Block,
-        block_hash: &str,
-        core_schema: &CoreSchema<&dyn Snapshot>,
-        schema: &Schema<&dyn Snapshot>,
-    ) -> ExecuteResponse {
-        let transactions = core_schema.block_transactions(block.height());
-
-        let mut trans_vector: Vec<TransactionRequest> = Vec::new();
-        for trans_hash in transactions.iter() {
-            let potential_trans = schema.neo4j_transaction(&trans_hash);
-            if let Some(neo4j_transaction) = potential_trans {
-                let queries = neo4j_transaction.queries().split(';');
-                let query_vector: Vec<::std::string::String> =
-                    queries.map(|s| s.to_string()).collect();
-                let proto_vec = protobuf::RepeatedField::from_vec(query_vector);
-                let mut trans_req = TransactionRequest::new();
-                trans_req.set_transaction_id(trans_hash.to_hex().as_str().to_string());
-                trans_req.set_queries(proto_vec);
-                trans_vector.push(trans_req);
-            }
-        }
-        if !trans_vector.is_empty() {
-            let mut request = BlockExecuteRequest::new();
-            request.set_block_id(block_hash.to_string());
-            request.set_transactions(protobuf::RepeatedField::from_vec(trans_vector));
-            let resp = self
-                .transaction_manager
-                .execute_block(RequestOptions::new(), request);
-
-            let result = resp.wait();
-            match result {
-                Ok(_) => OkExe(()),
-                Err(e) => ExecuteResponse::Error(ErrorMsg::new(format!("{:?}", e).as_str())),
-            }
+///Graph database the service runs neo4j transactions on. Every validator has to end up with the
+/// same changes, so implementations assign uuids and report changes the way the transaction
+/// manager plugin does.
+pub trait GraphBackend: Send + Sync {
+    ///Executes the transactions of a block and stores their changes under the block id.
+    fn execute_block(&self, request: BlockExecuteRequest) -> Result<(), String>;
+
+    ///Executes an index or constraint statement and adds its result to the changes of the block.
+    fn execute_schema_change(&self, request: SchemaChangeRequest) -> Result<(), String>;
+
+    ///Changes stored for the block with the given id.
+    fn retrieve_block_changes(&self, block_id: &str) -> Result<BlockChangesResponse, String>;
+
+    ///Deletes the changes stored for the block with the given id, false if there were none.
+    fn delete_block_changes(&self, block_id: &str) -> Result<bool, String>;
+
+    ///Checks that the database accepts transactions.
+    fn health(&self) -> Result<(), String>;
+}
+
+impl GraphBackend for Neo4jRpc {
+    fn execute_block(&self, request: BlockExecuteRequest) -> Result<(), String> {
+        self.transaction_manager
+            .execute_block(RequestOptions::new(), request)
+            .wait()
+            .map(|_| ())
+            .map_err(|e| format!("{:?}", e))
+    }
+
+    fn execute_schema_change(&self, request: SchemaChangeRequest) -> Result<(), String> {
+        self.transaction_manager
+            .execute_schema_change(RequestOptions::new(), request)
+            .wait()
+            .map(|_| ())
+            .map_err(|e| format!("{:?}", e))
+    }
+
+    fn retrieve_block_changes(&self, block_id: &str) -> Result<BlockChangesResponse, String> {
+        let mut request = BlockChangesRequest::new();
+        request.set_block_id(block_id.to_string());
+        self.transaction_manager
+            .retrieve_block_changes(RequestOptions::new(), request)
+            .wait()
+            .map(|x| x.1)
+            .map_err(|e| format!("{:?}", e))
+    }
+
+    fn delete_block_changes(&self, block_id: &str) -> Result<bool, String> {
+        let mut request = DeleteBlockRequest::new();
+        request.set_block_id(block_id.to_string());
+        self.transaction_manager
+            .delete_block_changes(RequestOptions::new(), request)
+            .wait()
+            .map(|x| x.1.get_success())
+            .map_err(|e| format!("{:?}", e))
+    }
+
+    fn health(&self) -> Result<(), String> {
+        let resp = self
+            .transaction_manager
+            .health(RequestOptions::new(), HealthRequest::new())
+            .wait()
+            .map_err(|e| format!("{:?}", e))?;
+        if resp.1.get_available() {
+            Ok(())
         } else {
-            NoCommits(())
+            Err(String::from("Neo4j is not available"))
         }
     }
+}
 
-    ///Ask Neo4j to remove audited changes. These are changes that were audited during the given block.
-    /// Meaning there had to have been an AuditBlocks transaction, which saved changes for certain blocks.
-    /// These blocks are stored in schema.audited_blocks
-    pub fn remove_audited_changes(
-        &self,
-        block: Block,
-        core_schema: CoreSchema<&dyn Snapshot>,
-        schema: Schema<&dyn Snapshot>,
-    ) {
-        let transactions = core_schema.block_transactions(block.height());
-        for trans_hash in transactions.iter() {
-            let audited_blocks = schema.audited_blocks(&trans_hash);
-            for block in audited_blocks.iter() {
-                let mut request = DeleteBlockRequest::new();
-                request.set_block_id(block.to_hex().as_str().to_string());
-                let resp = self
-                    .transaction_manager
-                    .delete_block_changes(RequestOptions::new(), request);
-                match resp.wait() {
-                    Ok(x) => {
-                        if x.1.get_success() {
-                            println!(
-                                "Succesfully deleted changes in neo4j for {}",
-                                block.to_hex().as_str()
-                            );
-                        } else {
-                            println!(
-                                "Failed to deleted changes in neo4j for {}",
-                                block.to_hex().as_str()
-                            );
-                        }
-                    }
-                    Err(e) => println!("{:?}", e),
-                }
+///Function that asks the backend to execute the whole block. It retrieves block transactions from Schema. If there are no transactions returns NoCommits(())
+pub fn execute_block(
+    backend: &dyn GraphBackend,
+    block: &Block,
+    block_hash: &str,
+    schema: &Schema<&dyn Snapshot>,
+) -> ExecuteResponse {
+    let transactions = schema.block_neo4j_transactions(block.height());
+
+    let mut trans_vector: Vec<TransactionRequest> = Vec::new();
+    let mut schema_changes: Vec<SchemaChangeRequest> = Vec::new();
+    for trans_hash in transactions {
+        let potential_trans = schema
+            .neo4j_transaction(&trans_hash)
+            .filter(|t| t.status() == Some(TransactionStatus::Pending));
+        // Rejected transactions are recorded, but never reach Neo4j.
+        if let Some(neo4j_transaction) = potential_trans {
+            // Index and constraint statements cannot share a transaction with data writes.
+            if schema.schema_changes().contains(&trans_hash) {
+                let mut change_req = SchemaChangeRequest::new();
+                change_req.set_block_id(block_hash.to_string());
+                change_req.set_transaction_id(trans_hash.to_hex().as_str().to_string());
+                change_req.set_statement(neo4j_transaction.queries().to_string());
+                schema_changes.push(change_req);
+                continue;
             }
+            // Queries that cannot be tokenized were rejected, unless recorded before they were
+            // checked, so these are left to Neo4j to refuse as a whole.
+            let queries = neo4j_transaction.queries();
+            let statements = cypher::split_statements(queries).unwrap_or_else(|_| vec![queries]);
+            let query_vector: Vec<::std::string::String> =
+                statements.into_iter().map(|s| s.to_string()).collect();
+            let proto_vec = protobuf::RepeatedField::from_vec(query_vector);
+            let mut trans_req = TransactionRequest::new();
+            trans_req.set_transaction_id(trans_hash.to_hex().as_str().to_string());
+            trans_req.set_queries(proto_vec);
+            trans_vector.push(trans_req);
         }
     }
+    if trans_vector.is_empty() && schema_changes.is_empty() {
+        return NoCommits(());
+    }
 
-    ///Retrieves block changes, given block_hash.
-    pub fn retrieve_block_changes(&self, block_hash: Hash) -> ExecuteResponse {
-        let mut request = BlockChangesRequest::new();
-        request.set_block_id(block_hash.to_hex().as_str().to_string());
-        let resp = self
-            .transaction_manager
-            .retrieve_block_changes(RequestOptions::new(), request);
+    // The block changes are written by `ExecuteBlock`, schema changes are added to them.
+    if !trans_vector.is_empty() {
+        let mut request = BlockExecuteRequest::new();
+        request.set_block_id(block_hash.to_string());
+        request.set_transactions(protobuf::RepeatedField::from_vec(trans_vector));
+        if let Err(e) = backend.execute_block(request) {
+            return ExecuteResponse::Error(ErrorMsg::new(e.as_str()));
+        }
+    }
+    for change_req in schema_changes {
+        if let Err(e) = backend.execute_schema_change(change_req) {
+            return ExecuteResponse::Error(ErrorMsg::new(e.as_str()));
+        }
+    }
+    OkExe(())
+}
+
+///Ask the backend to remove audited changes. These are changes that were audited during the given block.
+/// Meaning there had to have been an AuditBlocks transaction, which saved changes for certain blocks.
+/// These blocks are stored in schema.audited_blocks
+pub fn remove_audited_changes(
+    backend: &dyn GraphBackend,
+    block: Block,
+    core_schema: CoreSchema<&dyn Snapshot>,
+    schema: Schema<&dyn Snapshot>,
+) {
+    let transactions = core_schema.block_transactions(block.height());
+    for trans_hash in transactions.iter() {
+        let audited_blocks = schema.audited_blocks(&trans_hash);
+        for block in audited_blocks.iter() {
+            match backend.delete_block_changes(block.to_hex().as_str()) {
+                Ok(true) => debug!("Deleted the audited changes of block {}", block.to_hex()),
+                Ok(false) => warn!(
+                    "Neo4j did not delete the audited changes of block {}",
+                    block.to_hex()
+                ),
+                Err(e) => warn!(
+                    "Could not delete the audited changes of block {}: {}",
+                    block.to_hex(),
+                    e
+                ),
+            }
+        }
+    }
+}
 
-        let result = resp.wait();
-        match result {
-            Ok(x) => ChangeResponse(x.1),
-            Err(e) => ExecuteResponse::Error(ErrorMsg::new(format!("{:?}", e).as_str())),
+///Retrieves the changes of the given blocks awaiting audit from the backend, to be carried by an
+/// `AuditBlocks` transaction. Blocks whose changes the backend cannot provide are listed as unavailable.
+pub fn collect_audited_changes(backend: &dyn GraphBackend, blocks: &[Hash]) -> AuditedChanges {
+    let mut audited = AuditedChanges::new();
+    for block_hash in blocks {
+        match backend.retrieve_block_changes(block_hash.to_hex().as_str()) {
+            Ok(mut changes) => {
+                changes.set_block_id(block_hash.to_hex());
+                audited.mut_blocks().push(changes);
+            }
+            Err(e) => {
+                warn!(
+                    "Could not retrieve the changes of block {}: {}",
+                    block_hash.to_hex(),
+                    e
+                );
+                audited.mut_unavailable_blocks().push(block_hash.to_hex());
+            }
         }
     }
+    audited
 }
 
 ///  Get DB changes list from proto
@@ -188,6 +280,14 @@ pub fn generate_database_changes_from_proto(
             new_node_property.get_key(),
             new_node_property.get_value(),
             transaction_id,
+            previous_value(
+                new_node_property.get_has_previous_value(),
+                new_node_property.get_previous_value(),
+            ),
+            previous_value_type(
+                new_node_property.get_has_previous_value(),
+                new_node_property.get_previous_value_type(),
+            ),
         );
         changes.push(ANP(new_change));
     }
@@ -196,6 +296,14 @@ pub fn generate_database_changes_from_proto(
             remove_node_property.get_node_UUID(),
             remove_node_property.get_key(),
             transaction_id,
+            previous_value(
+                remove_node_property.get_has_previous_value(),
+                remove_node_property.get_previous_value(),
+            ),
+            previous_value_type(
+                remove_node_property.get_has_previous_value(),
+                remove_node_property.get_previous_value_type(),
+            ),
         );
         changes.push(RNP(new_change));
     }
@@ -211,6 +319,7 @@ pub fn generate_database_changes_from_proto(
         let r: Relation = Relation::new(
             new_relation.get_start_node_UUID(),
             new_relation.get_end_node_UUID(),
+            new_relation.get_field_type(),
         );
         schema.add_relation(r, new_relation.get_relationship_UUID());
     }
@@ -227,7 +336,10 @@ pub fn generate_database_changes_from_proto(
                 changes.push(RR(new_change));
             }
             _ => {
-                println!("ERROR: Should not be here 004");
+                error!(
+                    "Neo4j reported a change of unknown relationship {}",
+                    remove_relation.get_relationship_UUID()
+                );
             }
         }
     }
@@ -242,11 +354,22 @@ pub fn generate_database_changes_from_proto(
                     relation.start_node_uuid(),
                     relation.end_node_uuid(),
                     transaction_id,
+                    previous_value(
+                        new_relation_property.get_has_previous_value(),
+                        new_relation_property.get_previous_value(),
+                    ),
+                    previous_value_type(
+                        new_relation_property.get_has_previous_value(),
+                        new_relation_property.get_previous_value_type(),
+                    ),
                 );
                 changes.push(ARP(new_change));
             }
             _ => {
-                println!("ERROR: Should not be here 004");
+                error!(
+                    "Neo4j reported a change of unknown relationship {}",
+                    new_relation_property.get_relationship_UUID()
+                );
             }
         }
     }
@@ -260,17 +383,48 @@ pub fn generate_database_changes_from_proto(
                     relation.start_node_uuid(),
                     relation.end_node_uuid(),
                     transaction_id,
+                    previous_value(
+                        remove_relation_property.get_has_previous_value(),
+                        remove_relation_property.get_previous_value(),
+                    ),
+                    previous_value_type(
+                        remove_relation_property.get_has_previous_value(),
+                        remove_relation_property.get_previous_value_type(),
+                    ),
                 );
                 changes.push(RRP(new_change));
             }
             _ => {
-                println!("ERROR: Should not be here 005");
+                error!(
+                    "Neo4j reported a change of unknown relationship {}",
+                    remove_relation_property.get_relationship_UUID()
+                );
             }
         }
     }
     changes
 }
 
+///Previous value of a property change, none if the property was not set or the plugin did not
+/// record it.
+fn previous_value(has_previous_value: bool, previous_value: &str) -> Option<&str> {
+    if has_previous_value {
+        Some(previous_value)
+    } else {
+        None
+    }
+}
+
+///Cypher type of the previous value of a property change, none if there is no previous value or
+/// the plugin recorded it without a type.
+fn previous_value_type(has_previous_value: bool, previous_value_type: &str) -> Option<&str> {
+    if has_previous_value && !previous_value_type.is_empty() {
+        Some(previous_value_type)
+    } else {
+        None
+    }
+}
+
 ///Gets an neo4j rpc client, with the port defined in neo4j.toml
 pub fn get_neo4j_rpc_client() -> Neo4jRpc {
     let mut port = 9994;
@@ -284,6 +438,19 @@ pub fn get_neo4j_rpc_client() -> Neo4jRpc {
     Neo4jRpc::new(neo4j_config)
 }
 
+///Creates the graph backend selected by `backend` in neo4j.toml, `neo4j` for the transaction
+/// manager plugin or `memory` for an in-memory graph. Used by the service factory, the service
+/// runs its transactions and audits on the backend it was created with.
+pub fn configured_backend() -> Arc<dyn GraphBackend> {
+    match parse_backend() {
+        Ok(Some(ref name)) if name == "memory" => Arc::new(MemoryBackend::new()),
+        Ok(Some(ref name)) if name != "neo4j" => {
+            panic!("Unknown graph backend in neo4j.toml: {}", name)
+        }
+        _ => Arc::new(get_neo4j_rpc_client()),
+    }
+}
+
 ///Response we get from communicating with neo4j
 #[derive(Clone, Debug)]
 pub enum ExecuteResponse {
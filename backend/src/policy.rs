@@ -0,0 +1,113 @@
+//! Checks of the changes of a transaction against the write policy of its author.
+//!
+//! Neo4j reports the changes of a transaction only after it committed them, so a violation is
+//! found during the audit and the changes are reverted with compensating queries.
+
+use std::collections::{BTreeSet, HashMap};
+
+use exonum::storage::Snapshot;
+
+use config::WritePolicy;
+use schema::Schema;
+use structures::NodeChange::{self, AL, AN, ANP, AR, ARP, RL, RN, RNP, RR, RRP};
+
+/// Checks that every node and relation the changes touch is one the policy allows to write.
+/// Returns a description of the first violation.
+///
+/// A node may be written if it has at least one label and all of its labels are allowed. Its
+/// labels are those it had before the changes, together with the ones the changes add or remove.
+/// A relation may be written if its type is allowed.
+pub fn check_changes<T: AsRef<dyn Snapshot>>(
+    schema: &Schema<T>,
+    policy: &WritePolicy,
+    changes: &[NodeChange],
+) -> Result<(), String> {
+    let mut labels: HashMap<&str, BTreeSet<String>> = HashMap::new();
+    let mut relation_types: HashMap<&str, &str> = HashMap::new();
+    for change in changes {
+        match change {
+            AL(x) => {
+                labels
+                    .entry(x.node_uuid())
+                    .or_insert_with(BTreeSet::new)
+                    .insert(x.label_name().to_string());
+            }
+            RL(x) => {
+                labels
+                    .entry(x.node_uuid())
+                    .or_insert_with(BTreeSet::new)
+                    .insert(x.label_name().to_string());
+            }
+            AR(x) => {
+                relation_types.insert(x.rel_uuid(), x.field_type());
+            }
+            _ => {}
+        }
+    }
+
+    for change in changes {
+        let result = match change {
+            AN(x) => check_node(schema, policy, &labels, x.node_uuid()),
+            RN(x) => check_node(schema, policy, &labels, x.node_uuid()),
+            AL(x) => check_node(schema, policy, &labels, x.node_uuid()),
+            RL(x) => check_node(schema, policy, &labels, x.node_uuid()),
+            ANP(x) => check_node(schema, policy, &labels, x.node_uuid()),
+            RNP(x) => check_node(schema, policy, &labels, x.node_uuid()),
+            AR(x) => check_relation(schema, policy, &relation_types, x.rel_uuid()),
+            RR(x) => check_relation(schema, policy, &relation_types, x.rel_uuid()),
+            ARP(x) => check_relation(schema, policy, &relation_types, x.relation_uuid()),
+            RRP(x) => check_relation(schema, policy, &relation_types, x.relation_uuid()),
+        };
+        result?;
+    }
+    Ok(())
+}
+
+fn check_node<T: AsRef<dyn Snapshot>>(
+    schema: &Schema<T>,
+    policy: &WritePolicy,
+    changed_labels: &HashMap<&str, BTreeSet<String>>,
+    node_uuid: &str,
+) -> Result<(), String> {
+    let mut labels: BTreeSet<String> = schema.node_labels(node_uuid).iter().collect();
+    if let Some(changed) = changed_labels.get(node_uuid) {
+        labels.extend(changed.iter().cloned());
+    }
+    if labels.is_empty() {
+        return Err(format!(
+            "Node {} has no label the author may write",
+            node_uuid
+        ));
+    }
+    match labels.iter().find(|label| !policy.labels.contains(label)) {
+        Some(label) => Err(format!(
+            "Author may not write nodes labelled {}, as node {} is",
+            label, node_uuid
+        )),
+        None => Ok(()),
+    }
+}
+
+fn check_relation<T: AsRef<dyn Snapshot>>(
+    schema: &Schema<T>,
+    policy: &WritePolicy,
+    created_types: &HashMap<&str, &str>,
+    relation_uuid: &str,
+) -> Result<(), String> {
+    // Relations stored before their type was recorded have an empty type, which is never allowed.
+    let field_type = match created_types.get(relation_uuid) {
+        Some(field_type) => field_type.to_string(),
+        None => schema
+            .relation(relation_uuid)
+            .map(|relation| relation.field_type().to_string())
+            .unwrap_or_default(),
+    };
+    if policy.relationship_types.contains(&field_type) {
+        Ok(())
+    } else {
+        Err(format!(
+            "Author may not write relationships of type {:?}, as relation {} is",
+            field_type, relation_uuid
+        ))
+    }
+}
@@ -15,13 +15,21 @@
 //! Cryptocurrency database schema.
 
 use exonum::{
-    crypto::{hash, Hash},
-    storage::{Fork, ListIndex, MapIndex, ProofListIndex, ProofMapIndex, Snapshot},
+    blockchain::Schema as CoreSchema,
+    crypto::{hash, Hash, PublicKey},
+    helpers::Height,
+    storage::{Fork, KeySetIndex, ListIndex, MapIndex, ProofListIndex, ProofMapIndex, Snapshot},
 };
 
+use exonum_time::schema::TimeSchema;
 use std::string::String;
 
-use structures::{Neo4jTransaction, NodeChange, Relation};
+use structures::NodeChange::{AL, ANP, RL, RNP};
+use structures::{
+    definition_hash, Neo4jTransaction, NodeChange, Proposal, Relation, ScheduledBatch,
+    SchemaChangeRecord, SchemaObject, SchemaOperation, StatementClassification, StatusTransition,
+    TransactionFailure, TransactionStatus,
+};
 
 /// Database schema for the cryptocurrency.
 #[derive(Debug)]
@@ -49,6 +57,42 @@ where
         ProofMapIndex::new("neo4j.queries", &self.view)
     }
 
+    ///Get neo4j transactions as stored bytes, to decode them without panicking on corrupt entries.
+    pub fn neo4j_transactions_raw(&self) -> ProofMapIndex<&T, Hash, Vec<u8>> {
+        ProofMapIndex::new("neo4j.queries", &self.view)
+    }
+
+    ///Get status transitions of a neo4j transaction, in the order they happened.
+    pub fn status_history(&self, hash: &Hash) -> ProofListIndex<&T, StatusTransition> {
+        ProofListIndex::new(
+            format!("neo4j.status_history_{}", hash.to_hex().as_str()),
+            &self.view,
+        )
+    }
+
+    ///Get status transitions as stored bytes, to decode them without panicking on corrupt entries.
+    pub fn status_history_raw(&self, hash: &Hash) -> ProofListIndex<&T, Vec<u8>> {
+        ProofListIndex::new(
+            format!("neo4j.status_history_{}", hash.to_hex().as_str()),
+            &self.view,
+        )
+    }
+
+    ///Get failure details of neo4j transactions that Neo4j failed to execute.
+    pub fn transaction_failures(&self) -> ProofMapIndex<&T, Hash, TransactionFailure> {
+        ProofMapIndex::new("neo4j.transaction_failures", &self.view)
+    }
+
+    ///Get failure details as stored bytes, to decode them without panicking on corrupt entries.
+    pub fn transaction_failures_raw(&self) -> ProofMapIndex<&T, Hash, Vec<u8>> {
+        ProofMapIndex::new("neo4j.transaction_failures", &self.view)
+    }
+
+    ///Get hashes of all neo4j transactions, in the order they were committed.
+    pub fn neo4j_transactions_ordered(&self) -> ProofListIndex<&T, Hash> {
+        ProofListIndex::new("neo4j.queries_ordered", &self.view)
+    }
+
     ///Gets hash value in hex for the last block that was audited.
     /// This is used to avoid auditing same blocks twice.
     pub fn get_last_confirmed_block(&self) -> Option<Hash> {
@@ -56,11 +100,182 @@ where
         index.get(&String::from("lastConfirmedBlock"))
     }
 
+    ///Gets the version of the layout of the `neo4j.*` indexes, none for chains started before it was recorded.
+    /// Stored in `neo4j.values` next to the last confirmed block, with its own value type.
+    pub fn schema_version(&self) -> Option<u32> {
+        let index: MapIndex<&T, String, u32> = MapIndex::new("neo4j.values", &self.view);
+        index.get(&String::from("schemaVersion"))
+    }
+
+    ///Get the labels a node currently carries.
+    pub fn node_labels(&self, node_uuid: &str) -> KeySetIndex<&T, String> {
+        KeySetIndex::new(format!("neo4j.node_labels_{}", node_uuid), &self.view)
+    }
+
+    ///Get compensating transactions, mapped to the transaction whose changes they undo.
+    pub fn compensations(&self) -> ProofMapIndex<&T, Hash, Hash> {
+        ProofMapIndex::new("neo4j.compensations", &self.view)
+    }
+
+    ///Get transactions created by the service at the given height, sent to Neo4j with that block.
+    pub fn dispatched(&self, height: Height) -> ListIndex<&T, Hash> {
+        ListIndex::new(format!("neo4j.dispatched_{}", height.0), &self.view)
+    }
+
+    ///Get hashes of transactions whose changes carry the property values from before them.
+    pub fn previous_values_recorded(&self) -> KeySetIndex<&T, Hash> {
+        KeySetIndex::new("neo4j.previous_values_recorded", &self.view)
+    }
+
+    ///Get reverted transactions, mapped to the `RevertTransaction` reverting them.
+    pub fn reverts(&self) -> ProofMapIndex<&T, Hash, Hash> {
+        ProofMapIndex::new("neo4j.reverts", &self.view)
+    }
+
+    ///Get proposals, keyed by the hash of their `ProposeQueries` transaction.
+    pub fn proposals(&self) -> ProofMapIndex<&T, Hash, Proposal> {
+        ProofMapIndex::new("neo4j.proposals", &self.view)
+    }
+
+    ///Get scheduled batches that were neither sent to Neo4j nor cancelled yet.
+    pub fn scheduled(&self) -> ProofMapIndex<&T, Hash, ScheduledBatch> {
+        ProofMapIndex::new("neo4j.scheduled", &self.view)
+    }
+
+    ///Get statements of `SchemaChange` transactions, by transaction hash.
+    pub fn schema_changes(&self) -> ProofMapIndex<&T, Hash, SchemaChangeRecord> {
+        ProofMapIndex::new("neo4j.schema_changes", &self.view)
+    }
+
+    ///Get indexes and constraints active in Neo4j, by hash of their definition.
+    pub fn schema_objects(&self) -> ProofMapIndex<&T, Hash, SchemaObject> {
+        ProofMapIndex::new("neo4j.schema_objects", &self.view)
+    }
+
+    ///Get hashes of the last accepted schema change of each index or constraint, by hash of its
+    /// definition. The change is pending while its transaction awaits its changes.
+    pub fn pending_schema_changes(&self) -> MapIndex<&T, Hash, Hash> {
+        MapIndex::new("neo4j.pending_schema_changes", &self.view)
+    }
+
+    ///Get the schema change of an index or constraint that Neo4j did not confirm yet, if any.
+    pub fn pending_schema_change(&self, definition: &Hash) -> Option<SchemaChangeRecord> {
+        self.pending_schema_changes()
+            .get(definition)
+            .filter(|transaction| {
+                self.neo4j_transaction(transaction)
+                    .and_then(|t| t.status())
+                    .map_or(false, |status| status.awaits_changes())
+            })
+            .and_then(|transaction| self.schema_changes().get(&transaction))
+    }
+
+    ///Whether an index or constraint exists once the pending schema changes are confirmed.
+    pub fn schema_object_will_exist(&self, definition: &Hash) -> bool {
+        match self.pending_schema_change(definition) {
+            Some(change) => change.operation() == SchemaOperation::Create,
+            None => self.schema_objects().contains(definition),
+        }
+    }
+
+    ///Get classes of the statements of committed queries, by transaction hash.
+    pub fn statement_classes(&self) -> ProofMapIndex<&T, Hash, StatementClassification> {
+        ProofMapIndex::new("neo4j.statement_classes", &self.view)
+    }
+
+    ///Get hashes of the successful schema changes of an index or constraint, by hash of its
+    /// definition.
+    pub fn schema_object_history(&self, definition: &Hash) -> ProofListIndex<&T, Hash> {
+        ProofListIndex::new(
+            format!(
+                "neo4j.schema_object_history_{}",
+                definition.to_hex().as_str()
+            ),
+            &self.view,
+        )
+    }
+
+    ///Time the validators agreed on through the exonum-time service, in seconds since the epoch.
+    /// None if the service is not running or no time was agreed on yet.
+    pub fn current_time(&self) -> Option<u64> {
+        TimeSchema::new(&self.view)
+            .time()
+            .get()
+            .map(|time| time.timestamp().max(0) as u64)
+    }
+
+    ///Get votes of validators for migrating to a schema version, mapping their service keys to
+    /// the height they voted at.
+    pub fn migration_votes(&self, target_version: u32) -> ProofMapIndex<&T, PublicKey, u64> {
+        ProofMapIndex::new(
+            format!("neo4j.migration_votes_{}", target_version),
+            &self.view,
+        )
+    }
+
+    ///Get approvals of a proposal, mapping approvers to the height they approved at.
+    pub fn proposal_approvals(&self, proposal: &Hash) -> ProofMapIndex<&T, PublicKey, u64> {
+        ProofMapIndex::new(
+            format!("neo4j.proposal_approvals_{}", proposal.to_hex().as_str()),
+            &self.view,
+        )
+    }
+
+    ///Get hashes of the transactions sent to Neo4j with the block at the given height: those of
+    /// the block, followed by those the service dispatched while executing it.
+    pub fn block_neo4j_transactions(&self, height: Height) -> Vec<Hash> {
+        let mut transactions: Vec<Hash> = CoreSchema::new(&self.view)
+            .block_transactions(height)
+            .iter()
+            .collect();
+        transactions.extend(self.dispatched(height).iter());
+        transactions
+    }
+
+    ///Get nodes reserved by a transaction carrying preconditions on them, mapped to the hash of
+    /// that transaction. A reservation holds until the transaction no longer awaits its changes.
+    pub fn reserved_nodes(&self) -> MapIndex<&T, String, Hash> {
+        MapIndex::new("neo4j.reserved_nodes", &self.view)
+    }
+
+    ///Get the open transaction that reserved a node, if any.
+    pub fn node_reservation(&self, node_uuid: &str) -> Option<Hash> {
+        self.reserved_nodes()
+            .get(&node_uuid.to_string())
+            .filter(|transaction| {
+                self.neo4j_transaction(transaction)
+                    .and_then(|t| t.status())
+                    .map_or(false, |status| status.awaits_changes())
+            })
+    }
+
+    ///Get uuids of all nodes that have a history.
+    pub fn known_nodes(&self) -> KeySetIndex<&T, String> {
+        KeySetIndex::new("neo4j.known_nodes", &self.view)
+    }
+
     ///Get a single query, by giving transaction hash as key
     pub fn neo4j_transaction(&self, hash: &Hash) -> Option<Neo4jTransaction> {
         self.neo4j_transactions().get(hash)
     }
 
+    ///Get hashes of all transactions submitted by an author, in the order they were committed.
+    pub fn author_transactions(&self, pub_key: &PublicKey) -> ProofListIndex<&T, Hash> {
+        ProofListIndex::new(
+            format!("neo4j.author_transactions_{}", pub_key.to_hex().as_str()),
+            &self.view,
+        )
+    }
+
+    ///Get heights of the accepted submissions of an author that count towards the rate limit,
+    /// in the order they were charged.
+    pub fn author_submissions(&self, pub_key: &PublicKey) -> ListIndex<&T, u64> {
+        ListIndex::new(
+            format!("neo4j.author_submissions_{}", pub_key.to_hex().as_str()),
+            &self.view,
+        )
+    }
+
     ///Get relations ProofMapIndex
     pub fn relations(&self) -> ProofMapIndex<&T, Hash, Relation> {
         ProofMapIndex::new("neo4j.relations", &self.view)
@@ -76,6 +291,61 @@ where
         ProofListIndex::new(format!("neo4j.node_changes_{}", node_name), &self.view)
     }
 
+    ///Get a node's history as stored bytes, to decode the changes without panicking on corrupt entries.
+    pub fn node_history_raw(&self, node_name: &str) -> ProofListIndex<&T, Vec<u8>> {
+        ProofListIndex::new(format!("neo4j.node_changes_{}", node_name), &self.view)
+    }
+
+    ///Get uuids of all nodes that ever carried the given label.
+    /// Index name is derived from the label's hash, since labels may contain any characters.
+    pub fn label_nodes(&self, label: &str) -> KeySetIndex<&T, String> {
+        KeySetIndex::new(
+            format!(
+                "neo4j.label_nodes_{}",
+                hash(label.as_bytes()).to_hex().as_str()
+            ),
+            &self.view,
+        )
+    }
+
+    ///Get uuids of all nodes where the given property key was ever set or removed.
+    pub fn property_nodes(&self, key: &str) -> KeySetIndex<&T, String> {
+        KeySetIndex::new(
+            format!(
+                "neo4j.property_nodes_{}",
+                hash(key.as_bytes()).to_hex().as_str()
+            ),
+            &self.view,
+        )
+    }
+
+    ///Get the changes a single Neo4j transaction made to the graph, by giving that transaction's hash.
+    pub fn transaction_changes(&self, transaction_hash: &Hash) -> ProofListIndex<&T, NodeChange> {
+        ProofListIndex::new(
+            format!(
+                "neo4j.transaction_changes_{}",
+                transaction_hash.to_hex().as_str()
+            ),
+            &self.view,
+        )
+    }
+
+    ///Get the changes of a transaction as stored bytes, to decode them without panicking on corrupt entries.
+    pub fn transaction_changes_raw(&self, transaction_hash: &Hash) -> ProofListIndex<&T, Vec<u8>> {
+        ProofListIndex::new(
+            format!(
+                "neo4j.transaction_changes_{}",
+                transaction_hash.to_hex().as_str()
+            ),
+            &self.view,
+        )
+    }
+
+    ///Get merkle roots of every transaction's changes list, so that the changes are part of the state hash.
+    pub fn transaction_changes_roots(&self) -> ProofMapIndex<&T, Hash, Hash> {
+        ProofMapIndex::new("neo4j.transaction_changes_roots", &self.view)
+    }
+
     ///Get blocks that were audited by a Audit transaction.
     pub fn audited_blocks(&self, transaction_hash: &Hash) -> ListIndex<&T, Hash> {
         ListIndex::new(
@@ -84,9 +354,74 @@ where
         )
     }
 
+    ///Get digests of the changes recorded for audited blocks, by block hash. The changes of a
+    /// block are recorded from the first audit carrying them.
+    pub fn audited_changes(&self) -> ProofMapIndex<&T, Hash, Hash> {
+        ProofMapIndex::new("neo4j.audited_changes", &self.view)
+    }
+
+    ///Get digests of the changes validators reported for an audited block that differ from the
+    /// recorded ones, by service key of the validator.
+    pub fn audit_divergences(&self, block_hash: &Hash) -> ProofMapIndex<&T, PublicKey, Hash> {
+        ProofMapIndex::new(
+            format!("neo4j.audit_divergences_{}", block_hash.to_hex().as_str()),
+            &self.view,
+        )
+    }
+
+    ///Get merkle roots of the diverging digests of every block, so that divergences are part of
+    /// the state hash.
+    pub fn audit_divergence_roots(&self) -> ProofMapIndex<&T, Hash, Hash> {
+        ProofMapIndex::new("neo4j.audit_divergence_roots", &self.view)
+    }
+
+    ///Whether a transaction sent to Neo4j with the block at the given height still waits for
+    /// its changes.
+    pub fn awaits_audit(&self, height: Height) -> bool {
+        self.block_neo4j_transactions(height).iter().any(|trans| {
+            self.neo4j_transaction(trans)
+                .and_then(|t| t.status())
+                .map_or(false, |status| status.awaits_changes())
+        })
+    }
+
+    ///Get hashes of the blocks since the last confirmed one whose changes are still to be audited.
+    pub fn unaudited_blocks(&self) -> Vec<Hash> {
+        let core_schema = CoreSchema::new(&self.view);
+        let block_hashes = core_schema.block_hashes_by_height();
+        let audited_changes = self.audited_changes();
+        (self.first_unconfirmed_height()..block_hashes.len())
+            .filter_map(|height| block_hashes.get(height).map(|hash| (height, hash)))
+            .filter(|&(height, ref block_hash)| {
+                !audited_changes.contains(block_hash) && self.awaits_audit(Height(height))
+            })
+            .map(|(_, block_hash)| block_hash)
+            .collect()
+    }
+
+    ///Height of the first block after the last confirmed one.
+    fn first_unconfirmed_height(&self) -> u64 {
+        self.get_last_confirmed_block()
+            .and_then(|block_hash| CoreSchema::new(&self.view).blocks().get(&block_hash))
+            .map_or(0, |block| block.height().0 + 1)
+    }
+
     ///Get state hash
     pub fn state_hash(&self) -> Vec<Hash> {
-        vec![self.neo4j_transactions().merkle_root()]
+        vec![
+            self.neo4j_transactions().merkle_root(),
+            self.transaction_changes_roots().merkle_root(),
+            self.transaction_failures().merkle_root(),
+            self.compensations().merkle_root(),
+            self.proposals().merkle_root(),
+            self.reverts().merkle_root(),
+            self.scheduled().merkle_root(),
+            self.schema_changes().merkle_root(),
+            self.schema_objects().merkle_root(),
+            self.statement_classes().merkle_root(),
+            self.audited_changes().merkle_root(),
+            self.audit_divergence_roots().merkle_root(),
+        ]
     }
 }
 
@@ -102,23 +437,79 @@ impl<'a> Schema<&'a mut Fork> {
         ProofListIndex::new("neo4j.queries_ordered", &mut self.view)
     }
 
-    ///Add a new variable to the table.
-    pub fn add_neo4j_transaction(&mut self, q: Neo4jTransaction, hash: &Hash) {
+    ///Get a mutable prooflistindex for an author's transactions
+    pub fn author_transactions_mut(
+        &mut self,
+        pub_key: &PublicKey,
+    ) -> ProofListIndex<&mut Fork, Hash> {
+        ProofListIndex::new(
+            format!("neo4j.author_transactions_{}", pub_key.to_hex().as_str()),
+            &mut self.view,
+        )
+    }
+
+    ///Get mutable heights of the charged submissions of an author.
+    pub fn author_submissions_mut(&mut self, pub_key: &PublicKey) -> ListIndex<&mut Fork, u64> {
+        ListIndex::new(
+            format!("neo4j.author_submissions_{}", pub_key.to_hex().as_str()),
+            &mut self.view,
+        )
+    }
+
+    ///Charges an accepted submission of the author at the given height to its rate limit.
+    pub fn charge_submission(&mut self, pub_key: &PublicKey, height: Height) {
+        self.author_submissions_mut(pub_key).push(height.0);
+    }
+
+    ///Get mutable failure details of neo4j transactions.
+    pub fn transaction_failures_mut(
+        &mut self,
+    ) -> ProofMapIndex<&mut Fork, Hash, TransactionFailure> {
+        ProofMapIndex::new("neo4j.transaction_failures", &mut self.view)
+    }
+
+    ///Get a mutable prooflistindex for a transaction's status transitions
+    pub fn status_history_mut(
+        &mut self,
+        hash: &Hash,
+    ) -> ProofListIndex<&mut Fork, StatusTransition> {
+        ProofListIndex::new(
+            format!("neo4j.status_history_{}", hash.to_hex().as_str()),
+            &mut self.view,
+        )
+    }
+
+    ///Add a new variable to the table, recording its initial status at the given height.
+    pub fn add_neo4j_transaction(&mut self, q: Neo4jTransaction, hash: &Hash, height: Height) {
+        let pub_key = *q.pub_key();
+        if let Some(status) = q.status() {
+            self.status_history_mut(hash)
+                .push(StatusTransition::from_status(status, height));
+        }
         self.neo4j_transactions_mut().put(hash, q);
         self.neo4j_transaction_ordered_mut().push(hash.clone());
+        self.author_transactions_mut(&pub_key).push(hash.clone());
     }
 
-    ///Update neo4j transaction. Only result and error_msg fields can be updated.
-    /// This is called when we retrieve changes from Neo4j.
-    pub fn update_neo4j_transaction(&mut self, hash: &Hash, error_msg: &str, result: &str) {
+    ///Update neo4j transaction. Only the status and error_msg fields can be updated.
+    /// The transition is recorded with the height of the block it happened in.
+    pub fn update_neo4j_transaction(
+        &mut self,
+        hash: &Hash,
+        error_msg: &str,
+        status: TransactionStatus,
+        height: Height,
+    ) {
         if let Some(neo4j_transaction) = self.neo4j_transaction(hash) {
             let updated_transaction = Neo4jTransaction::new(
                 neo4j_transaction.queries(),
                 error_msg,
-                result,
+                status,
                 neo4j_transaction.pub_key(),
             );
             self.neo4j_transactions_mut().put(hash, updated_transaction);
+            self.status_history_mut(hash)
+                .push(StatusTransition::from_status(status, height));
         }
     }
 
@@ -133,6 +524,189 @@ impl<'a> Schema<&'a mut Fork> {
         index.put(&i_str, block_hash);
     }
 
+    ///Moves the last confirmed block over the blocks that no longer wait for an audit.
+    pub fn advance_last_confirmed_block(&mut self) {
+        let mut last_confirmed = None;
+        {
+            let core_schema = CoreSchema::new(&self.view);
+            let block_hashes = core_schema.block_hashes_by_height();
+            for height in self.first_unconfirmed_height()..block_hashes.len() {
+                let block_hash = match block_hashes.get(height) {
+                    Some(block_hash) => block_hash,
+                    None => break,
+                };
+                if !self.audited_changes().contains(&block_hash)
+                    && self.awaits_audit(Height(height))
+                {
+                    break;
+                }
+                last_confirmed = Some(block_hash);
+            }
+        }
+        if let Some(block_hash) = last_confirmed {
+            self.set_last_confirmed_block(block_hash);
+        }
+    }
+
+    ///Sets the version of the layout of the `neo4j.*` indexes.
+    pub fn set_schema_version(&mut self, version: u32) {
+        let mut index: MapIndex<&mut Fork, String, u32> =
+            MapIndex::new("neo4j.values", &mut self.view);
+        index.put(&String::from("schemaVersion"), version);
+    }
+
+    ///Get the mutable labels a node currently carries.
+    pub fn node_labels_mut(&mut self, node_uuid: &str) -> KeySetIndex<&mut Fork, String> {
+        KeySetIndex::new(format!("neo4j.node_labels_{}", node_uuid), &mut self.view)
+    }
+
+    ///Get mutable compensating transactions.
+    pub fn compensations_mut(&mut self) -> ProofMapIndex<&mut Fork, Hash, Hash> {
+        ProofMapIndex::new("neo4j.compensations", &mut self.view)
+    }
+
+    ///Get mutable transactions created by the service at the given height.
+    pub fn dispatched_mut(&mut self, height: Height) -> ListIndex<&mut Fork, Hash> {
+        ListIndex::new(format!("neo4j.dispatched_{}", height.0), &mut self.view)
+    }
+
+    ///Get mutable hashes of transactions whose changes carry previous property values.
+    pub fn previous_values_recorded_mut(&mut self) -> KeySetIndex<&mut Fork, Hash> {
+        KeySetIndex::new("neo4j.previous_values_recorded", &mut self.view)
+    }
+
+    ///Get mutable reverted transactions.
+    pub fn reverts_mut(&mut self) -> ProofMapIndex<&mut Fork, Hash, Hash> {
+        ProofMapIndex::new("neo4j.reverts", &mut self.view)
+    }
+
+    ///Get mutable proposals.
+    pub fn proposals_mut(&mut self) -> ProofMapIndex<&mut Fork, Hash, Proposal> {
+        ProofMapIndex::new("neo4j.proposals", &mut self.view)
+    }
+
+    ///Get mutable scheduled batches.
+    pub fn scheduled_mut(&mut self) -> ProofMapIndex<&mut Fork, Hash, ScheduledBatch> {
+        ProofMapIndex::new("neo4j.scheduled", &mut self.view)
+    }
+
+    ///Get mutable statements of schema changes.
+    pub fn schema_changes_mut(&mut self) -> ProofMapIndex<&mut Fork, Hash, SchemaChangeRecord> {
+        ProofMapIndex::new("neo4j.schema_changes", &mut self.view)
+    }
+
+    ///Get mutable active indexes and constraints.
+    pub fn schema_objects_mut(&mut self) -> ProofMapIndex<&mut Fork, Hash, SchemaObject> {
+        ProofMapIndex::new("neo4j.schema_objects", &mut self.view)
+    }
+
+    ///Get mutable last accepted schema changes.
+    pub fn pending_schema_changes_mut(&mut self) -> MapIndex<&mut Fork, Hash, Hash> {
+        MapIndex::new("neo4j.pending_schema_changes", &mut self.view)
+    }
+
+    ///Get mutable classes of the statements of committed queries.
+    pub fn statement_classes_mut(
+        &mut self,
+    ) -> ProofMapIndex<&mut Fork, Hash, StatementClassification> {
+        ProofMapIndex::new("neo4j.statement_classes", &mut self.view)
+    }
+
+    ///Get mutable history of an index or constraint.
+    pub fn schema_object_history_mut(
+        &mut self,
+        definition: &Hash,
+    ) -> ProofListIndex<&mut Fork, Hash> {
+        ProofListIndex::new(
+            format!(
+                "neo4j.schema_object_history_{}",
+                definition.to_hex().as_str()
+            ),
+            &mut self.view,
+        )
+    }
+
+    ///Updates the registry with a schema change Neo4j executed successfully.
+    pub fn apply_schema_change(&mut self, transaction: &Hash, height: Height) {
+        if let Some(change) = self.schema_changes().get(transaction) {
+            let key = definition_hash(change.definition());
+            match change.operation() {
+                SchemaOperation::Create => {
+                    let object = SchemaObject::new(
+                        change.kind(),
+                        change.definition(),
+                        transaction,
+                        height.0,
+                    );
+                    self.schema_objects_mut().put(&key, object);
+                }
+                SchemaOperation::Drop => self.schema_objects_mut().remove(&key),
+            }
+            self.schema_object_history_mut(&key).push(*transaction);
+        }
+    }
+
+    ///Get mutable votes for migrating to a schema version.
+    pub fn migration_votes_mut(
+        &mut self,
+        target_version: u32,
+    ) -> ProofMapIndex<&mut Fork, PublicKey, u64> {
+        ProofMapIndex::new(
+            format!("neo4j.migration_votes_{}", target_version),
+            &mut self.view,
+        )
+    }
+
+    ///Get mutable approvals of a proposal.
+    pub fn proposal_approvals_mut(
+        &mut self,
+        proposal: &Hash,
+    ) -> ProofMapIndex<&mut Fork, PublicKey, u64> {
+        ProofMapIndex::new(
+            format!("neo4j.proposal_approvals_{}", proposal.to_hex().as_str()),
+            &mut self.view,
+        )
+    }
+
+    ///Records a transaction undoing the changes of `original` and schedules it to be sent to Neo4j
+    /// with the block at `height`. Returns the hash of the new transaction.
+    pub fn add_compensation(
+        &mut self,
+        original: &Hash,
+        queries: &str,
+        pub_key: &PublicKey,
+        height: Height,
+    ) -> Hash {
+        let compensation_hash = hash(&[original.as_ref(), b"compensation"].concat());
+        self.dispatch_neo4j_transaction(&compensation_hash, queries, pub_key, height);
+        self.compensations_mut().put(&compensation_hash, *original);
+        compensation_hash
+    }
+
+    ///Adds a pending neo4j transaction that is not part of a block, to be sent to Neo4j with
+    /// the block at `height`.
+    pub fn dispatch_neo4j_transaction(
+        &mut self,
+        hash: &Hash,
+        queries: &str,
+        pub_key: &PublicKey,
+        height: Height,
+    ) {
+        let q = Neo4jTransaction::new(queries, "", TransactionStatus::Pending, pub_key);
+        self.add_neo4j_transaction(q, hash, height);
+        self.dispatched_mut(height).push(*hash);
+    }
+
+    ///Get mutable nodes reserved by transactions carrying preconditions.
+    pub fn reserved_nodes_mut(&mut self) -> MapIndex<&mut Fork, String, Hash> {
+        MapIndex::new("neo4j.reserved_nodes", &mut self.view)
+    }
+
+    ///Get mutable uuids of all nodes that have a history.
+    pub fn known_nodes_mut(&mut self) -> KeySetIndex<&mut Fork, String> {
+        KeySetIndex::new("neo4j.known_nodes", &mut self.view)
+    }
+
     ///Get mutable relations proofmapindex
     pub fn relations_mut(&mut self) -> ProofMapIndex<&mut Fork, Hash, Relation> {
         ProofMapIndex::new("neo4j.relations", &mut self.view)
@@ -149,11 +723,132 @@ impl<'a> Schema<&'a mut Fork> {
         ProofListIndex::new(format!("neo4j.node_changes_{}", node_name), &mut self.view)
     }
 
+    ///Get a node's history as stored bytes, for rewriting it without decoding every change.
+    pub fn node_history_raw_mut(&mut self, node_name: &str) -> ProofListIndex<&mut Fork, Vec<u8>> {
+        ProofListIndex::new(format!("neo4j.node_changes_{}", node_name), &mut self.view)
+    }
+
     ///Add to node history
     pub fn add_node_history(&mut self, uuid: &str, node_change: &NodeChange) {
+        self.known_nodes_mut().insert(uuid.to_string());
         self.node_history_mut(uuid).push(node_change.clone())
     }
 
+    ///Get mutable label index for the given label
+    pub fn label_nodes_mut(&mut self, label: &str) -> KeySetIndex<&mut Fork, String> {
+        KeySetIndex::new(
+            format!(
+                "neo4j.label_nodes_{}",
+                hash(label.as_bytes()).to_hex().as_str()
+            ),
+            &mut self.view,
+        )
+    }
+
+    ///Get mutable property index for the given property key
+    pub fn property_nodes_mut(&mut self, key: &str) -> KeySetIndex<&mut Fork, String> {
+        KeySetIndex::new(
+            format!(
+                "neo4j.property_nodes_{}",
+                hash(key.as_bytes()).to_hex().as_str()
+            ),
+            &mut self.view,
+        )
+    }
+
+    ///Update label and property indexes with an audited change.
+    pub fn index_node_change(&mut self, node_change: &NodeChange) {
+        match node_change {
+            AL(x) => {
+                self.label_nodes_mut(x.label_name())
+                    .insert(x.node_uuid().to_string());
+                self.node_labels_mut(x.node_uuid())
+                    .insert(x.label_name().to_string());
+            }
+            RL(x) => self
+                .node_labels_mut(x.node_uuid())
+                .remove(&x.label_name().to_string()),
+            ANP(x) => self
+                .property_nodes_mut(x.key())
+                .insert(x.node_uuid().to_string()),
+            RNP(x) => self
+                .property_nodes_mut(x.key())
+                .insert(x.node_uuid().to_string()),
+            _ => {}
+        }
+    }
+
+    ///Get a mutable prooflistindex for the changes of a single Neo4j transaction
+    pub fn transaction_changes_mut(
+        &mut self,
+        transaction_hash: &Hash,
+    ) -> ProofListIndex<&mut Fork, NodeChange> {
+        ProofListIndex::new(
+            format!(
+                "neo4j.transaction_changes_{}",
+                transaction_hash.to_hex().as_str()
+            ),
+            &mut self.view,
+        )
+    }
+
+    ///Get mutable transaction changes roots proofmapindex
+    pub fn transaction_changes_roots_mut(&mut self) -> ProofMapIndex<&mut Fork, Hash, Hash> {
+        ProofMapIndex::new("neo4j.transaction_changes_roots", &mut self.view)
+    }
+
+    ///Get the changes of a transaction as stored bytes, for rewriting them without decoding every change.
+    pub fn transaction_changes_raw_mut(
+        &mut self,
+        transaction_hash: &Hash,
+    ) -> ProofListIndex<&mut Fork, Vec<u8>> {
+        ProofListIndex::new(
+            format!(
+                "neo4j.transaction_changes_{}",
+                transaction_hash.to_hex().as_str()
+            ),
+            &mut self.view,
+        )
+    }
+
+    ///Add to the changes of a Neo4j transaction and update its root in the state.
+    pub fn add_transaction_change(&mut self, transaction_hash: &Hash, node_change: &NodeChange) {
+        let root = {
+            let mut changes = self.transaction_changes_mut(transaction_hash);
+            changes.push(node_change.clone());
+            changes.merkle_root()
+        };
+        self.transaction_changes_roots_mut()
+            .put(transaction_hash, root);
+    }
+
+    ///Get mutable digests of the changes recorded for audited blocks.
+    pub fn audited_changes_mut(&mut self) -> ProofMapIndex<&mut Fork, Hash, Hash> {
+        ProofMapIndex::new("neo4j.audited_changes", &mut self.view)
+    }
+
+    ///Get mutable diverging digests of the changes of an audited block.
+    pub fn audit_divergences_mut(
+        &mut self,
+        block_hash: &Hash,
+    ) -> ProofMapIndex<&mut Fork, PublicKey, Hash> {
+        ProofMapIndex::new(
+            format!("neo4j.audit_divergences_{}", block_hash.to_hex().as_str()),
+            &mut self.view,
+        )
+    }
+
+    ///Records the digest of diverging changes a validator reported for an audited block.
+    pub fn add_audit_divergence(&mut self, block_hash: &Hash, pub_key: &PublicKey, digest: Hash) {
+        let root = {
+            let mut divergences = self.audit_divergences_mut(block_hash);
+            divergences.put(pub_key, digest);
+            divergences.merkle_root()
+        };
+        ProofMapIndex::<&mut Fork, Hash, Hash>::new("neo4j.audit_divergence_roots", &mut self.view)
+            .put(block_hash, root);
+    }
+
     ///Adds a block that was audited and the hash for the AuditBlocks transaction that did the auditing.
     pub fn add_audited_block(&mut self, transaction_hash: &Hash, block_hash: Hash) {
         let mut index: ListIndex<&mut Fork, Hash> = ListIndex::new(
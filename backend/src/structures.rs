@@ -7,17 +7,306 @@ extern crate protobuf;
 use exonum::storage::StorageValue;
 use exonum::{
     crypto::{hash, CryptoHash, Hash, PublicKey},
+    helpers::Height,
     storage::Fork,
 };
 use grpc::RequestOptions;
+use neo4j::proto::transaction_manager::{Error, ErrorCode};
 use schema::Schema;
 use std::borrow::Cow;
-use std::fmt;
+use std::str::FromStr;
 use structures::NodeChange::{AL, AN, ANP, AR, ARP, RL, RN, RNP, RR, RRP};
 use util;
 
+use neo4j::proto::neo4j_service as pb;
+use protobuf::Message;
+
+///First byte of values stored as protobuf messages.
+/// Values written before the migration start with a segment offset or a small number, never with this byte.
+pub const PROTOBUF_MARKER: u8 = 0xFF;
+
+///Conversion of a storage type to and from its protobuf message,
+/// shaped after the `ProtobufConvert` of exonum v0.10.0.
+pub trait ProtobufConvert: Sized {
+    ///Protobuf message of the type
+    type ProtoStruct: Message;
+
+    ///Converts the value into its message.
+    fn to_pb(&self) -> Self::ProtoStruct;
+
+    ///Converts a message into the value, failing if the message is not a valid value.
+    fn from_pb(message: Self::ProtoStruct) -> Result<Self, ::failure::Error>;
+}
+
+///Decoding of values written before the migration to protobuf.
+trait LegacyDecode {
+    ///Checks that `bytes` hold the `encoding_struct!` layout of the value.
+    fn check_legacy(bytes: &[u8]) -> Result<(), String>;
+
+    ///Decodes the `encoding_struct!` layout of the value, which must have been checked.
+    fn from_legacy(bytes: &[u8]) -> Self;
+}
+
+///Storage types whose legacy layout only has string fields, so it can be checked before decoding.
+trait LegacyStrings: LegacyDecode {
+    ///Names of the fields, in the order of the layout
+    const FIELDS: &'static [&'static str];
+}
+
+///Field of a legacy layout.
+#[derive(Clone, Copy)]
+enum LegacyField {
+    ///String segment, stored in the header as offset and length.
+    Str,
+    ///Value of the given size stored in the header itself.
+    Fixed(usize),
+}
+
+///Checks that every segment of a legacy layout with `fields` strings lies inside `bytes` and is UTF-8.
+fn check_legacy_strings(bytes: &[u8], fields: usize) -> Result<(), String> {
+    check_legacy_layout(bytes, &vec![LegacyField::Str; fields])
+}
+
+///Checks that `bytes` fit the header of the legacy layout and that its string segments lie inside `bytes` and are UTF-8.
+fn check_legacy_layout(bytes: &[u8], fields: &[LegacyField]) -> Result<(), String> {
+    let header_size = fields
+        .iter()
+        .map(|field| match *field {
+            LegacyField::Str => 8,
+            LegacyField::Fixed(size) => size,
+        })
+        .sum();
+    if bytes.len() < header_size {
+        return Err(format!(
+            "{} bytes do not fit a header of {} fields",
+            bytes.len(),
+            fields.len()
+        ));
+    }
+    let read_u32 = |at: usize| {
+        (0..4).fold(0usize, |value, i| {
+            value | (bytes[at + i] as usize) << (8 * i)
+        })
+    };
+    let mut at = 0;
+    for (field, kind) in fields.iter().enumerate() {
+        match *kind {
+            LegacyField::Str => {
+                let offset = read_u32(at);
+                let len = read_u32(at + 4);
+                if offset < header_size || offset + len > bytes.len() {
+                    return Err(format!("Field {} points outside of the value", field));
+                }
+                ::std::str::from_utf8(&bytes[offset..offset + len])
+                    .map_err(|e| format!("Field {} is not UTF-8: {}", field, e))?;
+                at += 8;
+            }
+            LegacyField::Fixed(size) => at += size,
+        }
+    }
+    Ok(())
+}
+
+fn encode_value<T: ProtobufConvert>(value: &T) -> Vec<u8> {
+    let mut bytes = vec![PROTOBUF_MARKER];
+    value
+        .to_pb()
+        .write_to_vec(&mut bytes)
+        .expect("Unable to encode protobuf message");
+    bytes
+}
+
+fn decode_protobuf<T: ProtobufConvert>(bytes: &[u8]) -> Result<T, ::failure::Error> {
+    match bytes.split_first() {
+        Some((&PROTOBUF_MARKER, message)) => {
+            let message = protobuf::parse_from_bytes::<T::ProtoStruct>(message)
+                .map_err(|e| format_err!("Unable to decode protobuf message: {}", e))?;
+            T::from_pb(message)
+        }
+        _ => bail!("Stored value is not a protobuf message"),
+    }
+}
+
+fn decode_value<T: ProtobufConvert + LegacyDecode>(bytes: &[u8]) -> Result<T, ::failure::Error> {
+    match bytes.first() {
+        Some(&PROTOBUF_MARKER) => decode_protobuf(bytes),
+        _ => {
+            T::check_legacy(bytes).map_err(|e| format_err!("Corrupt legacy value: {}", e))?;
+            Ok(T::from_legacy(bytes))
+        }
+    }
+}
+
+fn u8_from_pb(value: u32) -> Result<u8, ::failure::Error> {
+    if value > u32::from(u8::max_value()) {
+        bail!("Value {} does not fit into u8", value);
+    }
+    Ok(value as u8)
+}
+
+///Implements `StorageValue` and `CryptoHash` through the protobuf message of the type.
+/// Types added after the migration to protobuf have no legacy encoding and are marked `protobuf_only`.
+macro_rules! impl_storage_value {
+    ($name:ident) => {
+        impl_storage_value!(@impl $name, decode_value);
+    };
+    ($name:ident, protobuf_only) => {
+        impl_storage_value!(@impl $name, decode_protobuf);
+    };
+    (@impl $name:ident, $decode:ident) => {
+        impl $name {
+            ///Decodes a stored value, reporting corrupt entries instead of panicking.
+            pub fn decode(bytes: &[u8]) -> Result<Self, ::failure::Error> {
+                $decode(bytes)
+            }
+        }
+
+        impl StorageValue for $name {
+            fn into_bytes(self) -> Vec<u8> {
+                encode_value(&self)
+            }
+
+            ///Panics on corrupt entries, use `decode` to handle them.
+            fn from_bytes(value: Cow<[u8]>) -> Self {
+                $decode(value.as_ref()).unwrap_or_else(|e| {
+                    panic!("Corrupt {} in storage: {}", stringify!($name), e)
+                })
+            }
+        }
+
+        impl CryptoHash for $name {
+            fn hash(&self) -> Hash {
+                hash(&encode_value(self))
+            }
+        }
+    };
+}
+
+///Defines a storage type with string fields, stored as the protobuf message of the same name.
+/// Values written before the migration are read with the type of the same name in `legacy`.
+/// Fields in `added` were introduced later, are optional and are `None` in legacy values.
+/// Their messages are `OptionalString`, to tell an absent value from an empty one.
+macro_rules! storage_struct {
+    (
+        $(#[$attr:meta])*
+        struct $name:ident {
+            $(
+                $(#[$field_attr:meta])*
+                $field:ident: &str
+            ),*
+            $(,)*
+        }
+    ) => {
+        storage_struct! {
+            $(#[$attr])*
+            struct $name {
+                $($(#[$field_attr])* $field: &str,)*
+            }
+            added {}
+        }
+    };
+    (
+        $(#[$attr:meta])*
+        struct $name:ident {
+            $(
+                $(#[$field_attr:meta])*
+                $field:ident: &str
+            ),*
+            $(,)*
+        }
+        added {
+            $(
+                $(#[$added_attr:meta])*
+                $added:ident: Option<&str>
+            ),*
+            $(,)*
+        }
+    ) => {
+        $(#[$attr])*
+        #[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+        pub struct $name {
+            $($field: String,)*
+            $(
+                #[serde(default)]
+                $added: Option<String>,
+            )*
+        }
+
+        impl $name {
+            ///Creates a new value from its fields.
+            pub fn new($($field: &str,)* $($added: Option<&str>,)*) -> Self {
+                $name {
+                    $($field: $field.to_string(),)*
+                    $($added: $added.map(str::to_string),)*
+                }
+            }
+
+            $(
+                $(#[$field_attr])*
+                pub fn $field(&self) -> &str {
+                    &self.$field
+                }
+            )*
+
+            $(
+                $(#[$added_attr])*
+                pub fn $added(&self) -> Option<&str> {
+                    self.$added.as_ref().map(String::as_str)
+                }
+            )*
+        }
+
+        impl ProtobufConvert for $name {
+            type ProtoStruct = pb::$name;
+
+            fn to_pb(&self) -> pb::$name {
+                let mut message = pb::$name::new();
+                $(message.$field = self.$field.clone();)*
+                $(message.$added = optional_string_to_pb(&self.$added);)*
+                message
+            }
+
+            fn from_pb(message: pb::$name) -> Result<Self, ::failure::Error> {
+                Ok($name {
+                    $($field: message.$field,)*
+                    $($added: message.$added.into_option().map(|value| value.value),)*
+                })
+            }
+        }
+
+        impl LegacyDecode for $name {
+            fn check_legacy(bytes: &[u8]) -> Result<(), String> {
+                check_legacy_strings(bytes, Self::FIELDS.len())
+            }
+
+            fn from_legacy(bytes: &[u8]) -> Self {
+                let value = <legacy::$name as StorageValue>::from_bytes(Cow::Borrowed(bytes));
+                $name {
+                    $($field: value.$field().to_string(),)*
+                    $($added: None,)*
+                }
+            }
+        }
+
+        impl LegacyStrings for $name {
+            const FIELDS: &'static [&'static str] = &[$(stringify!($field)),*];
+        }
+
+        impl_storage_value!($name);
+    };
+}
+
+fn optional_string_to_pb(value: &Option<String>) -> protobuf::SingularPtrField<pb::OptionalString> {
+    let message = value.as_ref().map(|value| {
+        let mut message = pb::OptionalString::new();
+        message.value = value.clone();
+        message
+    });
+    protobuf::SingularPtrField::from_option(message)
+}
+
 ///add node
-encoding_struct! {
+storage_struct! {
     ///add node
     struct AddNode {
         ///node uuid, made of transaction hash for prefix and index.
@@ -28,7 +317,7 @@ encoding_struct! {
 }
 
 ///remove node
-encoding_struct! {
+storage_struct! {
     ///remove node
     struct RemoveNode {
         ///node uuid
@@ -39,7 +328,7 @@ encoding_struct! {
 }
 
 ///add relation
-encoding_struct! {
+storage_struct! {
     ///add relation
     struct AddRelation {
         ///relation uuid, needed since we add to our own database
@@ -56,7 +345,7 @@ encoding_struct! {
 }
 
 ///remove relation
-encoding_struct! {
+storage_struct! {
     ///add relation
     struct RemoveRelation {
         ///relation uuid, needed since we add to our own database
@@ -71,7 +360,7 @@ encoding_struct! {
 }
 
 ///add label
-encoding_struct! {
+storage_struct! {
     ///add label
     struct AddLabel {
         ///node uuid
@@ -84,7 +373,7 @@ encoding_struct! {
 }
 
 ///remove label
-encoding_struct! {
+storage_struct! {
     ///Remove label
     struct RemoveLabel {
         ///Node uuid
@@ -97,7 +386,7 @@ encoding_struct! {
 }
 
 ///add new property
-encoding_struct! {
+storage_struct! {
     ///Add new property
     struct AddNodeProperty {
         ///Node uuid
@@ -109,10 +398,16 @@ encoding_struct! {
         ///hash value for the the transaction it is part of
         transaction_id: &str,
     }
+    added {
+        ///property value before the change, none if it was not set or not recorded
+        previous_value: Option<&str>,
+        ///Cypher type of the previous value, none if it was recorded without one
+        previous_value_type: Option<&str>,
+    }
 }
 
 ///remove node property
-encoding_struct! {
+storage_struct! {
     ///remove node property
     struct RemoveNodeProperty {
         ///Node uuid
@@ -122,10 +417,16 @@ encoding_struct! {
         ///hash value for the the transaction it is part of
         transaction_id: &str,
     }
+    added {
+        ///property value before the removal, none if it was not recorded
+        previous_value: Option<&str>,
+        ///Cypher type of the previous value, none if it was recorded without one
+        previous_value_type: Option<&str>,
+    }
 }
 
 ///Add relation property
-encoding_struct! {
+storage_struct! {
     ///Add relation property
     struct AddRelationProperty {
         ///relation's uuid
@@ -141,10 +442,16 @@ encoding_struct! {
         ///hash value for the the transaction it is part of
         transaction_id: &str,
     }
+    added {
+        ///property value before the change, none if it was not set or not recorded
+        previous_value: Option<&str>,
+        ///Cypher type of the previous value, none if it was recorded without one
+        previous_value_type: Option<&str>,
+    }
 }
 
 ///Remove relation property
-encoding_struct! {
+storage_struct! {
     ///Remove relation property
     struct RemoveRelationProperty {
         ///relation's uuid
@@ -158,144 +465,187 @@ encoding_struct! {
         ///hash value for the the transaction it is part of
         transaction_id: &str,
     }
+    added {
+        ///property value before the removal, none if it was not recorded
+        previous_value: Option<&str>,
+        ///Cypher type of the previous value, none if it was recorded without one
+        previous_value_type: Option<&str>,
+    }
 }
 
-///All possible node changes
-#[derive(Clone, Debug)]
+///All possible node changes.
+/// In JSON a change is an object with the fields of its variant and a `kind` discriminator.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "kind")]
 pub enum NodeChange {
     ///Add new node
+    #[serde(rename = "add_node")]
     AN(AddNode),
     ///Remove existing node
+    #[serde(rename = "remove_node")]
     RN(RemoveNode),
     ///Add new relation
+    #[serde(rename = "add_relation")]
     AR(AddRelation),
     ///Remove existing relation
+    #[serde(rename = "remove_relation")]
     RR(RemoveRelation),
     ///Add new label
+    #[serde(rename = "add_label")]
     AL(AddLabel),
     ///Remove existing label
+    #[serde(rename = "remove_label")]
     RL(RemoveLabel),
     ///Add new node property, Could be part of modification.
+    #[serde(rename = "add_node_property")]
     ANP(AddNodeProperty),
     ///Delete existing node property, Could be part of modification.
+    #[serde(rename = "remove_node_property")]
     RNP(RemoveNodeProperty),
     ///Add new relation property. Could be part of modification.
+    #[serde(rename = "add_relation_property")]
     ARP(AddRelationProperty),
     ///Remove existing relation property. Could be part of modification.
+    #[serde(rename = "remove_relation_property")]
     RRP(RemoveRelationProperty),
 }
 
+///First byte of an encoded `NodeChange`, never the first byte of a legacy encoding.
+pub const NODE_CHANGE_MAGIC: u8 = 0xFE;
+///Current encoding version of `NodeChange`
+pub const NODE_CHANGE_VERSION: u8 = 1;
+
+///Reasons a stored `NodeChange` could not be decoded.
+#[derive(Debug, Fail, PartialEq)]
+pub enum NodeChangeError {
+    ///No bytes at all
+    #[fail(display = "Node change is empty")]
+    Empty,
+    ///Header ends before the tag
+    #[fail(display = "Node change header is truncated")]
+    Truncated,
+    ///Written by a newer version of the service
+    #[fail(display = "Unknown node change version: {}", _0)]
+    UnknownVersion(u8),
+    ///Tag does not name a variant
+    #[fail(display = "Unknown node change tag: {}", _0)]
+    UnknownTag(u8),
+    ///Payload does not decode as the tagged variant
+    #[fail(display = "Corrupt node change payload: {}", _0)]
+    CorruptPayload(String),
+}
+
+///Decodes the payload of the variant with the given tag, returning early on unknown tags.
+macro_rules! decode_tagged {
+    ($tag:expr, $payload:expr, $parse:ident) => {
+        match $tag {
+            1 => AN($parse($payload)?),
+            2 => RN($parse($payload)?),
+            3 => ANP($parse($payload)?),
+            4 => RNP($parse($payload)?),
+            5 => AL($parse($payload)?),
+            6 => RL($parse($payload)?),
+            7 => AR($parse($payload)?),
+            8 => RR($parse($payload)?),
+            9 => ARP($parse($payload)?),
+            10 => RRP($parse($payload)?),
+            tag => return Err(NodeChangeError::UnknownTag(tag)),
+        }
+    };
+}
+
+fn parse_payload<T: ProtobufConvert>(payload: &[u8]) -> Result<T, NodeChangeError> {
+    protobuf::parse_from_bytes::<T::ProtoStruct>(payload)
+        .map_err(|e| NodeChangeError::CorruptPayload(e.to_string()))
+        .and_then(|message| {
+            T::from_pb(message).map_err(|e| NodeChangeError::CorruptPayload(e.to_string()))
+        })
+}
+
+///Payload of a change with a trailing tag, either a marked protobuf message or the `encoding_struct!` layout.
+fn parse_legacy_payload<T: ProtobufConvert + LegacyStrings>(
+    payload: &[u8],
+) -> Result<T, NodeChangeError> {
+    match payload.split_first() {
+        Some((&PROTOBUF_MARKER, message)) => parse_payload(message),
+        _ => {
+            check_legacy_strings(payload, T::FIELDS.len())
+                .map_err(NodeChangeError::CorruptPayload)?;
+            Ok(T::from_legacy(payload))
+        }
+    }
+}
+
 impl StorageValue for NodeChange {
+    ///Encodes the change as `[NODE_CHANGE_MAGIC, NODE_CHANGE_VERSION, tag]` followed by the protobuf message of the variant.
     fn into_bytes(self) -> Vec<u8> {
-        match self {
-            AN(x) => {
-                let mut bytes = x.raw;
-                bytes.push(1);
-                bytes
-            }
-            RN(x) => {
-                let mut bytes = x.raw;
-                bytes.push(2);
-                bytes
-            }
-            ANP(x) => {
-                let mut bytes = x.raw;
-                bytes.push(3);
-                bytes
-            }
-            RNP(x) => {
-                let mut bytes = x.raw;
-                bytes.push(4);
-                bytes
-            }
-            AL(x) => {
-                let mut bytes = x.raw;
-                bytes.push(5);
-                bytes
-            }
-            RL(x) => {
-                let mut bytes = x.raw;
-                bytes.push(6);
-                bytes
-            }
-            AR(x) => {
-                let mut bytes = x.raw;
-                bytes.push(7);
-                bytes
-            }
-            RR(x) => {
-                let mut bytes = x.raw;
-                bytes.push(8);
-                bytes
-            }
-            ARP(x) => {
-                let mut bytes = x.raw;
-                bytes.push(9);
-                bytes
-            }
-            RRP(x) => {
-                let mut bytes = x.raw;
-                bytes.push(10);
-                bytes
-            }
-        }
+        let mut bytes = vec![NODE_CHANGE_MAGIC, NODE_CHANGE_VERSION, self.tag()];
+        let payload = match self {
+            AN(x) => x.to_pb().write_to_bytes(),
+            RN(x) => x.to_pb().write_to_bytes(),
+            ANP(x) => x.to_pb().write_to_bytes(),
+            RNP(x) => x.to_pb().write_to_bytes(),
+            AL(x) => x.to_pb().write_to_bytes(),
+            RL(x) => x.to_pb().write_to_bytes(),
+            AR(x) => x.to_pb().write_to_bytes(),
+            RR(x) => x.to_pb().write_to_bytes(),
+            ARP(x) => x.to_pb().write_to_bytes(),
+            RRP(x) => x.to_pb().write_to_bytes(),
+        };
+        bytes.extend(payload.expect("Unable to encode protobuf message"));
+        bytes
     }
 
+    ///Panics on corrupt entries, use `NodeChange::decode` to handle them.
     fn from_bytes(v: ::std::borrow::Cow<[u8]>) -> Self {
-        let mut data = v.into_owned();
-        let i = data.pop();
-
-        let nc: NodeChange = match i {
-            Some(1) => AN(AddNode::from_bytes(Cow::Borrowed(&data))),
-            Some(2) => RN(RemoveNode::from_bytes(Cow::Borrowed(&data))),
-            Some(3) => ANP(AddNodeProperty::from_bytes(Cow::Borrowed(&data))),
-            Some(4) => RNP(RemoveNodeProperty::from_bytes(Cow::Borrowed(&data))),
-            Some(5) => AL(AddLabel::from_bytes(Cow::Borrowed(&data))),
-            Some(6) => RL(RemoveLabel::from_bytes(Cow::Borrowed(&data))),
-            Some(7) => AR(AddRelation::from_bytes(Cow::Borrowed(&data))),
-            Some(8) => RR(RemoveRelation::from_bytes(Cow::Borrowed(&data))),
-            Some(9) => ARP(AddRelationProperty::from_bytes(Cow::Borrowed(&data))),
-            _ => RRP(RemoveRelationProperty::from_bytes(Cow::Borrowed(&data))),
-        };
-        nc
+        NodeChange::decode(v.as_ref()).expect("Corrupt node change in storage")
     }
 }
 
 impl CryptoHash for NodeChange {
     fn hash(&self) -> Hash {
-        match self {
-            AN(x) => hash(x.raw.as_ref()),
-            RN(x) => hash(x.raw.as_ref()),
-            ANP(x) => hash(x.raw.as_ref()),
-            RNP(x) => hash(x.raw.as_ref()),
-            AL(x) => hash(x.raw.as_ref()),
-            RL(x) => hash(x.raw.as_ref()),
-            AR(x) => hash(x.raw.as_ref()),
-            RR(x) => hash(x.raw.as_ref()),
-            ARP(x) => hash(x.raw.as_ref()),
-            RRP(x) => hash(x.raw.as_ref()),
-        }
+        hash(&self.clone().into_bytes())
     }
 }
 
-impl fmt::Display for NodeChange {
-    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+impl NodeChange {
+    ///Tag of the variant in the encoding, the same numbers as the legacy trailing tag.
+    pub fn tag(&self) -> u8 {
         match self {
-            AN(x) => write!(f, "Node with UUID <b>{}</b> created", x.node_uuid()),
-            RN(x) => write!(f, "Node with UUID <b>{}</b> deleted", x.node_uuid()),
-            AL(x) => write!(f, "Label, <b>{}</b>, added to node", x.label_name()),
-            RL(x) => write!(f, "Label, <b>{}</b>, removed from node", x.label_name()),
-            ANP(x) => write!(f, "Added new property, key <b>{}</b>, value <b>{}</b>", x.key(), x.value()),
-            RNP(x) => write!(f, "Removed property, key <b>{}</b>", x.key()),
-            AR(x) => write!(f, "Relationship of type <b>{}</b> with UUID <b>{}</b> added. Starting from <b>{}</b>, and going to <b>{}</b>", x.field_type(), x.rel_uuid(), x.from_uuid(), x.to_uuid()),
-            RR(x) => write!(f, "Relationship with UUID <b>{}</b> removed. Starting from <b>{}</b>, and going to <b>{}</b>", x.rel_uuid(), x.from_uuid(), x.to_uuid()),
-            ARP(x) => write!(f, "Property, <b>{}</b>, with value <b>{}</b>, added to relation with uuid <b>{}</b>", x.key(), x.value(), x.relation_uuid()),
-            RRP(x) => write!(f, "Property, <b>{}</b>, removed to relation with uuid <b>{}</b>", x.key(), x.relation_uuid()),
+            AN(_) => 1,
+            RN(_) => 2,
+            ANP(_) => 3,
+            RNP(_) => 4,
+            AL(_) => 5,
+            RL(_) => 6,
+            AR(_) => 7,
+            RR(_) => 8,
+            ARP(_) => 9,
+            RRP(_) => 10,
+        }
+    }
+
+    ///Decodes a stored change, reporting corrupt entries instead of guessing the variant.
+    /// Changes written before the header was introduced carry their tag in the last byte.
+    pub fn decode(bytes: &[u8]) -> Result<NodeChange, NodeChangeError> {
+        match bytes.split_first() {
+            None => Err(NodeChangeError::Empty),
+            Some((&NODE_CHANGE_MAGIC, header)) => {
+                if header.len() < 2 {
+                    return Err(NodeChangeError::Truncated);
+                }
+                if header[0] != NODE_CHANGE_VERSION {
+                    return Err(NodeChangeError::UnknownVersion(header[0]));
+                }
+                Ok(decode_tagged!(header[1], &header[2..], parse_payload))
+            }
+            Some(_) => {
+                let (tag, payload) = bytes.split_last().ok_or(NodeChangeError::Empty)?;
+                Ok(decode_tagged!(*tag, payload, parse_legacy_payload))
+            }
         }
     }
-}
 
-impl NodeChange {
     ///This defines the logic of which nodes we add specific changes. Some changes, related to relations we have to add to both end points.
     pub fn get_uuis(&self) -> Vec<&str> {
         match self {
@@ -329,7 +679,7 @@ impl NodeChange {
 }
 
 ///Error msg
-encoding_struct! {
+storage_struct! {
     ///Error msg
     struct ErrorMsg {
         ///message itself.
@@ -338,28 +688,1277 @@ encoding_struct! {
 }
 
 ///Relation struct
-encoding_struct! {
-    ///Relation struct
-    struct Relation {
-        ///Start node
-        start_node_uuid: &str,
-        ///End node
-        end_node_uuid: &str
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Relation {
+    start_node_uuid: String,
+    end_node_uuid: String,
+    #[serde(default)]
+    field_type: String,
+}
+
+impl Relation {
+    ///Creates a new value from its fields.
+    pub fn new(start_node_uuid: &str, end_node_uuid: &str, field_type: &str) -> Self {
+        Relation {
+            start_node_uuid: start_node_uuid.to_string(),
+            end_node_uuid: end_node_uuid.to_string(),
+            field_type: field_type.to_string(),
+        }
+    }
+
+    ///Start node
+    pub fn start_node_uuid(&self) -> &str {
+        &self.start_node_uuid
+    }
+
+    ///End node
+    pub fn end_node_uuid(&self) -> &str {
+        &self.end_node_uuid
+    }
+
+    ///Relationship type, empty for relations stored before it was recorded
+    pub fn field_type(&self) -> &str {
+        &self.field_type
+    }
+}
+
+impl ProtobufConvert for Relation {
+    type ProtoStruct = pb::Relation;
+
+    fn to_pb(&self) -> pb::Relation {
+        let mut message = pb::Relation::new();
+        message.start_node_uuid = self.start_node_uuid.clone();
+        message.end_node_uuid = self.end_node_uuid.clone();
+        message.field_type = self.field_type.clone();
+        message
+    }
+
+    fn from_pb(message: pb::Relation) -> Result<Self, ::failure::Error> {
+        Ok(Relation {
+            start_node_uuid: message.start_node_uuid,
+            end_node_uuid: message.end_node_uuid,
+            field_type: message.field_type,
+        })
+    }
+}
+
+impl LegacyDecode for Relation {
+    fn check_legacy(bytes: &[u8]) -> Result<(), String> {
+        check_legacy_strings(bytes, 2)
+    }
+
+    fn from_legacy(bytes: &[u8]) -> Self {
+        let value = <legacy::Relation as StorageValue>::from_bytes(Cow::Borrowed(bytes));
+        Relation::new(value.start_node_uuid(), value.end_node_uuid(), "")
     }
 }
 
+impl_storage_value!(Relation);
+
 ///Our queries structure. This represents a set of queries for a single transaction
 /// It has related transaction hash and in case of error, the appropriate message.
-encoding_struct! {
-    ///Queries struct
-    struct Neo4jTransaction {
-        ///queries themselves
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Neo4jTransaction {
+    queries: String,
+    error_msg: String,
+    status: u8,
+    pub_key: PublicKey,
+}
+
+impl Neo4jTransaction {
+    ///Creates a new value from its fields.
+    pub fn new(
         queries: &str,
-        ///error from the database if any.
         error_msg: &str,
-        ///Result, empty when not processed, otherwise either FAILURE or SUCCESS
-        result: &str,
-        ///Public key of the transaction initiator
-        pub_key: &PublicKey
+        status: TransactionStatus,
+        pub_key: &PublicKey,
+    ) -> Self {
+        Neo4jTransaction {
+            queries: queries.to_string(),
+            error_msg: error_msg.to_string(),
+            status: status as u8,
+            pub_key: *pub_key,
+        }
+    }
+
+    ///queries themselves
+    pub fn queries(&self) -> &str {
+        &self.queries
+    }
+
+    ///Details of the status, like the error from the database or why the service refused the
+    /// transaction, empty if there are none.
+    pub fn error_msg(&self) -> &str {
+        &self.error_msg
+    }
+
+    ///Status, number of a `TransactionStatus`
+    pub fn status_number(&self) -> u8 {
+        self.status
+    }
+
+    ///Public key of the transaction initiator
+    pub fn pub_key(&self) -> &PublicKey {
+        &self.pub_key
+    }
+
+    ///Parsed status of the transaction, none if the stored number is unknown.
+    pub fn status(&self) -> Option<TransactionStatus> {
+        TransactionStatus::from_u8(self.status)
+    }
+}
+
+///Number stored for status names written before statuses were stored as numbers that are not
+/// known to this version of the service.
+pub const UNKNOWN_STATUS: u8 = u8::MAX;
+
+///Number of a status stored by name.
+fn status_from_name(name: &str) -> u8 {
+    name.parse::<TransactionStatus>()
+        .map_or(UNKNOWN_STATUS, |status| status as u8)
+}
+
+impl ProtobufConvert for Neo4jTransaction {
+    type ProtoStruct = pb::Neo4jTransaction;
+
+    fn to_pb(&self) -> pb::Neo4jTransaction {
+        let mut message = pb::Neo4jTransaction::new();
+        message.queries = self.queries.clone();
+        message.error_msg = self.error_msg.clone();
+        message.status = u32::from(self.status);
+        message.pub_key = self.pub_key.as_ref().to_vec();
+        message
+    }
+
+    fn from_pb(message: pb::Neo4jTransaction) -> Result<Self, ::failure::Error> {
+        let pub_key = PublicKey::from_slice(&message.pub_key)
+            .ok_or_else(|| format_err!("Wrong public key length: {}", message.pub_key.len()))?;
+        // Values written before the status was stored as a number carry its name.
+        let status = if message.result.is_empty() {
+            u8_from_pb(message.status)?
+        } else {
+            status_from_name(&message.result)
+        };
+        Ok(Neo4jTransaction {
+            queries: message.queries,
+            error_msg: message.error_msg,
+            status,
+            pub_key,
+        })
+    }
+}
+
+impl LegacyDecode for Neo4jTransaction {
+    fn check_legacy(bytes: &[u8]) -> Result<(), String> {
+        use self::LegacyField::{Fixed, Str};
+        check_legacy_layout(bytes, &[Str, Str, Str, Fixed(32)])
+    }
+
+    fn from_legacy(bytes: &[u8]) -> Self {
+        let value = <legacy::Neo4jTransaction as StorageValue>::from_bytes(Cow::Borrowed(bytes));
+        Neo4jTransaction {
+            queries: value.queries().to_string(),
+            error_msg: value.error_msg().to_string(),
+            status: status_from_name(value.result()),
+            pub_key: *value.pub_key(),
+        }
+    }
+}
+
+impl_storage_value!(Neo4jTransaction);
+
+///Lifecycle states of a neo4j transaction.
+/// Stored by number in `Neo4jTransaction.status` and `StatusTransition.status`, and by name in
+/// values written before, so existing variants must keep both.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[repr(u8)]
+pub enum TransactionStatus {
+    ///Committed to the blockchain, not yet executed by Neo4j
+    #[serde(rename = "PENDING")]
+    Pending = 0,
+    ///Executed by Neo4j, changes are audited
+    #[serde(rename = "SUCCESS")]
+    Success = 1,
+    ///Neo4j failed to execute the transaction
+    #[serde(rename = "ERROR")]
+    Error = 2,
+    ///Neo4j executed the block, its changes are not audited yet
+    #[serde(rename = "EXECUTED_AWAITING_AUDIT")]
+    ExecutedAwaitingAudit = 3,
+    ///Changes could not be retrieved from Neo4j during an audit, another audit may still record them
+    #[serde(rename = "NEO4J_UNAVAILABLE")]
+    Neo4jUnavailable = 4,
+    ///Refused by the service, never sent to Neo4j
+    #[serde(rename = "REJECTED")]
+    Rejected = 5,
+    ///Waiting for its activation height or time, not yet sent to Neo4j
+    #[serde(rename = "SCHEDULED")]
+    Scheduled = 6,
+    ///Cancelled by its author before activation, never sent to Neo4j
+    #[serde(rename = "CANCELLED")]
+    Cancelled = 7,
+}
+
+impl TransactionStatus {
+    ///Name of the status, as carried by values written before it was stored as a number
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            TransactionStatus::Pending => "PENDING",
+            TransactionStatus::Success => "SUCCESS",
+            TransactionStatus::Error => "ERROR",
+            TransactionStatus::ExecutedAwaitingAudit => "EXECUTED_AWAITING_AUDIT",
+            TransactionStatus::Neo4jUnavailable => "NEO4J_UNAVAILABLE",
+            TransactionStatus::Rejected => "REJECTED",
+            TransactionStatus::Scheduled => "SCHEDULED",
+            TransactionStatus::Cancelled => "CANCELLED",
+        }
+    }
+
+    ///Status from its number as stored in `StatusTransition.status`
+    pub fn from_u8(value: u8) -> Option<Self> {
+        match value {
+            0 => Some(TransactionStatus::Pending),
+            1 => Some(TransactionStatus::Success),
+            2 => Some(TransactionStatus::Error),
+            3 => Some(TransactionStatus::ExecutedAwaitingAudit),
+            4 => Some(TransactionStatus::Neo4jUnavailable),
+            5 => Some(TransactionStatus::Rejected),
+            6 => Some(TransactionStatus::Scheduled),
+            7 => Some(TransactionStatus::Cancelled),
+            _ => None,
+        }
+    }
+
+    ///Whether Neo4j is not done with the transaction yet
+    pub fn is_open(&self) -> bool {
+        match self {
+            TransactionStatus::Pending | TransactionStatus::ExecutedAwaitingAudit => true,
+            _ => false,
+        }
+    }
+
+    ///Whether the changes of the transaction may still be recorded by an audit. Changes one
+    /// validator could not retrieve may still be provided by another one.
+    pub fn awaits_changes(&self) -> bool {
+        self.is_open() || *self == TransactionStatus::Neo4jUnavailable
+    }
+}
+
+impl FromStr for TransactionStatus {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "PENDING" => Ok(TransactionStatus::Pending),
+            "SUCCESS" => Ok(TransactionStatus::Success),
+            "ERROR" => Ok(TransactionStatus::Error),
+            "EXECUTED_AWAITING_AUDIT" => Ok(TransactionStatus::ExecutedAwaitingAudit),
+            "NEO4J_UNAVAILABLE" => Ok(TransactionStatus::Neo4jUnavailable),
+            "REJECTED" => Ok(TransactionStatus::Rejected),
+            "SCHEDULED" => Ok(TransactionStatus::Scheduled),
+            "CANCELLED" => Ok(TransactionStatus::Cancelled),
+            _ => Err(format!("Unknown transaction status: {}", s)),
+        }
+    }
+}
+
+///Current encoding version of `StatusTransition`
+pub const STATUS_TRANSITION_VERSION: u8 = 1;
+
+///Status transition of a neo4j transaction
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct StatusTransition {
+    version: u8,
+    status: u8,
+    height: u64,
+}
+
+impl StatusTransition {
+    ///Creates a new value from its fields.
+    pub fn new(version: u8, status: u8, height: u64) -> Self {
+        StatusTransition {
+            version,
+            status,
+            height,
+        }
+    }
+
+    ///encoding version, `STATUS_TRANSITION_VERSION` when written
+    pub fn version(&self) -> u8 {
+        self.version
+    }
+
+    ///new status, number of a `TransactionStatus`
+    pub fn status(&self) -> u8 {
+        self.status
+    }
+
+    ///height of the block in which the transition happened
+    pub fn height(&self) -> u64 {
+        self.height
+    }
+
+    ///Creates a transition of the current encoding version.
+    pub fn from_status(status: TransactionStatus, height: Height) -> Self {
+        StatusTransition::new(STATUS_TRANSITION_VERSION, status as u8, height.0)
+    }
+
+    ///Parsed status, none if the stored number is unknown.
+    pub fn transaction_status(&self) -> Option<TransactionStatus> {
+        TransactionStatus::from_u8(self.status())
+    }
+}
+
+impl ProtobufConvert for StatusTransition {
+    type ProtoStruct = pb::StatusTransition;
+
+    fn to_pb(&self) -> pb::StatusTransition {
+        let mut message = pb::StatusTransition::new();
+        message.version = u32::from(self.version);
+        message.status = u32::from(self.status);
+        message.height = self.height;
+        message
+    }
+
+    fn from_pb(message: pb::StatusTransition) -> Result<Self, ::failure::Error> {
+        Ok(StatusTransition {
+            version: u8_from_pb(message.version)?,
+            status: u8_from_pb(message.status)?,
+            height: message.height,
+        })
+    }
+}
+
+impl LegacyDecode for StatusTransition {
+    fn check_legacy(bytes: &[u8]) -> Result<(), String> {
+        use self::LegacyField::Fixed;
+        check_legacy_layout(bytes, &[Fixed(1), Fixed(1), Fixed(8)])
+    }
+
+    fn from_legacy(bytes: &[u8]) -> Self {
+        let value = <legacy::StatusTransition as StorageValue>::from_bytes(Cow::Borrowed(bytes));
+        StatusTransition::new(value.version(), value.status(), value.height())
+    }
+}
+
+impl_storage_value!(StatusTransition);
+
+///Reason a neo4j transaction failed. Codes below 100 mirror `ErrorCode` of the transaction manager,
+/// codes from 100 on are set by the service itself.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[repr(u8)]
+pub enum FailureCode {
+    ///Transaction had no queries
+    #[serde(rename = "EMPTY_TRANSACTION")]
+    EmptyTransaction = 0,
+    ///Transaction had no id to prefix the uuids with
+    #[serde(rename = "EMPTY_UUID_PREFIX")]
+    EmptyUuidPrefix = 1,
+    ///One of the statements failed
+    #[serde(rename = "FAILED_QUERY")]
+    FailedQuery = 2,
+    ///A statement tried to modify a uuid
+    #[serde(rename = "MODIFIED_UUID")]
+    ModifiedUuid = 3,
+    ///Neo4j rolled the transaction back
+    #[serde(rename = "TRANSACTION_ROLLBACK")]
+    TransactionRollback = 4,
+    ///Neo4j threw an unexpected exception
+    #[serde(rename = "RUNTIME_EXCEPTION")]
+    RuntimeException = 5,
+    ///A constraint of the database was violated
+    #[serde(rename = "CONSTRAINT_VIOLATION")]
+    ConstraintViolation = 6,
+    ///Author is not allowed to submit the transaction, set by the service
+    #[serde(rename = "NOT_AUTHORIZED")]
+    NotAuthorized = 100,
+    ///Changes touched labels or relationship types the author may not write, set by the service
+    #[serde(rename = "POLICY_VIOLATION")]
+    PolicyViolation = 101,
+    ///A node history changed since the transaction was made, set by the service
+    #[serde(rename = "PRECONDITION_FAILED")]
+    PreconditionFailed = 102,
+    ///Transaction has more statements than configured
+    #[serde(rename = "TOO_MANY_STATEMENTS")]
+    TooManyStatements = 103,
+    ///Queries of the transaction are larger than configured
+    #[serde(rename = "QUERY_TOO_LARGE")]
+    QueryTooLarge = 104,
+    ///Author submitted too many transactions recently
+    #[serde(rename = "RATE_LIMITED")]
+    RateLimited = 105,
+    ///Queries call a function or procedure of the deny-list
+    #[serde(rename = "NON_DETERMINISTIC_QUERY")]
+    NonDeterministicQuery = 106,
+    ///Queries only read the graph and would waste a block
+    #[serde(rename = "READ_ONLY_QUERY")]
+    ReadOnlyQuery = 107,
+    ///Queries create or drop an index or constraint, which needs a `SchemaChange`
+    #[serde(rename = "SCHEMA_IN_QUERIES")]
+    SchemaInQueries = 108,
+    ///Queries cannot be tokenized
+    #[serde(rename = "INVALID_QUERY")]
+    InvalidQuery = 109,
+}
+
+impl FailureCode {
+    ///Code from its number as stored in `TransactionFailure.code`
+    pub fn from_u8(value: u8) -> Option<Self> {
+        match value {
+            0 => Some(FailureCode::EmptyTransaction),
+            1 => Some(FailureCode::EmptyUuidPrefix),
+            2 => Some(FailureCode::FailedQuery),
+            3 => Some(FailureCode::ModifiedUuid),
+            4 => Some(FailureCode::TransactionRollback),
+            5 => Some(FailureCode::RuntimeException),
+            6 => Some(FailureCode::ConstraintViolation),
+            100 => Some(FailureCode::NotAuthorized),
+            101 => Some(FailureCode::PolicyViolation),
+            102 => Some(FailureCode::PreconditionFailed),
+            103 => Some(FailureCode::TooManyStatements),
+            104 => Some(FailureCode::QueryTooLarge),
+            105 => Some(FailureCode::RateLimited),
+            106 => Some(FailureCode::NonDeterministicQuery),
+            107 => Some(FailureCode::ReadOnlyQuery),
+            108 => Some(FailureCode::SchemaInQueries),
+            109 => Some(FailureCode::InvalidQuery),
+            _ => None,
+        }
+    }
+}
+
+impl From<ErrorCode> for FailureCode {
+    fn from(code: ErrorCode) -> Self {
+        match code {
+            ErrorCode::EMPTY_TRANSACTION => FailureCode::EmptyTransaction,
+            ErrorCode::EMPTY_UUID_PREFIX => FailureCode::EmptyUuidPrefix,
+            ErrorCode::FAILED_QUERY => FailureCode::FailedQuery,
+            ErrorCode::MODIFIED_UUID => FailureCode::ModifiedUuid,
+            ErrorCode::TRANSACTION_ROLLBACK => FailureCode::TransactionRollback,
+            ErrorCode::RUNTIME_EXCEPTION => FailureCode::RuntimeException,
+            ErrorCode::CONSTRAINT_VIOLATION => FailureCode::ConstraintViolation,
+        }
+    }
+}
+
+///Details of why Neo4j failed to execute a transaction
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TransactionFailure {
+    code: u8,
+    statement_index: u32,
+    query: String,
+    query_error: String,
+    neo4j_code: String,
+}
+
+impl TransactionFailure {
+    ///Creates a new value from its fields.
+    pub fn new(
+        code: u8,
+        statement_index: u32,
+        query: &str,
+        query_error: &str,
+        neo4j_code: &str,
+    ) -> Self {
+        TransactionFailure {
+            code,
+            statement_index,
+            query: query.to_string(),
+            query_error: query_error.to_string(),
+            neo4j_code: neo4j_code.to_string(),
+        }
+    }
+
+    ///number of a `FailureCode`
+    pub fn code(&self) -> u8 {
+        self.code
+    }
+
+    ///index of the failed statement, only meaningful with a query
+    pub fn statement_index(&self) -> u32 {
+        self.statement_index
+    }
+
+    ///failed statement, empty if no single statement failed
+    pub fn query(&self) -> &str {
+        &self.query
+    }
+
+    ///error Neo4j gave for the failed statement
+    pub fn query_error(&self) -> &str {
+        &self.query_error
+    }
+
+    ///Neo4j status code of the failed statement, e.g. `Neo.ClientError.Statement.SyntaxError`
+    pub fn neo4j_code(&self) -> &str {
+        &self.neo4j_code
+    }
+
+    ///Reads the failure reported by the transaction manager.
+    pub fn from_proto(error: &Error) -> Self {
+        let failed_query = error.get_failed_query();
+        TransactionFailure::new(
+            FailureCode::from(error.get_code()) as u8,
+            failed_query.get_statement_index(),
+            failed_query.get_query(),
+            failed_query.get_error(),
+            failed_query.get_error_code(),
+        )
+    }
+
+    ///Parsed code, none if the stored number is unknown.
+    pub fn failure_code(&self) -> Option<FailureCode> {
+        FailureCode::from_u8(self.code())
+    }
+}
+
+impl ProtobufConvert for TransactionFailure {
+    type ProtoStruct = pb::TransactionFailure;
+
+    fn to_pb(&self) -> pb::TransactionFailure {
+        let mut message = pb::TransactionFailure::new();
+        message.code = u32::from(self.code);
+        message.statement_index = self.statement_index;
+        message.query = self.query.clone();
+        message.query_error = self.query_error.clone();
+        message.neo4j_code = self.neo4j_code.clone();
+        message
+    }
+
+    fn from_pb(message: pb::TransactionFailure) -> Result<Self, ::failure::Error> {
+        Ok(TransactionFailure {
+            code: u8_from_pb(message.code)?,
+            statement_index: message.statement_index,
+            query: message.query,
+            query_error: message.query_error,
+            neo4j_code: message.neo4j_code,
+        })
+    }
+}
+
+impl LegacyDecode for TransactionFailure {
+    fn check_legacy(bytes: &[u8]) -> Result<(), String> {
+        use self::LegacyField::{Fixed, Str};
+        check_legacy_layout(bytes, &[Fixed(1), Fixed(4), Str, Str, Str])
+    }
+
+    fn from_legacy(bytes: &[u8]) -> Self {
+        let value = <legacy::TransactionFailure as StorageValue>::from_bytes(Cow::Borrowed(bytes));
+        TransactionFailure::new(
+            value.code(),
+            value.statement_index(),
+            value.query(),
+            value.query_error(),
+            value.neo4j_code(),
+        )
+    }
+}
+
+impl_storage_value!(TransactionFailure);
+
+///State of a proposal. Only approval is stored, expiry follows from the current height.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ProposalStatus {
+    ///Waiting for approvals
+    #[serde(rename = "OPEN")]
+    Open,
+    ///Reached its threshold, its queries were sent to Neo4j
+    #[serde(rename = "APPROVED")]
+    Approved,
+    ///Did not reach its threshold in time
+    #[serde(rename = "EXPIRED")]
+    Expired,
+}
+
+///Set of queries that is only sent to Neo4j once enough authors approved it
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Proposal {
+    queries: String,
+    pub_key: PublicKey,
+    height: u64,
+    expires_at: u64,
+    threshold: u32,
+    approved: bool,
+}
+
+impl Proposal {
+    ///Creates a new value from its fields.
+    pub fn new(
+        queries: &str,
+        pub_key: &PublicKey,
+        height: u64,
+        expires_at: u64,
+        threshold: u32,
+        approved: bool,
+    ) -> Self {
+        Proposal {
+            queries: queries.to_string(),
+            pub_key: *pub_key,
+            height,
+            expires_at,
+            threshold,
+            approved,
+        }
+    }
+
+    ///queries of the proposal, separated by `;`
+    pub fn queries(&self) -> &str {
+        &self.queries
+    }
+
+    ///key of the proposing author
+    pub fn pub_key(&self) -> &PublicKey {
+        &self.pub_key
+    }
+
+    ///height of the block the proposal was made in
+    pub fn height(&self) -> u64 {
+        self.height
+    }
+
+    ///first height at which approvals are no longer accepted
+    pub fn expires_at(&self) -> u64 {
+        self.expires_at
+    }
+
+    ///amount of distinct approvers needed, as configured when the proposal was made
+    pub fn threshold(&self) -> u32 {
+        self.threshold
+    }
+
+    ///whether the threshold was reached
+    pub fn approved(&self) -> bool {
+        self.approved
+    }
+
+    ///Status of the proposal at the given height.
+    pub fn status(&self, height: Height) -> ProposalStatus {
+        if self.approved {
+            ProposalStatus::Approved
+        } else if height.0 >= self.expires_at {
+            ProposalStatus::Expired
+        } else {
+            ProposalStatus::Open
+        }
+    }
+}
+
+impl ProtobufConvert for Proposal {
+    type ProtoStruct = pb::Proposal;
+
+    fn to_pb(&self) -> pb::Proposal {
+        let mut message = pb::Proposal::new();
+        message.queries = self.queries.clone();
+        message.pub_key = self.pub_key.as_ref().to_vec();
+        message.height = self.height;
+        message.expires_at = self.expires_at;
+        message.threshold = self.threshold;
+        message.approved = self.approved;
+        message
+    }
+
+    fn from_pb(message: pb::Proposal) -> Result<Self, ::failure::Error> {
+        let pub_key = PublicKey::from_slice(&message.pub_key)
+            .ok_or_else(|| format_err!("Wrong public key length: {}", message.pub_key.len()))?;
+        Ok(Proposal {
+            queries: message.queries,
+            pub_key,
+            height: message.height,
+            expires_at: message.expires_at,
+            threshold: message.threshold,
+            approved: message.approved,
+        })
+    }
+}
+
+impl_storage_value!(Proposal, protobuf_only);
+
+///Activation of a scheduled set of queries, whose queries are stored as its neo4j transaction
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ScheduledBatch {
+    pub_key: PublicKey,
+    height: u64,
+    activation_height: u64,
+    activation_time: u64,
+}
+
+impl ScheduledBatch {
+    ///Creates a new value from its fields.
+    pub fn new(
+        pub_key: &PublicKey,
+        height: u64,
+        activation_height: u64,
+        activation_time: u64,
+    ) -> Self {
+        ScheduledBatch {
+            pub_key: *pub_key,
+            height,
+            activation_height,
+            activation_time,
+        }
+    }
+
+    ///key of the scheduling author
+    pub fn pub_key(&self) -> &PublicKey {
+        &self.pub_key
+    }
+
+    ///height of the block the batch was scheduled in
+    pub fn height(&self) -> u64 {
+        self.height
+    }
+
+    ///first height at which the batch may be sent to Neo4j, zero if not limited by height
+    pub fn activation_height(&self) -> u64 {
+        self.activation_height
+    }
+
+    ///first time, in seconds since the epoch, at which the batch may be sent to Neo4j, zero if
+    /// not limited by time
+    pub fn activation_time(&self) -> u64 {
+        self.activation_time
+    }
+
+    ///Whether the batch may be sent with the block at the given height. A time limited batch is
+    /// never due while the validators did not agree on a time.
+    pub fn is_due(&self, height: Height, time: Option<u64>) -> bool {
+        let height_reached = height.0 >= self.activation_height;
+        let time_reached =
+            self.activation_time == 0 || time.map_or(false, |time| time >= self.activation_time);
+        height_reached && time_reached
+    }
+}
+
+impl ProtobufConvert for ScheduledBatch {
+    type ProtoStruct = pb::ScheduledBatch;
+
+    fn to_pb(&self) -> pb::ScheduledBatch {
+        let mut message = pb::ScheduledBatch::new();
+        message.pub_key = self.pub_key.as_ref().to_vec();
+        message.height = self.height;
+        message.activation_height = self.activation_height;
+        message.activation_time = self.activation_time;
+        message
+    }
+
+    fn from_pb(message: pb::ScheduledBatch) -> Result<Self, ::failure::Error> {
+        let pub_key = PublicKey::from_slice(&message.pub_key)
+            .ok_or_else(|| format_err!("Wrong public key length: {}", message.pub_key.len()))?;
+        Ok(ScheduledBatch {
+            pub_key,
+            height: message.height,
+            activation_height: message.activation_height,
+            activation_time: message.activation_time,
+        })
+    }
+}
+
+impl_storage_value!(ScheduledBatch, protobuf_only);
+
+///Kind of a database schema object
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[repr(u8)]
+pub enum SchemaObjectKind {
+    ///Index on a label and property
+    #[serde(rename = "INDEX")]
+    Index = 0,
+    ///Constraint on nodes with a label
+    #[serde(rename = "CONSTRAINT")]
+    Constraint = 1,
+}
+
+///Whether a schema change creates or drops its object
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[repr(u8)]
+pub enum SchemaOperation {
+    ///Creates the object
+    #[serde(rename = "CREATE")]
+    Create = 0,
+    ///Drops the object
+    #[serde(rename = "DROP")]
+    Drop = 1,
+}
+
+///Collapses the whitespace of a schema object definition, so that equal definitions share a key.
+pub fn normalize_definition(definition: &str) -> String {
+    definition
+        .split_whitespace()
+        .collect::<Vec<&str>>()
+        .join(" ")
+}
+
+///Key of a schema object in the registry, the hash of its normalized definition.
+pub fn definition_hash(definition: &str) -> Hash {
+    hash(normalize_definition(definition).as_bytes())
+}
+
+///Index or constraint statement of a `SchemaChange` transaction
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SchemaChangeRecord {
+    operation: SchemaOperation,
+    kind: SchemaObjectKind,
+    definition: String,
+    pub_key: PublicKey,
+}
+
+impl SchemaChangeRecord {
+    ///Creates a new value from its fields.
+    pub fn new(
+        operation: SchemaOperation,
+        kind: SchemaObjectKind,
+        definition: &str,
+        pub_key: &PublicKey,
+    ) -> Self {
+        SchemaChangeRecord {
+            operation,
+            kind,
+            definition: normalize_definition(definition),
+            pub_key: *pub_key,
+        }
+    }
+
+    ///Parses a single `CREATE` or `DROP` statement of an index or constraint, such as
+    /// `CREATE INDEX ON :Person(name)`. The definition is the statement without its operation.
+    pub fn parse(statement: &str, pub_key: &PublicKey) -> Result<Self, String> {
+        if statement.contains(';') {
+            return Err(String::from("A schema change holds a single statement"));
+        }
+        let mut words = statement.split_whitespace();
+        let operation = match words.next().map(|w| w.to_uppercase()) {
+            Some(ref w) if w == "CREATE" => SchemaOperation::Create,
+            Some(ref w) if w == "DROP" => SchemaOperation::Drop,
+            _ => return Err(String::from("A schema change starts with CREATE or DROP")),
+        };
+        let kind = match words.next().map(|w| w.to_uppercase()) {
+            Some(ref w) if w == "INDEX" => SchemaObjectKind::Index,
+            Some(ref w) if w == "CONSTRAINT" => SchemaObjectKind::Constraint,
+            _ => {
+                return Err(String::from(
+                    "A schema change creates or drops an INDEX or CONSTRAINT",
+                ))
+            }
+        };
+        let rest: Vec<&str> = words.collect();
+        if rest.is_empty() {
+            return Err(String::from("A schema change needs a definition"));
+        }
+        let keyword = match kind {
+            SchemaObjectKind::Index => "INDEX",
+            SchemaObjectKind::Constraint => "CONSTRAINT",
+        };
+        let definition = format!("{} {}", keyword, rest.join(" "));
+        Ok(SchemaChangeRecord::new(
+            operation,
+            kind,
+            &definition,
+            pub_key,
+        ))
+    }
+
+    ///whether the object is created or dropped
+    pub fn operation(&self) -> SchemaOperation {
+        self.operation
+    }
+
+    ///kind of the object
+    pub fn kind(&self) -> SchemaObjectKind {
+        self.kind
+    }
+
+    ///normalized definition of the object, like `INDEX ON :Person(name)`
+    pub fn definition(&self) -> &str {
+        &self.definition
+    }
+
+    ///key of the author
+    pub fn pub_key(&self) -> &PublicKey {
+        &self.pub_key
+    }
+
+    ///Statement sent to Neo4j.
+    pub fn statement(&self) -> String {
+        let operation = match self.operation {
+            SchemaOperation::Create => "CREATE",
+            SchemaOperation::Drop => "DROP",
+        };
+        format!("{} {}", operation, self.definition)
+    }
+}
+
+fn schema_kind_from_pb(value: u32) -> Result<SchemaObjectKind, ::failure::Error> {
+    match value {
+        0 => Ok(SchemaObjectKind::Index),
+        1 => Ok(SchemaObjectKind::Constraint),
+        _ => Err(format_err!("Unknown schema object kind: {}", value)),
+    }
+}
+
+impl ProtobufConvert for SchemaChangeRecord {
+    type ProtoStruct = pb::SchemaChangeRecord;
+
+    fn to_pb(&self) -> pb::SchemaChangeRecord {
+        let mut message = pb::SchemaChangeRecord::new();
+        message.operation = self.operation as u32;
+        message.kind = self.kind as u32;
+        message.definition = self.definition.clone();
+        message.pub_key = self.pub_key.as_ref().to_vec();
+        message
+    }
+
+    fn from_pb(message: pb::SchemaChangeRecord) -> Result<Self, ::failure::Error> {
+        let operation = match message.operation {
+            0 => SchemaOperation::Create,
+            1 => SchemaOperation::Drop,
+            value => bail!("Unknown schema operation: {}", value),
+        };
+        let pub_key = PublicKey::from_slice(&message.pub_key)
+            .ok_or_else(|| format_err!("Wrong public key length: {}", message.pub_key.len()))?;
+        Ok(SchemaChangeRecord {
+            operation,
+            kind: schema_kind_from_pb(message.kind)?,
+            definition: message.definition,
+            pub_key,
+        })
+    }
+}
+
+impl_storage_value!(SchemaChangeRecord, protobuf_only);
+
+///Index or constraint that is active in Neo4j
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SchemaObject {
+    kind: SchemaObjectKind,
+    definition: String,
+    transaction: Hash,
+    height: u64,
+}
+
+impl SchemaObject {
+    ///Creates a new value from its fields.
+    pub fn new(kind: SchemaObjectKind, definition: &str, transaction: &Hash, height: u64) -> Self {
+        SchemaObject {
+            kind,
+            definition: definition.to_string(),
+            transaction: *transaction,
+            height,
+        }
+    }
+
+    ///kind of the object
+    pub fn kind(&self) -> SchemaObjectKind {
+        self.kind
+    }
+
+    ///normalized definition of the object
+    pub fn definition(&self) -> &str {
+        &self.definition
+    }
+
+    ///hash of the `SchemaChange` transaction that created the object
+    pub fn transaction(&self) -> &Hash {
+        &self.transaction
+    }
+
+    ///height of the audit that confirmed the creation
+    pub fn height(&self) -> u64 {
+        self.height
+    }
+}
+
+impl ProtobufConvert for SchemaObject {
+    type ProtoStruct = pb::SchemaObject;
+
+    fn to_pb(&self) -> pb::SchemaObject {
+        let mut message = pb::SchemaObject::new();
+        message.kind = self.kind as u32;
+        message.definition = self.definition.clone();
+        message.transaction = self.transaction.as_ref().to_vec();
+        message.height = self.height;
+        message
+    }
+
+    fn from_pb(message: pb::SchemaObject) -> Result<Self, ::failure::Error> {
+        let transaction = Hash::from_slice(&message.transaction)
+            .ok_or_else(|| format_err!("Wrong hash length: {}", message.transaction.len()))?;
+        Ok(SchemaObject {
+            kind: schema_kind_from_pb(message.kind)?,
+            definition: message.definition,
+            transaction,
+            height: message.height,
+        })
+    }
+}
+
+impl_storage_value!(SchemaObject, protobuf_only);
+
+///What a statement does to the database
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[repr(u8)]
+pub enum StatementClass {
+    ///Only reads the graph
+    #[serde(rename = "READ_ONLY")]
+    ReadOnly = 0,
+    ///Creates, changes or deletes nodes and relations
+    #[serde(rename = "DATA_WRITE")]
+    DataWrite = 1,
+    ///Creates or drops an index or constraint
+    #[serde(rename = "SCHEMA_WRITE")]
+    SchemaWrite = 2,
+}
+
+fn statement_class_from_pb(value: u32) -> Result<StatementClass, ::failure::Error> {
+    match value {
+        0 => Ok(StatementClass::ReadOnly),
+        1 => Ok(StatementClass::DataWrite),
+        2 => Ok(StatementClass::SchemaWrite),
+        _ => Err(format_err!("Unknown statement class: {}", value)),
+    }
+}
+
+///Classes of the statements of a transaction, in the order of the statements
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct StatementClassification {
+    classes: Vec<StatementClass>,
+}
+
+impl StatementClassification {
+    ///Creates a new value from its fields.
+    pub fn new(classes: Vec<StatementClass>) -> Self {
+        StatementClassification { classes }
+    }
+
+    ///class of each non-empty statement
+    pub fn classes(&self) -> &[StatementClass] {
+        &self.classes
+    }
+
+    ///Whether no statement writes to the database.
+    pub fn is_read_only(&self) -> bool {
+        self.classes.iter().all(|c| *c == StatementClass::ReadOnly)
+    }
+
+    ///Whether any statement creates or drops an index or constraint.
+    pub fn has_schema_writes(&self) -> bool {
+        self.classes.contains(&StatementClass::SchemaWrite)
+    }
+}
+
+impl ProtobufConvert for StatementClassification {
+    type ProtoStruct = pb::StatementClassification;
+
+    fn to_pb(&self) -> pb::StatementClassification {
+        let mut message = pb::StatementClassification::new();
+        message.classes = self.classes.iter().map(|c| *c as u32).collect();
+        message
+    }
+
+    fn from_pb(message: pb::StatementClassification) -> Result<Self, ::failure::Error> {
+        let classes = message
+            .classes
+            .into_iter()
+            .map(statement_class_from_pb)
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(StatementClassification { classes })
+    }
+}
+
+impl_storage_value!(StatementClassification, protobuf_only);
+
+///Storage types as they were encoded before the migration to protobuf, only used to read existing values.
+mod legacy {
+    use exonum::crypto::PublicKey;
+
+    encoding_struct! {
+        ///add node
+        struct AddNode {
+            ///node uuid, made of transaction hash for prefix and index.
+            node_uuid: &str,
+            ///hash value for the the transaction it is part of
+            transaction_id: &str,
+        }
+    }
+
+    encoding_struct! {
+        ///remove node
+        struct RemoveNode {
+            ///node uuid
+            node_uuid: &str,
+            ///hash value for the the transaction it is part of
+            transaction_id: &str,
+        }
+    }
+
+    encoding_struct! {
+        ///add relation
+        struct AddRelation {
+            ///relation uuid, needed since we add to our own database
+            rel_uuid: &str,
+            ///relationship type
+            field_type: &str,
+            ///from node uuid
+            from_uuid: &str,
+            ///to node uuid
+            to_uuid: &str,
+            ///hash value for the the transaction it is part of
+            transaction_id: &str,
+        }
+    }
+
+    encoding_struct! {
+        ///add relation
+        struct RemoveRelation {
+            ///relation uuid, needed since we add to our own database
+            rel_uuid: &str,
+            ///from node uuid
+            from_uuid: &str,
+            ///to node uuid
+            to_uuid: &str,
+            ///hash value for the the transaction it is part of
+            transaction_id: &str,
+        }
+    }
+
+    encoding_struct! {
+        ///add label
+        struct AddLabel {
+            ///node uuid
+            node_uuid: &str,
+            ///label name
+            label_name: &str,
+            ///hash value for the the transaction it is part of
+            transaction_id: &str,
+        }
+    }
+
+    encoding_struct! {
+        ///Remove label
+        struct RemoveLabel {
+            ///Node uuid
+            node_uuid: &str,
+            ///label name
+            label_name: &str,
+            ///hash value for the the transaction it is part of
+            transaction_id: &str,
+        }
+    }
+
+    encoding_struct! {
+        ///Add new property
+        struct AddNodeProperty {
+            ///Node uuid
+            node_uuid: &str,
+            ///property key
+            key: &str,
+            ///property new value
+            value: &str,
+            ///hash value for the the transaction it is part of
+            transaction_id: &str,
+        }
+    }
+
+    encoding_struct! {
+        ///remove node property
+        struct RemoveNodeProperty {
+            ///Node uuid
+            node_uuid: &str,
+            ///property key
+            key: &str,
+            ///hash value for the the transaction it is part of
+            transaction_id: &str,
+        }
+    }
+
+    encoding_struct! {
+        ///Add relation property
+        struct AddRelationProperty {
+            ///relation's uuid
+            relation_uuid: &str,
+            ///property key
+            key: &str,
+            ///property new value
+            value: &str,
+            ///from node uuid
+            from_uuid: &str,
+            ///to node uuid
+            to_uuid: &str,
+            ///hash value for the the transaction it is part of
+            transaction_id: &str,
+        }
+    }
+
+    encoding_struct! {
+        ///Remove relation property
+        struct RemoveRelationProperty {
+            ///relation's uuid
+            relation_uuid: &str,
+            ///property key
+            key: &str,
+            ///from node uuid
+            from_uuid: &str,
+            ///to node uuid
+            to_uuid: &str,
+            ///hash value for the the transaction it is part of
+            transaction_id: &str,
+        }
+    }
+
+    encoding_struct! {
+        ///Error msg
+        struct ErrorMsg {
+            ///message itself.
+            msg: &str,
+        }
+    }
+
+    encoding_struct! {
+        ///Relation struct
+        struct Relation {
+            ///Start node
+            start_node_uuid: &str,
+            ///End node
+            end_node_uuid: &str
+        }
+    }
+
+    encoding_struct! {
+        ///Queries struct
+        struct Neo4jTransaction {
+            ///queries themselves
+            queries: &str,
+            ///error from the database if any.
+            error_msg: &str,
+            ///Result, name of a `TransactionStatus`
+            result: &str,
+            ///Public key of the transaction initiator
+            pub_key: &PublicKey
+        }
+    }
+
+    encoding_struct! {
+        ///Status transition of a neo4j transaction
+        struct StatusTransition {
+            ///encoding version, `STATUS_TRANSITION_VERSION` when written
+            version: u8,
+            ///new status, number of a `TransactionStatus`
+            status: u8,
+            ///height of the block in which the transition happened
+            height: u64,
+        }
+    }
+
+    encoding_struct! {
+        ///Details of why Neo4j failed to execute a transaction
+        struct TransactionFailure {
+            ///number of a `FailureCode`
+            code: u8,
+            ///index of the failed statement, only meaningful with a query
+            statement_index: u32,
+            ///failed statement, empty if no single statement failed
+            query: &str,
+            ///error Neo4j gave for the failed statement
+            query_error: &str,
+            ///Neo4j status code of the failed statement, e.g. `Neo.ClientError.Statement.SyntaxError`
+            neo4j_code: &str,
+        }
     }
 }
@@ -2,19 +2,28 @@
 #![allow(warnings)]
 /// Transaction documentation
 use exonum::{
-    blockchain::{ExecutionResult, Schema as CoreSchema, Transaction},
-    crypto::{CryptoHash, Hash, PublicKey},
+    blockchain::{ExecutionError, ExecutionResult, Schema as CoreSchema, Transaction},
+    crypto::{hash, CryptoHash, Hash, PublicKey},
     encoding::serialize::FromHex,
     helpers::Height,
     messages::Message,
-    storage::Fork,
+    storage::{Fork, Snapshot},
 };
 
-use neo4j::proto::transaction_manager::{BlockChangesResponse, Status};
-use neo4j::ExecuteResponse::{ChangeResponse, Error as DBError};
-use neo4j::{generate_database_changes_from_proto, get_neo4j_rpc_client, ExecuteResponse};
+use compensation;
+use config::{Role, ServiceConfig};
+use cypher;
+use limits;
+use migrations::{self, MigrationError};
+use neo4j::generate_database_changes_from_proto;
+use neo4j::proto::transaction_manager::{AuditedChanges, BlockChangesResponse, Status};
+use policy;
+use protobuf::{self, Message as ProtobufMessage};
 use schema::Schema;
-use structures::{ErrorMsg, Neo4jTransaction, NodeChange};
+use structures::{
+    definition_hash, ErrorMsg, FailureCode, Neo4jTransaction, NodeChange, Proposal, ProposalStatus,
+    ScheduledBatch, SchemaChangeRecord, SchemaOperation, TransactionFailure, TransactionStatus,
+};
 
 use NEO4J_SERVICE_ID;
 
@@ -22,6 +31,18 @@ use grpc::RequestOptions;
 
 //use std::io::{self, Write};
 
+encoding_struct! {
+    ///Expected state of a node history, for `CommitQueriesWithPreconditions`.
+    struct HistoryPrecondition {
+        ///Node uuid
+        node_uuid: &str,
+        ///Expected amount of changes in the node history
+        history_len: u64,
+        ///Expected merkle root of the node history, not checked when zero
+        history_hash: &Hash,
+    }
+}
+
 ///Transaction groups
 transactions! {
     /// Our neo4j Transaction group.
@@ -37,9 +58,104 @@ transactions! {
             pub_key: &PublicKey
         }
 
-        ///Retrieves all changes from Neo4j that are supposed to be executed.
+        ///Records the changes of the blocks awaiting audit, as a validator retrieved them from
+        /// its Neo4j. Signed by a validator.
         struct AuditBlocks {
+            ///Block whose execution by Neo4j led to the audit
             block_id: &str,
+            ///Protobuf encoded `AuditedChanges`
+            changes: &[u8],
+            ///Service key of the validator
+            pub_key: &PublicKey,
+        }
+
+        ///Votes for migrating the `neo4j.*` indexes to a newer schema version. Signed by a
+        /// validator, the migration runs with the vote of more than two thirds of them.
+        struct MigrateSchema {
+            ///Schema version to migrate to
+            target_version: u32,
+            ///Height agreed upon by the validators, before which the migration is refused
+            activation_height: u64,
+            ///Service key of the validator
+            pub_key: &PublicKey,
+        }
+
+        ///Proposes a set of queries, sent to neo4j once enough authors approved it
+        struct ProposeQueries {
+            ///Queries of the proposal
+            queries: &str,
+            ///Date and time, to separate same proposals
+            datetime: &str,
+            ///Pub key of the proposing author
+            pub_key: &PublicKey
+        }
+
+        ///Approves a proposed set of queries
+        struct ApproveQueries {
+            ///Hash of the `ProposeQueries` transaction
+            proposal: &Hash,
+            ///Pub key of the approving author
+            pub_key: &PublicKey
+        }
+
+        ///Reverts the audited changes of an earlier transaction
+        struct RevertTransaction {
+            ///Hash of the transaction to revert
+            transaction: &Hash,
+            ///Pub key of the author, an admin or the author of the reverted transaction
+            pub_key: &PublicKey
+        }
+
+        ///Commits a set of queries, unless one of the node histories has changed since
+        struct CommitQueriesWithPreconditions {
+            ///Queries for the transaction
+            queries: &str,
+            ///Date and time, to separate same queries
+            datetime: &str,
+            ///Node histories the queries were written against
+            preconditions: Vec<HistoryPrecondition>,
+            ///Pub key
+            pub_key: &PublicKey
+        }
+
+        ///Schedules a set of queries, sent to neo4j once its activation height and time are reached
+        struct ScheduleQueries {
+            ///Queries of the batch
+            queries: &str,
+            ///Date and time, to separate same batches
+            datetime: &str,
+            ///First height at which the batch is sent, zero if not limited by height
+            activation_height: u64,
+            ///First time in seconds since the epoch at which the batch is sent, zero if not limited by time
+            activation_time: u64,
+            ///Pub key of the scheduling author
+            pub_key: &PublicKey
+        }
+
+        ///Cancels a scheduled batch before its activation
+        struct CancelScheduled {
+            ///Hash of the `ScheduleQueries` transaction
+            batch: &Hash,
+            ///Pub key of the author, an admin or the author of the batch
+            pub_key: &PublicKey
+        }
+
+        ///Sends a scheduled batch that is due to neo4j. Signed by a validator.
+        struct ActivateScheduled {
+            ///Hash of the `ScheduleQueries` transaction
+            batch: &Hash,
+            ///Service key of the validator
+            pub_key: &PublicKey
+        }
+
+        ///Creates or drops an index or constraint, executed by neo4j in its own transaction
+        struct SchemaChange {
+            ///Single `CREATE` or `DROP` statement of an index or constraint
+            statement: &str,
+            ///Date and time, to separate same changes
+            datetime: &str,
+            ///Pub key of the author
+            pub_key: &PublicKey
         }
     }
 }
@@ -54,165 +170,1047 @@ pub enum Error {
     ///Database error
     #[fail(display = "Possible connection error with database")]
     PossibleConnectionError(ErrorMsg),
+    ///Transaction must be signed by a validator
+    #[fail(display = "Signer is not a validator")]
+    NotValidator,
+    ///Migration was sent before its activation height
+    #[fail(display = "Migration is not active before height {}", _0)]
+    MigrationNotActive(u64),
+    ///Migration could not run
+    #[fail(display = "Migration failed: {}", _0)]
+    MigrationFailed(MigrationError),
+    ///Author lacks the role the transaction needs
+    #[fail(display = "Author is not authorized")]
+    NotAuthorized,
+    ///No proposal with the given hash
+    #[fail(display = "Proposal not found")]
+    ProposalNotFound,
+    ///Proposal no longer accepts approvals
+    #[fail(display = "Proposal expired at height {}", _0)]
+    ProposalExpired(u64),
+    ///Proposal already reached its threshold
+    #[fail(display = "Proposal is already approved")]
+    ProposalApproved,
+    ///Author already approved the proposal
+    #[fail(display = "Author already approved the proposal")]
+    DuplicateApproval,
+    ///No neo4j transaction with the given hash
+    #[fail(display = "Transaction not found")]
+    TransactionNotFound,
+    ///Transaction cannot be reverted
+    #[fail(display = "Transaction cannot be reverted: {}", _0)]
+    NotRevertible(String),
+    ///Transaction was already reverted
+    #[fail(display = "Transaction is already reverted by {}", _0)]
+    AlreadyReverted(Hash),
+    ///A later transaction changed a node the reverted transaction changed
+    #[fail(display = "Node {} was changed by a later transaction", _0)]
+    ChangedLater(String),
+    ///No batch with the given hash is waiting for activation
+    #[fail(display = "Scheduled batch not found")]
+    ScheduleNotFound,
+    ///Batch is not due yet
+    #[fail(
+        display = "Scheduled batch is not due before height {} and time {}",
+        _0, _1
+    )]
+    ScheduleNotDue(u64, u64),
+    ///Schema change does not fit the registry of indexes and constraints
+    #[fail(display = "Invalid schema change: {}", _0)]
+    InvalidSchemaChange(String),
+    ///Transaction has more statements than configured
+    #[fail(display = "{}", _0)]
+    TooManyStatements(String),
+    ///Queries of the transaction are larger than configured
+    #[fail(display = "{}", _0)]
+    QueryTooLarge(String),
+    ///Author submitted too many transactions recently
+    #[fail(display = "{}", _0)]
+    RateLimited(String),
+    ///Queries call a function or procedure of the deny-list
+    #[fail(display = "{}", _0)]
+    NonDeterministicQuery(String),
+    ///Queries only read the graph
+    #[fail(display = "{}", _0)]
+    ReadOnlyQuery(String),
+    ///Queries create or drop an index or constraint
+    #[fail(display = "{}", _0)]
+    SchemaInQueries(String),
+    ///Audit carries changes that cannot be recorded
+    #[fail(display = "Invalid audit: {}", _0)]
+    InvalidAudit(String),
+    ///Validator already voted for the migration
+    #[fail(display = "Validator already voted for the migration")]
+    DuplicateVote,
+    ///Queries cannot be tokenized
+    #[fail(display = "{}", _0)]
+    InvalidQuery(String),
+    ///Queries touch labels or relationship types the author may not write
+    #[fail(display = "{}", _0)]
+    PolicyViolation(String),
+    ///A node history changed since the transaction was made
+    #[fail(display = "{}", _0)]
+    PreconditionFailed(String),
+    ///Author of the proposal approved it
+    #[fail(display = "Proposals cannot be approved by their author")]
+    SelfApproval,
+}
+
+impl Error {
+    ///Code of the error in the execution result.
+    pub fn code(&self) -> u8 {
+        match self {
+            Error::DataBaseError(_) => 0,
+            Error::PossibleConnectionError(_) => 1,
+            Error::NotValidator => 2,
+            Error::MigrationNotActive(_) => 3,
+            Error::MigrationFailed(_) => 4,
+            Error::NotAuthorized => 5,
+            Error::ProposalNotFound => 6,
+            Error::ProposalExpired(_) => 7,
+            Error::ProposalApproved => 8,
+            Error::DuplicateApproval => 9,
+            Error::TransactionNotFound => 10,
+            Error::NotRevertible(_) => 11,
+            Error::AlreadyReverted(_) => 12,
+            Error::ChangedLater(_) => 13,
+            Error::ScheduleNotFound => 14,
+            Error::ScheduleNotDue(_, _) => 15,
+            Error::InvalidSchemaChange(_) => 16,
+            Error::TooManyStatements(_) => 17,
+            Error::QueryTooLarge(_) => 18,
+            Error::RateLimited(_) => 19,
+            Error::NonDeterministicQuery(_) => 20,
+            Error::ReadOnlyQuery(_) => 21,
+            Error::SchemaInQueries(_) => 22,
+            Error::InvalidAudit(_) => 23,
+            Error::DuplicateVote => 24,
+            Error::InvalidQuery(_) => 25,
+            Error::PolicyViolation(_) => 26,
+            Error::PreconditionFailed(_) => 27,
+            Error::SelfApproval => 28,
+        }
+    }
+
+    ///Error for a transaction refused with the given failure code, for transactions that fail
+    /// instead of being recorded as rejected.
+    pub fn from_failure((code, description): (FailureCode, String)) -> Self {
+        match code {
+            FailureCode::EmptyTransaction
+            | FailureCode::EmptyUuidPrefix
+            | FailureCode::FailedQuery
+            | FailureCode::ModifiedUuid
+            | FailureCode::TransactionRollback
+            | FailureCode::RuntimeException
+            | FailureCode::ConstraintViolation => Error::DataBaseError(ErrorMsg::new(&description)),
+            FailureCode::NotAuthorized => Error::NotAuthorized,
+            FailureCode::PolicyViolation => Error::PolicyViolation(description),
+            FailureCode::PreconditionFailed => Error::PreconditionFailed(description),
+            FailureCode::TooManyStatements => Error::TooManyStatements(description),
+            FailureCode::QueryTooLarge => Error::QueryTooLarge(description),
+            FailureCode::RateLimited => Error::RateLimited(description),
+            FailureCode::NonDeterministicQuery => Error::NonDeterministicQuery(description),
+            FailureCode::ReadOnlyQuery => Error::ReadOnlyQuery(description),
+            FailureCode::SchemaInQueries => Error::SchemaInQueries(description),
+            FailureCode::InvalidQuery => Error::InvalidQuery(description),
+        }
+    }
+}
+
+impl From<Error> for ExecutionError {
+    fn from(value: Error) -> ExecutionError {
+        let description = format!("{}", value);
+        ExecutionError::with_description(value.code(), description)
+    }
+}
+
+///Height of the block that is currently being executed.
+pub fn current_height(fork: &Fork) -> Height {
+    Height(CoreSchema::new(fork).block_hashes_by_height().len())
+}
+
+///Whether the key is the service key of one of the validators.
+fn is_validator(fork: &Fork, pub_key: &PublicKey) -> bool {
+    CoreSchema::new(fork)
+        .actual_configuration()
+        .validator_keys
+        .iter()
+        .any(|keys| keys.service_key == *pub_key)
 }
 
 impl AuditBlocks {
-    pub fn retrieve_changes_from_neo4j(&self, fork: &Fork) -> Vec<BlockChangesResponse> {
-        let schema: Schema<&Fork> = Schema::new(fork);
-        let core_schema: CoreSchema<&Fork> = CoreSchema::new(&fork);
-        let all_blocks_by_height = core_schema.block_hashes_by_height();
-        let all_blocks = core_schema.blocks();
-
-        let mut returnVector: Vec<BlockChangesResponse> = Vec::new();
-
-        let neo4j_rpc = get_neo4j_rpc_client();
-
-        let last_block_option = schema.get_last_confirmed_block();
-        let mut last_block_index = 0;
-        match last_block_option {
-            Some(block_hash) => {
-                let block_option = all_blocks.get(&block_hash);
-                match block_option {
-                    Some(block) => {
-                        last_block_index = block.height().0 + 1;
-                    }
-                    None => {
-                        println!("ERROR: Should not be here 001"); //shouldn't get here
-                    }
-                }
+    ///Neo4j has executed the block this audit was sent for, so its pending transactions are
+    /// marked as awaiting audit.
+    pub fn mark_block_executed(&self, fork: &mut Fork, height: Height) {
+        let transactions: Vec<Hash> = {
+            let core_schema = CoreSchema::new(&*fork);
+            let block = Hash::from_hex(self.block_id())
+                .ok()
+                .and_then(|block_hash| core_schema.blocks().get(&block_hash));
+            match block {
+                Some(block) => Schema::new(&*fork).block_neo4j_transactions(block.height()),
+                None => Vec::new(),
             }
-            None => {}
-        }
-        for x in last_block_index..all_blocks_by_height.len() {
-            let block_hash_option = all_blocks_by_height.get(x);
-            match block_hash_option {
-                Some(block_hash) => {
-                    let mut ignore = true;
-                    let h = Height(x);
-                    let transactions = core_schema.block_transactions(h);
-                    for trans in transactions.iter() {
-                        match schema.neo4j_transaction(&trans) {
-                            Some(_) => ignore = false,
-                            None => {}
-                        }
-                    }
-                    if ignore {
-                        continue;
-                    }
+        };
 
-                    match neo4j_rpc.retrieve_block_changes(block_hash) {
-                        ChangeResponse(changes) => returnVector.push(changes),
-                        DBError(e) => println!("{:?}", e.msg()), //TODO error handling, this is obviously going to fail in consensus, but do we want to do something before that.
-                        _ => {}
-                    }
+        let mut schema: Schema<&mut Fork> = Schema::new(fork);
+        for trans in transactions {
+            if let Some(TransactionStatus::Pending) =
+                schema.neo4j_transaction(&trans).and_then(|t| t.status())
+            {
+                schema.update_neo4j_transaction(
+                    &trans,
+                    "",
+                    TransactionStatus::ExecutedAwaitingAudit,
+                    height,
+                );
+            }
+        }
+    }
+
+    ///Transactions of blocks whose changes the validator could not retrieve are marked as such,
+    /// unless another audit already recorded the changes of the block. The blocks stay to be
+    /// audited, so that another validator can still provide their changes.
+    pub fn mark_blocks_unavailable(
+        &self,
+        fork: &mut Fork,
+        blocks: &[String],
+        height: Height,
+    ) -> Result<(), Error> {
+        let mut transactions: Vec<Hash> = Vec::new();
+        {
+            let schema = Schema::new(&*fork);
+            for block_id in blocks {
+                let (block_hash, block_height) = audited_block(&*fork, block_id)?;
+                if !schema.audited_changes().contains(&block_hash) {
+                    transactions.extend(schema.block_neo4j_transactions(block_height));
                 }
-                None => {
-                    println!("ERROR: Should not be here 002");
-                } //shouldn't get here
             }
         }
-        returnVector
+
+        let mut schema: Schema<&mut Fork> = Schema::new(fork);
+        for trans in transactions {
+            let status = schema.neo4j_transaction(&trans).and_then(|t| t.status());
+            if status.map_or(false, |status| status.is_open()) {
+                schema.update_neo4j_transaction(
+                    &trans,
+                    "Changes could not be retrieved from Neo4j",
+                    TransactionStatus::Neo4jUnavailable,
+                    height,
+                );
+            }
+        }
+        Ok(())
     }
 
+    ///Records the changes of a block. If another audit already recorded them, changes that
+    /// differ are only noted as a divergence of the validator. Only the transactions of the
+    /// block that still wait for their changes are updated.
     pub fn add_changes_to_exonum(
         &self,
         fork: &mut Fork,
-        changes_per_block: Vec<BlockChangesResponse>,
+        block_changes: &BlockChangesResponse,
         current_transaction: Hash,
-    ) {
+        height: Height,
+    ) -> Result<(), Error> {
+        let (block_hash, block_height) = audited_block(&*fork, block_changes.get_block_id())?;
+        let config = ServiceConfig::actual(&*fork);
         let mut schema: Schema<&mut Fork> = Schema::new(fork);
-        for block_changes in changes_per_block {
-            match Hash::from_hex(block_changes.get_block_id()) {
-                Ok(block_hash) => {
-                    schema.add_audited_block(&current_transaction, block_hash);
-                }
-                _ => {
-                    println!("ERROR: Should not be here 003");
-                } //Should not get here
+        let digest = block_changes
+            .write_to_bytes()
+            .map(|bytes| hash(&bytes))
+            .map_err(|e| Error::InvalidAudit(e.to_string()))?;
+        if let Some(recorded) = schema.audited_changes().get(&block_hash) {
+            if recorded != digest {
+                warn!(
+                    "Validator {} reported diverging changes for block {}",
+                    self.pub_key().to_hex(),
+                    block_hash.to_hex()
+                );
+                schema.add_audit_divergence(&block_hash, self.pub_key(), digest);
             }
-            for transaction_changes in block_changes.get_transactions() {
-                match Hash::from_hex(transaction_changes.get_transaction_id()) {
-                    Ok(transaction_hash) => match transaction_changes.get_result() {
-                        Status::SUCCESS => {
-                            let changes = transaction_changes.get_modifications();
-                            let change_vec: Vec<NodeChange> = generate_database_changes_from_proto(
-                                changes,
-                                &mut schema,
-                                transaction_changes.get_transaction_id(),
-                            );
-                            for nc in change_vec {
-                                for uuid in nc.get_uuis() {
-                                    schema.add_node_history(uuid, &nc)
-                                }
+            return Ok(());
+        }
+        schema.audited_changes_mut().put(&block_hash, digest);
+        schema.add_audited_block(&current_transaction, block_hash);
+
+        let awaiting: Vec<Hash> = schema
+            .block_neo4j_transactions(block_height)
+            .into_iter()
+            .filter(|trans| {
+                schema
+                    .neo4j_transaction(trans)
+                    .and_then(|t| t.status())
+                    .map_or(false, |status| status.awaits_changes())
+            })
+            .collect();
+        for transaction_changes in block_changes.get_transactions() {
+            match Hash::from_hex(transaction_changes.get_transaction_id()) {
+                Ok(ref transaction_hash) if !awaiting.contains(transaction_hash) => {}
+                Ok(transaction_hash) => match transaction_changes.get_result() {
+                    Status::SUCCESS => {
+                        let changes = transaction_changes.get_modifications();
+                        let previous_values_recorded = changes.get_records_previous_values();
+                        if previous_values_recorded {
+                            schema
+                                .previous_values_recorded_mut()
+                                .insert(transaction_hash);
+                        }
+                        let change_vec: Vec<NodeChange> = generate_database_changes_from_proto(
+                            changes,
+                            &mut schema,
+                            transaction_changes.get_transaction_id(),
+                        );
+                        let author = schema
+                            .neo4j_transaction(&transaction_hash)
+                            .map(|t| *t.pub_key());
+                        // Compensations revert changes, so they are never checked themselves.
+                        let violation = match author
+                            .as_ref()
+                            .and_then(|key| config.write_policy(key))
+                        {
+                            Some(policy) if !schema.compensations().contains(&transaction_hash) => {
+                                policy::check_changes(&schema, policy, &change_vec).err()
+                            }
+                            _ => None,
+                        };
+                        let compensating_queries = violation.as_ref().map(|_| {
+                            compensation::compensating_queries(
+                                &schema,
+                                &change_vec,
+                                previous_values_recorded,
+                            )
+                        });
+
+                        // Changes are recorded even when violating, since Neo4j committed them.
+                        for nc in change_vec {
+                            for uuid in nc.get_uuis() {
+                                schema.add_node_history(uuid, &nc)
                             }
-                            schema.update_neo4j_transaction(&transaction_hash, "", "SUCCESS");
+                            schema.index_node_change(&nc);
+                            schema.add_transaction_change(&transaction_hash, &nc);
                         }
-                        Status::FAILURE => {
-                            let error = transaction_changes.get_error();
-                            let failed_query = error.get_failed_query();
-
-                            let error_msg = if failed_query.get_query().is_empty() {
-                                format!("{}", error.get_message())
-                            } else {
-                                format!(
-                                    "{}\n|||Happened in query: {}\n|||Error: {}",
-                                    error.get_message(),
-                                    failed_query.get_query(),
-                                    failed_query.get_error()
-                                )
-                            };
-                            schema.update_neo4j_transaction(
-                                &transaction_hash,
-                                error_msg.as_str(),
-                                "ERROR",
-                            );
+                        match (violation, compensating_queries) {
+                            (Some(description), Some(queries)) => {
+                                let failure = TransactionFailure::new(
+                                    FailureCode::PolicyViolation as u8,
+                                    0,
+                                    "",
+                                    "",
+                                    "",
+                                );
+                                schema
+                                    .transaction_failures_mut()
+                                    .put(&transaction_hash, failure);
+                                schema.update_neo4j_transaction(
+                                    &transaction_hash,
+                                    &description,
+                                    TransactionStatus::Error,
+                                    height,
+                                );
+                                // Sent by the service, so it is recorded under the key of the
+                                // auditing validator rather than counted against the violator.
+                                if !queries.is_empty() {
+                                    schema.add_compensation(
+                                        &transaction_hash,
+                                        &queries,
+                                        self.pub_key(),
+                                        height,
+                                    );
+                                }
+                            }
+                            _ => {
+                                schema.update_neo4j_transaction(
+                                    &transaction_hash,
+                                    "",
+                                    TransactionStatus::Success,
+                                    height,
+                                );
+                                schema.apply_schema_change(&transaction_hash, height);
+                            }
                         }
-                    },
-                    _ => {}
-                }
+                    }
+                    Status::FAILURE => {
+                        let error = transaction_changes.get_error();
+                        schema
+                            .transaction_failures_mut()
+                            .put(&transaction_hash, TransactionFailure::from_proto(error));
+                        schema.update_neo4j_transaction(
+                            &transaction_hash,
+                            error.get_message(),
+                            TransactionStatus::Error,
+                            height,
+                        );
+                    }
+                },
+                _ => {}
             }
         }
+        Ok(())
+    }
+}
+
+///Hash and height of a block an audit carries changes for, which has to be committed already.
+fn audited_block(fork: &Fork, block_id: &str) -> Result<(Hash, Height), Error> {
+    let block_hash = Hash::from_hex(block_id)
+        .map_err(|_| Error::InvalidAudit(format!("{} is not a block hash", block_id)))?;
+    let block = CoreSchema::new(fork)
+        .blocks()
+        .get(&block_hash)
+        .ok_or_else(|| Error::InvalidAudit(format!("block {} is not committed", block_id)))?;
+    Ok((block_hash, block.height()))
+}
+
+impl Transaction for AuditBlocks {
+    fn verify(&self) -> bool {
+        self.verify_signature(self.pub_key())
+    }
+
+    fn execute(&self, fork: &mut Fork) -> ExecutionResult {
+        if !is_validator(fork, self.pub_key()) {
+            Err(Error::NotValidator)?;
+        }
+        let audited: AuditedChanges = protobuf::parse_from_bytes(self.changes())
+            .map_err(|e| Error::InvalidAudit(e.to_string()))?;
+        let hash = self.hash();
+        let height = current_height(fork);
+        self.mark_block_executed(fork, height);
+        for block_changes in audited.get_blocks() {
+            self.add_changes_to_exonum(fork, block_changes, hash, height)?;
+        }
+        self.mark_blocks_unavailable(fork, audited.get_unavailable_blocks(), height)?;
+        Schema::new(fork).advance_last_confirmed_block();
+        Ok(())
+    }
+}
+
+///Records a transaction the service refused, so that it is never sent to Neo4j.
+/// Rejections keep the fork, since returning an error from `execute` would discard the record.
+pub fn record_rejection(
+    schema: &mut Schema<&mut Fork>,
+    hash: &Hash,
+    queries: &str,
+    pub_key: &PublicKey,
+    failure: TransactionFailure,
+    error_msg: &str,
+    height: Height,
+) {
+    let q = Neo4jTransaction::new(queries, error_msg, TransactionStatus::Rejected, pub_key);
+    schema.add_neo4j_transaction(q, hash, height);
+    schema.transaction_failures_mut().put(hash, failure);
+}
+
+///Records queries as a pending neo4j transaction along with the classes of their statements,
+/// unless the author may not commit queries, the queries cannot be tokenized, the statements
+/// cannot be sent as one batch or one of the preconditions does not hold, in which case they are
+/// rejected.
+fn commit_queries(
+    fork: &mut Fork,
+    hash: &Hash,
+    queries: &str,
+    pub_key: &PublicKey,
+    preconditions: &[HistoryPrecondition],
+) {
+    let height = current_height(fork);
+    let config = ServiceConfig::actual(&*fork);
+
+    let mut schema: Schema<&mut Fork> = Schema::new(fork);
+    let classification = cypher::classify_queries(queries);
+    if let Ok(ref classification) = classification {
+        schema
+            .statement_classes_mut()
+            .put(hash, classification.clone());
+    }
+    if !config.is_authorized(pub_key, Role::Writer) {
+        record_rejection(
+            &mut schema,
+            hash,
+            queries,
+            pub_key,
+            TransactionFailure::new(FailureCode::NotAuthorized as u8, 0, "", "", ""),
+            "Author is not authorized to commit queries",
+            height,
+        );
+        return;
+    }
+    if let Err((code, error_msg)) =
+        limits::check_limits(&schema, &config.limits, queries, pub_key, height)
+    {
+        record_rejection(
+            &mut schema,
+            hash,
+            &limits::stored_queries(code, queries),
+            pub_key,
+            TransactionFailure::new(code as u8, 0, "", "", ""),
+            &error_msg,
+            height,
+        );
+        return;
+    }
+    let classification = match classification {
+        Ok(classification) => classification,
+        Err(e) => {
+            record_rejection(
+                &mut schema,
+                hash,
+                queries,
+                pub_key,
+                TransactionFailure::new(FailureCode::InvalidQuery as u8, 0, "", "", ""),
+                &e.to_string(),
+                height,
+            );
+            return;
+        }
+    };
+    if let Err(e) = cypher::check_denied(&config.deny_list, queries) {
+        record_rejection(
+            &mut schema,
+            hash,
+            queries,
+            pub_key,
+            TransactionFailure::new(FailureCode::NonDeterministicQuery as u8, 0, "", "", ""),
+            &e.to_string(),
+            height,
+        );
+        return;
+    }
+    if let Err((code, error_msg)) = cypher::check_classes(&classification) {
+        record_rejection(
+            &mut schema,
+            hash,
+            queries,
+            pub_key,
+            TransactionFailure::new(code as u8, 0, "", "", ""),
+            &error_msg,
+            height,
+        );
+        return;
+    }
+    if let Err(error_msg) = check_preconditions(&schema, preconditions) {
+        record_rejection(
+            &mut schema,
+            hash,
+            queries,
+            pub_key,
+            TransactionFailure::new(FailureCode::PreconditionFailed as u8, 0, "", "", ""),
+            &error_msg,
+            height,
+        );
+        return;
+    }
+    let q = Neo4jTransaction::new(queries, "", TransactionStatus::Pending, pub_key);
+
+    schema.add_neo4j_transaction(q, hash, height);
+    schema.charge_submission(pub_key, height);
+    // Histories only move on at the audit, so until then the nodes are kept from other
+    // transactions written against the same histories.
+    for precondition in preconditions {
+        schema
+            .reserved_nodes_mut()
+            .put(&precondition.node_uuid().to_string(), *hash);
+    }
+}
+
+///Checks that the node histories did not move on since the preconditions were made, and that
+/// no open transaction carrying preconditions reserved the nodes.
+pub fn check_preconditions<T: AsRef<dyn Snapshot>>(
+    schema: &Schema<T>,
+    preconditions: &[HistoryPrecondition],
+) -> Result<(), String> {
+    for precondition in preconditions {
+        if let Some(transaction) = schema.node_reservation(precondition.node_uuid()) {
+            return Err(format!(
+                "Node {} is reserved by transaction {} until it is audited",
+                precondition.node_uuid(),
+                transaction.to_hex()
+            ));
+        }
+        let history = schema.node_history_raw(precondition.node_uuid());
+        if history.len() != precondition.history_len() {
+            return Err(format!(
+                "History of node {} has {} changes, expected {}",
+                precondition.node_uuid(),
+                history.len(),
+                precondition.history_len()
+            ));
+        }
+        let expected_hash = precondition.history_hash();
+        if *expected_hash != Hash::zero() && history.merkle_root() != *expected_hash {
+            return Err(format!(
+                "History of node {} has root {}, expected {}",
+                precondition.node_uuid(),
+                history.merkle_root().to_hex(),
+                expected_hash.to_hex()
+            ));
+        }
     }
+    Ok(())
+}
 
-    pub fn update_last_block(&self, fork: &mut Fork) {
-        let last_block = { CoreSchema::new(&fork).block_hashes_by_height().last() };
+impl Transaction for CommitQueries {
+    fn verify(&self) -> bool {
+        limits::within_hard_caps(self.queries()) && self.verify_signature(self.pub_key())
+    }
+
+    fn execute(&self, fork: &mut Fork) -> ExecutionResult {
+        commit_queries(fork, &self.hash(), self.queries(), self.pub_key(), &[]);
+        Ok(())
+    }
+}
+
+impl Transaction for CommitQueriesWithPreconditions {
+    fn verify(&self) -> bool {
+        limits::within_hard_caps(self.queries()) && self.verify_signature(self.pub_key())
+    }
+
+    fn execute(&self, fork: &mut Fork) -> ExecutionResult {
+        commit_queries(
+            fork,
+            &self.hash(),
+            self.queries(),
+            self.pub_key(),
+            &self.preconditions(),
+        );
+        Ok(())
+    }
+}
+
+impl Transaction for MigrateSchema {
+    fn verify(&self) -> bool {
+        self.verify_signature(self.pub_key())
+    }
+
+    fn execute(&self, fork: &mut Fork) -> ExecutionResult {
+        let height = current_height(fork);
+        if !is_validator(fork, self.pub_key()) {
+            Err(Error::NotValidator)?;
+        }
+        // Versions this service does not know are refused before a vote is recorded.
+        migrations::check_schema_version(&Schema::new(&*fork))
+            .and_then(|_| migrations::check_target_version(self.target_version()))
+            .map_err(Error::MigrationFailed)?;
+        if height.0 < self.activation_height() {
+            Err(Error::MigrationNotActive(self.activation_height()))?;
+        }
+        if Schema::new(&*fork)
+            .migration_votes(self.target_version())
+            .contains(self.pub_key())
+        {
+            Err(Error::DuplicateVote)?;
+        }
+
+        Schema::new(&mut *fork)
+            .migration_votes_mut(self.target_version())
+            .put(self.pub_key(), height.0);
+        let (votes, validators) = {
+            let snapshot: &Fork = fork;
+            let validators = CoreSchema::new(snapshot)
+                .actual_configuration()
+                .validator_keys
+                .len();
+            let votes = Schema::new(snapshot)
+                .migration_votes(self.target_version())
+                .keys()
+                .filter(|key| is_validator(snapshot, key))
+                .count();
+            (votes, validators)
+        };
+        // Later votes find the indexes at the target version already, so no step runs twice.
+        if votes < validators * 2 / 3 + 1 {
+            return Ok(());
+        }
+        migrations::migrate(fork, self.target_version()).map_err(Error::MigrationFailed)?;
+        Ok(())
+    }
+}
+
+///Hash of the neo4j transaction an approved proposal is sent to Neo4j as. It differs from the
+/// proposal hash, since the proposal was made in an earlier block than the one it is sent with.
+pub fn proposal_transaction_hash(proposal: &Hash) -> Hash {
+    hash(&[proposal.as_ref(), b"approved"].concat())
+}
+
+impl Transaction for ProposeQueries {
+    fn verify(&self) -> bool {
+        limits::within_hard_caps(self.queries()) && self.verify_signature(self.pub_key())
+    }
+
+    fn execute(&self, fork: &mut Fork) -> ExecutionResult {
+        let height = current_height(fork);
+        let config = ServiceConfig::actual(&*fork);
+        if !config.is_authorized(self.pub_key(), Role::Writer) {
+            Err(Error::NotAuthorized)?;
+        }
+        limits::check_limits(
+            &Schema::new(&*fork),
+            &config.limits,
+            self.queries(),
+            self.pub_key(),
+            height,
+        )
+        .map_err(Error::from_failure)?;
+        let classification = cypher::classify_queries(self.queries())
+            .map_err(|e| Error::InvalidQuery(e.to_string()))?;
+        cypher::check_denied(&config.deny_list, self.queries())
+            .map_err(|e| Error::NonDeterministicQuery(e.to_string()))?;
+        cypher::check_classes(&classification).map_err(Error::from_failure)?;
+
+        // The threshold in effect when proposing applies, even if the configuration changes.
+        let proposal = Proposal::new(
+            self.queries(),
+            self.pub_key(),
+            height.0,
+            height.0.saturating_add(config.approvals.ttl),
+            config.approvals.threshold.max(1),
+            false,
+        );
+        let mut schema = Schema::new(fork);
+        schema.proposals_mut().put(&self.hash(), proposal);
+        // Approving the proposal later is not charged again.
+        schema.charge_submission(self.pub_key(), height);
+        Ok(())
+    }
+}
+
+impl Transaction for ApproveQueries {
+    fn verify(&self) -> bool {
+        self.verify_signature(self.pub_key())
+    }
+
+    fn execute(&self, fork: &mut Fork) -> ExecutionResult {
+        let height = current_height(fork);
+        let config = ServiceConfig::actual(&*fork);
+        if !config.is_authorized(self.pub_key(), Role::Approver) {
+            Err(Error::NotAuthorized)?;
+        }
 
         let mut schema: Schema<&mut Fork> = Schema::new(fork);
-        match last_block {
-            Some(hash) => schema.set_last_confirmed_block(hash),
-            None => {}
+        let proposal = schema
+            .proposals()
+            .get(self.proposal())
+            .ok_or(Error::ProposalNotFound)?;
+        match proposal.status(height) {
+            ProposalStatus::Open => {}
+            ProposalStatus::Approved => Err(Error::ProposalApproved)?,
+            ProposalStatus::Expired => Err(Error::ProposalExpired(proposal.expires_at()))?,
+        }
+        if proposal.pub_key() == self.pub_key() {
+            Err(Error::SelfApproval)?;
+        }
+        if schema
+            .proposal_approvals(self.proposal())
+            .contains(self.pub_key())
+        {
+            Err(Error::DuplicateApproval)?;
+        }
+
+        schema
+            .proposal_approvals_mut(self.proposal())
+            .put(self.pub_key(), height.0);
+        let approvals = schema.proposal_approvals(self.proposal()).keys().count();
+        if approvals >= proposal.threshold() as usize {
+            schema.dispatch_neo4j_transaction(
+                &proposal_transaction_hash(self.proposal()),
+                proposal.queries(),
+                proposal.pub_key(),
+                height,
+            );
+            let approved = Proposal::new(
+                proposal.queries(),
+                proposal.pub_key(),
+                proposal.height(),
+                proposal.expires_at(),
+                proposal.threshold(),
+                true,
+            );
+            schema.proposals_mut().put(self.proposal(), approved);
         }
+        Ok(())
     }
 }
 
-impl Transaction for AuditBlocks {
+impl Transaction for RevertTransaction {
     fn verify(&self) -> bool {
-        true
+        self.verify_signature(self.pub_key())
     }
 
     fn execute(&self, fork: &mut Fork) -> ExecutionResult {
         let hash = self.hash();
-        let changes = self.retrieve_changes_from_neo4j(fork);
-        self.add_changes_to_exonum(fork, changes, hash);
-        self.update_last_block(fork);
+        let height = current_height(fork);
+        let config = ServiceConfig::actual(&*fork);
+
+        let mut schema: Schema<&mut Fork> = Schema::new(fork);
+        let target = self.transaction();
+        let transaction = schema
+            .neo4j_transaction(target)
+            .ok_or(Error::TransactionNotFound)?;
+        let is_author = transaction.pub_key() == self.pub_key()
+            && config.is_authorized(self.pub_key(), Role::Writer);
+        if !is_author && !config.is_authorized(self.pub_key(), Role::Admin) {
+            Err(Error::NotAuthorized)?;
+        }
+        if transaction.status() != Some(TransactionStatus::Success) {
+            Err(Error::NotRevertible(format!(
+                "its status is {}",
+                transaction
+                    .status()
+                    .map_or("unknown", |status| status.as_str())
+            )))?;
+        }
+        if !schema.previous_values_recorded().contains(target) {
+            Err(Error::NotRevertible(String::from(
+                "previous property values were not recorded",
+            )))?;
+        }
+        // A revert that Neo4j failed to execute may be retried.
+        if let Some(revert) = schema.reverts().get(target) {
+            let status = schema.neo4j_transaction(&revert).and_then(|t| t.status());
+            if status != Some(TransactionStatus::Error) {
+                Err(Error::AlreadyReverted(revert))?;
+            }
+        }
+
+        let changes = schema
+            .transaction_changes_raw(target)
+            .iter()
+            .map(|bytes| NodeChange::decode(&bytes))
+            .collect::<Result<Vec<NodeChange>, _>>()
+            .map_err(|e| Error::NotRevertible(format!("{}", e)))?;
+        compensation::check_unchanged_since(&schema, &target.to_hex(), &changes)
+            .map_err(Error::ChangedLater)?;
+        compensation::check_audited_since(&schema, target).map_err(|later| {
+            Error::NotRevertible(format!(
+                "transaction {} committed after it is not audited yet",
+                later.to_hex()
+            ))
+        })?;
+        let queries = compensation::compensating_queries(&schema, &changes, true);
+        if queries.is_empty() {
+            Err(Error::NotRevertible(String::from("it changed nothing")))?;
+        }
+
+        let q = Neo4jTransaction::new(&queries, "", TransactionStatus::Pending, self.pub_key());
+        schema.add_neo4j_transaction(q, &hash, height);
+        schema.compensations_mut().put(&hash, *target);
+        schema.reverts_mut().put(target, hash);
         Ok(())
     }
 }
 
-impl Transaction for CommitQueries {
+impl Transaction for ScheduleQueries {
     fn verify(&self) -> bool {
-        true
+        (self.activation_height() != 0 || self.activation_time() != 0)
+            && limits::within_hard_caps(self.queries())
+            && self.verify_signature(self.pub_key())
     }
 
     fn execute(&self, fork: &mut Fork) -> ExecutionResult {
         let hash = self.hash();
+        let height = current_height(fork);
+        let config = ServiceConfig::actual(&*fork);
+        if !config.is_authorized(self.pub_key(), Role::Writer) {
+            Err(Error::NotAuthorized)?;
+        }
+        limits::check_limits(
+            &Schema::new(&*fork),
+            &config.limits,
+            self.queries(),
+            self.pub_key(),
+            height,
+        )
+        .map_err(Error::from_failure)?;
+        let classification = cypher::classify_queries(self.queries())
+            .map_err(|e| Error::InvalidQuery(e.to_string()))?;
+        cypher::check_denied(&config.deny_list, self.queries())
+            .map_err(|e| Error::NonDeterministicQuery(e.to_string()))?;
+        cypher::check_classes(&classification).map_err(Error::from_failure)?;
+
+        let batch = ScheduledBatch::new(
+            self.pub_key(),
+            height.0,
+            self.activation_height(),
+            self.activation_time(),
+        );
+        // A batch that is already due is sent with its own block.
+        if batch.is_due(height, Schema::new(&*fork).current_time()) {
+            commit_queries(fork, &hash, self.queries(), self.pub_key(), &[]);
+            return Ok(());
+        }
+
+        let mut schema: Schema<&mut Fork> = Schema::new(fork);
+        let q = Neo4jTransaction::new(
+            self.queries(),
+            "",
+            TransactionStatus::Scheduled,
+            self.pub_key(),
+        );
+        schema.add_neo4j_transaction(q, &hash, height);
+        schema.charge_submission(self.pub_key(), height);
+        schema.scheduled_mut().put(&hash, batch);
+        Ok(())
+    }
+}
+
+impl Transaction for CancelScheduled {
+    fn verify(&self) -> bool {
+        self.verify_signature(self.pub_key())
+    }
+
+    fn execute(&self, fork: &mut Fork) -> ExecutionResult {
+        let height = current_height(fork);
+        let config = ServiceConfig::actual(&*fork);
 
         let mut schema: Schema<&mut Fork> = Schema::new(fork);
-        let pub_key = println!("Adding transaction: {}", self.queries());
+        let batch = schema
+            .scheduled()
+            .get(self.batch())
+            .ok_or(Error::ScheduleNotFound)?;
+        let is_author =
+            batch.pub_key() == self.pub_key() && config.is_authorized(self.pub_key(), Role::Writer);
+        if !is_author && !config.is_authorized(self.pub_key(), Role::Admin) {
+            Err(Error::NotAuthorized)?;
+        }
 
-        let q = Neo4jTransaction::new(self.queries(), "", "PENDING", self.pub_key());
+        schema.scheduled_mut().remove(self.batch());
+        schema.update_neo4j_transaction(self.batch(), "", TransactionStatus::Cancelled, height);
+        Ok(())
+    }
+}
+
+impl Transaction for ActivateScheduled {
+    fn verify(&self) -> bool {
+        self.verify_signature(self.pub_key())
+    }
+
+    fn execute(&self, fork: &mut Fork) -> ExecutionResult {
+        let height = current_height(fork);
+        if !is_validator(fork, self.pub_key()) {
+            Err(Error::NotValidator)?;
+        }
+        let config = ServiceConfig::actual(&*fork);
+
+        let mut schema: Schema<&mut Fork> = Schema::new(fork);
+        let batch = schema
+            .scheduled()
+            .get(self.batch())
+            .ok_or(Error::ScheduleNotFound)?;
+        if !batch.is_due(height, schema.current_time()) {
+            Err(Error::ScheduleNotDue(
+                batch.activation_height(),
+                batch.activation_time(),
+            ))?;
+        }
+
+        // The configuration may have changed since the batch was scheduled. The batch already
+        // counted towards the rate of its author when it was scheduled.
+        schema.scheduled_mut().remove(self.batch());
+        let queries = schema
+            .neo4j_transaction(self.batch())
+            .map(|t| t.queries().to_string())
+            .unwrap_or_default();
+        let rejection = if !config.is_authorized(batch.pub_key(), Role::Writer) {
+            Some((
+                FailureCode::NotAuthorized,
+                String::from("Author is not authorized to commit queries"),
+            ))
+        } else if let Err(e) = cypher::check_denied(&config.deny_list, &queries) {
+            Some((FailureCode::NonDeterministicQuery, e.to_string()))
+        } else {
+            limits::check_size(&config.limits, &queries).err()
+        };
+        if let Some((code, error_msg)) = rejection {
+            schema.update_neo4j_transaction(
+                self.batch(),
+                &error_msg,
+                TransactionStatus::Rejected,
+                height,
+            );
+            schema.transaction_failures_mut().put(
+                self.batch(),
+                TransactionFailure::new(code as u8, 0, "", "", ""),
+            );
+            return Ok(());
+        }
+
+        // Sent with the block at this height, like the transactions of the block itself.
+        schema.update_neo4j_transaction(self.batch(), "", TransactionStatus::Pending, height);
+        schema.dispatched_mut(height).push(*self.batch());
+        Ok(())
+    }
+}
+
+impl Transaction for SchemaChange {
+    fn verify(&self) -> bool {
+        limits::within_hard_caps(self.statement())
+            && SchemaChangeRecord::parse(self.statement(), self.pub_key()).is_ok()
+            && self.verify_signature(self.pub_key())
+    }
+
+    fn execute(&self, fork: &mut Fork) -> ExecutionResult {
+        let hash = self.hash();
+        let height = current_height(fork);
+        let config = ServiceConfig::actual(&*fork);
+        let change = SchemaChangeRecord::parse(self.statement(), self.pub_key())
+            .map_err(Error::InvalidSchemaChange)?;
+
+        let mut schema: Schema<&mut Fork> = Schema::new(fork);
+        if !config.is_authorized(self.pub_key(), Role::SchemaManager) {
+            record_rejection(
+                &mut schema,
+                &hash,
+                self.statement(),
+                self.pub_key(),
+                TransactionFailure::new(FailureCode::NotAuthorized as u8, 0, "", "", ""),
+                "Author is not authorized to change the schema",
+                height,
+            );
+            return Ok(());
+        }
+        if let Err((code, error_msg)) = limits::check_limits(
+            &schema,
+            &config.limits,
+            self.statement(),
+            self.pub_key(),
+            height,
+        ) {
+            record_rejection(
+                &mut schema,
+                &hash,
+                &limits::stored_queries(code, self.statement()),
+                self.pub_key(),
+                TransactionFailure::new(code as u8, 0, "", "", ""),
+                &error_msg,
+                height,
+            );
+            return Ok(());
+        }
+        // Changes of the object that Neo4j did not confirm yet are taken into account as well.
+        let definition = definition_hash(change.definition());
+        match (
+            change.operation(),
+            schema.schema_object_will_exist(&definition),
+        ) {
+            (SchemaOperation::Create, true) => Err(Error::InvalidSchemaChange(format!(
+                "{} already exists",
+                change.definition()
+            )))?,
+            (SchemaOperation::Drop, false) => Err(Error::InvalidSchemaChange(format!(
+                "{} does not exist",
+                change.definition()
+            )))?,
+            _ => {}
+        }
 
-        schema.add_neo4j_transaction(q, &hash);
+        let q = Neo4jTransaction::new(
+            &change.statement(),
+            "",
+            TransactionStatus::Pending,
+            self.pub_key(),
+        );
+        schema.add_neo4j_transaction(q, &hash, height);
+        schema.charge_submission(self.pub_key(), height);
+        schema.schema_changes_mut().put(&hash, change);
+        schema.pending_schema_changes_mut().put(&definition, hash);
         Ok(())
     }
 }
@@ -1,4 +1,4 @@
-//! utility functions. Currently just reading port from our custom .toml conf
+//! utility functions. Currently just reading the port and graph backend from our custom .toml conf
 
 use std::fs::File;
 use std::io::prelude::*;
@@ -23,3 +23,23 @@ pub fn parse_port() -> std::io::Result<u16> {
 
     Ok(port)
 }
+
+/// Attempt to load the name of the graph backend from the config file.
+/// None if the file does not name one.
+pub fn parse_backend() -> std::io::Result<Option<String>> {
+    let path = "neo4j.toml".to_string();
+    let mut config_toml = String::new();
+
+    let mut file = File::open(&path)?;
+
+    file.read_to_string(&mut config_toml)
+        .unwrap_or_else(|err| panic!("Error while reading config: [{}]", err));
+
+    let toml = config_toml.as_str().parse::<toml::Value>().unwrap();
+
+    Ok(toml
+        .get("info")
+        .and_then(|info| info.get("backend"))
+        .and_then(|backend| backend.as_str())
+        .map(String::from))
+}
@@ -0,0 +1,651 @@
+extern crate exonum;
+extern crate exonum_neo4j;
+#[macro_use]
+extern crate exonum_testkit;
+extern crate serde_json;
+
+use exonum::api;
+use exonum::blockchain::Transaction;
+use exonum::crypto::{self, Hash, PublicKey};
+use exonum::helpers::Height;
+use exonum::messages::Message;
+use exonum::storage::ProofMapIndex;
+use exonum_neo4j::api::{
+    AuthorTransactionsQuery, CommitResponse, ErrorBody, Event, EventsPage, EventsQuery,
+    GetQueryQuery, LabelNodesQuery, NodeHistoryQuery, PropertyNodesQuery, TransactionsPage,
+    TransactionsQuery, MAX_EVENT_BLOCKS,
+};
+use exonum_neo4j::config::{LimitsConfig, ServiceConfig};
+use exonum_neo4j::neo4j::proto::transaction_manager::{
+    DatabaseModifications_AssignedLabel, DatabaseModifications_AssignedNodeProperty,
+    DatabaseModifications_RemovedLabel, DatabaseModifications_RemovedNodeProperty, ErrorCode,
+    TransactionRequest, TransactionResponse,
+};
+use exonum_neo4j::schema::Schema;
+use exonum_neo4j::structures::{NodeChange, TransactionStatus};
+use exonum_neo4j::transactions::CommitQueries;
+use exonum_testkit::{ApiKind, TestKitApi};
+
+pub mod support;
+
+use support::{
+    created_nodes, failed_transaction, init_testkit, init_testkit_with_config, succeeded, Method,
+};
+
+/// Creates a node for every transaction, labelled `Door` when the queries mention doors.
+fn create_doors(t: &TransactionRequest) -> TransactionResponse {
+    let uuid = format!("{}_0", t.get_transaction_id());
+    let mut response = created_nodes(t.get_transaction_id(), &[&uuid]);
+    if t.get_queries().iter().any(|q| q.contains(":Door")) {
+        let mut label = DatabaseModifications_AssignedLabel::new();
+        label.set_node_UUID(uuid);
+        label.set_name(String::from("Door"));
+        response
+            .mut_modifications()
+            .mut_assigned_labels()
+            .push(label);
+    }
+    response
+}
+
+fn events(api: &TestKitApi, query: &EventsQuery) -> EventsPage {
+    api.public(ApiKind::Service("neo4j_blockchain"))
+        .query(query)
+        .get("v1/events")
+        .unwrap()
+}
+
+fn events_query(from_height: Option<u64>) -> EventsQuery {
+    EventsQuery {
+        from_height,
+        node_uuid: None,
+        label: None,
+        pub_key: None,
+    }
+}
+
+/// Hash of the transaction an event belongs to.
+fn event_transaction(event: &Event) -> Hash {
+    match *event {
+        Event::Status { tx_hash, .. } | Event::Change { tx_hash, .. } => tx_hash,
+    }
+}
+
+/// Creates the node `door` with a label and two properties, or takes them away again.
+fn door_changes(t: &TransactionRequest) -> TransactionResponse {
+    let queries = t.get_queries().join(";");
+    if queries.starts_with("CREATE") {
+        let mut response = created_nodes(t.get_transaction_id(), &["door"]);
+        let modifications = response.mut_modifications();
+        let mut label = DatabaseModifications_AssignedLabel::new();
+        label.set_node_UUID(String::from("door"));
+        label.set_name(String::from("Door"));
+        modifications.mut_assigned_labels().push(label);
+        for key in &["state", "width"] {
+            let mut property = DatabaseModifications_AssignedNodeProperty::new();
+            property.set_node_UUID(String::from("door"));
+            property.set_key(key.to_string());
+            property.set_value(String::from("1"));
+            modifications.mut_assigned_node_properties().push(property);
+        }
+        response
+    } else {
+        let mut response = succeeded(t.get_transaction_id());
+        let modifications = response.mut_modifications();
+        let mut label = DatabaseModifications_RemovedLabel::new();
+        label.set_node_UUID(String::from("door"));
+        label.set_name(String::from("Door"));
+        modifications.mut_removed_labels().push(label);
+        let mut property = DatabaseModifications_RemovedNodeProperty::new();
+        property.set_node_UUID(String::from("door"));
+        property.set_key(String::from("color"));
+        modifications.mut_removed_node_properties().push(property);
+        response
+    }
+}
+
+fn label_nodes(api: &TestKitApi, label: &str) -> Vec<String> {
+    api.public(ApiKind::Service("neo4j_blockchain"))
+        .query(&LabelNodesQuery {
+            label: label.to_string(),
+        })
+        .get("v1/label_nodes")
+        .unwrap()
+}
+
+fn property_nodes(api: &TestKitApi, key: &str) -> Vec<String> {
+    api.public(ApiKind::Service("neo4j_blockchain"))
+        .query(&PropertyNodesQuery {
+            key: key.to_string(),
+        })
+        .get("v1/property_nodes")
+        .unwrap()
+}
+
+fn submit(api: &TestKitApi, transaction: &CommitQueries) -> api::Result<CommitResponse> {
+    api.public(ApiKind::Service("neo4j_blockchain"))
+        .query(transaction)
+        .post("v1/insert_transaction")
+}
+
+/// Body of a refused request, sent by exonum as a bad request.
+fn bad_request(result: api::Result<CommitResponse>) -> ErrorBody {
+    match result {
+        Err(api::Error::BadRequest(body)) => serde_json::from_str(&body).unwrap(),
+        other => panic!("expected a bad request, got {:?}", other),
+    }
+}
+
+fn transactions(api: &TestKitApi, query: &TransactionsQuery) -> TransactionsPage {
+    api.public(ApiKind::Service("neo4j_blockchain"))
+        .query(query)
+        .get("v1/transactions")
+        .unwrap()
+}
+
+fn transactions_query(cursor: Option<u64>, limit: Option<u64>) -> TransactionsQuery {
+    TransactionsQuery {
+        cursor,
+        limit,
+        status: None,
+        pub_key: None,
+        from_height: None,
+        to_height: None,
+    }
+}
+
+fn page_hashes(page: &TransactionsPage) -> Vec<Hash> {
+    page.transactions
+        .iter()
+        .map(|entry| entry.tx_hash)
+        .collect()
+}
+
+fn node_history(api: &TestKitApi, uuid: &str) -> Vec<NodeChange> {
+    api.public(ApiKind::Service("neo4j_blockchain"))
+        .query(&NodeHistoryQuery::new(uuid))
+        .get("v1/node_history")
+        .unwrap()
+}
+
+#[test]
+fn test_node_history() {
+    let (mut testkit, mock) = init_testkit();
+    mock.script_transactions(|t| created_nodes(t.get_transaction_id(), &["u1"]));
+    let (pub_key, secret_key) = crypto::gen_keypair();
+    testkit.create_block_with_transactions(txvec![CommitQueries::new(
+        "CREATE (n)",
+        "15-OCT",
+        &pub_key,
+        &secret_key
+    )]);
+    testkit.create_block();
+
+    let api = testkit.api();
+    assert_eq!(node_history(&api, "u1").len(), 1);
+    // Nodes without changes have an empty history rather than being unknown.
+    assert!(node_history(&api, "u2").is_empty());
+}
+
+fn transaction_changes(api: &TestKitApi, tx_hash: &str) -> api::Result<Vec<NodeChange>> {
+    api.public(ApiKind::Service("neo4j_blockchain"))
+        .query(&GetQueryQuery::new(tx_hash))
+        .get("v1/transaction_changes")
+}
+
+#[test]
+fn test_transaction_changes() {
+    let (mut testkit, mock) = init_testkit();
+    mock.script_transactions(|t| {
+        let uuid = format!("{}_0", t.get_transaction_id());
+        created_nodes(t.get_transaction_id(), &[&uuid])
+    });
+    let (pub_key, secret_key) = crypto::gen_keypair();
+    let first = CommitQueries::new("CREATE (n)", "18-OCT", &pub_key, &secret_key);
+    let second = CommitQueries::new("CREATE (m)", "18-OCT", &pub_key, &secret_key);
+    let (first_hash, second_hash) = (first.hash(), second.hash());
+    testkit.create_block_with_transactions(txvec![first, second]);
+    let api = testkit.api();
+    // Nothing changed before the audit.
+    assert!(transaction_changes(&api, &first_hash.to_hex())
+        .unwrap()
+        .is_empty());
+
+    testkit.create_block();
+    let api = testkit.api();
+    // Each transaction lists only its own changes, even from the same block.
+    for tx_hash in &[first_hash, second_hash] {
+        let changes = transaction_changes(&api, &tx_hash.to_hex()).unwrap();
+        assert_eq!(changes.len(), 1);
+        match changes[0] {
+            NodeChange::AN(ref change) => {
+                assert_eq!(change.node_uuid(), format!("{}_0", tx_hash.to_hex()));
+                assert_eq!(change.transaction_id(), tx_hash.to_hex());
+            }
+            ref other => panic!("Unexpected change {:?}", other),
+        }
+    }
+
+    match transaction_changes(&api, &crypto::hash(b"unknown").to_hex()) {
+        Err(api::Error::NotFound(_)) => {}
+        other => panic!("expected not found, got {:?}", other),
+    }
+    match transaction_changes(&api, "not a hash") {
+        Err(api::Error::BadRequest(_)) => {}
+        other => panic!("expected a bad request, got {:?}", other),
+    }
+}
+
+#[test]
+fn test_events() {
+    let (mut testkit, mock) = init_testkit();
+    mock.script_transactions(create_doors);
+    let (door_author, door_key) = crypto::gen_keypair();
+    let (other_author, other_key) = crypto::gen_keypair();
+    let door = CommitQueries::new("CREATE (n:Door)", "15-OCT", &door_author, &door_key);
+    let other = CommitQueries::new("CREATE (n:Wall)", "15-OCT", &other_author, &other_key);
+    let (door_hash, other_hash) = (door.hash(), other.hash());
+    testkit.create_block_with_transactions(txvec![door, other]);
+    testkit.create_block();
+    let api = testkit.api();
+
+    // Without a height only the height to poll from next is returned.
+    let page = events(&api, &events_query(None));
+    assert!(page.events.is_empty());
+    assert_eq!(page.next_height, 3);
+
+    let page = events(&api, &events_query(Some(1)));
+    assert_eq!(page.next_height, 3);
+    let statuses: Vec<(u64, Hash, TransactionStatus)> = page
+        .events
+        .iter()
+        .filter_map(|event| match *event {
+            Event::Status {
+                height,
+                tx_hash,
+                status,
+                ..
+            } => Some((height, tx_hash, status)),
+            _ => None,
+        })
+        .collect();
+    assert_eq!(
+        statuses,
+        vec![
+            (1, door_hash, TransactionStatus::Pending),
+            (1, other_hash, TransactionStatus::Pending),
+            (2, door_hash, TransactionStatus::ExecutedAwaitingAudit),
+            (2, door_hash, TransactionStatus::Success),
+            (2, other_hash, TransactionStatus::ExecutedAwaitingAudit),
+            (2, other_hash, TransactionStatus::Success),
+        ]
+    );
+    let changes = page
+        .events
+        .iter()
+        .filter(|event| match **event {
+            Event::Change { height, .. } => height == 2,
+            _ => false,
+        })
+        .count();
+    // Each transaction created a node, and the door got its label.
+    assert_eq!(changes, 3);
+
+    // Blocks before the first height are left out.
+    let page = events(&api, &events_query(Some(2)));
+    assert!(page.events.iter().all(|event| match *event {
+        Event::Status { height, .. } | Event::Change { height, .. } => height == 2,
+    }));
+
+    // Filtering by label or node only returns the audited changes and the statuses of the
+    // transactions that made them.
+    let page = events(
+        &api,
+        &EventsQuery {
+            label: Some(String::from("Door")),
+            ..events_query(Some(1))
+        },
+    );
+    assert!(!page.events.is_empty());
+    assert!(page
+        .events
+        .iter()
+        .all(|event| event_transaction(event) == door_hash));
+
+    let page = events(
+        &api,
+        &EventsQuery {
+            pub_key: Some(other_author),
+            ..events_query(Some(1))
+        },
+    );
+    assert_eq!(page.events.len(), 4);
+    assert!(page
+        .events
+        .iter()
+        .all(|event| event_transaction(event) == other_hash));
+
+    let page = events(
+        &api,
+        &EventsQuery {
+            node_uuid: Some(format!("{}_0", other_hash.to_hex())),
+            ..events_query(Some(1))
+        },
+    );
+    assert_eq!(page.events.len(), 3);
+    assert!(page
+        .events
+        .iter()
+        .all(|event| event_transaction(event) == other_hash));
+}
+
+#[test]
+fn test_events_block_range() {
+    let (mut testkit, _mock) = init_testkit();
+    testkit.create_blocks_until(Height(MAX_EVENT_BLOCKS + 10));
+    let api = testkit.api();
+
+    // A single request scans at most `MAX_EVENT_BLOCKS` blocks.
+    let page = events(&api, &events_query(Some(1)));
+    assert_eq!(page.next_height, MAX_EVENT_BLOCKS + 1);
+    let page = events(&api, &events_query(Some(page.next_height)));
+    assert_eq!(page.next_height, MAX_EVENT_BLOCKS + 11);
+
+    // Heights past the last block do not overflow the range.
+    let page = events(&api, &events_query(Some(u64::max_value())));
+    assert!(page.events.is_empty());
+    assert_eq!(page.next_height, u64::max_value());
+}
+
+#[test]
+fn test_label_and_property_indexes() {
+    let (mut testkit, mock) = init_testkit();
+    mock.script_transactions(door_changes);
+    let (pub_key, secret_key) = crypto::gen_keypair();
+    testkit.create_block_with_transactions(txvec![CommitQueries::new(
+        "CREATE (n:Door {state: 1, width: 1})",
+        "15-OCT",
+        &pub_key,
+        &secret_key
+    )]);
+    testkit.create_block();
+    {
+        let api = testkit.api();
+        assert_eq!(label_nodes(&api, "Door"), vec!["door"]);
+        assert_eq!(property_nodes(&api, "state"), vec!["door"]);
+        assert_eq!(property_nodes(&api, "width"), vec!["door"]);
+        assert!(property_nodes(&api, "color").is_empty());
+        let snapshot = testkit.snapshot();
+        assert!(Schema::new(&snapshot)
+            .node_labels("door")
+            .contains(&String::from("Door")));
+    }
+
+    testkit.create_block_with_transactions(txvec![CommitQueries::new(
+        "MATCH (n:Door) REMOVE n:Door REMOVE n.color",
+        "15-OCT",
+        &pub_key,
+        &secret_key
+    )]);
+    testkit.create_block();
+    let api = testkit.api();
+    // The indexes hold every node that ever carried the label or had the property changed.
+    assert_eq!(label_nodes(&api, "Door"), vec!["door"]);
+    assert_eq!(property_nodes(&api, "color"), vec!["door"]);
+    assert!(label_nodes(&api, "Wall").is_empty());
+    // Only the labels the node carries now are kept with the node.
+    let snapshot = testkit.snapshot();
+    assert_eq!(Schema::new(&snapshot).node_labels("door").iter().count(), 0);
+    assert_eq!(node_history(&api, "door").len(), 6);
+}
+
+#[test]
+fn test_transactions_pages() {
+    let (mut testkit, mock) = init_testkit();
+    mock.script_transactions(|t| {
+        if t.get_queries().iter().any(|q| q.contains("fail")) {
+            failed_transaction(t.get_transaction_id(), ErrorCode::FAILED_QUERY, "Invalid")
+        } else {
+            succeeded(t.get_transaction_id())
+        }
+    });
+    let (first_author, first_key) = crypto::gen_keypair();
+    let (second_author, second_key) = crypto::gen_keypair();
+    let commit = |queries: &str, first: bool| {
+        if first {
+            CommitQueries::new(queries, "15-OCT", &first_author, &first_key)
+        } else {
+            CommitQueries::new(queries, "15-OCT", &second_author, &second_key)
+        }
+    };
+    let block_one = vec![commit("CREATE (a)", true), commit("CREATE (b)", false)];
+    let block_two = vec![
+        commit("CREATE (c) fail", true),
+        commit("CREATE (d)", false),
+        commit("CREATE (e)", true),
+    ];
+    let mut committed: Vec<Hash> = Vec::new();
+    for block in vec![block_one, block_two] {
+        committed.extend(block.iter().map(|t| t.hash()));
+        testkit.create_block_with_transactions(
+            block
+                .into_iter()
+                .map(|t| Box::new(t) as Box<dyn Transaction>)
+                .collect::<Vec<_>>(),
+        );
+    }
+    testkit.create_block();
+    let api = testkit.api();
+
+    // Transactions are listed in commit order, `limit` at a time.
+    let first = transactions(&api, &transactions_query(None, Some(2)));
+    assert_eq!(first.total, 5);
+    assert_eq!(page_hashes(&first), &committed[..2]);
+    assert_eq!(first.next_cursor, Some(2));
+    assert_eq!(first.transactions[0].height, Some(1));
+    let second = transactions(&api, &transactions_query(first.next_cursor, Some(2)));
+    assert_eq!(page_hashes(&second), &committed[2..4]);
+    assert_eq!(second.next_cursor, Some(4));
+    assert_eq!(second.transactions[0].height, Some(2));
+    let last = transactions(&api, &transactions_query(second.next_cursor, Some(2)));
+    assert_eq!(page_hashes(&last), &committed[4..]);
+    assert_eq!(last.next_cursor, None);
+    // A page that ends exactly with the last transaction has no next one.
+    let whole = transactions(&api, &transactions_query(None, Some(5)));
+    assert_eq!(page_hashes(&whole), committed);
+    assert_eq!(whole.next_cursor, None);
+    let past_the_end = transactions(&api, &transactions_query(Some(5), None));
+    assert!(past_the_end.transactions.is_empty());
+    assert_eq!(past_the_end.next_cursor, None);
+
+    // The transactions of an author are paged through their own index.
+    let query = TransactionsQuery {
+        pub_key: Some(first_author),
+        ..transactions_query(None, Some(2))
+    };
+    let filtered = transactions(&api, &query);
+    assert_eq!(filtered.total, 3);
+    assert_eq!(page_hashes(&filtered), vec![committed[0], committed[2]]);
+    assert_eq!(filtered.next_cursor, Some(2));
+    let query = TransactionsQuery {
+        cursor: filtered.next_cursor,
+        ..query
+    };
+    let filtered = transactions(&api, &query);
+    assert_eq!(page_hashes(&filtered), vec![committed[4]]);
+    assert_eq!(filtered.next_cursor, None);
+
+    // Filters skip transactions without shortening the page, the cursor continues after the
+    // last transaction looked at.
+    let query = TransactionsQuery {
+        status: Some(String::from("error")),
+        ..transactions_query(None, None)
+    };
+    let failed = transactions(&api, &query);
+    assert_eq!(page_hashes(&failed), vec![committed[2]]);
+    assert!(failed.transactions[0].failure.is_some());
+    let query = TransactionsQuery {
+        status: Some(String::from("success")),
+        ..transactions_query(None, Some(1))
+    };
+    let succeeded = transactions(&api, &query);
+    assert_eq!(page_hashes(&succeeded), vec![committed[0]]);
+    assert_eq!(succeeded.next_cursor, Some(1));
+    let query = TransactionsQuery {
+        cursor: Some(2),
+        ..query
+    };
+    let succeeded = transactions(&api, &query);
+    assert_eq!(page_hashes(&succeeded), vec![committed[3]]);
+    assert_eq!(succeeded.next_cursor, Some(4));
+
+    let query = TransactionsQuery {
+        from_height: Some(2),
+        to_height: Some(2),
+        ..transactions_query(None, None)
+    };
+    assert_eq!(page_hashes(&transactions(&api, &query)), &committed[2..]);
+    let query = TransactionsQuery {
+        to_height: Some(1),
+        ..transactions_query(None, None)
+    };
+    assert_eq!(page_hashes(&transactions(&api, &query)), &committed[..2]);
+}
+
+fn author_transactions(
+    api: &TestKitApi,
+    pub_key: PublicKey,
+    offset: Option<u64>,
+    limit: Option<u64>,
+) -> TransactionsPage {
+    api.public(ApiKind::Service("neo4j_blockchain"))
+        .query(&AuthorTransactionsQuery {
+            pub_key,
+            offset,
+            limit,
+        })
+        .get("v1/author_transactions")
+        .unwrap()
+}
+
+#[test]
+fn test_author_transactions() {
+    let (mut testkit, mock) = init_testkit();
+    mock.script_transactions(|t| {
+        if t.get_queries().iter().any(|q| q.contains("fail")) {
+            failed_transaction(
+                t.get_transaction_id(),
+                ErrorCode::CONSTRAINT_VIOLATION,
+                "Node already exists",
+            )
+        } else {
+            succeeded(t.get_transaction_id())
+        }
+    });
+    let (officer, officer_key) = crypto::gen_keypair();
+    let (other, other_key) = crypto::gen_keypair();
+    let first = CommitQueries::new("CREATE (a)", "18-OCT", &officer, &officer_key);
+    let foreign = CommitQueries::new("CREATE (b)", "18-OCT", &other, &other_key);
+    let failing = CommitQueries::new("CREATE (c) fail", "18-OCT", &officer, &officer_key);
+    let last = CommitQueries::new("CREATE (d)", "18-OCT", &officer, &officer_key);
+    let officer_hashes = vec![first.hash(), failing.hash(), last.hash()];
+    testkit.create_block_with_transactions(txvec![first, foreign, failing]);
+    testkit.create_block_with_transactions(txvec![last]);
+    testkit.create_block();
+    let api = testkit.api();
+
+    // Only the author's submissions are listed, in commit order and with their results.
+    let page = author_transactions(&api, officer, None, None);
+    assert_eq!(page.total, 3);
+    assert_eq!(page_hashes(&page), officer_hashes);
+    assert_eq!(page.next_cursor, None);
+    let statuses: Vec<Option<TransactionStatus>> = page
+        .transactions
+        .iter()
+        .map(|entry| entry.transaction.status())
+        .collect();
+    assert_eq!(
+        statuses,
+        vec![
+            Some(TransactionStatus::Success),
+            Some(TransactionStatus::Error),
+            Some(TransactionStatus::Success),
+        ]
+    );
+    assert!(page.transactions[1].failure.is_some());
+    assert_eq!(page.transactions[2].height, Some(2));
+
+    let first_page = author_transactions(&api, officer, None, Some(2));
+    assert_eq!(page_hashes(&first_page), &officer_hashes[..2]);
+    assert_eq!(first_page.next_cursor, Some(2));
+    let second_page = author_transactions(&api, officer, first_page.next_cursor, Some(2));
+    assert_eq!(page_hashes(&second_page), &officer_hashes[2..]);
+    assert_eq!(second_page.next_cursor, None);
+
+    let unknown = author_transactions(&api, crypto::gen_keypair().0, None, None);
+    assert_eq!(unknown.total, 0);
+    assert!(unknown.transactions.is_empty());
+}
+
+#[test]
+fn test_transactions_skip_corrupt_entries() {
+    let (mut testkit, _mock) = init_testkit();
+    let (pub_key, secret_key) = crypto::gen_keypair();
+    let first = CommitQueries::new("CREATE (a)", "18-OCT", &pub_key, &secret_key);
+    let second = CommitQueries::new("CREATE (b)", "18-OCT", &pub_key, &secret_key);
+    let (first_hash, second_hash) = (first.hash(), second.hash());
+    testkit.create_block_with_transactions(txvec![first, second]);
+    {
+        let mut fork = testkit.blockchain_mut().fork();
+        ProofMapIndex::<_, Hash, Vec<u8>>::new("neo4j.queries", &mut fork)
+            .put(&first_hash, vec![0xff, 0x01]);
+        testkit.blockchain_mut().merge(fork.into_patch()).unwrap();
+    }
+
+    // The corrupt transaction is reported, the rest of the page is still served.
+    let page = transactions(&testkit.api(), &transactions_query(None, None));
+    assert_eq!(page_hashes(&page), vec![second_hash]);
+    assert_eq!(page.corrupt, vec![first_hash]);
+    assert_eq!(page.next_cursor, None);
+}
+
+#[test]
+fn test_submission_errors() {
+    let config = ServiceConfig {
+        limits: LimitsConfig {
+            max_query_bytes: 40,
+            max_transactions: 1,
+            ..LimitsConfig::default()
+        },
+        ..ServiceConfig::default()
+    };
+    let (mut testkit, mock) = init_testkit_with_config(config);
+    let api = testkit.api();
+    let (pub_key, secret_key) = crypto::gen_keypair();
+
+    let oversized = CommitQueries::new(&"CREATE (n) ".repeat(10), "15-OCT", &pub_key, &secret_key);
+    let body = bad_request(submit(&api, &oversized));
+    assert_eq!((body.code.as_str(), body.status), ("LIMIT_EXCEEDED", 413));
+
+    let first = CommitQueries::new("CREATE (n)", "15-OCT", &pub_key, &secret_key);
+    assert_eq!(submit(&api, &first).unwrap().tx_hash, first.hash());
+    testkit.create_block();
+    let second = CommitQueries::new("CREATE (m)", "15-OCT", &pub_key, &secret_key);
+    let body = bad_request(submit(&api, &second));
+    assert_eq!((body.code.as_str(), body.status), ("RATE_LIMITED", 429));
+
+    // Outages are not the client's fault, exonum sends them as internal errors.
+    mock.fail(Method::Health, "Neo4j is down");
+    let (other_key, other_secret_key) = crypto::gen_keypair();
+    let third = CommitQueries::new("CREATE (n)", "15-OCT", &other_key, &other_secret_key);
+    match submit(&api, &third) {
+        Err(api::Error::InternalError(e)) => {
+            let message = e.to_string();
+            assert!(message.contains("NEO4J_UNAVAILABLE"), "{}", message);
+            assert!(message.contains("503"), "{}", message);
+        }
+        other => panic!("expected an internal error, got {:?}", other),
+    }
+    mock.recover();
+    assert!(submit(&api, &third).is_ok());
+}
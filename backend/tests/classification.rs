@@ -0,0 +1,133 @@
+extern crate exonum;
+extern crate exonum_neo4j;
+#[macro_use]
+extern crate exonum_testkit;
+
+use exonum::crypto;
+use exonum::messages::Message;
+use exonum_neo4j::cypher::{check_classes, classify_queries};
+use exonum_neo4j::schema::Schema;
+use exonum_neo4j::structures::{FailureCode, StatementClass, TransactionStatus};
+use exonum_neo4j::transactions::{CommitQueries, Error};
+
+pub mod support;
+
+#[test]
+fn test_classify_statements() {
+    let classification = classify_queries(
+        "MATCH (n:Person) RETURN n.name; \
+         MATCH (n {uuid: 't1_0'}) SET n.name = 'Door'; \
+         create index ON :Person(name); \
+         CALL db.labels(); \
+         MATCH (n) RETURN n {.set, create: 1};",
+    )
+    .unwrap();
+    assert_eq!(
+        classification.classes(),
+        &[
+            StatementClass::ReadOnly,
+            StatementClass::DataWrite,
+            StatementClass::SchemaWrite,
+            StatementClass::DataWrite,
+            StatementClass::ReadOnly,
+        ]
+    );
+
+    // Statements without a known clause are left to Neo4j.
+    assert_eq!(
+        classify_queries("INSERT something").unwrap().classes(),
+        &[StatementClass::DataWrite]
+    );
+    assert!(classify_queries("MATCH (n {name: 'open}) RETURN n").is_err());
+
+    // Explained statements are planned but never run.
+    assert_eq!(
+        classify_queries("EXPLAIN CREATE (n:Person); explain MATCH (n) SET n.name = 'Door'")
+            .unwrap()
+            .classes(),
+        &[StatementClass::ReadOnly, StatementClass::ReadOnly]
+    );
+}
+
+#[test]
+fn test_check_classes() {
+    let check = |queries: &str| check_classes(&classify_queries(queries).unwrap());
+    assert_eq!(check("MATCH (n) RETURN n; CREATE (m)"), Ok(()));
+    assert_eq!(
+        check("MATCH (n) RETURN n").unwrap_err().0,
+        FailureCode::ReadOnlyQuery
+    );
+    assert_eq!(
+        check("CREATE (n:Person); CREATE INDEX ON :Person(name)")
+            .unwrap_err()
+            .0,
+        FailureCode::SchemaInQueries
+    );
+}
+
+#[test]
+fn test_classification_is_stored() {
+    let (mut testkit, _mock) = support::init_testkit();
+    let (pub_key, secret_key) = crypto::gen_keypair();
+    let write = CommitQueries::new(
+        "MATCH (n) RETURN n; CREATE (m)",
+        "15-OCT",
+        &pub_key,
+        &secret_key,
+    );
+    let read = CommitQueries::new("MATCH (n) RETURN n", "15-OCT", &pub_key, &secret_key);
+    let invalid = CommitQueries::new("CREATE (n {name: 'open})", "15-OCT", &pub_key, &secret_key);
+    let (write_hash, read_hash, invalid_hash) = (write.hash(), read.hash(), invalid.hash());
+    testkit.create_block_with_transactions(txvec![write, read, invalid]);
+
+    let snapshot = testkit.snapshot();
+    let schema = Schema::new(&snapshot);
+    assert_eq!(
+        schema.neo4j_transaction(&write_hash).unwrap().status(),
+        Some(TransactionStatus::Pending)
+    );
+    assert_eq!(
+        schema
+            .statement_classes()
+            .get(&write_hash)
+            .unwrap()
+            .classes(),
+        &[StatementClass::ReadOnly, StatementClass::DataWrite]
+    );
+
+    assert_eq!(
+        schema.neo4j_transaction(&read_hash).unwrap().status(),
+        Some(TransactionStatus::Rejected)
+    );
+    assert_eq!(
+        schema
+            .transaction_failures()
+            .get(&read_hash)
+            .unwrap()
+            .failure_code(),
+        Some(FailureCode::ReadOnlyQuery)
+    );
+    assert!(schema.statement_classes().contains(&read_hash));
+
+    // Queries that cannot be tokenized are invalid, whatever functions they call.
+    assert_eq!(
+        schema
+            .transaction_failures()
+            .get(&invalid_hash)
+            .unwrap()
+            .failure_code(),
+        Some(FailureCode::InvalidQuery)
+    );
+    assert!(!schema.statement_classes().contains(&invalid_hash));
+}
+
+#[test]
+fn test_errors_from_failures() {
+    let error = |code| Error::from_failure((code, String::from("description")));
+    assert_eq!(error(FailureCode::InvalidQuery).code(), 25);
+    assert_eq!(error(FailureCode::ReadOnlyQuery).code(), 21);
+    assert_eq!(error(FailureCode::RateLimited).code(), 19);
+    assert_eq!(error(FailureCode::PreconditionFailed).code(), 27);
+    assert_eq!(error(FailureCode::NotAuthorized).code(), 5);
+    assert_eq!(error(FailureCode::FailedQuery).code(), 0);
+}
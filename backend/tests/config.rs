@@ -0,0 +1,68 @@
+extern crate exonum;
+extern crate exonum_neo4j;
+#[macro_use]
+extern crate exonum_testkit;
+#[macro_use]
+extern crate serde_json;
+
+use exonum::crypto;
+use exonum::messages::Message;
+use exonum_neo4j::config::{Author, Role, ServiceConfig};
+use exonum_neo4j::schema::Schema;
+use exonum_neo4j::structures::{FailureCode, TransactionStatus};
+use exonum_neo4j::transactions::CommitQueries;
+
+pub mod support;
+
+#[test]
+fn test_config_json() {
+    let (pub_key, _) = crypto::gen_keypair();
+    let config: ServiceConfig = serde_json::from_value(json!({
+        "authors": [{ "pub_key": pub_key, "roles": ["writer", "schema-manager"] }]
+    }))
+    .unwrap();
+    assert!(config.is_authorized(&pub_key, Role::Writer));
+    assert!(config.is_authorized(&pub_key, Role::SchemaManager));
+    assert!(!config.is_authorized(&pub_key, Role::Admin));
+    assert!(!config.is_authorized(&crypto::gen_keypair().0, Role::Writer));
+
+    assert!(ServiceConfig::default().is_authorized(&pub_key, Role::Admin));
+}
+
+#[test]
+fn test_reject_unauthorized_author() {
+    let (writer, writer_key) = crypto::gen_keypair();
+    let (stranger, stranger_key) = crypto::gen_keypair();
+    let (mut testkit, _mock) = support::init_testkit_with_config(ServiceConfig {
+        authors: Some(vec![Author {
+            pub_key: writer,
+            roles: vec![Role::Writer],
+        }]),
+        ..ServiceConfig::default()
+    });
+
+    let allowed = CommitQueries::new("CREATE (n)", "15-OCT", &writer, &writer_key);
+    let refused = CommitQueries::new("CREATE (n)", "15-OCT", &stranger, &stranger_key);
+    let (allowed_hash, refused_hash) = (allowed.hash(), refused.hash());
+    testkit.create_block_with_transactions(txvec![allowed, refused]);
+
+    let snapshot = testkit.snapshot();
+    let schema = Schema::new(&snapshot);
+    assert_eq!(
+        schema.neo4j_transaction(&allowed_hash).unwrap().status(),
+        Some(TransactionStatus::Pending)
+    );
+    assert_eq!(
+        schema.neo4j_transaction(&refused_hash).unwrap().status(),
+        Some(TransactionStatus::Rejected)
+    );
+    assert_eq!(
+        schema
+            .transaction_failures()
+            .get(&refused_hash)
+            .unwrap()
+            .failure_code(),
+        Some(FailureCode::NotAuthorized)
+    );
+    assert!(schema.transaction_failures().get(&allowed_hash).is_none());
+}
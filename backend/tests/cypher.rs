@@ -0,0 +1,176 @@
+extern crate exonum_neo4j;
+extern crate serde_json;
+
+use exonum_neo4j::config::DenyList;
+use exonum_neo4j::cypher::{
+    calls, check_denied, classify_queries, split_statements, tokenize, TokenKind,
+};
+use exonum_neo4j::structures::StatementClass;
+
+#[test]
+fn test_tokenize() {
+    let tokens =
+        tokenize("MATCH (n:`Odd ``name`) // comment\nWHERE n.x = 'a;b\\'c' /* x */ RETURN $p, 1.5")
+            .unwrap();
+    let texts: Vec<&str> = tokens.iter().map(|t| t.text).collect();
+    assert_eq!(
+        texts,
+        vec![
+            "MATCH",
+            "(",
+            "n",
+            ":",
+            "Odd ``name",
+            ")",
+            "WHERE",
+            "n",
+            ".",
+            "x",
+            "=",
+            "a;b\\'c",
+            "RETURN",
+            "$p",
+            ",",
+            "1.5",
+        ]
+    );
+    assert_eq!(tokens[11].kind, TokenKind::String);
+    assert_eq!(tokens[13].kind, TokenKind::Parameter);
+    assert_eq!(tokens[15].kind, TokenKind::Number);
+    assert_eq!(tokens[6].offset, 34);
+
+    let error = tokenize("CREATE (n {name: 'open})").unwrap_err();
+    assert_eq!(error.offset, 17);
+}
+
+#[test]
+fn test_calls() {
+    let tokens =
+        tokenize("CALL db.labels() YIELD label CALL apoc.help RETURN toUpper(label)").unwrap();
+    let found: Vec<(String, bool, Option<bool>)> = calls(&tokens)
+        .into_iter()
+        .map(|c| (c.name, c.procedure, c.has_arguments))
+        .collect();
+    assert_eq!(
+        found,
+        vec![
+            (String::from("db.labels"), true, Some(false)),
+            (String::from("apoc.help"), true, None),
+            (String::from("toUpper"), false, Some(true)),
+        ]
+    );
+
+    // Labels and properties are not calls.
+    let tokens = tokenize("CREATE INDEX ON :Person(name)").unwrap();
+    assert!(calls(&tokens).iter().all(|c| c.name != "Person"));
+}
+
+#[test]
+fn test_deny_list() {
+    let deny_list = DenyList::default();
+    assert_eq!(
+        check_denied(&deny_list, "CREATE (n {at: datetime({epochMillis: 1})})"),
+        Ok(())
+    );
+    assert_eq!(
+        check_denied(&deny_list, "CREATE (n {name: 'rand()'})"),
+        Ok(())
+    );
+
+    let error = check_denied(&deny_list, "CREATE (n); CREATE (m {at: datetime()})").unwrap_err();
+    assert_eq!(error.statement, 1);
+    assert_eq!(error.offset, 16);
+    assert_eq!(error.token, "datetime");
+
+    let error = check_denied(&deny_list, "MATCH (n) SET n.r = RAND()").unwrap_err();
+    assert_eq!(error.token, "RAND");
+    let error = check_denied(&deny_list, "CALL apoc.periodic.iterate('a', 'b', {})").unwrap_err();
+    assert_eq!(error.token, "apoc.periodic.iterate");
+    let error = check_denied(&deny_list, "RETURN apoc.create.uuid()").unwrap_err();
+    assert_eq!(error.token, "apoc.create.uuid");
+
+    for type_name in &["date", "datetime", "localdatetime", "time", "localtime"] {
+        for clock in &["realtime", "statement", "transaction"] {
+            let function = format!("{}.{}", type_name, clock);
+            for call in &[
+                format!("RETURN {}()", function),
+                format!("CREATE (n {{at: {}('Europe/Berlin')}})", function),
+            ] {
+                let error = check_denied(&deny_list, call).unwrap_err();
+                assert_eq!(error.token, function);
+            }
+        }
+    }
+    assert_eq!(
+        check_denied(&deny_list, "CREATE (n {at: datetime.fromepoch(1, 0)})"),
+        Ok(())
+    );
+
+    let allow_all = DenyList {
+        functions: Vec::new(),
+        procedures: Vec::new(),
+        clauses: Vec::new(),
+    };
+    assert_eq!(check_denied(&allow_all, "RETURN rand()"), Ok(()));
+    assert_eq!(
+        check_denied(
+            &allow_all,
+            "LOAD CSV FROM 'file:///a.csv' AS row CREATE (n)"
+        ),
+        Ok(())
+    );
+}
+
+#[test]
+fn test_deny_clauses() {
+    let deny_list = DenyList::default();
+    let error = check_denied(
+        &deny_list,
+        "CREATE (n);load  csv WITH HEADERS FROM 'https://a/b.csv' AS row CREATE (m)",
+    )
+    .unwrap_err();
+    assert_eq!((error.statement, error.offset), (1, 0));
+    assert_eq!(error.token, "LOAD CSV");
+
+    // Keywords used as names, or inside strings, are not the clause.
+    assert_eq!(
+        check_denied(
+            &deny_list,
+            "CREATE (n:Load {csv: 'LOAD CSV'}) SET n.load = 1"
+        ),
+        Ok(())
+    );
+    assert_eq!(
+        check_denied(&deny_list, "MATCH (n) SET n:LOAD, n.csv = 1"),
+        Ok(())
+    );
+
+    // Configurations written before clauses were denied still deny the default ones.
+    let legacy: DenyList = serde_json::from_str(r#"{"functions": [], "procedures": []}"#).unwrap();
+    assert_eq!(legacy.clauses, vec![String::from("LOAD CSV")]);
+}
+
+#[test]
+fn test_split_statements() {
+    let queries = "CREATE (n {s: 'a;b'});CREATE (`m;`) // c;\n;/* ; */RETURN 1;";
+    assert_eq!(
+        split_statements(queries).unwrap(),
+        vec![
+            "CREATE (n {s: 'a;b'})",
+            "CREATE (`m;`) // c;\n",
+            "/* ; */RETURN 1",
+            "",
+        ]
+    );
+
+    // Separators in strings neither split the statement for the deny-list nor for the classes.
+    let deny_list = DenyList::default();
+    let error = check_denied(&deny_list, "CREATE (n {s: ';'}) SET n.r = rand()").unwrap_err();
+    assert_eq!(error.statement, 0);
+    let classes = classify_queries("CREATE (n {s: 'x; MATCH (m) RETURN m'})").unwrap();
+    assert_eq!(classes.classes(), &[StatementClass::DataWrite][..]);
+
+    // Errors point into the statement that cannot be tokenized.
+    let error = split_statements("CREATE (n); CREATE (m {s: 'open})").unwrap_err();
+    assert_eq!((error.statement, error.offset), (1, 15));
+}
@@ -0,0 +1,48 @@
+extern crate exonum_neo4j;
+#[macro_use]
+extern crate serde_json;
+
+use exonum_neo4j::api::FailureDetails;
+use exonum_neo4j::neo4j::proto::transaction_manager::{Error, ErrorCode, FailedQuery};
+use exonum_neo4j::structures::{FailureCode, TransactionFailure};
+
+#[test]
+fn test_failed_query_details() {
+    let mut failed_query = FailedQuery::new();
+    failed_query.set_query(String::from("CREAT (n)"));
+    failed_query.set_error(String::from("Invalid input"));
+    failed_query.set_error_code(String::from("Neo.ClientError.Statement.SyntaxError"));
+    failed_query.set_statement_index(1);
+    let mut error = Error::new();
+    error.set_message(String::from("Query failed"));
+    error.set_code(ErrorCode::FAILED_QUERY);
+    error.set_failed_query(failed_query);
+
+    let failure = TransactionFailure::from_proto(&error);
+    assert_eq!(failure.failure_code(), Some(FailureCode::FailedQuery));
+    assert_eq!(failure.statement_index(), 1);
+
+    let value = serde_json::to_value(&FailureDetails::from(failure)).unwrap();
+    assert_eq!(
+        value,
+        json!({
+            "code": "FAILED_QUERY",
+            "statement_index": 1,
+            "query": "CREAT (n)",
+            "query_error": "Invalid input",
+            "neo4j_code": "Neo.ClientError.Statement.SyntaxError"
+        })
+    );
+}
+
+#[test]
+fn test_failure_without_query() {
+    let mut error = Error::new();
+    error.set_message(String::from("Constraint violated"));
+    error.set_code(ErrorCode::CONSTRAINT_VIOLATION);
+
+    let details = FailureDetails::from(TransactionFailure::from_proto(&error));
+    assert_eq!(details.code, Some(FailureCode::ConstraintViolation));
+    assert_eq!(details.statement_index, None);
+    assert_eq!(details.query, None);
+}
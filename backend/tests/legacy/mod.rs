@@ -0,0 +1,51 @@
+//! Values in the `encoding_struct!` layout used before the migration to protobuf.
+//!
+//! The layout starts with a header holding, for each string field, the offset and length of its
+//! segment as little-endian `u32`, and fixed-size fields in place. The segments follow the header.
+
+// Every test crate includes the module, but none of them uses all of it.
+#![allow(dead_code)]
+
+use exonum::crypto::{PublicKey, PUBLIC_KEY_LENGTH};
+
+pub fn le_bytes(value: u32) -> [u8; 4] {
+    [
+        value as u8,
+        (value >> 8) as u8,
+        (value >> 16) as u8,
+        (value >> 24) as u8,
+    ]
+}
+
+/// Encodes string fields in the legacy layout.
+pub fn legacy_bytes(fields: &[&str]) -> Vec<u8> {
+    let header_size = fields.len() * 8;
+    let mut header = Vec::new();
+    let mut body = Vec::new();
+    for field in fields {
+        let offset = (header_size + body.len()) as u32;
+        header.extend_from_slice(&le_bytes(offset));
+        header.extend_from_slice(&le_bytes(field.len() as u32));
+        body.extend_from_slice(field.as_bytes());
+    }
+    header.extend(body);
+    header
+}
+
+/// Encodes a neo4j transaction in the legacy layout, where the public key follows the segments
+/// of the strings.
+pub fn legacy_transaction(queries: &str, result: &str, pub_key: &PublicKey) -> Vec<u8> {
+    let fields = [queries, "", result];
+    let header_size = fields.len() * 8 + PUBLIC_KEY_LENGTH;
+    let mut header = Vec::new();
+    let mut body = Vec::new();
+    for field in &fields {
+        let offset = (header_size + body.len()) as u32;
+        header.extend_from_slice(&le_bytes(offset));
+        header.extend_from_slice(&le_bytes(field.len() as u32));
+        body.extend_from_slice(field.as_bytes());
+    }
+    header.extend_from_slice(pub_key.as_ref());
+    header.extend(body);
+    header
+}
@@ -0,0 +1,209 @@
+extern crate exonum;
+extern crate exonum_neo4j;
+#[macro_use]
+extern crate exonum_testkit;
+
+use exonum::crypto;
+use exonum::helpers::Height;
+use exonum::messages::Message;
+use exonum_neo4j::config::{LimitsConfig, ServiceConfig};
+use exonum_neo4j::limits::{
+    check_size, statement_count, stored_queries, within_hard_caps, MAX_QUERY_BYTES,
+    STORED_QUERY_BYTES,
+};
+use exonum_neo4j::schema::Schema;
+use exonum_neo4j::structures::{FailureCode, TransactionStatus};
+use exonum_neo4j::transactions::{CommitQueries, ProposeQueries, ScheduleQueries};
+
+pub mod support;
+
+fn limits(max_statements: u32, max_query_bytes: u64) -> LimitsConfig {
+    LimitsConfig {
+        max_statements,
+        max_query_bytes,
+        ..LimitsConfig::default()
+    }
+}
+
+#[test]
+fn test_size_limits() {
+    assert_eq!(statement_count("CREATE (n); CREATE (m);"), 2);
+    assert_eq!(
+        check_size(&limits(2, 100), "CREATE (n); CREATE (m)"),
+        Ok(())
+    );
+    assert_eq!(
+        check_size(&limits(1, 100), "CREATE (n); CREATE (m)")
+            .unwrap_err()
+            .0,
+        FailureCode::TooManyStatements
+    );
+    assert_eq!(
+        check_size(&limits(2, 10), "CREATE (n); CREATE (m)")
+            .unwrap_err()
+            .0,
+        FailureCode::QueryTooLarge
+    );
+    // Zero disables a limit.
+    assert_eq!(check_size(&limits(0, 0), "CREATE (n); CREATE (m)"), Ok(()));
+
+    assert!(within_hard_caps("CREATE (n)"));
+    assert!(!within_hard_caps(&"x".repeat(MAX_QUERY_BYTES + 1)));
+}
+
+#[test]
+fn test_rate_limit() {
+    let (mut testkit, _mock) = support::init_testkit_with_config(ServiceConfig {
+        limits: LimitsConfig {
+            max_transactions: 2,
+            window: 3,
+            ..LimitsConfig::default()
+        },
+        ..ServiceConfig::default()
+    });
+
+    let (pub_key, secret_key) = crypto::gen_keypair();
+    let commit = |datetime: &str| CommitQueries::new("CREATE (n)", datetime, &pub_key, &secret_key);
+    let transactions = vec![commit("1"), commit("2"), commit("3")];
+    let hashes: Vec<_> = transactions.iter().map(|tx| tx.hash()).collect();
+    testkit.create_block_with_transactions(txvec![
+        transactions[0].clone(),
+        transactions[1].clone(),
+        transactions[2].clone(),
+    ]);
+    {
+        let snapshot = testkit.snapshot();
+        let schema = Schema::new(&snapshot);
+        let rejected: Vec<_> = hashes
+            .iter()
+            .filter(|hash| {
+                schema.neo4j_transaction(hash).unwrap().status()
+                    == Some(TransactionStatus::Rejected)
+            })
+            .collect();
+        assert_eq!(rejected.len(), 1);
+        assert_eq!(
+            schema
+                .transaction_failures()
+                .get(rejected[0])
+                .unwrap()
+                .failure_code(),
+            Some(FailureCode::RateLimited)
+        );
+    }
+
+    // Once the window passed the author may submit again.
+    testkit.create_blocks_until(Height(5));
+    let fourth = commit("4");
+    let fourth_hash = fourth.hash();
+    testkit.create_block_with_transactions(txvec![fourth]);
+    let snapshot = testkit.snapshot();
+    let schema = Schema::new(&snapshot);
+    assert_eq!(
+        schema.neo4j_transaction(&fourth_hash).unwrap().status(),
+        Some(TransactionStatus::Pending)
+    );
+}
+
+#[test]
+fn test_oversized_rejections_are_stored_truncated() {
+    let long = "CREATE (n:Door {name: 'é'}); ".repeat(100);
+    let stored = stored_queries(FailureCode::QueryTooLarge, &long);
+    assert!(stored.len() < STORED_QUERY_BYTES + 100);
+    assert!(stored.ends_with(&format!(
+        "... ({} bytes, hash {})",
+        long.len(),
+        crypto::hash(long.as_bytes()).to_hex()
+    )));
+    assert_eq!(
+        stored_queries(FailureCode::QueryTooLarge, "CREATE (n)"),
+        "CREATE (n)"
+    );
+    assert_eq!(stored_queries(FailureCode::RateLimited, &long), long);
+
+    let (mut testkit, _mock) = support::init_testkit_with_config(ServiceConfig {
+        limits: limits(0, 100),
+        ..ServiceConfig::default()
+    });
+    let (pub_key, secret_key) = crypto::gen_keypair();
+    let commit = CommitQueries::new(&long, "15-OCT", &pub_key, &secret_key);
+    let hash = commit.hash();
+    testkit.create_block_with_transactions(txvec![commit]);
+    let snapshot = testkit.snapshot();
+    let transaction = Schema::new(&snapshot).neo4j_transaction(&hash).unwrap();
+    assert_eq!(transaction.status(), Some(TransactionStatus::Rejected));
+    assert_eq!(transaction.queries(), stored);
+}
+
+#[test]
+fn test_rejections_do_not_extend_the_lockout() {
+    let (mut testkit, _mock) = support::init_testkit_with_config(ServiceConfig {
+        limits: LimitsConfig {
+            max_transactions: 1,
+            window: 3,
+            ..LimitsConfig::default()
+        },
+        ..ServiceConfig::default()
+    });
+    let (pub_key, secret_key) = crypto::gen_keypair();
+    let commit = |datetime: &str| CommitQueries::new("CREATE (n)", datetime, &pub_key, &secret_key);
+
+    let mut statuses = Vec::new();
+    for datetime in &["1", "2", "3", "4"] {
+        let tx = commit(datetime);
+        let hash = tx.hash();
+        testkit.create_block_with_transactions(txvec![tx]);
+        let snapshot = testkit.snapshot();
+        statuses.push(
+            Schema::new(&snapshot)
+                .neo4j_transaction(&hash)
+                .unwrap()
+                .status(),
+        );
+    }
+    // The retries in between were refused, only the first transaction counts towards the window.
+    assert_eq!(
+        statuses,
+        vec![
+            Some(TransactionStatus::Pending),
+            Some(TransactionStatus::Rejected),
+            Some(TransactionStatus::Rejected),
+            Some(TransactionStatus::Pending),
+        ]
+    );
+}
+
+#[test]
+fn test_proposals_and_schedules_are_charged() {
+    let (mut testkit, _mock) = support::init_testkit_with_config(ServiceConfig {
+        limits: LimitsConfig {
+            max_transactions: 2,
+            window: 10,
+            ..LimitsConfig::default()
+        },
+        ..ServiceConfig::default()
+    });
+    let (pub_key, secret_key) = crypto::gen_keypair();
+    testkit.create_block_with_transactions(txvec![
+        ProposeQueries::new("CREATE (n)", "1", &pub_key, &secret_key),
+        ScheduleQueries::new("CREATE (n)", "2", 100, 0, &pub_key, &secret_key),
+    ]);
+    let commit = CommitQueries::new("CREATE (n)", "3", &pub_key, &secret_key);
+    let hash = commit.hash();
+    testkit.create_block_with_transactions(txvec![commit]);
+
+    let snapshot = testkit.snapshot();
+    let schema = Schema::new(&snapshot);
+    assert_eq!(
+        schema.neo4j_transaction(&hash).unwrap().status(),
+        Some(TransactionStatus::Rejected)
+    );
+    assert_eq!(
+        schema
+            .transaction_failures()
+            .get(&hash)
+            .unwrap()
+            .failure_code(),
+        Some(FailureCode::RateLimited)
+    );
+}
@@ -0,0 +1,322 @@
+extern crate exonum_neo4j;
+extern crate protobuf;
+
+use exonum_neo4j::neo4j::memory::{MemoryBackend, Value};
+use exonum_neo4j::neo4j::{
+    BlockExecuteRequest, ErrorCode, GraphBackend, SchemaChangeRequest, Status, TransactionRequest,
+    TransactionResponse,
+};
+use protobuf::RepeatedField;
+
+fn execute(backend: &MemoryBackend, block_id: &str, transactions: &[(&str, &[&str])]) {
+    let mut request = BlockExecuteRequest::new();
+    request.set_block_id(block_id.to_string());
+    for (transaction_id, queries) in transactions {
+        let mut transaction = TransactionRequest::new();
+        transaction.set_transaction_id(transaction_id.to_string());
+        transaction.set_queries(RepeatedField::from_vec(
+            queries.iter().map(|q| q.to_string()).collect(),
+        ));
+        request.mut_transactions().push(transaction);
+    }
+    backend.execute_block(request).unwrap();
+}
+
+fn changes(backend: &MemoryBackend, block_id: &str) -> Vec<TransactionResponse> {
+    backend
+        .retrieve_block_changes(block_id)
+        .unwrap()
+        .get_transactions()
+        .to_vec()
+}
+
+#[test]
+fn test_created_entities_get_uuids() {
+    let backend = MemoryBackend::new();
+    execute(
+        &backend,
+        "b1",
+        &[(
+            "t1",
+            &["CREATE (a:Person {name: 'Ann'})-[:KNOWS {since: 2018}]->(b:Person {name: 'Bob'})"],
+        )],
+    );
+    let transactions = changes(&backend, "b1");
+    assert_eq!(transactions.len(), 1);
+    assert_eq!(transactions[0].get_result(), Status::SUCCESS);
+
+    let modifications = transactions[0].get_modifications();
+    assert!(modifications.get_records_previous_values());
+    let nodes: Vec<&str> = modifications
+        .get_created_nodes()
+        .iter()
+        .map(|n| n.get_node_UUID())
+        .collect();
+    assert_eq!(nodes, vec!["t1_0", "t1_1"]);
+    let relationship = &modifications.get_created_relationships()[0];
+    assert_eq!(relationship.get_relationship_UUID(), "t1_2");
+    assert_eq!(relationship.get_field_type(), "KNOWS");
+    assert_eq!(relationship.get_start_node_UUID(), "t1_0");
+    assert_eq!(relationship.get_end_node_UUID(), "t1_1");
+    assert_eq!(modifications.get_assigned_labels().len(), 2);
+    assert_eq!(
+        modifications.get_assigned_relationship_properties()[1].get_value(),
+        "t1_2"
+    );
+    assert_eq!(backend.label_nodes("Person"), vec!["t1_0", "t1_1"]);
+}
+
+#[test]
+fn test_property_changes_carry_previous_values() {
+    let backend = MemoryBackend::new();
+    execute(
+        &backend,
+        "b1",
+        &[
+            ("t1", &["CREATE (n:Door {state: 'open', width: 1.0})"]),
+            (
+                "t2",
+                &["MATCH (n:Door) WHERE n.state = 'open' SET n.state = 'closed' REMOVE n.width"],
+            ),
+        ],
+    );
+    let transactions = changes(&backend, "b1");
+    let modifications = transactions[1].get_modifications();
+    let assigned = &modifications.get_assigned_node_properties()[0];
+    assert_eq!(assigned.get_node_UUID(), "t1_0");
+    assert_eq!(assigned.get_key(), "state");
+    assert_eq!(assigned.get_value(), "closed");
+    assert!(assigned.get_has_previous_value());
+    assert_eq!(assigned.get_previous_value(), "open");
+    assert_eq!(assigned.get_previous_value_type(), "STRING");
+    let removed = &modifications.get_removed_node_properties()[0];
+    assert_eq!(removed.get_key(), "width");
+    assert_eq!(removed.get_previous_value(), "1.0");
+    assert_eq!(removed.get_previous_value_type(), "FLOAT");
+    assert_eq!(
+        backend.node_property("t1_0", "state"),
+        Some(Value::String(String::from("closed")))
+    );
+    assert_eq!(backend.node_property("t1_0", "width"), None);
+}
+
+#[test]
+fn test_failed_transactions_leave_the_graph_unchanged() {
+    let backend = MemoryBackend::new();
+    execute(
+        &backend,
+        "b1",
+        &[
+            ("t1", &["CREATE (n:Person)", "MATCH (n)"]),
+            (
+                "t2",
+                &["CREATE (n:Person)", "MATCH (n) SET n.uuid = 'mine'"],
+            ),
+            (
+                "t3",
+                &[
+                    "CREATE (a:Person)-[:KNOWS]->(b)",
+                    "MATCH (a:Person) DELETE a",
+                ],
+            ),
+        ],
+    );
+    let transactions = changes(&backend, "b1");
+    let failed_query = transactions[0].get_error();
+    assert_eq!(transactions[0].get_result(), Status::FAILURE);
+    assert_eq!(failed_query.get_code(), ErrorCode::FAILED_QUERY);
+    assert_eq!(failed_query.get_failed_query().get_statement_index(), 1);
+    assert_eq!(
+        failed_query.get_failed_query().get_error_code(),
+        "Neo.ClientError.Statement.SyntaxError"
+    );
+    assert_eq!(
+        transactions[1].get_error().get_code(),
+        ErrorCode::MODIFIED_UUID
+    );
+    assert_eq!(
+        transactions[2].get_error().get_code(),
+        ErrorCode::CONSTRAINT_VIOLATION
+    );
+    assert!(backend.label_nodes("Person").is_empty());
+}
+
+#[test]
+fn test_block_changes() {
+    let backend = MemoryBackend::new();
+    let mut request = SchemaChangeRequest::new();
+    request.set_block_id(String::from("b1"));
+    request.set_transaction_id(String::from("t1"));
+    request.set_statement(String::from("CREATE INDEX ON :Person(name)"));
+    backend.execute_schema_change(request.clone()).unwrap();
+    backend.execute_schema_change(request).unwrap();
+
+    let transactions = changes(&backend, "b1");
+    assert_eq!(transactions[0].get_result(), Status::SUCCESS);
+    assert_eq!(
+        transactions[1].get_error().get_code(),
+        ErrorCode::FAILED_QUERY
+    );
+
+    assert!(backend.execute_block(BlockExecuteRequest::new()).is_err());
+    assert_eq!(backend.delete_block_changes("b1"), Ok(true));
+    assert_eq!(backend.delete_block_changes("b1"), Ok(false));
+    assert!(backend.retrieve_block_changes("b1").is_err());
+    assert_eq!(backend.health(), Ok(()));
+}
+
+#[test]
+fn test_merge_on_create_and_on_match() {
+    let backend = MemoryBackend::new();
+    let merge =
+        "MERGE (n:Person {name: 'Ann'}) ON CREATE SET n.visits = 1 ON MATCH SET n.visits = 2";
+    execute(&backend, "b1", &[("t1", &[merge]), ("t2", &[merge])]);
+    let transactions = changes(&backend, "b1");
+
+    let created = transactions[0].get_modifications();
+    assert_eq!(created.get_created_nodes()[0].get_node_UUID(), "t1_0");
+    let matched = transactions[1].get_modifications();
+    assert!(matched.get_created_nodes().is_empty());
+    let assigned = &matched.get_assigned_node_properties()[0];
+    assert_eq!(assigned.get_node_UUID(), "t1_0");
+    assert_eq!(assigned.get_key(), "visits");
+    assert_eq!(assigned.get_value(), "2");
+    assert_eq!(assigned.get_previous_value(), "1");
+    assert_eq!(backend.label_nodes("Person"), vec!["t1_0"]);
+    assert_eq!(
+        backend.node_property("t1_0", "visits"),
+        Some(Value::Integer(2))
+    );
+}
+
+#[test]
+fn test_where_filters_matched_nodes() {
+    let backend = MemoryBackend::new();
+    execute(
+        &backend,
+        "b1",
+        &[
+            (
+                "t1",
+                &["CREATE (a:Person {name: 'Ann', age: 30}), (b:Person {name: 'Bob', age: 20})"],
+            ),
+            (
+                "t2",
+                &["MATCH (p:Person) WHERE p.age > 25 SET p.adult = true"],
+            ),
+        ],
+    );
+    let transactions = changes(&backend, "b1");
+    let assigned = transactions[1]
+        .get_modifications()
+        .get_assigned_node_properties();
+    assert_eq!(assigned.len(), 1);
+    assert_eq!(assigned[0].get_node_UUID(), "t1_0");
+    assert_eq!(assigned[0].get_value(), "true");
+    assert_eq!(backend.node_property("t1_1", "adult"), None);
+}
+
+#[test]
+fn test_delete_needs_detach_for_connected_nodes() {
+    let backend = MemoryBackend::new();
+    execute(
+        &backend,
+        "b1",
+        &[
+            (
+                "t1",
+                &["CREATE (a:Person {name: 'Ann'})-[:KNOWS]->(b:Person {name: 'Bob'})"],
+            ),
+            ("t2", &["MATCH (a:Person {name: 'Ann'}) DELETE a"]),
+            ("t3", &["MATCH (a:Person {name: 'Ann'}) DETACH DELETE a"]),
+        ],
+    );
+    let transactions = changes(&backend, "b1");
+    assert_eq!(
+        transactions[1].get_error().get_code(),
+        ErrorCode::CONSTRAINT_VIOLATION
+    );
+
+    assert_eq!(transactions[2].get_result(), Status::SUCCESS);
+    let modifications = transactions[2].get_modifications();
+    assert_eq!(modifications.get_deleted_nodes()[0].get_node_UUID(), "t1_0");
+    assert_eq!(
+        modifications.get_deleted_relationships()[0].get_relationship_UUID(),
+        "t1_2"
+    );
+    assert_eq!(backend.label_nodes("Person"), vec!["t1_1"]);
+}
+
+#[test]
+fn test_remove_label() {
+    let backend = MemoryBackend::new();
+    execute(
+        &backend,
+        "b1",
+        &[
+            ("t1", &["CREATE (n:Person:Admin)"]),
+            ("t2", &["MATCH (n:Admin) REMOVE n:Admin"]),
+        ],
+    );
+    let transactions = changes(&backend, "b1");
+    let removed = &transactions[1].get_modifications().get_removed_labels()[0];
+    assert_eq!(removed.get_node_UUID(), "t1_0");
+    assert_eq!(removed.get_name(), "Admin");
+    assert!(backend.label_nodes("Admin").is_empty());
+    assert_eq!(backend.label_nodes("Person"), vec!["t1_0"]);
+}
+
+#[test]
+fn test_uuids_cannot_be_modified() {
+    let backend = MemoryBackend::new();
+    execute(
+        &backend,
+        "b1",
+        &[
+            ("t1", &["CREATE (a:Person)-[:KNOWS]->(b:Person)"]),
+            ("t2", &["MATCH (n:Person) REMOVE n.uuid"]),
+            ("t3", &["MATCH (a)-[r:KNOWS]->(b) SET r.uuid = 'mine'"]),
+        ],
+    );
+    let transactions = changes(&backend, "b1");
+    for transaction in &transactions[1..] {
+        assert_eq!(transaction.get_result(), Status::FAILURE);
+        assert_eq!(transaction.get_error().get_code(), ErrorCode::MODIFIED_UUID);
+    }
+    assert_eq!(
+        backend.node_property("t1_0", "uuid"),
+        Some(Value::String(String::from("t1_0")))
+    );
+}
+
+#[test]
+fn test_uuids_number_nodes_before_relationships() {
+    let backend = MemoryBackend::new();
+    execute(
+        &backend,
+        "b1",
+        &[
+            ("t1", &["CREATE (a:A)-[:R]->(b:B) CREATE (c:C)"]),
+            ("t2", &["CREATE (d:D)"]),
+        ],
+    );
+    let transactions = changes(&backend, "b1");
+    let modifications = transactions[0].get_modifications();
+    let nodes: Vec<&str> = modifications
+        .get_created_nodes()
+        .iter()
+        .map(|n| n.get_node_UUID())
+        .collect();
+    assert_eq!(nodes, vec!["t1_0", "t1_1", "t1_2"]);
+    // The relationship was created before node c, but relationships are numbered after nodes.
+    assert_eq!(
+        modifications.get_created_relationships()[0].get_relationship_UUID(),
+        "t1_3"
+    );
+    assert_eq!(backend.label_nodes("C"), vec!["t1_2"]);
+    // Every transaction numbers its entities from zero.
+    assert_eq!(
+        transactions[1].get_modifications().get_created_nodes()[0].get_node_UUID(),
+        "t2_0"
+    );
+}
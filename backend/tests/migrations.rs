@@ -0,0 +1,276 @@
+extern crate exonum;
+extern crate exonum_neo4j;
+#[macro_use]
+extern crate exonum_testkit;
+extern crate protobuf;
+
+use exonum::crypto::{self, Hash};
+use exonum::storage::{Database, Fork, MemoryDB, ProofListIndex, ProofMapIndex, StorageValue};
+use exonum_neo4j::migrations::{
+    self, MigrationError, CURRENT_SCHEMA_VERSION, LEGACY_SCHEMA_VERSION,
+};
+use exonum_neo4j::neo4j::proto::neo4j_service as pb;
+use exonum_neo4j::schema::Schema;
+use exonum_neo4j::structures::{
+    AddLabel, Neo4jTransaction, NodeChange, Relation, TransactionStatus, PROTOBUF_MARKER,
+};
+use exonum_neo4j::transactions::{CommitQueries, MigrateSchema};
+use exonum_neo4j::Neo4jService;
+use exonum_testkit::{TestKit, TestKitBuilder};
+use protobuf::Message;
+
+pub mod legacy;
+pub mod support;
+
+use legacy::{legacy_bytes, legacy_transaction};
+use support::MockTransactionManager;
+
+fn legacy_label(uuid: &str, label: &str) -> Vec<u8> {
+    let mut change = legacy_bytes(&[uuid, label, "71afce3e"]);
+    change.push(5);
+    change
+}
+
+fn new_label(uuid: &str, label: &str) -> Vec<u8> {
+    NodeChange::AL(AddLabel::new(uuid, label, "71afce3e")).into_bytes()
+}
+
+/// Indexes as a chain started before the schema version was recorded left them: no list of
+/// known nodes, no changes indexed by transaction and every value in the legacy encoding.
+fn legacy_fork(db: &MemoryDB) -> (Fork, Vec<String>) {
+    let mut fork = db.fork();
+    let transaction = crypto::hash(b"t1");
+    let created: Vec<String> = (0..2)
+        .map(|index| format!("{}_{}", transaction.to_hex(), index))
+        .collect();
+    {
+        let mut queries: ProofMapIndex<&mut Fork, Hash, Vec<u8>> =
+            ProofMapIndex::new("neo4j.queries", &mut fork);
+        let pub_key = crypto::gen_keypair().0;
+        queries.put(
+            &transaction,
+            legacy_transaction("CREATE (a)-[:R]->(b)", "SUCCESS", &pub_key),
+        );
+    }
+    {
+        // The end points of a relation are nodes, even if no transaction of the chain created
+        // them.
+        let mut relations: ProofMapIndex<&mut Fork, Hash, Vec<u8>> =
+            ProofMapIndex::new("neo4j.relations", &mut fork);
+        relations.put(&crypto::hash(b"r1"), legacy_bytes(&[&created[0], "u1"]));
+    }
+    for uuid in created.iter().map(String::as_str).chain(vec!["u1"]) {
+        let mut history: ProofListIndex<&mut Fork, Vec<u8>> =
+            ProofListIndex::new(format!("neo4j.node_changes_{}", uuid), &mut fork);
+        history.push(legacy_label(uuid, "R"));
+    }
+    let mut nodes = created;
+    nodes.push(String::from("u1"));
+    (fork, nodes)
+}
+
+#[test]
+fn test_migrate_legacy_chain() {
+    let db = MemoryDB::new();
+    let (mut fork, nodes) = legacy_fork(&db);
+    assert_eq!(Schema::new(&fork).schema_version(), None);
+    assert_eq!(Schema::new(&fork).known_nodes().iter().count(), 0);
+
+    assert_eq!(
+        migrations::migrate(&mut fork, CURRENT_SCHEMA_VERSION),
+        Ok(2)
+    );
+
+    let schema = Schema::new(&fork);
+    assert_eq!(schema.schema_version(), Some(CURRENT_SCHEMA_VERSION));
+    let relations: ProofMapIndex<&Fork, Hash, Vec<u8>> =
+        ProofMapIndex::new("neo4j.relations", &fork);
+    let relation = relations.get(&crypto::hash(b"r1")).unwrap();
+    assert_eq!(relation[0], PROTOBUF_MARKER);
+    assert_eq!(
+        Relation::from_bytes(relation.into()),
+        Relation::new(&nodes[0], "u1", "")
+    );
+    let queries: ProofMapIndex<&Fork, Hash, Vec<u8>> = ProofMapIndex::new("neo4j.queries", &fork);
+    assert_eq!(
+        queries.get(&crypto::hash(b"t1")).unwrap()[0],
+        PROTOBUF_MARKER
+    );
+    assert_eq!(
+        schema
+            .neo4j_transaction(&crypto::hash(b"t1"))
+            .unwrap()
+            .queries(),
+        "CREATE (a)-[:R]->(b)"
+    );
+    assert_eq!(
+        schema
+            .neo4j_transaction(&crypto::hash(b"t1"))
+            .unwrap()
+            .status(),
+        Some(TransactionStatus::Success)
+    );
+
+    // Every node history is found and rewritten, so reading it no longer needs the legacy
+    // decoding.
+    for uuid in &nodes {
+        assert_eq!(
+            schema.node_history_raw(uuid).get(0).unwrap(),
+            new_label(uuid, "R")
+        );
+        assert!(schema.known_nodes().contains(uuid));
+    }
+    assert_eq!(
+        schema.node_history(&nodes[1]).get(0),
+        Some(NodeChange::AL(AddLabel::new(&nodes[1], "R", "71afce3e")))
+    );
+}
+
+#[test]
+fn test_migrate_status_names() {
+    let db = MemoryDB::new();
+    let mut fork = db.fork();
+    let pub_key = crypto::gen_keypair().0;
+    let mut message = pb::Neo4jTransaction::new();
+    message.set_queries(String::from("CREATE (n)"));
+    message.set_error_msg(String::from("Refused"));
+    message.set_result(String::from("REJECTED"));
+    message.set_pub_key(pub_key.as_ref().to_vec());
+    let mut bytes = vec![PROTOBUF_MARKER];
+    message.write_to_vec(&mut bytes).unwrap();
+    {
+        let mut queries: ProofMapIndex<&mut Fork, Hash, Vec<u8>> =
+            ProofMapIndex::new("neo4j.queries", &mut fork);
+        queries.put(&crypto::hash(b"t1"), bytes);
+    }
+    Schema::new(&mut fork).set_schema_version(2);
+
+    assert_eq!(
+        migrations::migrate(&mut fork, CURRENT_SCHEMA_VERSION),
+        Ok(1)
+    );
+    let queries: ProofMapIndex<&Fork, Hash, Vec<u8>> = ProofMapIndex::new("neo4j.queries", &fork);
+    assert_eq!(
+        queries.get(&crypto::hash(b"t1")).unwrap(),
+        Neo4jTransaction::new(
+            "CREATE (n)",
+            "Refused",
+            TransactionStatus::Rejected,
+            &pub_key
+        )
+        .into_bytes()
+    );
+}
+
+#[test]
+fn test_migration_needs_a_quorum_of_validators() {
+    let mock = MockTransactionManager::start();
+    let mut testkit = TestKitBuilder::validator()
+        .with_validators(4)
+        .with_service(Neo4jService::new(mock.rpc()))
+        .create();
+    {
+        let mut fork = testkit.blockchain_mut().fork();
+        Schema::new(&mut fork).set_schema_version(LEGACY_SCHEMA_VERSION);
+        testkit.blockchain_mut().merge(fork.into_patch()).unwrap();
+    }
+    let vote = |testkit: &TestKit, validator: usize, target_version: u32, activation_height| {
+        let validators = testkit.network().validators();
+        let (pub_key, secret_key) = validators[validator].service_keypair();
+        MigrateSchema::new(target_version, activation_height, pub_key, secret_key)
+    };
+    let schema_version = |testkit: &TestKit| {
+        let snapshot = testkit.snapshot();
+        migrations::schema_version(&Schema::new(&snapshot))
+    };
+
+    // Votes of other keys, for versions this service does not know, before their activation
+    // height or twice from the same validator are not recorded.
+    let (pub_key, secret_key) = crypto::gen_keypair();
+    let stranger = MigrateSchema::new(CURRENT_SCHEMA_VERSION, 1, &pub_key, &secret_key);
+    let unknown = vote(&testkit, 0, CURRENT_SCHEMA_VERSION + 1, 1);
+    let early = vote(&testkit, 1, CURRENT_SCHEMA_VERSION, 10);
+    let first = vote(&testkit, 0, CURRENT_SCHEMA_VERSION, 1);
+    testkit.create_block_with_transactions(txvec![stranger, unknown, early, first]);
+    let twice = vote(&testkit, 0, CURRENT_SCHEMA_VERSION, 0);
+    let second = vote(&testkit, 1, CURRENT_SCHEMA_VERSION, 1);
+    testkit.create_block_with_transactions(txvec![twice, second]);
+    {
+        let snapshot = testkit.snapshot();
+        let schema = Schema::new(&snapshot);
+        assert_eq!(
+            schema
+                .migration_votes(CURRENT_SCHEMA_VERSION)
+                .keys()
+                .count(),
+            2
+        );
+        assert!(!schema
+            .migration_votes(CURRENT_SCHEMA_VERSION)
+            .contains(&pub_key));
+        assert!(schema
+            .migration_votes(CURRENT_SCHEMA_VERSION + 1)
+            .keys()
+            .next()
+            .is_none());
+    }
+    // Two of four validators are not more than two thirds of them.
+    assert_eq!(schema_version(&testkit), LEGACY_SCHEMA_VERSION);
+
+    testkit.create_block_with_transactions(txvec![vote(&testkit, 2, CURRENT_SCHEMA_VERSION, 1)]);
+    assert_eq!(schema_version(&testkit), CURRENT_SCHEMA_VERSION);
+
+    // The last vote is recorded, but finds nothing left to migrate.
+    testkit.create_block_with_transactions(txvec![vote(&testkit, 3, CURRENT_SCHEMA_VERSION, 1)]);
+    assert_eq!(schema_version(&testkit), CURRENT_SCHEMA_VERSION);
+}
+
+#[test]
+fn test_refuse_unknown_versions() {
+    let db = MemoryDB::new();
+    let mut fork = db.fork();
+    assert_eq!(
+        migrations::migrate(&mut fork, CURRENT_SCHEMA_VERSION + 1),
+        Err(MigrationError::UnknownVersion(
+            CURRENT_SCHEMA_VERSION + 1,
+            CURRENT_SCHEMA_VERSION
+        ))
+    );
+
+    Schema::new(&mut fork).set_schema_version(CURRENT_SCHEMA_VERSION + 1);
+    assert!(migrations::check_schema_version(&Schema::new(&fork)).is_err());
+    assert_eq!(
+        migrations::migrate(&mut fork, CURRENT_SCHEMA_VERSION),
+        Err(MigrationError::Downgrade(
+            CURRENT_SCHEMA_VERSION + 1,
+            CURRENT_SCHEMA_VERSION
+        ))
+    );
+}
+
+#[test]
+fn test_newer_indexes_stop_the_service() {
+    let db = MemoryDB::new();
+    let mut fork = db.fork();
+    Schema::new(&mut fork).set_schema_version(CURRENT_SCHEMA_VERSION + 1);
+    db.merge(fork.into_patch()).unwrap();
+    let mock = MockTransactionManager::start();
+    assert!(Neo4jService::checked(mock.rpc(), db.snapshot().as_ref()).is_err());
+    assert!(Neo4jService::checked(mock.rpc(), MemoryDB::new().snapshot().as_ref()).is_ok());
+
+    // A service that could not check the indexes when it was created keeps committing blocks,
+    // but runs none of them on Neo4j.
+    let mut testkit = TestKitBuilder::validator()
+        .with_service(Neo4jService::new(mock.rpc()))
+        .create();
+    {
+        let mut fork = testkit.blockchain_mut().fork();
+        Schema::new(&mut fork).set_schema_version(CURRENT_SCHEMA_VERSION + 1);
+        testkit.blockchain_mut().merge(fork.into_patch()).unwrap();
+    }
+    let (pub_key, secret_key) = crypto::gen_keypair();
+    let commit = CommitQueries::new("CREATE (n)", "18-OCT", &pub_key, &secret_key);
+    testkit.create_block_with_transactions(txvec![commit]);
+    testkit.create_block();
+    assert!(mock.executed_blocks().is_empty());
+}
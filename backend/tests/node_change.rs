@@ -0,0 +1,35 @@
+extern crate exonum_neo4j;
+#[macro_use]
+extern crate serde_json;
+
+use exonum_neo4j::structures::{AddLabel, AddRelation, NodeChange};
+
+#[test]
+fn test_node_change_json() {
+    let change = NodeChange::AL(AddLabel::new("u1", "R", "71afce3e"));
+    let value = serde_json::to_value(&change).unwrap();
+    assert_eq!(
+        value,
+        json!({
+            "kind": "add_label",
+            "node_uuid": "u1",
+            "label_name": "R",
+            "transaction_id": "71afce3e"
+        })
+    );
+
+    match serde_json::from_value(value).unwrap() {
+        NodeChange::AL(x) => assert_eq!(x.label_name(), "R"),
+        other => panic!("Unexpected change {:?}", other),
+    }
+}
+
+#[test]
+fn test_relation_change_json() {
+    let change = NodeChange::AR(AddRelation::new("r1", "GRANTED", "u1", "u2", "71afce3e"));
+    let value = serde_json::to_value(&change).unwrap();
+    assert_eq!(value["kind"], "add_relation");
+    assert_eq!(value["field_type"], "GRANTED");
+    assert_eq!(value["from_uuid"], "u1");
+    assert_eq!(value["to_uuid"], "u2");
+}
@@ -0,0 +1,117 @@
+extern crate exonum;
+extern crate exonum_neo4j;
+#[macro_use]
+extern crate proptest;
+
+use exonum::storage::StorageValue;
+use exonum_neo4j::structures::{
+    AddLabel, AddNode, AddNodeProperty, AddRelation, AddRelationProperty, NodeChange,
+    NodeChangeError, RemoveLabel, RemoveNode, RemoveNodeProperty, RemoveRelation,
+    RemoveRelationProperty, NODE_CHANGE_MAGIC, NODE_CHANGE_VERSION,
+};
+use proptest::prelude::*;
+
+fn node_change() -> BoxedStrategy<NodeChange> {
+    let s = || any::<String>();
+    let p = || any::<Option<String>>();
+    prop_oneof![
+        (s(), s()).prop_map(|(a, t)| NodeChange::AN(AddNode::new(&a, &t))),
+        (s(), s()).prop_map(|(a, t)| NodeChange::RN(RemoveNode::new(&a, &t))),
+        (s(), s(), s(), s(), s())
+            .prop_map(|(r, f, a, b, t)| NodeChange::AR(AddRelation::new(&r, &f, &a, &b, &t))),
+        (s(), s(), s(), s())
+            .prop_map(|(r, a, b, t)| NodeChange::RR(RemoveRelation::new(&r, &a, &b, &t))),
+        (s(), s(), s()).prop_map(|(a, l, t)| NodeChange::AL(AddLabel::new(&a, &l, &t))),
+        (s(), s(), s()).prop_map(|(a, l, t)| NodeChange::RL(RemoveLabel::new(&a, &l, &t))),
+        (s(), s(), s(), s(), p(), p()).prop_map(|(a, k, v, t, pv, pt)| {
+            NodeChange::ANP(AddNodeProperty::new(
+                &a,
+                &k,
+                &v,
+                &t,
+                pv.as_ref().map(String::as_str),
+                pt.as_ref().map(String::as_str),
+            ))
+        }),
+        (s(), s(), s(), p(), p()).prop_map(|(a, k, t, pv, pt)| {
+            NodeChange::RNP(RemoveNodeProperty::new(
+                &a,
+                &k,
+                &t,
+                pv.as_ref().map(String::as_str),
+                pt.as_ref().map(String::as_str),
+            ))
+        }),
+        (s(), s(), s(), s(), s(), s(), p(), p()).prop_map(|(r, k, v, a, b, t, pv, pt)| {
+            let pv = pv.as_ref().map(String::as_str);
+            let pt = pt.as_ref().map(String::as_str);
+            NodeChange::ARP(AddRelationProperty::new(&r, &k, &v, &a, &b, &t, pv, pt))
+        }),
+        (s(), s(), s(), s(), s(), p(), p()).prop_map(|(r, k, a, b, t, pv, pt)| {
+            let pv = pv.as_ref().map(String::as_str);
+            let pt = pt.as_ref().map(String::as_str);
+            NodeChange::RRP(RemoveRelationProperty::new(&r, &k, &a, &b, &t, pv, pt))
+        }),
+    ]
+    .boxed()
+}
+
+proptest! {
+    #[test]
+    fn test_round_trip(change in node_change()) {
+        let bytes = change.clone().into_bytes();
+        prop_assert_eq!(&bytes[..3], &[NODE_CHANGE_MAGIC, NODE_CHANGE_VERSION, change.tag()][..]);
+        prop_assert_eq!(NodeChange::decode(&bytes), Ok(change));
+    }
+
+    #[test]
+    fn test_unknown_tag_is_reported(change in node_change(), tag in 11u8..) {
+        let mut bytes = change.into_bytes();
+        bytes[2] = tag;
+        prop_assert_eq!(NodeChange::decode(&bytes), Err(NodeChangeError::UnknownTag(tag)));
+    }
+
+    #[test]
+    fn test_corrupt_bytes_do_not_panic(change in node_change(), index in any::<usize>(), byte in any::<u8>()) {
+        let mut bytes = change.into_bytes();
+        let index = index % bytes.len();
+        bytes[index] = byte;
+        let _ = NodeChange::decode(&bytes);
+    }
+}
+
+#[test]
+fn test_unknown_version_is_reported() {
+    let mut bytes = NodeChange::AN(AddNode::new("u1", "71afce3e")).into_bytes();
+    bytes[1] = NODE_CHANGE_VERSION + 1;
+    assert_eq!(
+        NodeChange::decode(&bytes),
+        Err(NodeChangeError::UnknownVersion(NODE_CHANGE_VERSION + 1))
+    );
+    assert_eq!(
+        NodeChange::decode(&[NODE_CHANGE_MAGIC]),
+        Err(NodeChangeError::Truncated)
+    );
+    assert_eq!(NodeChange::decode(&[]), Err(NodeChangeError::Empty));
+}
+
+#[test]
+fn test_legacy_unknown_tag_is_reported() {
+    let mut bytes = vec![16, 0, 0, 0, 0, 0, 0, 0, 16, 0, 0, 0, 0, 0, 0, 0];
+    bytes.push(11);
+    assert_eq!(
+        NodeChange::decode(&bytes),
+        Err(NodeChangeError::UnknownTag(11))
+    );
+}
+
+#[test]
+fn test_legacy_truncated_payload_is_reported() {
+    // `AddNode` layout whose first field points past the end of the value.
+    let mut bytes = vec![16, 0, 0, 0, 8, 0, 0, 0, 16, 0, 0, 0, 0, 0, 0, 0];
+    bytes.push(1);
+    match NodeChange::decode(&bytes) {
+        Err(NodeChangeError::CorruptPayload(_)) => {}
+        other => panic!("Unexpected result {:?}", other),
+    }
+}
@@ -0,0 +1,205 @@
+extern crate exonum;
+extern crate exonum_neo4j;
+#[macro_use]
+extern crate exonum_testkit;
+
+use exonum::crypto;
+use exonum::messages::Message;
+use exonum::storage::{Database, Fork, MemoryDB};
+use exonum_neo4j::compensation::{compensating_queries, literal};
+use exonum_neo4j::config::{Author, Role, ServiceConfig, WritePolicy};
+use exonum_neo4j::policy::check_changes;
+use exonum_neo4j::schema::Schema;
+use exonum_neo4j::structures::NodeChange::{AL, AN, ANP, AR, ARP, RN};
+use exonum_neo4j::structures::{
+    AddLabel, AddNode, AddNodeProperty, AddRelation, AddRelationProperty, FailureCode, NodeChange,
+    Relation, RemoveNode, TransactionStatus,
+};
+use exonum_neo4j::transactions::CommitQueries;
+
+pub mod support;
+
+use support::created_nodes;
+
+fn facility_manager() -> WritePolicy {
+    WritePolicy {
+        pub_key: crypto::gen_keypair().0,
+        labels: vec![String::from("D")],
+        relationship_types: vec![String::from("LOCATED_IN")],
+    }
+}
+
+/// Records the changes the way an audit does.
+fn apply(fork: &mut Fork, changes: &[NodeChange]) {
+    let mut schema = Schema::new(fork);
+    for change in changes {
+        for uuid in change.get_uuis() {
+            schema.add_node_history(uuid, change);
+        }
+        schema.index_node_change(change);
+    }
+}
+
+#[test]
+fn test_allowed_changes() {
+    let db = MemoryDB::new();
+    let fork = db.fork();
+    let changes = vec![
+        AN(AddNode::new("t1_0", "t1")),
+        AL(AddLabel::new("t1_0", "D", "t1")),
+        ANP(AddNodeProperty::new(
+            "t1_0", "name", "Door 1", "t1", None, None,
+        )),
+    ];
+    assert_eq!(
+        check_changes(&Schema::new(&fork), &facility_manager(), &changes),
+        Ok(())
+    );
+}
+
+#[test]
+fn test_labels_before_the_changes_are_checked() {
+    let db = MemoryDB::new();
+    let mut fork = db.fork();
+    apply(
+        &mut fork,
+        &[
+            AN(AddNode::new("t1_0", "t1")),
+            AL(AddLabel::new("t1_0", "Person", "t1")),
+        ],
+    );
+    let changes = vec![ANP(AddNodeProperty::new(
+        "t1_0", "name", "Mallory", "t2", None, None,
+    ))];
+    assert!(check_changes(&Schema::new(&fork), &facility_manager(), &changes).is_err());
+}
+
+#[test]
+fn test_unlabelled_node_is_refused() {
+    let db = MemoryDB::new();
+    let fork = db.fork();
+    let changes = vec![AN(AddNode::new("t1_0", "t1"))];
+    assert!(check_changes(&Schema::new(&fork), &facility_manager(), &changes).is_err());
+}
+
+#[test]
+fn test_relationship_types() {
+    let db = MemoryDB::new();
+    let mut fork = db.fork();
+    let granted = vec![AR(AddRelation::new(
+        "t1_2", "GRANTED", "t1_0", "t1_1", "t1",
+    ))];
+    assert!(check_changes(&Schema::new(&fork), &facility_manager(), &granted).is_err());
+
+    // The type of relations created in earlier transactions is taken from storage.
+    Schema::new(&mut fork).add_relation(Relation::new("t1_0", "t1_1", "LOCATED_IN"), "t1_3");
+    let changes = vec![ARP(AddRelationProperty::new(
+        "t1_3", "since", "2018", "t1_0", "t1_1", "t2", None, None,
+    ))];
+    assert_eq!(
+        check_changes(&Schema::new(&fork), &facility_manager(), &changes),
+        Ok(())
+    );
+}
+
+#[test]
+fn test_write_policy_lookup() {
+    let policy = facility_manager();
+    let (admin, _) = crypto::gen_keypair();
+    let admin_policy = WritePolicy {
+        pub_key: admin,
+        ..policy.clone()
+    };
+    let config = ServiceConfig {
+        authors: Some(vec![Author {
+            pub_key: admin,
+            roles: vec![Role::Admin],
+        }]),
+        write_policies: vec![policy.clone(), admin_policy],
+        ..ServiceConfig::default()
+    };
+    assert_eq!(config.write_policy(&policy.pub_key), Some(&policy));
+    assert_eq!(config.write_policy(&admin), None);
+    assert_eq!(config.write_policy(&crypto::gen_keypair().0), None);
+}
+
+#[test]
+fn test_compensating_queries() {
+    let db = MemoryDB::new();
+    let mut fork = db.fork();
+    apply(
+        &mut fork,
+        &[
+            AN(AddNode::new("t1_0", "t1")),
+            AL(AddLabel::new("t1_0", "D", "t1")),
+            ANP(AddNodeProperty::new(
+                "t1_0", "name", "Door 1", "t1", None, None,
+            )),
+        ],
+    );
+    let changes = vec![
+        AN(AddNode::new("t2_0", "t2")),
+        ANP(AddNodeProperty::new(
+            "t1_0", "name", "Door 2", "t2", None, None,
+        )),
+        RN(RemoveNode::new("t1_1", "t2")),
+    ];
+    let queries = compensating_queries(&Schema::new(&fork), &changes, false);
+    let statements: Vec<&str> = queries.split(';').collect();
+    assert_eq!(
+        statements,
+        vec![
+            "CREATE (n {restored_uuid: 't1_1'})",
+            "MATCH (n) WHERE (n.uuid = 't1_0' OR n.restored_uuid = 't1_0') SET n.`name` = 'Door 1'",
+            "MATCH (n) WHERE (n.uuid = 't2_0' OR n.restored_uuid = 't2_0') DETACH DELETE n",
+        ]
+    );
+}
+
+#[test]
+fn test_literal_escaping() {
+    assert_eq!(literal("it's; done\\"), "'it\\'s\\u003B done\\\\'");
+}
+
+#[test]
+fn test_violation_is_compensated_by_the_service() {
+    let (pub_key, secret_key) = crypto::gen_keypair();
+    let config = ServiceConfig {
+        write_policies: vec![WritePolicy {
+            pub_key,
+            ..facility_manager()
+        }],
+        ..ServiceConfig::default()
+    };
+    let (mut testkit, mock) = support::init_testkit_with_config(config);
+    mock.script_transactions(|t| created_nodes(t.get_transaction_id(), &["u1"]));
+    let transaction = CommitQueries::new("CREATE (n)", "15-OCT", &pub_key, &secret_key);
+    let tx_hash = transaction.hash();
+    testkit.create_block_with_transactions(txvec![transaction]);
+    testkit.create_block();
+
+    let snapshot = testkit.snapshot();
+    let schema = Schema::new(&snapshot);
+    let transaction = schema.neo4j_transaction(&tx_hash).unwrap();
+    assert_eq!(transaction.status(), Some(TransactionStatus::Error));
+    assert_eq!(
+        schema
+            .transaction_failures()
+            .get(&tx_hash)
+            .and_then(|f| f.failure_code()),
+        Some(FailureCode::PolicyViolation)
+    );
+
+    let compensation_hash = crypto::hash(&[tx_hash.as_ref(), b"compensation"].concat());
+    assert_eq!(
+        schema.compensations().get(&compensation_hash),
+        Some(tx_hash)
+    );
+    let compensation = schema.neo4j_transaction(&compensation_hash).unwrap();
+    let validator = testkit.network().validators()[0].service_keypair().0;
+    assert_eq!(compensation.pub_key(), validator);
+    // The violating author is not charged with the compensation.
+    let author_transactions = schema.author_transactions(&pub_key);
+    assert_eq!(author_transactions.len(), 1);
+    assert_eq!(author_transactions.get(0), Some(tx_hash));
+}
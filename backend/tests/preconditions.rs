@@ -0,0 +1,139 @@
+extern crate exonum;
+extern crate exonum_neo4j;
+#[macro_use]
+extern crate exonum_testkit;
+
+use exonum::crypto::{self, Hash};
+use exonum::messages::Message;
+use exonum::storage::{Database, MemoryDB};
+use exonum_neo4j::schema::Schema;
+use exonum_neo4j::structures::NodeChange::AN;
+use exonum_neo4j::structures::{AddNode, FailureCode, TransactionStatus};
+use exonum_neo4j::transactions::{
+    check_preconditions, CommitQueriesWithPreconditions, HistoryPrecondition,
+};
+use exonum_testkit::TestKit;
+
+pub mod support;
+
+#[test]
+fn test_check_preconditions() {
+    let db = MemoryDB::new();
+    let mut fork = db.fork();
+    Schema::new(&mut fork).add_node_history("t1_0", &AN(AddNode::new("t1_0", "t1")));
+    let root = Schema::new(&fork).node_history_raw("t1_0").merkle_root();
+    let schema = Schema::new(&fork);
+
+    let expected = HistoryPrecondition::new("t1_0", 1, &root);
+    assert_eq!(check_preconditions(&schema, &[expected]), Ok(()));
+    let length_only = HistoryPrecondition::new("t1_0", 1, &Hash::zero());
+    assert_eq!(check_preconditions(&schema, &[length_only]), Ok(()));
+    let unknown_node = HistoryPrecondition::new("t2_0", 0, &Hash::zero());
+    assert_eq!(check_preconditions(&schema, &[unknown_node]), Ok(()));
+
+    let moved_on = HistoryPrecondition::new("t1_0", 0, &Hash::zero());
+    assert!(check_preconditions(&schema, &[moved_on]).is_err());
+    let other_root = HistoryPrecondition::new("t1_0", 1, &crypto::hash(b"other"));
+    assert!(check_preconditions(&schema, &[other_root]).is_err());
+}
+
+#[test]
+fn test_reject_moved_on_history() {
+    let (mut testkit, _mock) = support::init_testkit();
+    let (pub_key, secret_key) = crypto::gen_keypair();
+    let unchanged = CommitQueriesWithPreconditions::new(
+        "MATCH (n {uuid: 't1_0'}) SET n.name = 'Door'",
+        "15-OCT",
+        vec![HistoryPrecondition::new("t1_0", 0, &Hash::zero())],
+        &pub_key,
+        &secret_key,
+    );
+    let moved_on = CommitQueriesWithPreconditions::new(
+        "MATCH (n {uuid: 't1_0'}) SET n.name = 'Gate'",
+        "15-OCT",
+        vec![HistoryPrecondition::new("t1_0", 3, &Hash::zero())],
+        &pub_key,
+        &secret_key,
+    );
+    let (unchanged_hash, moved_on_hash) = (unchanged.hash(), moved_on.hash());
+    testkit.create_block_with_transactions(txvec![unchanged, moved_on]);
+
+    let snapshot = testkit.snapshot();
+    let schema = Schema::new(&snapshot);
+    assert_eq!(
+        schema.neo4j_transaction(&unchanged_hash).unwrap().status(),
+        Some(TransactionStatus::Pending)
+    );
+    assert_eq!(
+        schema.neo4j_transaction(&moved_on_hash).unwrap().status(),
+        Some(TransactionStatus::Rejected)
+    );
+    assert_eq!(
+        schema
+            .transaction_failures()
+            .get(&moved_on_hash)
+            .unwrap()
+            .failure_code(),
+        Some(FailureCode::PreconditionFailed)
+    );
+}
+
+#[test]
+fn test_reserve_nodes_until_audited() {
+    let (mut testkit, _mock) = support::init_testkit();
+    let (pub_key, secret_key) = crypto::gen_keypair();
+    let edit = |name: &str| {
+        CommitQueriesWithPreconditions::new(
+            &format!("MATCH (n {{uuid: 't1_0'}}) SET n.name = '{}'", name),
+            "15-OCT",
+            vec![HistoryPrecondition::new("t1_0", 0, &Hash::zero())],
+            &pub_key,
+            &secret_key,
+        )
+    };
+    let status = |testkit: &TestKit, hash: &Hash| {
+        let snapshot = testkit.snapshot();
+        Schema::new(&snapshot)
+            .neo4j_transaction(hash)
+            .and_then(|t| t.status())
+    };
+
+    // Both edits were written against the same history, which only moves on at the audit of
+    // the first one, so the second one is rejected.
+    let (first, second) = (edit("Door"), edit("Gate"));
+    let (first_hash, second_hash) = (first.hash(), second.hash());
+    testkit.create_block_with_transactions(txvec![first]);
+    testkit.create_block_with_transactions(txvec![second]);
+    assert_eq!(
+        status(&testkit, &first_hash),
+        Some(TransactionStatus::Pending)
+    );
+    assert_eq!(
+        status(&testkit, &second_hash),
+        Some(TransactionStatus::Rejected)
+    );
+    {
+        let snapshot = testkit.snapshot();
+        let schema = Schema::new(&snapshot);
+        assert_eq!(schema.node_reservation("t1_0"), Some(first_hash));
+        assert!(check_preconditions(
+            &schema,
+            &[HistoryPrecondition::new("t1_0", 0, &Hash::zero())]
+        )
+        .is_err());
+    }
+
+    // Once the first edit is audited the reservation is released.
+    testkit.create_block();
+    assert_eq!(
+        status(&testkit, &first_hash),
+        Some(TransactionStatus::Success)
+    );
+    let third = edit("Window");
+    let third_hash = third.hash();
+    testkit.create_block_with_transactions(txvec![third]);
+    assert_eq!(
+        status(&testkit, &third_hash),
+        Some(TransactionStatus::Pending)
+    );
+}
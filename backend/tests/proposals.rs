@@ -0,0 +1,166 @@
+extern crate exonum;
+extern crate exonum_neo4j;
+#[macro_use]
+extern crate exonum_testkit;
+
+use exonum::crypto::{self, PublicKey, SecretKey};
+use exonum::helpers::Height;
+use exonum::messages::Message;
+use exonum_neo4j::config::{ApprovalConfig, Author, Role, ServiceConfig};
+use exonum_neo4j::schema::Schema;
+use exonum_neo4j::structures::{ProposalStatus, TransactionStatus};
+use exonum_neo4j::transactions::{proposal_transaction_hash, ApproveQueries, ProposeQueries};
+use exonum_testkit::TestKit;
+
+pub mod support;
+
+use support::MockTransactionManager;
+
+struct Officers {
+    writer: (PublicKey, SecretKey),
+    first: (PublicKey, SecretKey),
+    second: (PublicKey, SecretKey),
+}
+
+fn init_testkit() -> (TestKit, MockTransactionManager, Officers) {
+    let officers = Officers {
+        writer: crypto::gen_keypair(),
+        first: crypto::gen_keypair(),
+        second: crypto::gen_keypair(),
+    };
+    let author = |pub_key: PublicKey, role: Role| Author {
+        pub_key,
+        roles: vec![role],
+    };
+    let config = ServiceConfig {
+        authors: Some(vec![
+            author(officers.writer.0, Role::Writer),
+            author(officers.first.0, Role::Approver),
+            author(officers.second.0, Role::Approver),
+        ]),
+        approvals: ApprovalConfig {
+            threshold: 2,
+            ttl: 5,
+        },
+        ..ServiceConfig::default()
+    };
+
+    let (testkit, mock) = support::init_testkit_with_config(config);
+    (testkit, mock, officers)
+}
+
+#[test]
+fn test_proposal_is_sent_once_approved() {
+    let (mut testkit, _mock, officers) = init_testkit();
+    let (ref writer, ref writer_key) = officers.writer;
+    let propose = ProposeQueries::new("CREATE (n:D)", "15-OCT", writer, writer_key);
+    let proposal_hash = propose.hash();
+    testkit.create_block_with_transactions(txvec![propose]);
+
+    let (ref first, ref first_key) = officers.first;
+    testkit.create_block_with_transactions(txvec![ApproveQueries::new(
+        &proposal_hash,
+        first,
+        first_key
+    )]);
+    let tx_hash = proposal_transaction_hash(&proposal_hash);
+    {
+        let snapshot = testkit.snapshot();
+        let schema = Schema::new(&snapshot);
+        let proposal = schema.proposals().get(&proposal_hash).unwrap();
+        assert_eq!(proposal.status(Height(4)), ProposalStatus::Open);
+        assert_eq!(schema.proposal_approvals(&proposal_hash).keys().count(), 1);
+        assert!(schema.neo4j_transaction(&tx_hash).is_none());
+    }
+
+    let (ref second, ref second_key) = officers.second;
+    testkit.create_block_with_transactions(txvec![ApproveQueries::new(
+        &proposal_hash,
+        second,
+        second_key
+    )]);
+    let snapshot = testkit.snapshot();
+    let schema = Schema::new(&snapshot);
+    let proposal = schema.proposals().get(&proposal_hash).unwrap();
+    assert_eq!(proposal.status(Height(5)), ProposalStatus::Approved);
+    let transaction = schema.neo4j_transaction(&tx_hash).unwrap();
+    assert_eq!(transaction.queries(), "CREATE (n:D)");
+    assert_eq!(transaction.pub_key(), writer);
+    assert_eq!(transaction.status(), Some(TransactionStatus::Pending));
+    assert_eq!(schema.dispatched(Height(5)).get(0), Some(tx_hash));
+}
+
+#[test]
+fn test_expired_proposal_is_not_sent() {
+    let (mut testkit, _mock, officers) = init_testkit();
+    let (ref writer, ref writer_key) = officers.writer;
+    let propose = ProposeQueries::new("CREATE (n:D)", "15-OCT", writer, writer_key);
+    let proposal_hash = propose.hash();
+    testkit.create_block_with_transactions(txvec![propose]);
+    testkit.create_blocks_until(Height(8));
+
+    let (ref first, ref first_key) = officers.first;
+    let (ref second, ref second_key) = officers.second;
+    testkit.create_block_with_transactions(txvec![
+        ApproveQueries::new(&proposal_hash, first, first_key),
+        ApproveQueries::new(&proposal_hash, second, second_key),
+    ]);
+
+    let snapshot = testkit.snapshot();
+    let schema = Schema::new(&snapshot);
+    let proposal = schema.proposals().get(&proposal_hash).unwrap();
+    assert_eq!(proposal.expires_at(), 8);
+    assert_eq!(proposal.status(Height(9)), ProposalStatus::Expired);
+    assert_eq!(schema.proposal_approvals(&proposal_hash).keys().count(), 0);
+    assert!(schema
+        .neo4j_transaction(&proposal_transaction_hash(&proposal_hash))
+        .is_none());
+}
+
+#[test]
+fn test_only_approvers_approve() {
+    let (mut testkit, _mock, officers) = init_testkit();
+    let (ref writer, ref writer_key) = officers.writer;
+    let propose = ProposeQueries::new("CREATE (n:D)", "15-OCT", writer, writer_key);
+    let proposal_hash = propose.hash();
+    testkit.create_block_with_transactions(txvec![
+        propose,
+        ApproveQueries::new(&proposal_hash, writer, writer_key),
+    ]);
+
+    let snapshot = testkit.snapshot();
+    let schema = Schema::new(&snapshot);
+    assert!(schema.proposals().get(&proposal_hash).is_some());
+    assert_eq!(schema.proposal_approvals(&proposal_hash).keys().count(), 0);
+}
+
+#[test]
+fn test_author_cannot_approve_own_proposal() {
+    // Without a list of authors everyone holds every role, the author of the proposal as well.
+    let config = ServiceConfig {
+        approvals: ApprovalConfig {
+            threshold: 1,
+            ttl: u64::max_value(),
+        },
+        ..ServiceConfig::default()
+    };
+    let (mut testkit, _mock) = support::init_testkit_with_config(config);
+    let (writer, writer_key) = crypto::gen_keypair();
+    let propose = ProposeQueries::new("CREATE (n:D)", "15-OCT", &writer, &writer_key);
+    let proposal_hash = propose.hash();
+    testkit.create_block_with_transactions(txvec![
+        propose,
+        ApproveQueries::new(&proposal_hash, &writer, &writer_key),
+    ]);
+
+    let snapshot = testkit.snapshot();
+    let schema = Schema::new(&snapshot);
+    let proposal = schema.proposals().get(&proposal_hash).unwrap();
+    // The expiry height saturates instead of overflowing.
+    assert_eq!(proposal.expires_at(), u64::max_value());
+    assert_eq!(proposal.status(Height(4)), ProposalStatus::Open);
+    assert_eq!(schema.proposal_approvals(&proposal_hash).keys().count(), 0);
+    assert!(schema
+        .neo4j_transaction(&proposal_transaction_hash(&proposal_hash))
+        .is_none());
+}
@@ -0,0 +1,187 @@
+extern crate exonum;
+extern crate exonum_neo4j;
+#[macro_use]
+extern crate exonum_testkit;
+
+use exonum::crypto;
+use exonum::messages::Message;
+use exonum::storage::{Database, Fork, MemoryDB};
+use exonum_neo4j::compensation::{check_unchanged_since, compensating_queries, typed_literal};
+use exonum_neo4j::schema::Schema;
+use exonum_neo4j::structures::NodeChange::{self, AN, ANP, RNP};
+use exonum_neo4j::structures::{AddNode, AddNodeProperty, RemoveNodeProperty, TransactionStatus};
+use exonum_neo4j::transactions::{CommitQueries, RevertTransaction};
+
+pub mod support;
+
+use support::{created_nodes, succeeded};
+
+/// Records the changes the way an audit does.
+fn apply(fork: &mut Fork, changes: &[NodeChange]) {
+    let mut schema = Schema::new(fork);
+    for change in changes {
+        for uuid in change.get_uuis() {
+            schema.add_node_history(uuid, change);
+        }
+    }
+}
+
+#[test]
+fn test_revert_uses_recorded_previous_values() {
+    let db = MemoryDB::new();
+    let fork = db.fork();
+    let changes = vec![
+        ANP(AddNodeProperty::new(
+            "t1_0",
+            "name",
+            "Door 2",
+            "t2",
+            Some("Door 1"),
+            Some("STRING"),
+        )),
+        ANP(AddNodeProperty::new("t1_0", "floor", "2", "t2", None, None)),
+        RNP(RemoveNodeProperty::new(
+            "t1_0",
+            "code",
+            "t2",
+            Some("1234"),
+            Some("INTEGER"),
+        )),
+        RNP(RemoveNodeProperty::new(
+            "t1_0",
+            "serial",
+            "t2",
+            Some("0042"),
+            None,
+        )),
+    ];
+    let queries = compensating_queries(&Schema::new(&fork), &changes, true);
+    let statements: Vec<&str> = queries.split(';').collect();
+    let node = "MATCH (n) WHERE (n.uuid = 't1_0' OR n.restored_uuid = 't1_0')";
+    assert_eq!(
+        statements,
+        vec![
+            format!("{} SET n.`name` = 'Door 1'", node),
+            format!("{} REMOVE n.`floor`", node),
+            format!("{} SET n.`code` = 1234", node),
+            format!("{} SET n.`serial` = '0042'", node),
+        ]
+    );
+}
+
+#[test]
+fn test_previous_values_keep_their_type() {
+    assert_eq!(typed_literal("true", Some("BOOLEAN")), "true");
+    assert_eq!(typed_literal("-12", Some("INTEGER")), "-12");
+    assert_eq!(typed_literal("1.0E7", Some("FLOAT")), "1.0E7");
+    assert_eq!(typed_literal("NaN", Some("FLOAT")), "0.0 / 0.0");
+    assert_eq!(typed_literal("-Infinity", Some("FLOAT")), "-1.0 / 0.0");
+    assert_eq!(typed_literal("it's", Some("STRING")), "'it\\'s'");
+    assert_eq!(
+        typed_literal("2018-10-15", Some("DATE")),
+        "date('2018-10-15')"
+    );
+    assert_eq!(typed_literal("PT1H", Some("DURATION")), "duration('PT1H')");
+    assert_eq!(
+        typed_literal("[\"1\",\"2\"]", Some("LIST<INTEGER>")),
+        "[1, 2]"
+    );
+    assert_eq!(
+        typed_literal("[\"a;b\"]", Some("LIST<STRING>")),
+        "['a\\u003Bb']"
+    );
+    // Values that do not read as their type are restored as strings.
+    assert_eq!(typed_literal("12 OR 1=1", Some("INTEGER")), "'12 OR 1=1'");
+    assert_eq!(typed_literal("[1", Some("LIST<INTEGER>")), "'[1'");
+    assert_eq!(typed_literal("x", Some("POINT")), "'x'");
+    assert_eq!(typed_literal("12", None), "'12'");
+}
+
+#[test]
+fn test_later_changes_block_the_revert() {
+    let db = MemoryDB::new();
+    let mut fork = db.fork();
+    let reverted = vec![
+        AN(AddNode::new("t1_0", "t1")),
+        AN(AddNode::new("t1_1", "t1")),
+    ];
+    apply(&mut fork, &reverted);
+    assert_eq!(
+        check_unchanged_since(&Schema::new(&fork), "t1", &reverted),
+        Ok(())
+    );
+
+    apply(
+        &mut fork,
+        &[ANP(AddNodeProperty::new(
+            "t1_1", "name", "Door", "t2", None, None,
+        ))],
+    );
+    assert_eq!(
+        check_unchanged_since(&Schema::new(&fork), "t1", &reverted),
+        Err(String::from("t1_1"))
+    );
+}
+
+#[test]
+fn test_pending_transaction_is_not_reverted() {
+    let (mut testkit, _mock) = support::init_testkit();
+    let (pub_key, secret_key) = crypto::gen_keypair();
+    let commit = CommitQueries::new("CREATE (n)", "15-OCT", &pub_key, &secret_key);
+    let revert = RevertTransaction::new(&commit.hash(), &pub_key, &secret_key);
+    let revert_hash = revert.hash();
+    testkit.create_block_with_transactions(txvec![commit]);
+    testkit.create_block_with_transactions(txvec![revert]);
+
+    let snapshot = testkit.snapshot();
+    let schema = Schema::new(&snapshot);
+    assert!(schema.neo4j_transaction(&revert_hash).is_none());
+}
+
+#[test]
+fn test_open_later_transaction_blocks_the_revert() {
+    let (mut testkit, mock) = support::init_testkit();
+    let (pub_key, secret_key) = crypto::gen_keypair();
+    let commit = CommitQueries::new("CREATE (n)", "15-OCT", &pub_key, &secret_key);
+    let commit_hash = commit.hash();
+    let reverted_id = commit_hash.to_hex();
+    mock.script_transactions(move |t| {
+        if t.get_transaction_id() != reverted_id {
+            return succeeded(t.get_transaction_id());
+        }
+        let mut r = created_nodes(t.get_transaction_id(), &["u1"]);
+        r.mut_modifications().set_records_previous_values(true);
+        r
+    });
+    testkit.create_block_with_transactions(txvec![commit]);
+    testkit.create_block();
+
+    // The later transaction is committed in the same block as the revert, before it, and its
+    // changes are not known yet.
+    let later = CommitQueries::new(
+        "MATCH (n) SET n.name = 'Door'",
+        "15-OCT",
+        &pub_key,
+        &secret_key,
+    );
+    let revert = RevertTransaction::new(&commit_hash, &pub_key, &secret_key);
+    let revert_hash = revert.hash();
+    testkit.create_block_with_transactions(txvec![later, revert]);
+    assert!(Schema::new(&testkit.snapshot())
+        .neo4j_transaction(&revert_hash)
+        .is_none());
+
+    // Once the later transaction is audited without touching the node, the revert goes through.
+    testkit.create_block();
+    let (admin_key, admin_secret_key) = crypto::gen_keypair();
+    let revert = RevertTransaction::new(&commit_hash, &admin_key, &admin_secret_key);
+    let revert_hash = revert.hash();
+    testkit.create_block_with_transactions(txvec![revert]);
+    let snapshot = testkit.snapshot();
+    let schema = Schema::new(&snapshot);
+    assert_eq!(
+        schema.neo4j_transaction(&revert_hash).unwrap().status(),
+        Some(TransactionStatus::Pending)
+    );
+    assert_eq!(schema.reverts().get(&commit_hash), Some(revert_hash));
+}
@@ -0,0 +1,116 @@
+extern crate exonum;
+extern crate exonum_neo4j;
+#[macro_use]
+extern crate exonum_testkit;
+
+use exonum::crypto;
+use exonum::helpers::Height;
+use exonum::messages::Message;
+use exonum_neo4j::config::ServiceConfig;
+use exonum_neo4j::schema::Schema;
+use exonum_neo4j::structures::{FailureCode, ScheduledBatch, TransactionStatus};
+use exonum_neo4j::transactions::{CancelScheduled, ScheduleQueries};
+
+pub mod support;
+
+#[test]
+fn test_batch_is_due() {
+    let pub_key = crypto::gen_keypair().0;
+    let by_height = ScheduledBatch::new(&pub_key, 1, 5, 0);
+    assert!(!by_height.is_due(Height(4), None));
+    assert!(by_height.is_due(Height(5), None));
+
+    let by_time = ScheduledBatch::new(&pub_key, 1, 0, 1_540_000_000);
+    assert!(!by_time.is_due(Height(5), None));
+    assert!(!by_time.is_due(Height(5), Some(1_539_999_999)));
+    assert!(by_time.is_due(Height(5), Some(1_540_000_000)));
+}
+
+#[test]
+fn test_batch_is_sent_once_due() {
+    let (mut testkit, _mock) = support::init_testkit();
+    let (pub_key, secret_key) = crypto::gen_keypair();
+    let schedule = ScheduleQueries::new("CREATE (n:D)", "15-OCT", 4, 0, &pub_key, &secret_key);
+    let batch_hash = schedule.hash();
+    testkit.create_block_with_transactions(txvec![schedule]);
+    {
+        let snapshot = testkit.snapshot();
+        let schema = Schema::new(&snapshot);
+        let transaction = schema.neo4j_transaction(&batch_hash).unwrap();
+        assert_eq!(transaction.status(), Some(TransactionStatus::Scheduled));
+        assert!(schema.scheduled().contains(&batch_hash));
+    }
+
+    // The activation is sent after the block before the activation height is committed.
+    testkit.create_blocks_until(Height(4));
+    let snapshot = testkit.snapshot();
+    let schema = Schema::new(&snapshot);
+    let transaction = schema.neo4j_transaction(&batch_hash).unwrap();
+    assert_eq!(transaction.status(), Some(TransactionStatus::Pending));
+    assert!(!schema.scheduled().contains(&batch_hash));
+    assert_eq!(schema.dispatched(Height(4)).get(0), Some(batch_hash));
+}
+
+#[test]
+fn test_only_the_author_cancels() {
+    let (mut testkit, _mock) = support::init_testkit();
+    let (pub_key, secret_key) = crypto::gen_keypair();
+    let (other_key, other_secret_key) = crypto::gen_keypair();
+    let schedule = ScheduleQueries::new("CREATE (n:D)", "15-OCT", 10, 0, &pub_key, &secret_key);
+    let batch_hash = schedule.hash();
+    testkit.create_block_with_transactions(txvec![
+        schedule,
+        CancelScheduled::new(&batch_hash, &other_key, &other_secret_key),
+    ]);
+    assert!(Schema::new(&testkit.snapshot())
+        .scheduled()
+        .contains(&batch_hash));
+
+    testkit.create_block_with_transactions(txvec![CancelScheduled::new(
+        &batch_hash,
+        &pub_key,
+        &secret_key
+    )]);
+    let snapshot = testkit.snapshot();
+    let schema = Schema::new(&snapshot);
+    let transaction = schema.neo4j_transaction(&batch_hash).unwrap();
+    assert_eq!(transaction.status(), Some(TransactionStatus::Cancelled));
+    assert!(!schema.scheduled().contains(&batch_hash));
+}
+
+#[test]
+fn test_activation_rechecks_the_author() {
+    let (mut testkit, _mock) = support::init_testkit();
+    let (pub_key, secret_key) = crypto::gen_keypair();
+    let schedule = ScheduleQueries::new("CREATE (n:D)", "15-OCT", 5, 0, &pub_key, &secret_key);
+    let batch_hash = schedule.hash();
+    testkit.create_block_with_transactions(txvec![schedule]);
+
+    // The author loses the writer role before the batch is due.
+    let mut proposal = testkit.configuration_change_proposal();
+    proposal.set_service_config(
+        "neo4j_blockchain",
+        ServiceConfig {
+            authors: Some(Vec::new()),
+            ..ServiceConfig::default()
+        },
+    );
+    proposal.set_actual_from(Height(3));
+    testkit.commit_configuration_change(proposal);
+    testkit.create_blocks_until(Height(5));
+
+    let snapshot = testkit.snapshot();
+    let schema = Schema::new(&snapshot);
+    let transaction = schema.neo4j_transaction(&batch_hash).unwrap();
+    assert_eq!(transaction.status(), Some(TransactionStatus::Rejected));
+    assert_eq!(
+        schema
+            .transaction_failures()
+            .get(&batch_hash)
+            .unwrap()
+            .failure_code(),
+        Some(FailureCode::NotAuthorized)
+    );
+    assert!(!schema.scheduled().contains(&batch_hash));
+    assert!(schema.dispatched(Height(5)).is_empty());
+}
@@ -0,0 +1,133 @@
+extern crate exonum;
+extern crate exonum_neo4j;
+#[macro_use]
+extern crate exonum_testkit;
+
+use exonum::crypto::{self, Hash};
+use exonum::helpers::Height;
+use exonum::messages::Message;
+use exonum::storage::{Database, MemoryDB};
+use exonum_neo4j::schema::Schema;
+use exonum_neo4j::structures::{
+    definition_hash, SchemaChangeRecord, SchemaObjectKind, SchemaOperation, TransactionStatus,
+};
+use exonum_neo4j::transactions::SchemaChange;
+
+pub mod support;
+
+#[test]
+fn test_parse_schema_statement() {
+    let pub_key = crypto::gen_keypair().0;
+    let change = SchemaChangeRecord::parse("create  index ON :Person(name)", &pub_key).unwrap();
+    assert_eq!(change.operation(), SchemaOperation::Create);
+    assert_eq!(change.kind(), SchemaObjectKind::Index);
+    assert_eq!(change.definition(), "INDEX ON :Person(name)");
+    assert_eq!(change.statement(), "CREATE INDEX ON :Person(name)");
+
+    let change = SchemaChangeRecord::parse(
+        "DROP CONSTRAINT ON (n:Person)\n ASSERT n.uuid IS UNIQUE",
+        &pub_key,
+    )
+    .unwrap();
+    assert_eq!(change.operation(), SchemaOperation::Drop);
+    assert_eq!(change.kind(), SchemaObjectKind::Constraint);
+    assert_eq!(
+        definition_hash(change.definition()),
+        definition_hash("CONSTRAINT ON (n:Person) ASSERT n.uuid IS UNIQUE")
+    );
+
+    assert!(SchemaChangeRecord::parse("CREATE (n:Person)", &pub_key).is_err());
+    assert!(SchemaChangeRecord::parse("DROP INDEX", &pub_key).is_err());
+    assert!(SchemaChangeRecord::parse(
+        "CREATE INDEX ON :Person(name); MATCH (n) DETACH DELETE n",
+        &pub_key
+    )
+    .is_err());
+}
+
+#[test]
+fn test_registry_follows_applied_changes() {
+    let pub_key = crypto::gen_keypair().0;
+    let db = MemoryDB::new();
+    let mut fork = db.fork();
+    let mut schema = Schema::new(&mut fork);
+    let create = crypto::hash(b"create");
+    let drop = crypto::hash(b"drop");
+    schema.schema_changes_mut().put(
+        &create,
+        SchemaChangeRecord::parse("CREATE INDEX ON :Person(name)", &pub_key).unwrap(),
+    );
+    schema.schema_changes_mut().put(
+        &drop,
+        SchemaChangeRecord::parse("DROP INDEX ON :Person(name)", &pub_key).unwrap(),
+    );
+    let key = definition_hash("INDEX ON :Person(name)");
+
+    schema.apply_schema_change(&create, Height(3));
+    let object = schema.schema_objects().get(&key).unwrap();
+    assert_eq!(object.transaction(), &create);
+    assert_eq!(object.height(), 3);
+
+    schema.apply_schema_change(&drop, Height(5));
+    assert!(schema.schema_objects().get(&key).is_none());
+    let history: Vec<Hash> = schema.schema_object_history(&key).iter().collect();
+    assert_eq!(history, vec![create, drop]);
+}
+
+#[test]
+fn test_schema_change_is_pending() {
+    let (mut testkit, _mock) = support::init_testkit();
+    let (pub_key, secret_key) = crypto::gen_keypair();
+    let change = SchemaChange::new(
+        "CREATE INDEX ON :Person(name)",
+        "15-OCT",
+        &pub_key,
+        &secret_key,
+    );
+    let tx_hash = change.hash();
+    testkit.create_block_with_transactions(txvec![change]);
+
+    let snapshot = testkit.snapshot();
+    let schema = Schema::new(&snapshot);
+    let transaction = schema.neo4j_transaction(&tx_hash).unwrap();
+    assert_eq!(transaction.status(), Some(TransactionStatus::Pending));
+    assert_eq!(transaction.queries(), "CREATE INDEX ON :Person(name)");
+    assert!(schema.schema_changes().contains(&tx_hash));
+    // The registry only changes once Neo4j confirmed the change during an audit.
+    assert_eq!(schema.schema_objects().keys().count(), 0);
+}
+
+#[test]
+fn test_pending_changes_count_as_applied() {
+    let (mut testkit, _mock) = support::init_testkit();
+    let (pub_key, secret_key) = crypto::gen_keypair();
+    let change = |statement: &str, datetime: &str| {
+        SchemaChange::new(statement, datetime, &pub_key, &secret_key)
+    };
+    let create = change("CREATE INDEX ON :Person(name)", "15-OCT");
+    let create_hash = create.hash();
+    testkit.create_block_with_transactions(txvec![create]);
+
+    // The index is not in the registry before the audit, but creating it twice is refused.
+    let twice = change("CREATE INDEX ON :Person(name)", "16-OCT");
+    let twice_hash = twice.hash();
+    let drop = change("DROP INDEX ON :Person(name)", "16-OCT");
+    let drop_hash = drop.hash();
+    let drop_again = change("DROP INDEX ON :Person(name)", "17-OCT");
+    let drop_again_hash = drop_again.hash();
+    testkit.create_block_with_transactions(txvec![twice, drop, drop_again]);
+
+    let snapshot = testkit.snapshot();
+    let schema = Schema::new(&snapshot);
+    let key = definition_hash("INDEX ON :Person(name)");
+    assert_eq!(schema.schema_objects().keys().count(), 0);
+    assert!(schema.neo4j_transaction(&twice_hash).is_none());
+    assert_eq!(
+        schema.neo4j_transaction(&drop_hash).unwrap().status(),
+        Some(TransactionStatus::Pending)
+    );
+    assert!(schema.neo4j_transaction(&drop_again_hash).is_none());
+    assert_eq!(schema.pending_schema_changes().get(&key), Some(drop_hash));
+    assert!(!schema.schema_object_will_exist(&key));
+    assert!(schema.schema_changes().contains(&create_hash));
+}
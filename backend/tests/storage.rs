@@ -0,0 +1,95 @@
+extern crate exonum;
+extern crate exonum_neo4j;
+extern crate protobuf;
+
+use exonum::crypto::{self, CryptoHash};
+use exonum::storage::StorageValue;
+use exonum_neo4j::neo4j::proto::neo4j_service as pb;
+use exonum_neo4j::structures::{
+    AddLabel, Neo4jTransaction, NodeChange, Relation, StatusTransition, TransactionStatus,
+    PROTOBUF_MARKER, UNKNOWN_STATUS,
+};
+use protobuf::Message;
+use std::borrow::Cow;
+
+pub mod legacy;
+
+use legacy::{legacy_bytes, legacy_transaction};
+
+#[test]
+fn test_protobuf_round_trip() {
+    let (pub_key, _) = crypto::gen_keypair();
+    let transaction = Neo4jTransaction::new("CREATE (n)", "", TransactionStatus::Pending, &pub_key);
+    let bytes = transaction.clone().into_bytes();
+    assert_eq!(bytes[0], PROTOBUF_MARKER);
+    assert_eq!(transaction.hash(), crypto::hash(&bytes));
+
+    let decoded = Neo4jTransaction::from_bytes(Cow::Owned(bytes));
+    assert_eq!(decoded, transaction);
+    assert_eq!(decoded.pub_key(), &pub_key);
+}
+
+#[test]
+fn test_node_change_round_trip() {
+    let change = NodeChange::AL(AddLabel::new("u1", "R", "71afce3e"));
+    match NodeChange::from_bytes(Cow::Owned(change.into_bytes())) {
+        NodeChange::AL(x) => assert_eq!(x, AddLabel::new("u1", "R", "71afce3e")),
+        other => panic!("Unexpected change {:?}", other),
+    }
+}
+
+#[test]
+fn test_read_legacy_values() {
+    let relation = Relation::from_bytes(Cow::Owned(legacy_bytes(&["u1", "u2"])));
+    assert_eq!(relation, Relation::new("u1", "u2", ""));
+
+    let mut bytes = legacy_bytes(&["u1", "R", "71afce3e"]);
+    bytes.push(5);
+    match NodeChange::from_bytes(Cow::Owned(bytes)) {
+        NodeChange::AL(x) => assert_eq!(x, AddLabel::new("u1", "R", "71afce3e")),
+        other => panic!("Unexpected change {:?}", other),
+    }
+}
+
+#[test]
+fn test_decode_reports_corrupt_values() {
+    let (pub_key, _) = crypto::gen_keypair();
+    let legacy = legacy_transaction("CREATE (n)", "SUCCESS", &pub_key);
+    let transaction = Neo4jTransaction::decode(&legacy).unwrap();
+    assert_eq!(transaction.queries(), "CREATE (n)");
+    assert_eq!(transaction.pub_key(), &pub_key);
+
+    // A legacy value whose segments point outside of it.
+    assert!(Neo4jTransaction::decode(&legacy[..legacy.len() - 1]).is_err());
+    assert!(Relation::decode(&legacy_bytes(&["u1"])).is_err());
+    // A marked value that is no protobuf message.
+    assert!(Neo4jTransaction::decode(&[PROTOBUF_MARKER, 0xFF, 0xFF]).is_err());
+    assert!(StatusTransition::decode(&[]).is_err());
+}
+
+#[test]
+fn test_status_stored_by_name() {
+    let (pub_key, _) = crypto::gen_keypair();
+    let legacy = legacy_transaction("CREATE (n)", "SUCCESS", &pub_key);
+    let transaction = Neo4jTransaction::decode(&legacy).unwrap();
+    assert_eq!(transaction.status(), Some(TransactionStatus::Success));
+
+    // Protobuf values written before the status was stored as a number.
+    let mut message = pb::Neo4jTransaction::new();
+    message.set_queries(String::from("CREATE (n)"));
+    message.set_result(String::from("REJECTED"));
+    message.set_pub_key(pub_key.as_ref().to_vec());
+    let mut bytes = vec![PROTOBUF_MARKER];
+    message.write_to_vec(&mut bytes).unwrap();
+    let transaction = Neo4jTransaction::decode(&bytes).unwrap();
+    assert_eq!(transaction.status(), Some(TransactionStatus::Rejected));
+    assert_eq!(
+        transaction.into_bytes(),
+        Neo4jTransaction::new("CREATE (n)", "", TransactionStatus::Rejected, &pub_key).into_bytes()
+    );
+
+    let unknown = legacy_transaction("CREATE (n)", "Failed to parse the result", &pub_key);
+    let transaction = Neo4jTransaction::decode(&unknown).unwrap();
+    assert_eq!(transaction.status_number(), UNKNOWN_STATUS);
+    assert_eq!(transaction.status(), None);
+}
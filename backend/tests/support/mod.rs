@@ -0,0 +1,398 @@
+//! Scriptable stand-in for the transaction manager plugin.
+//!
+//! Each `MockTransactionManager` serves the gRPC interface of the plugin on its own port and
+//! stops when dropped. By default every executed transaction succeeds without modifications and
+//! its result is kept under the block id, the way the plugin keeps block changes until the
+//! audit retrieves them. Tests can script the changes of transactions or of whole blocks, make
+//! calls fail or slow down, and inspect the requests the service sent.
+//!
+//! `init_testkit` gives a testkit whose service runs on a new mock, for tests that only need the
+//! graph to be reachable.
+
+// Every test crate includes the module, but none of them uses all of it.
+#![allow(dead_code)]
+
+extern crate grpc;
+extern crate protobuf;
+extern crate tls_api_native_tls;
+
+use self::grpc::{RequestOptions, ServerBuilder, SingleResponse};
+use self::protobuf::RepeatedField;
+use self::tls_api_native_tls::TlsAcceptor;
+use exonum::helpers::Height;
+use exonum_neo4j::config::ServiceConfig;
+use exonum_neo4j::neo4j::proto::transaction_manager::{
+    BlockChangesRequest, BlockChangesResponse, BlockExecuteRequest, BlockExecuteResponse,
+    DatabaseModifications, DatabaseModifications_CreatedNode, DeleteBlockRequest,
+    DeleteBlockResponse, Error, ErrorCode, HealthRequest, HealthResponse, SchemaChangeRequest,
+    SchemaChangeResponse, Status, TransactionRequest, TransactionResponse,
+};
+use exonum_neo4j::neo4j::proto::transaction_manager_grpc::{
+    TransactionManager, TransactionManagerServer,
+};
+use exonum_neo4j::neo4j::{Neo4jConfig, Neo4jRpc};
+use exonum_neo4j::Neo4jService;
+use exonum_testkit::{TestKit, TestKitBuilder};
+use std::collections::HashMap;
+use std::net::TcpListener;
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+/// Call of the transaction manager interface.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Method {
+    ExecuteBlock,
+    RetrieveBlockChanges,
+    DeleteBlockChanges,
+    ExecuteSchemaChange,
+    Health,
+}
+
+/// Request received by the mock, in the order of arrival.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Request {
+    ExecuteBlock(BlockExecuteRequest),
+    RetrieveBlockChanges(String),
+    DeleteBlockChanges(String),
+    ExecuteSchemaChange(SchemaChangeRequest),
+    Health,
+}
+
+type TransactionScript = Box<dyn Fn(&TransactionRequest) -> TransactionResponse + Send>;
+
+struct Failure {
+    message: String,
+    /// Number of calls left to fail, none to fail until the mock recovers.
+    remaining: Option<usize>,
+}
+
+struct MockState {
+    transaction_script: TransactionScript,
+    scripted_blocks: HashMap<String, Vec<TransactionResponse>>,
+    executed_blocks: HashMap<String, Vec<TransactionResponse>>,
+    failures: HashMap<Method, Failure>,
+    delays: HashMap<Method, Duration>,
+    requests: Vec<Request>,
+}
+
+impl MockState {
+    /// Records the request, waits for the scripted delay and tells whether the call fails.
+    fn receive(state: &Mutex<MockState>, method: Method, request: Request) -> Result<(), String> {
+        let delay = {
+            let mut state = state.lock().unwrap();
+            state.requests.push(request);
+            state.delays.get(&method).cloned()
+        };
+        if let Some(delay) = delay {
+            thread::sleep(delay);
+        }
+        let mut state = state.lock().unwrap();
+        let (message, exhausted) = match state.failures.get_mut(&method) {
+            Some(failure) => {
+                if let Some(ref mut remaining) = failure.remaining {
+                    *remaining -= 1;
+                }
+                (failure.message.clone(), failure.remaining == Some(0))
+            }
+            None => return Ok(()),
+        };
+        if exhausted {
+            state.failures.remove(&method);
+        }
+        Err(message)
+    }
+}
+
+fn failed<T: Send + 'static>(message: String) -> SingleResponse<T> {
+    SingleResponse::err(grpc::Error::Panic(message))
+}
+
+struct MockServerImpl {
+    state: Arc<Mutex<MockState>>,
+}
+
+impl TransactionManager for MockServerImpl {
+    fn execute_block(
+        &self,
+        _o: RequestOptions,
+        p: BlockExecuteRequest,
+    ) -> SingleResponse<BlockExecuteResponse> {
+        if let Err(message) = MockState::receive(
+            &self.state,
+            Method::ExecuteBlock,
+            Request::ExecuteBlock(p.clone()),
+        ) {
+            return failed(message);
+        }
+        let mut state = self.state.lock().unwrap();
+        let results: Vec<TransactionResponse> = p
+            .get_transactions()
+            .iter()
+            .map(|t| (state.transaction_script)(t))
+            .collect();
+        state
+            .executed_blocks
+            .insert(p.get_block_id().to_string(), results);
+        let mut r = BlockExecuteResponse::new();
+        r.set_success(true);
+        SingleResponse::completed(r)
+    }
+
+    fn retrieve_block_changes(
+        &self,
+        _o: RequestOptions,
+        p: BlockChangesRequest,
+    ) -> SingleResponse<BlockChangesResponse> {
+        let block_id = p.get_block_id().to_string();
+        if let Err(message) = MockState::receive(
+            &self.state,
+            Method::RetrieveBlockChanges,
+            Request::RetrieveBlockChanges(block_id.clone()),
+        ) {
+            return failed(message);
+        }
+        let state = self.state.lock().unwrap();
+        let transactions = state
+            .scripted_blocks
+            .get(&block_id)
+            .or_else(|| state.executed_blocks.get(&block_id));
+        match transactions {
+            Some(transactions) => {
+                let mut r = BlockChangesResponse::new();
+                r.set_block_id(block_id);
+                r.set_transactions(RepeatedField::from_vec(transactions.clone()));
+                SingleResponse::completed(r)
+            }
+            None => failed(String::from("Could not find the changes for this block.")),
+        }
+    }
+
+    fn delete_block_changes(
+        &self,
+        _o: RequestOptions,
+        p: DeleteBlockRequest,
+    ) -> SingleResponse<DeleteBlockResponse> {
+        let block_id = p.get_block_id().to_string();
+        if let Err(message) = MockState::receive(
+            &self.state,
+            Method::DeleteBlockChanges,
+            Request::DeleteBlockChanges(block_id.clone()),
+        ) {
+            return failed(message);
+        }
+        let mut state = self.state.lock().unwrap();
+        let scripted = state.scripted_blocks.remove(&block_id).is_some();
+        let executed = state.executed_blocks.remove(&block_id).is_some();
+        let mut r = DeleteBlockResponse::new();
+        r.set_success(scripted || executed);
+        SingleResponse::completed(r)
+    }
+
+    fn execute_schema_change(
+        &self,
+        _o: RequestOptions,
+        p: SchemaChangeRequest,
+    ) -> SingleResponse<SchemaChangeResponse> {
+        if let Err(message) = MockState::receive(
+            &self.state,
+            Method::ExecuteSchemaChange,
+            Request::ExecuteSchemaChange(p.clone()),
+        ) {
+            return failed(message);
+        }
+        let mut state = self.state.lock().unwrap();
+        let mut result = TransactionResponse::new();
+        result.set_transaction_id(p.get_transaction_id().to_string());
+        result.set_result(Status::SUCCESS);
+        state
+            .executed_blocks
+            .entry(p.get_block_id().to_string())
+            .or_insert_with(Vec::new)
+            .push(result);
+        let mut r = SchemaChangeResponse::new();
+        r.set_success(true);
+        SingleResponse::completed(r)
+    }
+
+    fn health(&self, _o: RequestOptions, _p: HealthRequest) -> SingleResponse<HealthResponse> {
+        let available = MockState::receive(&self.state, Method::Health, Request::Health).is_ok();
+        let mut r = HealthResponse::new();
+        r.set_available(available);
+        SingleResponse::completed(r)
+    }
+}
+
+/// Transaction manager serving scripted responses.
+pub struct MockTransactionManager {
+    state: Arc<Mutex<MockState>>,
+    port: u16,
+    _server: grpc::Server,
+}
+
+impl MockTransactionManager {
+    /// Starts a mock on a free port.
+    pub fn start() -> Self {
+        // The port is released right before the server binds it, which is good enough for tests.
+        let port = TcpListener::bind("127.0.0.1:0")
+            .and_then(|listener| listener.local_addr())
+            .map(|address| address.port())
+            .expect("free port");
+        Self::start_on(port)
+    }
+
+    /// Starts a mock on the given port, for code that reads the port from neo4j.toml.
+    pub fn start_on(port: u16) -> Self {
+        let state = Arc::new(Mutex::new(MockState {
+            transaction_script: Box::new(|t| succeeded(t.get_transaction_id())),
+            scripted_blocks: HashMap::new(),
+            executed_blocks: HashMap::new(),
+            failures: HashMap::new(),
+            delays: HashMap::new(),
+            requests: Vec::new(),
+        }));
+        let mut server: ServerBuilder<TlsAcceptor> = ServerBuilder::new();
+        server.http.set_port(port);
+        server.add_service(TransactionManagerServer::new_service_def(MockServerImpl {
+            state: state.clone(),
+        }));
+        server.http.set_cpu_pool_threads(1);
+        let server = server.build().expect("server");
+        MockTransactionManager {
+            state,
+            port,
+            _server: server,
+        }
+    }
+
+    /// Port the mock serves on.
+    pub fn port(&self) -> u16 {
+        self.port
+    }
+
+    /// Client of the mock, to create the service with.
+    pub fn rpc(&self) -> Neo4jRpc {
+        Neo4jRpc::new(Neo4jConfig {
+            address: String::from("127.0.0.1"),
+            port: self.port,
+        })
+    }
+
+    /// Sets the result of every transaction executed from now on.
+    pub fn script_transactions<F>(&self, script: F)
+    where
+        F: Fn(&TransactionRequest) -> TransactionResponse + Send + 'static,
+    {
+        self.state.lock().unwrap().transaction_script = Box::new(script);
+    }
+
+    /// Sets the changes returned for a block, whatever was executed in it.
+    pub fn script_block(&self, block_id: &str, transactions: Vec<TransactionResponse>) {
+        self.state
+            .lock()
+            .unwrap()
+            .scripted_blocks
+            .insert(block_id.to_string(), transactions);
+    }
+
+    /// Makes every call of the method fail until the mock recovers.
+    pub fn fail(&self, method: Method, message: &str) {
+        self.set_failure(method, message, None);
+    }
+
+    /// Makes the next calls of the method fail.
+    pub fn fail_next(&self, method: Method, times: usize, message: &str) {
+        self.set_failure(method, message, Some(times));
+    }
+
+    fn set_failure(&self, method: Method, message: &str, remaining: Option<usize>) {
+        let failure = Failure {
+            message: message.to_string(),
+            remaining,
+        };
+        self.state.lock().unwrap().failures.insert(method, failure);
+    }
+
+    /// Lets every call succeed again.
+    pub fn recover(&self) {
+        self.state.lock().unwrap().failures.clear();
+    }
+
+    /// Delays the answer to every call of the method.
+    pub fn delay(&self, method: Method, delay: Duration) {
+        self.state.lock().unwrap().delays.insert(method, delay);
+    }
+
+    /// Requests received so far.
+    pub fn requests(&self) -> Vec<Request> {
+        self.state.lock().unwrap().requests.clone()
+    }
+
+    /// Ids of the blocks the mock was asked to execute.
+    pub fn executed_blocks(&self) -> Vec<String> {
+        self.requests()
+            .into_iter()
+            .filter_map(|r| match r {
+                Request::ExecuteBlock(block) => Some(block.get_block_id().to_string()),
+                _ => None,
+            })
+            .collect()
+    }
+}
+
+/// Successful transaction without modifications.
+pub fn succeeded(transaction_id: &str) -> TransactionResponse {
+    let mut r = TransactionResponse::new();
+    r.set_transaction_id(transaction_id.to_string());
+    r.set_result(Status::SUCCESS);
+    r.set_modifications(DatabaseModifications::new());
+    r
+}
+
+/// Successful transaction creating nodes with the given uuids.
+pub fn created_nodes(transaction_id: &str, uuids: &[&str]) -> TransactionResponse {
+    let mut r = succeeded(transaction_id);
+    for uuid in uuids {
+        let mut node = DatabaseModifications_CreatedNode::new();
+        node.set_node_UUID(uuid.to_string());
+        r.mut_modifications().mut_created_nodes().push(node);
+    }
+    r
+}
+
+/// Transaction that Neo4j refused.
+pub fn failed_transaction(
+    transaction_id: &str,
+    code: ErrorCode,
+    message: &str,
+) -> TransactionResponse {
+    let mut error = Error::new();
+    error.set_code(code);
+    error.set_message(message.to_string());
+    let mut r = TransactionResponse::new();
+    r.set_transaction_id(transaction_id.to_string());
+    r.set_result(Status::FAILURE);
+    r.set_error(error);
+    r
+}
+
+/// Testkit of a single validator whose service runs on a new mock. The mock stops when dropped,
+/// so it has to be kept for as long as the testkit.
+pub fn init_testkit() -> (TestKit, MockTransactionManager) {
+    let mock = MockTransactionManager::start();
+    let testkit = TestKitBuilder::validator()
+        .with_service(Neo4jService::new(mock.rpc()))
+        .create();
+    (testkit, mock)
+}
+
+/// Like `init_testkit`, with the service configuration in effect from height 2 on.
+pub fn init_testkit_with_config(config: ServiceConfig) -> (TestKit, MockTransactionManager) {
+    let (mut testkit, mock) = init_testkit();
+    let mut proposal = testkit.configuration_change_proposal();
+    proposal.set_service_config("neo4j_blockchain", config);
+    proposal.set_actual_from(Height(2));
+    testkit.commit_configuration_change(proposal);
+    testkit.create_blocks_until(Height(2));
+    (testkit, mock)
+}
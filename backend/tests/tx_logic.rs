@@ -2,39 +2,32 @@ extern crate exonum;
 extern crate exonum_neo4j;
 #[macro_use]
 extern crate exonum_testkit;
+extern crate protobuf;
 
+use exonum::blockchain::Schema as CoreSchema;
 use exonum::crypto;
-use exonum_testkit::{TestKit, TestKitBuilder};
+use exonum::messages::Message;
 // Import datatypes used in tests from the crate where the service is defined.
-use exonum_neo4j::neo4j;
+use exonum_neo4j::neo4j::{AuditedChanges, BlockChangesResponse, GraphBackend};
 use exonum_neo4j::schema::Schema;
-use exonum_neo4j::structures::NodeChange;
+use exonum_neo4j::structures::{NodeChange, TransactionStatus};
 use exonum_neo4j::transactions::{AuditBlocks, CommitQueries};
-use exonum_neo4j::Neo4jService;
+use exonum_testkit::TestKit;
 
-pub mod transaction_test_server;
+pub mod support;
 
-fn init_testkit(port: u16) -> TestKit {
-    let neo4j_config = neo4j::Neo4jConfig {
-        address: String::from("127.0.0.1"),
-        port: port,
-    };
+use protobuf::Message as ProtobufMessage;
 
-    let neo4j_rpc = neo4j::Neo4jRpc::new(neo4j_config);
-    TestKitBuilder::validator()
-        .with_service(Neo4jService::new(neo4j_rpc))
-        .create()
-}
+use support::{created_nodes, init_testkit, Method, Request};
 
 #[test]
 fn test_wrong_query() {
-    let _server = transaction_test_server::TestServer::new(50051);
-
-    let mut testkit = init_testkit(50051);
-    let (_pubkey, key) = crypto::gen_keypair();
+    let (mut testkit, _mock) = init_testkit();
+    let (pubkey, key) = crypto::gen_keypair();
     testkit.create_block_with_transactions(txvec![CommitQueries::new(
         "abort;CREAT (n)",
         "15-OCT",
+        &pubkey,
         &key
     ),]);
     let snapshot = testkit.snapshot();
@@ -46,20 +39,16 @@ fn test_wrong_query() {
 
 #[test]
 fn test_commit_query() {
-    let _server = transaction_test_server::TestServer::new(50052);
-
-    let mut testkit = init_testkit(50052);
+    let (mut testkit, mock) = init_testkit();
     let snapshot = testkit.snapshot();
     let schema = Schema::new(&snapshot);
     let queries = schema.neo4j_transactions();
 
     assert_eq!(queries.values().count(), 0);
-    let (_pubkey, key) = crypto::gen_keypair();
-    testkit.create_block_with_transactions(txvec![CommitQueries::new(
-        "INSERT something",
-        "15-OCT",
-        &key
-    ),]);
+    let (pubkey, key) = crypto::gen_keypair();
+    let tx = CommitQueries::new("INSERT something", "15-OCT", &pubkey, &key);
+    let tx_hash = tx.hash();
+    testkit.create_block_with_transactions(txvec![tx]);
     let snapshot = testkit.snapshot();
     let schema = Schema::new(&snapshot);
     let queries = schema.neo4j_transactions();
@@ -69,33 +58,158 @@ fn test_commit_query() {
         None => panic!("Null query found"),
     }
     assert_eq!(queries.values().count(), 1);
+
+    let author_transactions = schema.author_transactions(&pubkey);
+    assert_eq!(author_transactions.len(), 1);
+    assert_eq!(author_transactions.get(0), Some(tx_hash));
+
+    let status_history = schema.status_history(&tx_hash);
+    assert_eq!(status_history.len(), 1);
+    assert_eq!(
+        status_history.get(0).unwrap().transaction_status(),
+        Some(TransactionStatus::Pending)
+    );
+
+    // The block was sent to Neo4j once it was committed.
+    let block_hash = CoreSchema::new(&snapshot)
+        .block_hashes_by_height()
+        .get(1)
+        .unwrap();
+    assert_eq!(mock.executed_blocks(), vec![block_hash.to_hex()]);
+    match mock.requests().first() {
+        Some(Request::ExecuteBlock(request)) => assert_eq!(
+            request.get_transactions()[0].get_transaction_id(),
+            tx_hash.to_hex()
+        ),
+        _ => panic!("Block was not executed"),
+    }
 }
 
 #[test]
 fn test_get_changes_query() {
-    let _server = transaction_test_server::TestServer::new(9994);
-
-    let mut testkit = init_testkit(9994);
-    let (_pubkey, key) = crypto::gen_keypair();
-    testkit.create_block_with_transactions(txvec![CommitQueries::new(
-        "INSERT something",
-        "15-OCT",
-        &key
-    ),]);
-    testkit.create_block_with_transactions(txvec![AuditBlocks::new("43827394273", &key),]);
+    let (mut testkit, mock) = init_testkit();
+    mock.script_transactions(|t| created_nodes(t.get_transaction_id(), &["u1", "u2"]));
+    let (pubkey, key) = crypto::gen_keypair();
+    let tx = CommitQueries::new("INSERT something", "15-OCT", &pubkey, &key);
+    let transaction_hash = tx.hash();
+    testkit.create_block_with_transactions(txvec![tx]);
+    // The audit of the block was sent once Neo4j executed it.
+    testkit.create_block();
     let snapshot = testkit.snapshot();
     let schema = Schema::new(&snapshot);
     let node_changes = schema.node_history("u1");
     assert_eq!(node_changes.len(), 1);
-    println!("{:?}", node_changes.get(0));
     match node_changes.get(0) {
-        Some(node_change) => match node_change {
-            NodeChange::AN(x) => assert_eq!(
-                x.transaction_id(),
-                "71afce3e6a18a05376fccf766bfba321aa801af0ea6aef1a07b30e521363b3f8".to_string()
-            ),
-            _ => assert_eq!(true, false),
-        },
-        _ => assert_eq!(true, false),
+        Some(NodeChange::AN(x)) => assert_eq!(x.transaction_id(), transaction_hash.to_hex()),
+        other => panic!("Unexpected change {:?}", other),
     }
+    let transaction_changes = schema.transaction_changes(&transaction_hash);
+    assert_eq!(transaction_changes.len(), 2);
+    assert_eq!(
+        schema.transaction_changes_roots().get(&transaction_hash),
+        Some(transaction_changes.merkle_root())
+    );
+    assert_eq!(
+        schema
+            .neo4j_transaction(&transaction_hash)
+            .unwrap()
+            .status(),
+        Some(TransactionStatus::Success)
+    );
+
+    // The audit recorded the changes of the block, so they are not retrieved again.
+    let block_hash = CoreSchema::new(&snapshot)
+        .block_hashes_by_height()
+        .get(1)
+        .unwrap();
+    assert!(schema.audited_changes().contains(&block_hash));
+    assert_eq!(schema.get_last_confirmed_block(), Some(block_hash));
+    assert!(schema.unaudited_blocks().is_empty());
+}
+
+#[test]
+fn test_audits_need_a_validator() {
+    let (mut testkit, _mock) = init_testkit();
+    let (pubkey, key) = crypto::gen_keypair();
+    let tx = CommitQueries::new("CREATE (n)", "15-OCT", &pubkey, &key);
+    let tx_hash = tx.hash();
+    testkit.create_block_with_transactions(txvec![tx]);
+    let block_id = CoreSchema::new(&testkit.snapshot())
+        .block_hashes_by_height()
+        .get(1)
+        .unwrap()
+        .to_hex();
+
+    let mut block_changes = BlockChangesResponse::new();
+    block_changes.set_block_id(block_id.clone());
+    block_changes
+        .mut_transactions()
+        .push(created_nodes(&tx_hash.to_hex(), &["forged"]));
+    let mut changes = AuditedChanges::new();
+    changes.mut_blocks().push(block_changes);
+    let forged = AuditBlocks::new(&block_id, &changes.write_to_bytes().unwrap(), &pubkey, &key);
+    let invalid = AuditBlocks::new(&block_id, &[1, 2, 3], &pubkey, &key);
+    testkit.create_block_with_transactions(txvec![forged, invalid]);
+
+    let snapshot = testkit.snapshot();
+    let schema = Schema::new(&snapshot);
+    assert_eq!(schema.node_history("forged").len(), 0);
+    assert_eq!(
+        schema.neo4j_transaction(&tx_hash).unwrap().status(),
+        Some(TransactionStatus::Pending)
+    );
+}
+
+#[test]
+fn test_neo4j_unavailable() {
+    let (mut testkit, mock) = init_testkit();
+    mock.fail(Method::ExecuteBlock, "Connection refused");
+    mock.fail(Method::Health, "Connection refused");
+    assert!(mock.rpc().health().is_err());
+
+    let (pubkey, key) = crypto::gen_keypair();
+    let tx = CommitQueries::new("CREATE (n)", "15-OCT", &pubkey, &key);
+    let tx_hash = tx.hash();
+    testkit.create_block_with_transactions(txvec![tx]);
+    // Without changes from Neo4j no audit is sent and the transaction stays pending.
+    testkit.create_block();
+
+    let snapshot = testkit.snapshot();
+    let schema = Schema::new(&snapshot);
+    assert_eq!(mock.executed_blocks().len(), 1);
+    assert_eq!(
+        schema.neo4j_transaction(&tx_hash).unwrap().status(),
+        Some(TransactionStatus::Pending)
+    );
+
+    mock.recover();
+    assert_eq!(mock.rpc().health(), Ok(()));
+}
+
+#[test]
+fn test_audits_are_retried() {
+    let (mut testkit, mock) = init_testkit();
+    mock.script_transactions(|t| created_nodes(t.get_transaction_id(), &["u1"]));
+    mock.fail_next(Method::RetrieveBlockChanges, 1, "Connection reset");
+    let (pubkey, key) = crypto::gen_keypair();
+    let tx = CommitQueries::new("CREATE (n)", "18-OCT", &pubkey, &key);
+    let tx_hash = tx.hash();
+    testkit.create_block_with_transactions(txvec![tx]);
+    // The first audit reports the changes as unavailable.
+    testkit.create_block();
+    let status = |testkit: &TestKit| {
+        let snapshot = testkit.snapshot();
+        let status = Schema::new(&snapshot)
+            .neo4j_transaction(&tx_hash)
+            .and_then(|t| t.status());
+        status
+    };
+    assert_eq!(status(&testkit), Some(TransactionStatus::Neo4jUnavailable));
+
+    // The block still awaits its changes, so the next commit audits it again.
+    testkit.create_block();
+    assert_eq!(status(&testkit), Some(TransactionStatus::Success));
+    assert_eq!(mock.executed_blocks().len(), 1);
+    let snapshot = testkit.snapshot();
+    assert!(Schema::new(&snapshot).unaudited_blocks().is_empty());
 }
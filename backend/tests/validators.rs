@@ -0,0 +1,315 @@
+extern crate exonum;
+extern crate exonum_neo4j;
+#[macro_use]
+extern crate exonum_testkit;
+
+use exonum::blockchain::{Schema as CoreSchema, Transaction};
+use exonum::crypto::{self, Hash, PublicKey};
+use exonum::encoding::serialize::FromHex;
+use exonum::helpers::Height;
+use exonum::messages::Message;
+use exonum_neo4j::api::{AuditDivergenceEntry, AuditDivergencesQuery};
+use exonum_neo4j::schema::Schema;
+use exonum_neo4j::structures::TransactionStatus;
+use exonum_neo4j::transactions::{AuditBlocks, CommitQueries};
+use exonum_neo4j::Neo4jService;
+use exonum_testkit::{ApiKind, TestKit, TestKitBuilder};
+
+pub mod support;
+
+use support::{created_nodes, Method, MockTransactionManager, Request};
+
+/// Validators of one network, each with its own Neo4j. The testkit runs the node of the first
+/// validator, whose service executes every block and sends its audits itself. The services of
+/// the other validators run each committed block on their own Neo4j, and their audits, signed
+/// with their service keys, are committed after the audit of the first validator.
+struct Network {
+    testkit: TestKit,
+    mocks: Vec<MockTransactionManager>,
+    services: Vec<Neo4jService>,
+    audits: Vec<AuditBlocks>,
+}
+
+impl Network {
+    /// Network whose validators' Neo4j create the nodes with the given uuids for every
+    /// transaction.
+    fn new(uuids: &[&[&'static str]]) -> Self {
+        let mocks: Vec<MockTransactionManager> = uuids
+            .iter()
+            .map(|uuids| {
+                let mock = MockTransactionManager::start();
+                let uuids = uuids.to_vec();
+                mock.script_transactions(move |t| created_nodes(t.get_transaction_id(), &uuids));
+                mock
+            })
+            .collect();
+        let testkit = TestKitBuilder::validator()
+            .with_validators(mocks.len() as u16)
+            .with_service(Neo4jService::new(mocks[0].rpc()))
+            .create();
+        let services = mocks[1..]
+            .iter()
+            .map(|mock| Neo4jService::new(mock.rpc()))
+            .collect();
+        Network {
+            testkit,
+            mocks,
+            services,
+            audits: Vec::new(),
+        }
+    }
+
+    fn create_block_with_transactions(&mut self, transactions: Vec<Box<dyn Transaction>>) {
+        self.testkit.create_block_with_transactions(transactions);
+        self.commit_on_other_validators();
+    }
+
+    /// Commits the pending audits: first those the first validator sent, then those of the
+    /// other validators.
+    fn audit(&mut self) {
+        self.commit_first_audit();
+        self.commit_other_audits();
+    }
+
+    fn commit_first_audit(&mut self) {
+        self.testkit.create_block();
+        self.commit_on_other_validators();
+    }
+
+    fn commit_other_audits(&mut self) {
+        let audits: Vec<Box<dyn Transaction>> = self
+            .audits
+            .drain(..)
+            .map(|audit| Box::new(audit) as Box<dyn Transaction>)
+            .collect();
+        self.testkit.create_block_with_transactions(audits);
+        self.commit_on_other_validators();
+    }
+
+    /// Lets the other validators run the last block on their Neo4j, as their own nodes would.
+    fn commit_on_other_validators(&mut self) {
+        let snapshot = self.testkit.snapshot();
+        let validators = self.testkit.network().validators();
+        for (service, validator) in self.services.iter().zip(&validators[1..]) {
+            let (public_key, secret_key) = validator.service_keypair();
+            if let Some(audit) = service.handle_commit(&*snapshot, public_key, secret_key) {
+                self.audits.push(audit);
+            }
+        }
+    }
+
+    fn block_id(&self, height: u64) -> String {
+        let snapshot = self.testkit.snapshot();
+        CoreSchema::new(&snapshot)
+            .block_hashes_by_height()
+            .get(height)
+            .unwrap()
+            .to_hex()
+    }
+
+    fn status(&self, hash: &Hash) -> Option<TransactionStatus> {
+        let snapshot = self.testkit.snapshot();
+        Schema::new(&snapshot)
+            .neo4j_transaction(hash)
+            .and_then(|t| t.status())
+    }
+
+    fn node_changes(&self, uuid: &str) -> u64 {
+        let snapshot = self.testkit.snapshot();
+        Schema::new(&snapshot).node_history(uuid).len()
+    }
+
+    fn service_key(&self, validator: usize) -> PublicKey {
+        *self.testkit.network().validators()[validator]
+            .service_keypair()
+            .0
+    }
+
+    /// Validators that reported changes for the block differing from the recorded ones.
+    fn divergences(&self, height: u64) -> Vec<PublicKey> {
+        let snapshot = self.testkit.snapshot();
+        let block_hash = CoreSchema::new(&snapshot)
+            .block_hashes_by_height()
+            .get(height)
+            .unwrap();
+        Schema::new(&snapshot)
+            .audit_divergences(&block_hash)
+            .keys()
+            .collect()
+    }
+
+    /// Blocks the validator asked its Neo4j the changes of.
+    fn retrieved_blocks(&self, validator: usize) -> Vec<String> {
+        self.mocks[validator]
+            .requests()
+            .into_iter()
+            .filter_map(|r| match r {
+                Request::RetrieveBlockChanges(block_id) => Some(block_id),
+                _ => None,
+            })
+            .collect()
+    }
+}
+
+fn commit_queries(queries: &str) -> CommitQueries {
+    let (pub_key, secret_key) = crypto::gen_keypair();
+    CommitQueries::new(queries, "15-OCT", &pub_key, &secret_key)
+}
+
+#[test]
+fn test_validators_agree() {
+    let mut network = Network::new(&[&["u1"], &["u1"], &["u1"]]);
+    let transaction = commit_queries("CREATE (n)");
+    let hash = transaction.hash();
+    network.create_block_with_transactions(txvec![transaction]);
+    network.audit();
+
+    assert_eq!(network.status(&hash), Some(TransactionStatus::Success));
+    assert_eq!(network.node_changes("u1"), 1);
+    assert!(network.divergences(1).is_empty());
+    // Every validator retrieved the changes from its own Neo4j.
+    for validator in 0..3 {
+        assert_eq!(
+            network.retrieved_blocks(validator),
+            vec![network.block_id(1)]
+        );
+    }
+}
+
+#[test]
+fn test_diverging_graphs() {
+    let mut network = Network::new(&[&["u1"], &["u1"], &["u1", "u2"]]);
+    let transaction = commit_queries("CREATE (n)");
+    let hash = transaction.hash();
+    network.create_block_with_transactions(txvec![transaction]);
+    network.audit();
+
+    // The changes of the first audit are recorded, the later audits record nothing twice.
+    assert_eq!(network.status(&hash), Some(TransactionStatus::Success));
+    assert_eq!(network.node_changes("u1"), 1);
+    assert_eq!(network.node_changes("u2"), 0);
+    // The validator whose Neo4j reported other changes is detected.
+    assert_eq!(network.divergences(1), vec![network.service_key(2)]);
+
+    // Divergences are part of the state hash and listed by the API.
+    let block_hash = Hash::from_hex(network.block_id(1)).unwrap();
+    {
+        let snapshot = network.testkit.snapshot();
+        let schema = Schema::new(&snapshot);
+        let root = schema.audit_divergences(&block_hash).merkle_root();
+        assert_eq!(schema.audit_divergence_roots().get(&block_hash), Some(root));
+        assert!(schema
+            .state_hash()
+            .contains(&schema.audit_divergence_roots().merkle_root()));
+    }
+    let entries: Vec<AuditDivergenceEntry> = network
+        .testkit
+        .api()
+        .public(ApiKind::Service("neo4j_blockchain"))
+        .get("v1/audit_divergences")
+        .unwrap();
+    assert_eq!(entries.len(), 1);
+    assert_eq!(entries[0].block_hash, block_hash);
+    assert_eq!(entries[0].pub_key, network.service_key(2));
+    let entries: Vec<AuditDivergenceEntry> = network
+        .testkit
+        .api()
+        .public(ApiKind::Service("neo4j_blockchain"))
+        .query(&AuditDivergencesQuery {
+            block_hash: Some(Hash::from_hex(network.block_id(2)).unwrap()),
+        })
+        .get("v1/audit_divergences")
+        .unwrap();
+    assert!(entries.is_empty());
+}
+
+#[test]
+fn test_one_neo4j_down() {
+    let mut network = Network::new(&[&["u1"], &["u1"], &["u1"]]);
+    network.mocks[2].fail(Method::ExecuteBlock, "Connection refused");
+    network.mocks[2].fail(Method::RetrieveBlockChanges, "Connection refused");
+    let transaction = commit_queries("CREATE (n)");
+    let hash = transaction.hash();
+    network.create_block_with_transactions(txvec![transaction]);
+    network.audit();
+
+    // The validator whose Neo4j is down sends no audit, the others audit the block for it.
+    assert!(network.retrieved_blocks(2).is_empty());
+    assert_eq!(network.status(&hash), Some(TransactionStatus::Success));
+    assert_eq!(network.node_changes("u1"), 1);
+}
+
+#[test]
+fn test_changes_unavailable_on_one_validator() {
+    let mut network = Network::new(&[&["u1"], &["u1"], &["u1"]]);
+    network.mocks[0].fail(Method::RetrieveBlockChanges, "Connection refused");
+    let transaction = commit_queries("CREATE (n)");
+    let hash = transaction.hash();
+    network.create_block_with_transactions(txvec![transaction]);
+
+    // The audit of the first validator reports the changes as unavailable. Every node records
+    // that from the signed audit, not from its own Neo4j.
+    network.commit_first_audit();
+    assert_eq!(
+        network.status(&hash),
+        Some(TransactionStatus::Neo4jUnavailable)
+    );
+    assert_eq!(network.node_changes("u1"), 0);
+
+    // The block is still to be audited, so the changes another validator retrieved are recorded.
+    network.commit_other_audits();
+    assert_eq!(network.status(&hash), Some(TransactionStatus::Success));
+    assert_eq!(network.node_changes("u1"), 1);
+    assert!(network.divergences(1).is_empty());
+}
+
+#[test]
+fn test_audits_in_one_block() {
+    let mut network = Network::new(&[&["u1"]]);
+    let first = commit_queries("CREATE (n)");
+    let second = commit_queries("CREATE (m)");
+    let (first_hash, second_hash) = (first.hash(), second.hash());
+
+    // The audit of the first block is still pending when the second block is committed, so
+    // the audit of the second block carries the changes of both blocks.
+    network.create_block_with_transactions(txvec![first]);
+    network.create_block_with_transactions(txvec![second]);
+    network.testkit.create_block();
+    assert_eq!(
+        network.retrieved_blocks(0),
+        vec![
+            network.block_id(1),
+            network.block_id(1),
+            network.block_id(2)
+        ]
+    );
+
+    // The first audit records the first block, the second one only the second block.
+    assert_eq!(
+        network.status(&first_hash),
+        Some(TransactionStatus::Success)
+    );
+    assert_eq!(
+        network.status(&second_hash),
+        Some(TransactionStatus::Success)
+    );
+    assert_eq!(network.node_changes("u1"), 2);
+
+    // Audited changes are deleted from Neo4j once the audit is committed.
+    let deleted: Vec<Request> = network.mocks[0]
+        .requests()
+        .into_iter()
+        .filter(|r| match r {
+            Request::DeleteBlockChanges(_) => true,
+            _ => false,
+        })
+        .collect();
+    assert_eq!(
+        deleted,
+        vec![
+            Request::DeleteBlockChanges(network.block_id(1)),
+            Request::DeleteBlockChanges(network.block_id(2)),
+        ]
+    );
+    assert_eq!(network.testkit.height(), Height(3));
+}
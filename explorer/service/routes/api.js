@@ -2,6 +2,16 @@ var express = require('express');
 var request = require('request');
 var router = express.Router();
 
+// Forward the blockchain's response, using the status carried by error bodies,
+// since exonum can only answer 400, 404 and 500 itself
+function forward(res, response, body) {
+    var status = response.statusCode;
+    if (status >= 400 && body && body.code && body.status) {
+        status = body.status;
+    }
+    res.status(status).json(body);
+}
+
 router.get('/*', function (req, res, next) {
     var query = req.params[0];
 
@@ -12,11 +22,13 @@ router.get('/*', function (req, res, next) {
         if (err) {
             return next(err);
         }
+        var parsed;
         try {
-            res.json(JSON.parse(body));
+            parsed = JSON.parse(body);
         } catch (e) {
-            res.json({});
+            parsed = {};
         }
+        forward(res, response, parsed);
     });
 });
 
@@ -31,7 +43,14 @@ router.post('/*', function (req, res, next) {
             if (err) {
                 return next(err);
             }
-            res.json(body);
+            if (typeof body === 'string') {
+                try {
+                    body = JSON.parse(body);
+                } catch (e) {
+                    body = {};
+                }
+            }
+            forward(res, response, body);
         });
 });
 
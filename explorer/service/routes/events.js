@@ -0,0 +1,58 @@
+var express = require('express');
+var request = require('request');
+var router = express.Router();
+
+// How often the blockchain is asked for new events, in milliseconds
+var POLL_INTERVAL = 1000;
+var FILTERS = ['node_uuid', 'label', 'pub_key'];
+
+// Server-sent events stream of transaction statuses and node changes
+router.get('/', function (req, res) {
+    var url = req.app.get('apiRoot') + '/api/services/neo4j_blockchain/v1/events';
+
+    var filters = {};
+    FILTERS.forEach(function (filter) {
+        if (req.query[filter]) {
+            filters[filter] = req.query[filter];
+        }
+    });
+
+    var nextHeight = req.query.from_height;
+    var closed = false;
+
+    res.writeHead(200, {
+        'Content-Type': 'text/event-stream',
+        'Cache-Control': 'no-cache',
+        'Connection': 'keep-alive'
+    });
+
+    req.on('close', function () {
+        closed = true;
+    });
+
+    var poll = function () {
+        if (closed) {
+            return;
+        }
+
+        var qs = Object.assign({}, filters);
+        if (nextHeight !== undefined) {
+            qs.from_height = nextHeight;
+        }
+
+        request.get({ url: url, qs: qs, json: true }, function (err, response, body) {
+            if (!err && body && Array.isArray(body.events)) {
+                body.events.forEach(function (event) {
+                    res.write('event: ' + event.type + '\n');
+                    res.write('data: ' + JSON.stringify(event) + '\n\n');
+                });
+                nextHeight = body.next_height;
+            }
+            setTimeout(poll, POLL_INTERVAL);
+        });
+    };
+
+    poll();
+});
+
+module.exports = router;
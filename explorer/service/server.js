@@ -46,6 +46,8 @@ app.use(express.static(join(__dirname, '..', 'dist')))
 // Activate routers
 var api = require('./routes/api')
 app.use('/api', api)
+var events = require('./routes/events')
+app.use('/events', events)
 
 // Single Page Application entry point
 app.get('/', function (req, res) {
@@ -4,6 +4,26 @@ import {
     getTx,
     NodeModification,
     getNodeHistory,
+    getTransactionChanges,
+    getStatementClasses,
+    StatementClass,
+    subscribe,
+    EventFilters,
+    StatusEvent,
+    ChangeEvent,
 } from './exonum/exonum'
 
-export { KeyPair, sendTx, getTx, NodeModification, getNodeHistory }
+export {
+    KeyPair,
+    sendTx,
+    getTx,
+    NodeModification,
+    getNodeHistory,
+    getTransactionChanges,
+    getStatementClasses,
+    StatementClass,
+    subscribe,
+    EventFilters,
+    StatusEvent,
+    ChangeEvent,
+}
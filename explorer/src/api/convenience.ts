@@ -1,3 +1,5 @@
+import { NodeModification } from './exonum/exonum'
+
 const shorten = (hash: string): string =>
     hash.length > 11
         ? `${hash.substring(0, 6)}~~${hash.substring(hash.length - 3)}`
@@ -6,4 +8,54 @@ const shorten = (hash: string): string =>
 const delay = <T>(ms: number, value: T): Promise<T> =>
     new Promise(resolve => setTimeout(() => resolve(value), ms))
 
-export { shorten, delay }
+const escape = (text: string): string =>
+    text
+        .replace(/&/g, '&amp;')
+        .replace(/</g, '&lt;')
+        .replace(/>/g, '&gt;')
+        .replace(/"/g, '&quot;')
+
+const bold = (text: string): string => `<b>${escape(text)}</b>`
+
+const describe = (m: NodeModification): string => {
+    switch (m.kind) {
+        case 'add_node':
+            return `Node with UUID ${bold(shorten(m.node_uuid))} created`
+        case 'remove_node':
+            return `Node with UUID ${bold(shorten(m.node_uuid))} deleted`
+        case 'add_label':
+            return `Label, ${bold(m.label_name)}, added to node`
+        case 'remove_label':
+            return `Label, ${bold(m.label_name)}, removed from node`
+        case 'add_node_property':
+            return `Added new property, key ${bold(m.key)}, value ${bold(
+                m.value,
+            )}`
+        case 'remove_node_property':
+            return `Removed property, key ${bold(m.key)}`
+        case 'add_relation':
+            return `Relationship of type ${bold(m.field_type)} with UUID ${bold(
+                shorten(m.rel_uuid),
+            )} added. Starting from ${bold(
+                shorten(m.from_uuid),
+            )}, and going to ${bold(shorten(m.to_uuid))}`
+        case 'remove_relation':
+            return `Relationship with UUID ${bold(
+                shorten(m.rel_uuid),
+            )} removed. Starting from ${bold(
+                shorten(m.from_uuid),
+            )}, and going to ${bold(shorten(m.to_uuid))}`
+        case 'add_relation_property':
+            return `Property, ${bold(m.key)}, with value ${bold(
+                m.value,
+            )}, added to relation with uuid ${bold(shorten(m.relation_uuid))}`
+        case 'remove_relation_property':
+            return `Property, ${bold(
+                m.key,
+            )}, removed from relation with uuid ${bold(
+                shorten(m.relation_uuid),
+            )}`
+    }
+}
+
+export { shorten, delay, describe }
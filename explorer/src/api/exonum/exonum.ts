@@ -60,10 +60,66 @@ function getTx(hash: string): Promise<any> {
     return axios.get(url).then(response => response.data)
 }
 
-export interface NodeModification {
-    transaction_id: string
-    description: string
-}
+export type NodeModification =
+    | { kind: 'add_node'; node_uuid: string; transaction_id: string }
+    | { kind: 'remove_node'; node_uuid: string; transaction_id: string }
+    | {
+          kind: 'add_relation'
+          rel_uuid: string
+          field_type: string
+          from_uuid: string
+          to_uuid: string
+          transaction_id: string
+      }
+    | {
+          kind: 'remove_relation'
+          rel_uuid: string
+          from_uuid: string
+          to_uuid: string
+          transaction_id: string
+      }
+    | {
+          kind: 'add_label'
+          node_uuid: string
+          label_name: string
+          transaction_id: string
+      }
+    | {
+          kind: 'remove_label'
+          node_uuid: string
+          label_name: string
+          transaction_id: string
+      }
+    | {
+          kind: 'add_node_property'
+          node_uuid: string
+          key: string
+          value: string
+          transaction_id: string
+      }
+    | {
+          kind: 'remove_node_property'
+          node_uuid: string
+          key: string
+          transaction_id: string
+      }
+    | {
+          kind: 'add_relation_property'
+          relation_uuid: string
+          key: string
+          value: string
+          from_uuid: string
+          to_uuid: string
+          transaction_id: string
+      }
+    | {
+          kind: 'remove_relation_property'
+          relation_uuid: string
+          key: string
+          from_uuid: string
+          to_uuid: string
+          transaction_id: string
+      }
 
 function getNodeHistory(uuid: string): Promise<NodeModification[]> {
     const url = `${SERVICE_URL}/node_history?node_uuid=${uuid}`
@@ -71,4 +127,67 @@ function getNodeHistory(uuid: string): Promise<NodeModification[]> {
     return axios.get(url).then(response => response.data)
 }
 
-export { sendTx, getTx, getNodeHistory }
+function getTransactionChanges(hash: string): Promise<NodeModification[]> {
+    const url = `${SERVICE_URL}/transaction_changes?hash_string=${hash}`
+    // console.log(url)
+    return axios.get(url).then(response => response.data)
+}
+
+export type StatementClass = 'READ_ONLY' | 'DATA_WRITE' | 'SCHEMA_WRITE'
+
+function getStatementClasses(hash: string): Promise<StatementClass[] | null> {
+    const url = `${SERVICE_URL}/statement_classes?hash_string=${hash}`
+    return axios
+        .get(url)
+        .then(response => (response.data ? response.data.classes : null))
+}
+
+export interface EventFilters {
+    node_uuid?: string
+    label?: string
+    pub_key?: string
+}
+
+export interface StatusEvent {
+    type: 'status'
+    height: number
+    tx_hash: string
+    pub_key: string
+    status: string
+    error_msg: string
+}
+
+export interface ChangeEvent {
+    type: 'node_change'
+    height: number
+    tx_hash: string
+    change: NodeModification
+}
+
+function subscribe(
+    filters: EventFilters,
+    onStatus: (event: StatusEvent) => void,
+    onChange: (event: ChangeEvent) => void,
+): EventSource {
+    const params = Object.entries(filters)
+        .filter(([, value]) => value)
+        .map(([key, value]) => `${key}=${encodeURIComponent(value as string)}`)
+        .join('&')
+    const source = new EventSource(`/events?${params}`)
+    source.addEventListener('status', (e: Event) =>
+        onStatus(JSON.parse((e as MessageEvent).data)),
+    )
+    source.addEventListener('node_change', (e: Event) =>
+        onChange(JSON.parse((e as MessageEvent).data)),
+    )
+    return source
+}
+
+export {
+    sendTx,
+    getTx,
+    getNodeHistory,
+    getTransactionChanges,
+    getStatementClasses,
+    subscribe,
+}
@@ -6,6 +6,7 @@ public class Properties {
     public static final String GRPC_KEY_PORT = "transaction_manager.grpc.port";
     public static final String DATABASE_CHANGES_FOLDER = "database_changes";
     public static final int GRPC_DEFAULT_PORT = 9994;
+    public static final long HEALTH_TIMEOUT_MS = 1000;
 
     public static String concatUUID(String prefix, int suffix) {
         return prefix + "_" + suffix;
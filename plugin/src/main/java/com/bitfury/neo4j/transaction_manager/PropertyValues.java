@@ -0,0 +1,96 @@
+package com.bitfury.neo4j.transaction_manager;
+
+import java.lang.reflect.Array;
+import java.time.LocalDate;
+import java.time.LocalDateTime;
+import java.time.LocalTime;
+import java.time.OffsetTime;
+import java.time.ZonedDateTime;
+import java.time.temporal.TemporalAmount;
+
+/**
+ * Text and Cypher type of property values, so that the Exonum service can restore them with their type.
+ * <p>
+ * Scalars are written with {@code toString}, arrays as a JSON array of their elements written the same way.
+ * Values of types without a Cypher literal, such as points, are reported as {@code STRING}.
+ */
+final class PropertyValues {
+
+    private PropertyValues() {
+    }
+
+    static String text(Object value) {
+        if (!value.getClass().isArray()) {
+            return value.toString();
+        }
+        StringBuilder json = new StringBuilder("[");
+        for (int i = 0; i < Array.getLength(value); i++) {
+            if (i > 0) {
+                json.append(',');
+            }
+            appendJsonString(json, String.valueOf(Array.get(value, i)));
+        }
+        return json.append(']').toString();
+    }
+
+    static String type(Object value) {
+        Class<?> type = value.getClass();
+        if (type.isArray()) {
+            return "LIST<" + scalarType(type.getComponentType()) + ">";
+        }
+        return scalarType(type);
+    }
+
+    private static String scalarType(Class<?> type) {
+        if (type == Boolean.class || type == boolean.class) {
+            return "BOOLEAN";
+        }
+        if (type == Long.class || type == long.class || type == Integer.class || type == int.class
+                || type == Short.class || type == short.class || type == Byte.class || type == byte.class) {
+            return "INTEGER";
+        }
+        if (type == Double.class || type == double.class || type == Float.class || type == float.class) {
+            return "FLOAT";
+        }
+        if (type == LocalDate.class) {
+            return "DATE";
+        }
+        if (type == OffsetTime.class) {
+            return "TIME";
+        }
+        if (type == LocalTime.class) {
+            return "LOCAL_TIME";
+        }
+        if (type == ZonedDateTime.class) {
+            return "DATE_TIME";
+        }
+        if (type == LocalDateTime.class) {
+            return "LOCAL_DATE_TIME";
+        }
+        if (TemporalAmount.class.isAssignableFrom(type)) {
+            return "DURATION";
+        }
+        return "STRING";
+    }
+
+    private static void appendJsonString(StringBuilder json, String text) {
+        json.append('"');
+        for (char c : text.toCharArray()) {
+            switch (c) {
+                case '"':
+                    json.append("\\\"");
+                    break;
+                case '\\':
+                    json.append("\\\\");
+                    break;
+                default:
+                    if (c < 0x20) {
+                        json.append(String.format("\\u%04x", (int) c));
+                    } else {
+                        json.append(c);
+                    }
+            }
+        }
+        json.append('"');
+    }
+}
@@ -208,6 +208,94 @@ public class TransactionManager extends TransactionManagerGrpc.TransactionManage
         responseObserver.onCompleted();
     }
 
+    /**
+     * Executes an index or constraint statement in its own transaction. Neo4j does not allow
+     * these to share a transaction with data writes, and they modify no nodes or relationships,
+     * so the result is added to the block changes without modifications.
+     *
+     * @param request
+     * @param responseObserver
+     */
+    @Override
+    public void executeSchemaChange(SchemaChangeRequest request, StreamObserver<SchemaChangeResponse> responseObserver) {
+
+        try {
+
+            if (request.getBlockId().isEmpty()) {
+                userLog.error("method=executeSchemaChange error=Missing block id");
+                throw new Exception("Block ID missing");
+            }
+
+            if (request.getTransactionId().isEmpty()) {
+                userLog.error("method=executeSchemaChange error=Missing transaction id");
+                throw new Exception("Transaction ID missing");
+            }
+
+            userLog.info("method=executeSchemaChange transactionID=" + request.getTransactionId());
+
+            TransactionStateMachine tsm = new TransactionStateMachine(request.getTransactionId());
+            // Keeps the transaction event handler from assigning UUIDs or recording changes.
+            tsm.schemaChange();
+            TransactionData.set(tsm);
+
+            try (Transaction tx = db.beginTx()) {
+                db.execute(request.getStatement());
+                tx.success();
+            } catch (QueryExecutionException ex) {
+                tsm.failure(new EError(new EFailedQuery(request.getStatement(), ex.getMessage(), ex.getStatusCode(), 0)));
+            } catch (Exception ex) {
+                tsm.failure(new EError(EError.ErrorType.RUNTIME_EXCEPTION, "Runtime exception:  " + ex.getMessage()));
+            }
+
+            if (!tsm.hasError()) {
+                tsm.finished();
+            }
+
+            // Add the result to the changes of the block, which exist if the block had data transactions
+            File file = new File(getDatabaseChangesFolder(), request.getBlockId());
+            BlockChangesResponse.Builder blockChanges = BlockChangesResponse.newBuilder();
+            try {
+                if (file.exists()) {
+                    FileInputStream input = new FileInputStream(file);
+                    blockChanges.mergeFrom(input);
+                    input.close();
+                } else {
+                    blockChanges.setBlockId(request.getBlockId());
+                }
+                blockChanges.addTransactions(tsm.getTransactionResponse());
+
+                FileOutputStream output = new FileOutputStream(file);
+                blockChanges.build().writeTo(output);
+                output.close();
+            } catch (IOException e) {
+                userLog.error("method=executeSchemaChange error=IOException block_id=" + request.getBlockId());
+                throw new Exception("IOException in writing to file");
+            }
+
+            responseObserver.onNext(SchemaChangeResponse.newBuilder().setSuccess(true).build());
+            responseObserver.onCompleted();
+
+        } catch (Exception e) {
+            responseObserver.onError(new StatusException(Status.INVALID_ARGUMENT.withDescription("Error = " + e.getMessage())));
+        }
+    }
+
+    /**
+     * Reports whether the database accepts transactions.
+     *
+     * @param request
+     * @param responseObserver
+     */
+    @Override
+    public void health(HealthRequest request, StreamObserver<HealthResponse> responseObserver) {
+
+        boolean available = db.isAvailable(Properties.HEALTH_TIMEOUT_MS);
+        userLog.debug("method=health available=" + available);
+
+        responseObserver.onNext(HealthResponse.newBuilder().setAvailable(available).build());
+        responseObserver.onCompleted();
+    }
+
     /**
      * Process a new transaction by executing the provided queries and assigning EUUID.
      * <p>
@@ -235,7 +323,8 @@ public class TransactionManager extends TransactionManagerGrpc.TransactionManage
 
         try (Transaction tx = db.beginTx()) {
 
-            for (String query : queries) {
+            for (int i = 0; i < queries.size(); i++) {
+                String query = queries.get(i);
                 try {
                     db.execute(query);
                 } catch (QueryExecutionException ex) {
@@ -245,7 +334,8 @@ public class TransactionManager extends TransactionManagerGrpc.TransactionManage
                                     new EFailedQuery(
                                             query,
                                             ex.getMessage(),
-                                            ex.getStatusCode()
+                                            ex.getStatusCode(),
+                                            i
                                     )
                             )
                     );
@@ -463,7 +553,9 @@ public class TransactionManager extends TransactionManagerGrpc.TransactionManage
                 tsm.addAssignedNodeProperty(new EProperty(
                         nodeUUID.getUUID(transactionData, property.entity().getId()),
                         property.key(),
-                        property.value().toString()
+                        property.value().toString(),
+                        previousValue(property),
+                        previousValueType(property)
                 ));
             }
 
@@ -472,7 +564,10 @@ public class TransactionManager extends TransactionManagerGrpc.TransactionManage
                 if (!property.key().equals(Properties.UUID)) {
                     tsm.addRemovedNodeProperty(new EProperty(
                             nodeUUID.getUUID(transactionData, property.entity().getId()),
-                            property.key()
+                            property.key(),
+                            null,
+                            previousValue(property),
+                        previousValueType(property)
                     ));
                 }
             }
@@ -482,7 +577,9 @@ public class TransactionManager extends TransactionManagerGrpc.TransactionManage
                 tsm.addAssignedRelationshipProperty(new EProperty(
                         relationUUID.getUUID(transactionData, property.entity().getId()),
                         property.key(),
-                        property.value().toString()
+                        property.value().toString(),
+                        previousValue(property),
+                        previousValueType(property)
                 ));
 
             }
@@ -491,7 +588,10 @@ public class TransactionManager extends TransactionManagerGrpc.TransactionManage
                 if (!property.key().equals(Properties.UUID)) {
                     tsm.addRemovedRelationshipProperty(new EProperty(
                             relationUUID.getUUID(transactionData, property.entity().getId()),
-                            property.key()
+                            property.key(),
+                            null,
+                            previousValue(property),
+                        previousValueType(property)
                     ));
                 }
             }
@@ -502,6 +602,22 @@ public class TransactionManager extends TransactionManagerGrpc.TransactionManage
         TransactionData.set(tsm);
     }
 
+    /**
+     * Value of the property committed before the transaction, null if it was not set.
+     */
+    private static String previousValue(PropertyEntry<?> property) {
+        Object value = property.previouslyCommitedValue();
+        return value == null ? null : PropertyValues.text(value);
+    }
+
+    /**
+     * Cypher type of the property value committed before the transaction, null if it was not set.
+     */
+    private static String previousValueType(PropertyEntry<?> property) {
+        Object value = property.previouslyCommitedValue();
+        return value == null ? null : PropertyValues.type(value);
+    }
+
     /**
      * Check whether a property key was set or changed based on the transaction data.
      * <p>
@@ -13,7 +13,8 @@ public class TransactionStateMachine {
         READY_TO_COMMIT,
         COMMITTED,
         FAILED,
-        FINISHED
+        FINISHED,
+        SCHEMA_CHANGE
     }
 
     private TransactionStatus status;
@@ -47,6 +48,10 @@ public class TransactionStateMachine {
         this.status = TransactionStatus.PENDING;
     }
 
+    public void schemaChange() {
+        this.status = TransactionStatus.SCHEMA_CHANGE;
+    }
+
     public void readyToCommit() {
         this.status = TransactionStatus.READY_TO_COMMIT;
     }
@@ -208,6 +213,14 @@ public class TransactionStateMachine {
                         .setNodeUUID(EProperty.getUUID())
                         .setKey(EProperty.getKey())
                         .setValue(EProperty.getValue());
+                if (EProperty.hasPreviousValue()) {
+                    propertyBuilder
+                            .setHasPreviousValue(true)
+                            .setPreviousValue(EProperty.getPreviousValue());
+                    if (EProperty.hasPreviousValueType()) {
+                        propertyBuilder.setPreviousValueType(EProperty.getPreviousValueType());
+                    }
+                }
 
                 modificationBuilder.addAssignedNodeProperties(propertyBuilder);
             }
@@ -216,12 +229,22 @@ public class TransactionStateMachine {
             removedNodeProperties.sort(null);
             for (EProperty EProperty : removedNodeProperties) {
 
-                modificationBuilder.addRemovedNodeProperties(
-                        DatabaseModifications.RemovedNodeProperty.newBuilder()
-                                .setNodeUUID(EProperty.getUUID())
-                                .setKey(EProperty.getKey())
+                DatabaseModifications.RemovedNodeProperty.Builder propertyBuilder =
+                        DatabaseModifications.RemovedNodeProperty.newBuilder();
 
-                );
+                propertyBuilder
+                        .setNodeUUID(EProperty.getUUID())
+                        .setKey(EProperty.getKey());
+                if (EProperty.hasPreviousValue()) {
+                    propertyBuilder
+                            .setHasPreviousValue(true)
+                            .setPreviousValue(EProperty.getPreviousValue());
+                    if (EProperty.hasPreviousValueType()) {
+                        propertyBuilder.setPreviousValueType(EProperty.getPreviousValueType());
+                    }
+                }
+
+                modificationBuilder.addRemovedNodeProperties(propertyBuilder);
             }
 
 
@@ -236,6 +259,14 @@ public class TransactionStateMachine {
                         .setRelationshipUUID(EProperty.getUUID())
                         .setKey(EProperty.getKey())
                         .setValue(EProperty.getValue());
+                if (EProperty.hasPreviousValue()) {
+                    propertyBuilder
+                            .setHasPreviousValue(true)
+                            .setPreviousValue(EProperty.getPreviousValue());
+                    if (EProperty.hasPreviousValueType()) {
+                        propertyBuilder.setPreviousValueType(EProperty.getPreviousValueType());
+                    }
+                }
 
                 modificationBuilder.addAssignedRelationshipProperties(propertyBuilder);
             }
@@ -244,13 +275,25 @@ public class TransactionStateMachine {
             removedRelationshipProperties.sort(null);
             for (EProperty EProperty : removedRelationshipProperties) {
 
-                modificationBuilder.addRemovedRelationProperties(
-                        DatabaseModifications.RemovedRelationshipProperty.newBuilder()
-                                .setRelationshipUUID(EProperty.getUUID())
-                                .setKey(EProperty.getKey())
-                );
+                DatabaseModifications.RemovedRelationshipProperty.Builder propertyBuilder =
+                        DatabaseModifications.RemovedRelationshipProperty.newBuilder();
+
+                propertyBuilder
+                        .setRelationshipUUID(EProperty.getUUID())
+                        .setKey(EProperty.getKey());
+                if (EProperty.hasPreviousValue()) {
+                    propertyBuilder
+                            .setHasPreviousValue(true)
+                            .setPreviousValue(EProperty.getPreviousValue());
+                    if (EProperty.hasPreviousValueType()) {
+                        propertyBuilder.setPreviousValueType(EProperty.getPreviousValueType());
+                    }
+                }
+
+                modificationBuilder.addRemovedRelationProperties(propertyBuilder);
             }
 
+            modificationBuilder.setRecordsPreviousValues(true);
             responseBuilder.setModifications(modificationBuilder);
 
         } else {
@@ -293,6 +336,8 @@ public class TransactionStateMachine {
                     FailedQuery.Builder failedQueryBuilder = FailedQuery.newBuilder();
                     failedQueryBuilder.setError(failedQuery.getError());
                     failedQueryBuilder.setQuery(failedQuery.getQuery());
+                    failedQueryBuilder.setErrorCode(failedQuery.getStatusCode());
+                    failedQueryBuilder.setStatementIndex(failedQuery.getStatementIndex());
                     errorBuilder.setFailedQuery(failedQueryBuilder);
                 }
 
@@ -5,11 +5,13 @@ public class EFailedQuery {
     private String query;
     private String error;
     private String statusCode;
+    private int statementIndex;
 
-    public EFailedQuery(String query, String error, String statusCode) {
+    public EFailedQuery(String query, String error, String statusCode, int statementIndex) {
         this.query = query;
         this.error = error;
         this.statusCode = statusCode;
+        this.statementIndex = statementIndex;
     }
 
     public String getError() {
@@ -23,4 +25,8 @@ public class EFailedQuery {
     public String getStatusCode() {
         return statusCode;
     }
+
+    public int getStatementIndex() {
+        return statementIndex;
+    }
 }
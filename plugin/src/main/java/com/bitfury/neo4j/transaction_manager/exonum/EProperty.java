@@ -7,17 +7,43 @@ public class EProperty implements Comparable<EProperty> {
     private String UUID;
     private String key;
     private String value;
+    private String previousValue;
+    private String previousValueType;
 
     public EProperty(String UUID, String key) {
         this.UUID = UUID;
         this.key = key;
         this.value = null;
+        this.previousValue = null;
     }
 
     public EProperty(String UUID, String key, String value) {
         this.UUID = UUID;
         this.key = key;
         this.value = value;
+        this.previousValue = null;
+    }
+
+    /**
+     * @param previousValue value committed before the transaction, null if the property was not set.
+     */
+    public EProperty(String UUID, String key, String value, String previousValue) {
+        this.UUID = UUID;
+        this.key = key;
+        this.value = value;
+        this.previousValue = previousValue;
+        this.previousValueType = null;
+    }
+
+    /**
+     * @param previousValueType Cypher type of the previous value, such as INTEGER or LIST&lt;STRING&gt;.
+     */
+    public EProperty(String UUID, String key, String value, String previousValue, String previousValueType) {
+        this.UUID = UUID;
+        this.key = key;
+        this.value = value;
+        this.previousValue = previousValue;
+        this.previousValueType = previousValueType;
     }
 
     public String getUUID() {
@@ -32,6 +58,22 @@ public class EProperty implements Comparable<EProperty> {
         return value;
     }
 
+    public String getPreviousValue() {
+        return previousValue;
+    }
+
+    public String getPreviousValueType() {
+        return previousValueType;
+    }
+
+    public boolean hasPreviousValueType() {
+        return previousValueType != null;
+    }
+
+    public boolean hasPreviousValue() {
+        return previousValue != null;
+    }
+
     @Override
     public int compareTo(EProperty o) {
         return Comparator.comparing(EProperty::getUUID)
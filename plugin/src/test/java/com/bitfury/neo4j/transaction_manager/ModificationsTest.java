@@ -486,6 +486,28 @@ public class ModificationsTest {
         assertEquals("Property has incorrect key", "rent", property.getKey());
     }
 
+    @Test
+    public void testPreviousValueTypes() {
+
+        sendQuery("MATCH (n {" + Properties.UUID + ": '" + testUUIDs.get(NODE_NO_LABEL) + "'}) SET n.tags = ['a', 'b\"c']");
+
+        TransactionResponse result = sendQuery("MATCH (n {" + Properties.UUID + ": '" + testUUIDs.get(NODE_NO_LABEL) + "'}) REMOVE n.tags");
+
+        DatabaseModifications.RemovedNodeProperty removedNodeProperty = result.getModifications().getRemovedNodeProperties(0);
+
+        assertTrue("Property should have a previous value", removedNodeProperty.getHasPreviousValue());
+        assertEquals("Property has incorrect previous value", "[\"a\",\"b\\\"c\"]", removedNodeProperty.getPreviousValue());
+        assertEquals("Property has incorrect previous value type", "LIST<STRING>", removedNodeProperty.getPreviousValueType());
+
+        result = sendQuery("MATCH ()-[r {" + Properties.UUID + ": '" + testUUIDs.get(LIVES_IN) + "'}]->() SET r.rent = 600");
+
+        DatabaseModifications.AssignedRelationshipProperty relationshipProperty = result.getModifications().getAssignedRelationshipProperties(0);
+
+        assertEquals("Property has incorrect value", "600", relationshipProperty.getValue());
+        assertEquals("Property has incorrect previous value", "500", relationshipProperty.getPreviousValue());
+        assertEquals("Property has incorrect previous value type", "INTEGER", relationshipProperty.getPreviousValueType());
+    }
+
     @Test
     public void testInvalidQueryTransaction() {
 
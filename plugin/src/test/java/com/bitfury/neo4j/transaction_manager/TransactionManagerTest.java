@@ -420,6 +420,72 @@ public class TransactionManagerTest {
         }
     }
 
+    @Test
+    public void testSchemaChangeIsAddedToBlockChanges() {
+
+        // Execute
+        TransactionRequest transactionRequest = TransactionRequest.newBuilder()
+                .addQueries("CREATE (n:Person { name: 'Sjoerd' })")
+                .setTransactionId("txID1")
+                .build();
+        BlockExecuteRequest executeRequest = BlockExecuteRequest.newBuilder()
+                .addTransactions(transactionRequest)
+                .setBlockId(getBlockID())
+                .build();
+        blockingStub.executeBlock(executeRequest);
+
+        SchemaChangeRequest schemaRequest = SchemaChangeRequest.newBuilder()
+                .setBlockId(getBlockID())
+                .setTransactionId("txID2")
+                .setStatement("CREATE INDEX ON :Person(name)")
+                .build();
+        SchemaChangeResponse schemaResponse = blockingStub.executeSchemaChange(schemaRequest);
+
+        assertTrue("Execute schema change failed.", schemaResponse.getSuccess());
+
+        // Retrieve
+        BlockChangesRequest changesRequest = BlockChangesRequest.newBuilder()
+                .setBlockId(getBlockID())
+                .build();
+        BlockChangesResponse changeResponse = blockingStub.retrieveBlockChanges(changesRequest);
+
+        assertEquals("Received incorrect number of transactions.", 2, changeResponse.getTransactionsCount());
+
+        TransactionResponse transactionResponse = changeResponse.getTransactions(1);
+
+        assertEquals("Schema change failed", Status.SUCCESS, transactionResponse.getResult());
+        assertEquals("Received incorrect transaction ID", "txID2", transactionResponse.getTransactionId());
+        assertEquals("Schema change created nodes.", 0, transactionResponse.getModifications().getCreatedNodesCount());
+    }
+
+    @Test
+    public void testFailedSchemaChange() {
+
+        SchemaChangeRequest schemaRequest = SchemaChangeRequest.newBuilder()
+                .setBlockId(getBlockID())
+                .setTransactionId("txID1")
+                .setStatement("CREATE INDEX ON Person")
+                .build();
+        blockingStub.executeSchemaChange(schemaRequest);
+
+        BlockChangesRequest changesRequest = BlockChangesRequest.newBuilder()
+                .setBlockId(getBlockID())
+                .build();
+        BlockChangesResponse changeResponse = blockingStub.retrieveBlockChanges(changesRequest);
+
+        assertEquals("Received incorrect block ID.", getBlockID(), changeResponse.getBlockId());
+        assertEquals("Schema change succeeded", Status.FAILURE, changeResponse.getTransactions(0).getResult());
+        assertEquals("Incorrect error code", ErrorCode.FAILED_QUERY, changeResponse.getTransactions(0).getError().getCode());
+    }
+
+    @Test
+    public void testHealth() {
+
+        HealthResponse healthResponse = blockingStub.health(HealthRequest.newBuilder().build());
+
+        assertTrue("Database is not available", healthResponse.getAvailable());
+    }
+
     private String getBlockID() {
         return "block_id" + block_id;
     }